@@ -0,0 +1,1047 @@
+//! cpclient: command line client for a cypherpost server.
+//!
+//! Mirrors the ffi exports so that admins can issue invites and support staff
+//! can reproduce a user's view from a terminal. Run `cpclient help` for usage.
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::Path;
+use std::process;
+use std::str::FromStr;
+use std::time::Duration;
+
+use bitcoin::network::constants::Network;
+use bitcoin::secp256k1::XOnlyPublicKey;
+use bitcoin::util::bip32::ExtendedPrivKey;
+use bitcoin::PrivateKey;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use cpclient::client::{self, Client};
+use cpclient::key::ec;
+use cpclient::network::badge::model::BadgeDetails;
+use cpclient::network::badge::reputation::FilterPolicy;
+use cpclient::network::handler::{AnnouncementType, InvitePermission, NetworkConfig, ProxyConfig};
+use cpclient::network::identity::contact::{self, ContactCard, Contacts};
+use cpclient::network::identity::directory::MemberDirectory;
+use cpclient::network::identity::model::UserIdentity;
+use cpclient::network::identity::profile::{Profile, SignedProfile};
+use cpclient::network::post::model::{Payload, PostLinks, PostsAsChat, Recipient};
+use cpclient::network::post::descriptor::{self, KeyContribution, MultisigDescriptor};
+use cpclient::network::post::attachment::AttachmentManifest;
+use cpclient::network::post::conversation::ConversationState;
+use cpclient::network::post::outbox::Outbox;
+use cpclient::network::post::payment::PaymentRequest;
+use cpclient::network::post::receipt::{ReceiptKind, ReceiptSettings};
+use cpclient::network::post::trade::{TradeAction, TradeMessage, TradeTerms};
+use cpclient::network::post::psbt::{self, PsbtSummary};
+use cpclient::util::e::{ErrorKind, S5Error};
+
+const USAGE: &str = "usage: cpclient <command> [--flag value ...]
+
+commands:
+  create-root   --master-root - [--account <n>]
+  server-id
+  admin-invite  --admin-secret <secret> [--kind std|priv] [--count <n>]
+  priv-invite   --invite-code <code>
+  invite-detail --invite-code <code>
+  invites
+  revoke-invite --invite-code <code>
+  admin-invites --admin-secret <secret>
+  admin-revoke-invite --admin-secret <secret> --invite-code <code>
+  members
+  refresh-members --directory <path>
+  check-username --username <name>
+  join          --username <name> --invite-code <code>
+  join-invoice
+  invoice-status --invoice-id <id>
+  wait-for-payment --invoice-id <id> --wait <seconds>
+  join-paid     --username <name> --invoice-id <id> [--wait <seconds>]
+  leave
+  send          --index <n> --to <direct|group:value> --kind <message|secret|psbt|xpub|descriptor|payment_request|trade> --value <text>
+                [--reply-to <post id> | --edits <post id> | --reacts-to <post id>]
+  keys          --index <n> --post-id <id> --recipients <pubkey,pubkey,...>
+  queue         --outbox <path> --to <direct|group:value> --kind <kind> --value <text> --recipients <pubkey,pubkey,...>
+                [--reply-to <post id> | --edits <post id> | --reacts-to <post id>]
+  outbox        --outbox <path> [--remove <id>]
+  flush         --outbox <path> [--wait <seconds>]
+  posts         [--genesis-filter <timestamp>]
+  chats         --directory <path> [--contacts <path>] [--policy <json file>|off] [--genesis-filter <timestamp>]
+  conversations --directory <path> [--contacts <path>] [--state <path>] [--offset <n>] [--limit <n>]
+  conversation-state --state <path> [--read <counter party> --genesis <timestamp>] [--mute <counter party>] [--unmute <counter party>]
+  safety-number --pubkey <pubkey>
+  contact-uri   --directory <path>
+  verify-contact --contacts <path> --directory <path> (--uri <contact uri> | --username <name> --pubkey <pubkey>) [--replace true]
+  post          --post-id <id>
+  publish-profile --index <n> --profile <json file> [--audience <pubkey,pubkey,...>]
+  profiles
+  profile-card  --profile <json file>
+  import-card   --card <card>
+  psbt-summary  --psbt <base64> [--network bitcoin|testnet|signet|regtest]
+  combine-psbts --psbts <base64,base64,...>
+  descriptor    --threshold <n> --keys <[fingerprint/path]xpub,...>
+  chat-descriptor --threshold <n> --chat <json file>
+  check-descriptor --descriptor <descriptor>
+  payment-request --address <address> [--amount <sats>] [--label <text>] [--expiry <timestamp>] [--wif -]
+  payment-details --owner <pubkey> --request <json>
+  trades
+  trade-message --action offer|counter|accept|cancel|complete [--trade-id <id>] [--terms <json file>]
+  receipts      --kind delivered|read [--post-ids <id,id,...>] [--settings <json file>]
+  attach        --to <direct|group:value> --recipients <pubkey,pubkey,...> --file <path> [--name <name>] [--mime <type>]
+  download      --post-id <attachment post id> [--out <path>]
+  split-secret  --secret - --threshold <n> --holders <pubkey,pubkey,...> [--label <text>]
+  recoveries
+  return-share  --set-id <id> --to <pubkey>
+  last-index
+  badges
+  give-badge    --to <pubkey> --kind trust|scam|escrow [--reason <text>] [--expiry <timestamp>] [--reference <id>]
+  reputation
+  explain       --pubkey <pubkey>
+
+common flags:
+  --config <path>        json file with any of: host, proxy, social_root, admin_secret, output
+  --host <url>           e.g. http://localhost:3021
+  --proxy <proxy>        socks5 port or proxy url, same as the ffi socks5 argument (default 0)
+  --social-root -        identity to act as
+  --admin-secret -       for the admin commands
+  --output json|table    default table
+
+flags override the config file.
+
+secrets are never taken on the command line, where other users can see them in the process list.
+--social-root, --admin-secret, --master-root, --wif and --secret only accept -, which reads the
+secret from the next line of stdin. social_root and admin_secret can also come from the config file
+or from CPCLIENT_SOCIAL_ROOT and CPCLIENT_ADMIN_SECRET.";
+
+/// Flags that hold secrets. Their value is read from stdin, never from argv.
+const SECRET_FLAGS: [&str; 5] = ["social_root", "admin_secret", "master_root", "wif", "secret"];
+
+/// Values read from `--config`. Every field can also be given as a flag.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct Config {
+    host: Option<String>,
+    proxy: Option<String>,
+    social_root: Option<String>,
+    admin_secret: Option<String>,
+    output: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Args {
+    command: String,
+    flags: BTreeMap<String, String>,
+}
+
+impl Args {
+    fn parse(args: &[String]) -> Result<Args, S5Error> {
+        let mut args = args.iter();
+        let command = match args.next() {
+            Some(command) => command.clone(),
+            None => return Err(S5Error::new(ErrorKind::Input, "No command given.")),
+        };
+        let mut flags = BTreeMap::new();
+        while let Some(flag) = args.next() {
+            let name = match flag.strip_prefix("--") {
+                Some(name) => name.replace('-', "_"),
+                None => return Err(S5Error::new(ErrorKind::Input, &format!("Unexpected argument {}", flag))),
+            };
+            let value = match args.next() {
+                Some(value) => value.clone(),
+                None => return Err(S5Error::new(ErrorKind::Input, &format!("Missing value for --{}", flag))),
+            };
+            if SECRET_FLAGS.contains(&name.as_str()) && value != "-" {
+                return Err(S5Error::new(
+                    ErrorKind::Input,
+                    &format!("{} takes a secret: pass - and write it to stdin instead", flag),
+                ));
+            }
+            flags.insert(name, value);
+        }
+        Ok(Args { command, flags })
+    }
+
+    /// Fills in anything not given as a flag from the config file.
+    fn with_config(mut self, config: &Config) -> Self {
+        let config = serde_json::to_value(config).unwrap();
+        if let Some(config) = config.as_object() {
+            for (key, value) in config {
+                if let Some(value) = value.as_str() {
+                    self.flags.entry(key.clone()).or_insert_with(|| value.to_string());
+                }
+            }
+        }
+        self
+    }
+
+    /// Fills in secrets not given otherwise from CPCLIENT_SOCIAL_ROOT and CPCLIENT_ADMIN_SECRET.
+    fn with_env(mut self) -> Self {
+        for name in ["social_root", "admin_secret"] {
+            if let Ok(value) = env::var(format!("CPCLIENT_{}", name.to_uppercase())) {
+                self.flags.entry(name.to_string()).or_insert(value);
+            }
+        }
+        self
+    }
+
+    /// Replaces every `-` secret flag with a line read from `input`, in flag name order.
+    fn read_secrets(mut self, input: &mut impl BufRead) -> Result<Self, S5Error> {
+        for (name, value) in self.flags.iter_mut() {
+            if SECRET_FLAGS.contains(&name.as_str()) && value == "-" {
+                let mut line = String::new();
+                input
+                    .read_line(&mut line)
+                    .map_err(|e| S5Error::new(ErrorKind::Input, &format!("Could not read --{}: {}", name, e)))?;
+                *value = line.trim().to_string();
+            }
+        }
+        Ok(self)
+    }
+
+    fn optional(&self, name: &str) -> Option<String> {
+        self.flags.get(name).cloned()
+    }
+    fn required(&self, name: &str) -> Result<String, S5Error> {
+        match self.flags.get(name) {
+            Some(value) => Ok(value.clone()),
+            None => Err(S5Error::new(
+                ErrorKind::Input,
+                &format!("Missing --{}", name.replace('_', "-")),
+            )),
+        }
+    }
+    fn number<T: FromStr>(&self, name: &str, default: Option<T>) -> Result<T, S5Error> {
+        match (self.flags.get(name), default) {
+            (Some(value), _) => value
+                .parse::<T>()
+                .map_err(|_| S5Error::new(ErrorKind::Input, &format!("--{} must be a number", name.replace('_', "-")))),
+            (None, Some(default)) => Ok(default),
+            (None, None) => Err(S5Error::new(ErrorKind::Input, &format!("Missing --{}", name.replace('_', "-")))),
+        }
+    }
+
+    fn client(&self) -> Result<Client, S5Error> {
+        let proxy = ProxyConfig::from_ffi_str(&self.optional("proxy").unwrap_or_else(|| "0".to_string()))?;
+        Ok(Client::new(&self.required("host")?).with_network(NetworkConfig::new(proxy)))
+    }
+    fn identity(&self) -> Result<UserIdentity, S5Error> {
+        UserIdentity::new(self.required("social_root")?)
+    }
+    /// The --directory file brought up to date with the server and written back.
+    fn directory(&self) -> Result<MemberDirectory, S5Error> {
+        let path = self.required("directory")?;
+        let mut directory = match fs::read_to_string(&path) {
+            Ok(contents) => MemberDirectory::structify(&contents)?,
+            Err(_) => MemberDirectory::default(),
+        };
+        self.client()?.refresh_directory(&self.identity()?, &mut directory)?;
+        fs::write(&path, serde_json::to_string(&directory).unwrap())
+            .map_err(|e| S5Error::new(ErrorKind::Input, &format!("Could not write {}: {}", path, e)))?;
+        Ok(directory)
+    }
+}
+
+fn run(args: &Args) -> Result<Value, S5Error> {
+    match args.command.as_str() {
+        "create-root" => {
+            let master_root = ExtendedPrivKey::from_str(&args.required("master_root")?)
+                .map_err(|_| S5Error::new(ErrorKind::Input, "Bad master root key string."))?;
+            let account = args.number("account", Some(0))?;
+            Ok(to_value(&client::create_social_root(&master_root, account)?))
+        }
+        "server-id" => Ok(to_value(&args.client()?.server_identity(&args.identity()?)?)),
+        "admin-invite" => {
+            let permission = match args.optional("kind").unwrap_or_else(|| "std".to_string()).as_str() {
+                "std" | "standard" => InvitePermission::Standard,
+                "priv" | "privileged" => InvitePermission::Privilege(args.number("count", None)?),
+                _ => return Err(S5Error::new(ErrorKind::Input, "--kind must be std or priv")),
+            };
+            Ok(to_value(&args.client()?.admin_invite(&args.required("admin_secret")?, permission)?))
+        }
+        "priv-invite" => Ok(to_value(
+            &args.client()?.priv_user_invite(&args.identity()?, &args.required("invite_code")?)?,
+        )),
+        "invite-detail" => Ok(to_value(
+            &args.client()?.invite_detail(&args.identity()?, &args.required("invite_code")?)?,
+        )),
+        "invites" => Ok(to_value(&args.client()?.my_invites(&args.identity()?)?)),
+        "revoke-invite" => {
+            args.client()?.revoke_invite(&args.identity()?, &args.required("invite_code")?)?;
+            Ok(json!({ "status": true }))
+        }
+        "admin-invites" => Ok(to_value(&args.client()?.admin_invites(&args.required("admin_secret")?)?)),
+        "admin-revoke-invite" => {
+            args.client()?
+                .admin_revoke_invite(&args.required("admin_secret")?, &args.required("invite_code")?)?;
+            Ok(json!({ "status": true }))
+        }
+        "members" => Ok(to_value(&args.client()?.members(&args.identity()?)?)),
+        "refresh-members" => Ok(to_value(&args.directory()?)),
+        "check-username" => Ok(to_value(
+            &args.client()?.check_username(&args.identity()?, &args.required("username")?)?,
+        )),
+        "join" => Ok(to_value(&args.client()?.join(
+            &args.identity()?,
+            &args.required("username")?,
+            &args.required("invite_code")?,
+        )?)),
+        "join-invoice" => Ok(to_value(&args.client()?.join_invoice(&args.identity()?)?)),
+        "invoice-status" => Ok(to_value(
+            &args.client()?.invoice_status(&args.identity()?, &args.required("invoice_id")?)?,
+        )),
+        "wait-for-payment" => Ok(to_value(&args.client()?.wait_for_payment(
+            &args.identity()?,
+            &args.required("invoice_id")?,
+            Duration::from_secs(args.number("wait", None)?),
+        )?)),
+        "join-paid" => {
+            let client = args.client()?;
+            let me = args.identity()?;
+            let invoice_id = args.required("invoice_id")?;
+            let wait: u64 = args.number("wait", Some(0))?;
+            if wait > 0 {
+                client.wait_for_payment(&me, &invoice_id, Duration::from_secs(wait))?;
+            }
+            Ok(to_value(&client.join_paid(&me, &args.required("username")?, &invoice_id)?))
+        }
+        "leave" => {
+            args.client()?.leave(&args.identity()?)?;
+            Ok(json!({ "status": true }))
+        }
+        "send" => {
+            let to = Recipient::from_str(&args.required("to")?)?;
+            let payload = Payload::from_str(&format!("{}:{}", args.required("kind")?, args.required("value")?))?;
+            let links = PostLinks {
+                reply_to: args.optional("reply_to"),
+                edits: args.optional("edits"),
+                reacts_to: args.optional("reacts_to"),
+            };
+            let id = args.client()?.send_post_with_links(
+                &args.identity()?,
+                args.number("index", None)?,
+                to,
+                payload,
+                links,
+            )?;
+            Ok(json!({ "id": id }))
+        }
+        "queue" => {
+            let me = args.identity()?;
+            let path = args.required("outbox")?;
+            let recipients = args
+                .required("recipients")?
+                .split(',')
+                .map(|pubkey| ec::pubkey_from_str(pubkey.trim()))
+                .collect::<Result<Vec<XOnlyPublicKey>, S5Error>>()?;
+            let links = PostLinks {
+                reply_to: args.optional("reply_to"),
+                edits: args.optional("edits"),
+                reacts_to: args.optional("reacts_to"),
+            };
+            let mut outbox = Outbox::load(Path::new(&path), &me.derive_storage_key())?;
+            let entry = outbox.queue(
+                Recipient::from_str(&args.required("to")?)?,
+                Payload::from_str(&format!("{}:{}", args.required("kind")?, args.required("value")?))?,
+                links,
+                recipients,
+            )?;
+            outbox.save(Path::new(&path), &me.derive_storage_key())?;
+            Ok(to_value(&entry))
+        }
+        "outbox" => {
+            let me = args.identity()?;
+            let path = args.required("outbox")?;
+            let mut outbox = Outbox::load(Path::new(&path), &me.derive_storage_key())?;
+            if let Some(id) = args.optional("remove") {
+                outbox.remove(&id)?;
+                outbox.save(Path::new(&path), &me.derive_storage_key())?;
+            }
+            Ok(to_value(&outbox))
+        }
+        "flush" => {
+            let path = args.required("outbox")?;
+            let wait: u64 = args.number("wait", Some(0))?;
+            let outbox = if wait > 0 {
+                args.client()?.drain_outbox(&args.identity()?, Path::new(&path), Duration::from_secs(wait))?
+            } else {
+                args.client()?.flush_outbox(&args.identity()?, Path::new(&path))?
+            };
+            Ok(to_value(&outbox))
+        }
+        "keys" => {
+            let recipients = args
+                .required("recipients")?
+                .split(',')
+                .map(ec::pubkey_from_str)
+                .collect::<Result<Vec<XOnlyPublicKey>, S5Error>>()?;
+            args.client()?.send_keys(
+                &args.identity()?,
+                args.number("index", None)?,
+                &args.required("post_id")?,
+                &recipients,
+            )?;
+            Ok(json!({ "status": true }))
+        }
+        "publish-profile" => {
+            let audience = match args.optional("audience") {
+                Some(audience) => Some(
+                    audience
+                        .split(',')
+                        .map(ec::pubkey_from_str)
+                        .collect::<Result<Vec<XOnlyPublicKey>, S5Error>>()?,
+                ),
+                None => None,
+            };
+            let post_id = args.client()?.publish_profile(
+                &args.identity()?,
+                args.number("index", None)?,
+                read_profile(&args.required("profile")?)?,
+                audience.as_deref(),
+            )?;
+            Ok(json!({ "id": post_id }))
+        }
+        "profiles" => Ok(to_value(&args.client()?.profiles(&args.identity()?)?)),
+        "profile-card" => {
+            let keys = ec::XOnlyPair::from_xprv(args.identity()?.social_root);
+            let signed = SignedProfile::new(read_profile(&args.required("profile")?)?, &keys)?;
+            Ok(json!({ "card": signed.to_card() }))
+        }
+        "import-card" => Ok(to_value(&SignedProfile::from_card(&args.required("card")?)?)),
+        "posts" => {
+            let filter: u64 = args.number("genesis_filter", Some(0))?;
+            let filter = if filter == 0 { None } else { Some(filter) };
+            Ok(to_value(&args.client()?.posts(&args.identity()?, filter)?))
+        }
+        "chats" => {
+            let filter: u64 = args.number("genesis_filter", Some(0))?;
+            let filter = if filter == 0 { None } else { Some(filter) };
+            let directory = args.directory()?;
+            let client = args.client()?;
+            let me = args.identity()?;
+            let contacts = match args.optional("contacts") {
+                Some(path) => read_contacts(&path)?,
+                None => Contacts::default(),
+            };
+            let policy = match args.optional("policy").as_deref() {
+                Some("off") => None,
+                Some(path) => Some(FilterPolicy::structify(&fs::read_to_string(path).map_err(|e| {
+                    S5Error::new(ErrorKind::Input, &format!("Could not read {}: {}", path, e))
+                })?)?),
+                None => Some(FilterPolicy::default()),
+            };
+            Ok(to_value(&client.chats(&me, filter, &directory, &contacts, policy.as_ref())?))
+        }
+        "conversations" => {
+            let directory = args.directory()?;
+            let client = args.client()?;
+            let me = args.identity()?;
+            let contacts = match args.optional("contacts") {
+                Some(path) => read_contacts(&path)?,
+                None => Contacts::default(),
+            };
+            let state = match args.optional("state") {
+                Some(path) => read_conversation_state(&path)?,
+                None => ConversationState::default(),
+            };
+            let offset: usize = args.number("offset", Some(0))?;
+            let limit: usize = args.number("limit", Some(0))?;
+            Ok(to_value(&client.conversations(&me, &directory, &contacts, &state, offset, limit)?))
+        }
+        "conversation-state" => {
+            let path = args.required("state")?;
+            let mut state = read_conversation_state(&path)?;
+            if let Some(counter_party) = args.optional("read") {
+                state.mark_read(&counter_party, args.number("genesis", None)?);
+            }
+            if let Some(counter_party) = args.optional("mute") {
+                state.set_muted(&counter_party, true);
+            }
+            if let Some(counter_party) = args.optional("unmute") {
+                state.set_muted(&counter_party, false);
+            }
+            fs::write(&path, serde_json::to_string(&state).unwrap())
+                .map_err(|e| S5Error::new(ErrorKind::Input, &format!("Could not write {}: {}", path, e)))?;
+            Ok(to_value(&state))
+        }
+        "safety-number" => {
+            let me = ec::XOnlyPair::from_xprv(args.identity()?.social_root).pubkey;
+            let pubkey = ec::pubkey_from_str(&args.required("pubkey")?)?;
+            Ok(json!({ "code": contact::safety_number(&me, &pubkey) }))
+        }
+        "contact-uri" => {
+            let keys = ec::XOnlyPair::from_xprv(args.identity()?.social_root);
+            let username = match args.directory()?.resolve(&keys.pubkey) {
+                Some(entry) if entry.left.is_none() => entry.username.clone(),
+                _ => return Err(S5Error::new(ErrorKind::NoResource, "Not a member of this server.")),
+            };
+            let card = ContactCard::new(&args.required("host")?, &username, &keys)?;
+            Ok(json!({ "code": card.to_uri() }))
+        }
+        "verify-contact" => {
+            let path = args.required("contacts")?;
+            let mut contacts = read_contacts(&path)?;
+            let directory = args.directory()?;
+            let replace = match args.optional("replace").as_deref() {
+                None | Some("false") => false,
+                Some("true") => true,
+                Some(_) => return Err(S5Error::new(ErrorKind::Input, "--replace must be true or false")),
+            };
+            match args.optional("uri") {
+                Some(uri) => {
+                    contacts.verify_card(&args.required("host")?, &ContactCard::from_uri(&uri)?, &directory, replace)?
+                }
+                None => contacts.verify(
+                    &args.required("username")?,
+                    ec::pubkey_from_str(&args.required("pubkey")?)?,
+                    &directory,
+                    replace,
+                )?,
+            }
+            fs::write(&path, serde_json::to_string(&contacts).unwrap())
+                .map_err(|e| S5Error::new(ErrorKind::Input, &format!("Could not write {}: {}", path, e)))?;
+            Ok(to_value(&contacts))
+        }
+        "psbt-summary" => {
+            let psbt = psbt::parse(&args.required("psbt")?)?;
+            let network = args.optional("network").unwrap_or_else(|| "bitcoin".to_string());
+            let network = Network::from_str(&network.to_lowercase())
+                .map_err(|_| S5Error::new(ErrorKind::Input, "--network must be bitcoin, testnet, signet or regtest"))?;
+            Ok(to_value(&PsbtSummary::new(&psbt, network)))
+        }
+        "combine-psbts" => {
+            let psbts: Vec<String> = args.required("psbts")?.split(',').map(|psbt| psbt.trim().to_string()).collect();
+            Ok(json!({ "psbt": psbt::combine(&psbts)? }))
+        }
+        "descriptor" => {
+            let keys = args
+                .required("keys")?
+                .split(',')
+                .map(KeyContribution::from_str)
+                .collect::<Result<Vec<KeyContribution>, S5Error>>()?;
+            Ok(to_value(&MultisigDescriptor::assemble(args.number("threshold", None)?, keys)?))
+        }
+        "chat-descriptor" => {
+            let path = args.required("chat")?;
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| S5Error::new(ErrorKind::Input, &format!("Could not read {}: {}", path, e)))?;
+            let chat = PostsAsChat::structify(&contents)?;
+            Ok(to_value(&descriptor::assemble_from_posts(args.number("threshold", None)?, &chat.posts)?))
+        }
+        "check-descriptor" => Ok(to_value(&MultisigDescriptor::from_descriptor(&args.required("descriptor")?)?)),
+        "payment-request" => {
+            let me = args.identity()?;
+            let owner = ec::XOnlyPair::from_xprv(me.social_root).pubkey;
+            let amount = args.optional("amount").map(|_| args.number("amount", None)).transpose()?;
+            let expiry = args.optional("expiry").map(|_| args.number("expiry", None)).transpose()?;
+            let mut request = PaymentRequest::new(&args.required("address")?, amount, args.optional("label"), expiry)?;
+            if let Some(wif) = args.optional("wif") {
+                let key = PrivateKey::from_wif(&wif).map_err(|_| S5Error::new(ErrorKind::Key, "Bad wif private key."))?;
+                request.prove(&owner, &key.inner)?;
+            }
+            request.verify(&owner, me.social_root.network)?;
+            Ok(to_value(&request))
+        }
+        "payment-details" => {
+            let network = args.identity()?.social_root.network;
+            let owner = ec::pubkey_from_str(&args.required("owner")?)?;
+            Ok(to_value(&PaymentRequest::structify(&args.required("request")?)?.verify(&owner, network)?))
+        }
+        "post" => Ok(to_value(&args.client()?.post(&args.identity()?, &args.required("post_id")?)?)),
+        "last-index" => Ok(to_value(&args.client()?.last_index(&args.identity()?)?)),
+        "trades" => Ok(to_value(&args.client()?.trades(&args.identity()?)?)),
+        "trade-message" => {
+            let me = args.identity()?;
+            let keys = ec::XOnlyPair::from_xprv(me.social_root);
+            let action = TradeAction::from_str(&args.required("action")?)?;
+            let terms = match args.optional("terms") {
+                Some(path) => Some(read_terms(&path)?),
+                None => None,
+            };
+            let message = match (action, args.optional("trade_id"), terms) {
+                (TradeAction::Offer, _, Some(terms)) => TradeMessage::offer(&keys, terms)?,
+                (TradeAction::Offer, _, None) => return Err(S5Error::new(ErrorKind::Input, "Missing --terms")),
+                (action, Some(trade_id), terms) => args.client()?.trades(&me)?.get(&trade_id)?.respond(&keys, action, terms)?,
+                (_, None, _) => return Err(S5Error::new(ErrorKind::Input, "Missing --trade-id")),
+            };
+            Ok(to_value(&message))
+        }
+        "receipts" => {
+            let post_ids: Option<Vec<String>> = args
+                .optional("post_ids")
+                .map(|ids| ids.split(',').map(|id| id.trim().to_string()).collect());
+            let settings = match args.optional("settings") {
+                Some(path) => read_receipt_settings(&path)?,
+                None => ReceiptSettings::default(),
+            };
+            Ok(to_value(&args.client()?.send_receipts(
+                &args.identity()?,
+                ReceiptKind::from_str(&args.required("kind")?)?,
+                post_ids.as_deref(),
+                &settings,
+            )?))
+        }
+        "attach" => {
+            let path = args.required("file")?;
+            let recipients = args
+                .required("recipients")?
+                .split(',')
+                .map(|pubkey| ec::pubkey_from_str(pubkey.trim()))
+                .collect::<Result<Vec<XOnlyPublicKey>, S5Error>>()?;
+            let name = match args.optional("name") {
+                Some(name) => name,
+                None => Path::new(&path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+            };
+            let mut file = fs::File::open(&path)
+                .map_err(|e| S5Error::new(ErrorKind::Input, &format!("Could not read {}: {}", path, e)))?;
+            let id = args.client()?.send_attachment(
+                &args.identity()?,
+                Recipient::from_str(&args.required("to")?)?,
+                &recipients,
+                &mut file,
+                &name,
+                args.optional("mime"),
+            )?;
+            Ok(json!({ "id": id }))
+        }
+        "download" => {
+            let me = args.identity()?;
+            let client = args.client()?;
+            let post = client.post(&me, &args.required("post_id")?)?;
+            let manifest = AttachmentManifest::structify(&post.post.payload.value)?;
+            let out = args.optional("out").unwrap_or_else(|| manifest.name.clone());
+            client.download_attachment(&me, &manifest, Path::new(&out))?;
+            Ok(json!({ "path": out, "size": manifest.size, "mime": manifest.mime }))
+        }
+        "split-secret" => {
+            let holders = args
+                .required("holders")?
+                .split(',')
+                .map(|pubkey| ec::pubkey_from_str(pubkey.trim()))
+                .collect::<Result<Vec<XOnlyPublicKey>, S5Error>>()?;
+            Ok(to_value(&args.client()?.distribute_shares(
+                &args.identity()?,
+                &args.required("secret")?,
+                args.number("threshold", None)?,
+                &holders,
+                args.optional("label"),
+            )?))
+        }
+        "recoveries" => Ok(to_value(&args.client()?.recoveries(&args.identity()?)?)),
+        "return-share" => {
+            let to = ec::pubkey_from_str(&args.required("to")?)?;
+            let id = args.client()?.return_share(&args.identity()?, &args.required("set_id")?, to)?;
+            Ok(json!({ "id": id }))
+        }
+        "badges" => Ok(to_value(&args.client()?.badges(&args.identity()?)?)),
+        "give-badge" => {
+            let to = ec::pubkey_from_str(&args.required("to")?)?;
+            let kind = match args.required("kind")?.to_lowercase().as_str() {
+                kind @ ("trust" | "scam" | "escrow") => AnnouncementType::from_str(kind)?,
+                _ => return Err(S5Error::new(ErrorKind::Input, "--kind must be trust, scam or escrow")),
+            };
+            let details = BadgeDetails {
+                reason: args.optional("reason"),
+                expiry: match args.optional("expiry") {
+                    Some(_) => Some(args.number("expiry", None)?),
+                    None => None,
+                },
+                reference: args.optional("reference"),
+            };
+            Ok(to_value(&args.client()?.give_badge_with_details(&args.identity()?, to, kind, details)?))
+        }
+        "reputation" => Ok(to_value(&args.client()?.scores(&args.identity()?)?)),
+        "explain" => {
+            let member = ec::pubkey_from_str(&args.required("pubkey")?)?;
+            Ok(to_value(&args.client()?.explain(&args.identity()?, &member)?))
+        }
+        command => Err(S5Error::new(ErrorKind::Input, &format!("Unknown command {}", command))),
+    }
+}
+
+fn to_value<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+/// Renders rows as aligned columns.
+fn table(headers: &[&str], rows: Vec<Vec<String>>) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for row in rows.iter() {
+        for (index, cell) in row.iter().enumerate() {
+            widths[index] = widths[index].max(cell.chars().count());
+        }
+    }
+    let line = |cells: Vec<String>| {
+        cells
+            .iter()
+            .enumerate()
+            .map(|(index, cell)| format!("{:width$}", cell, width = widths[index]))
+            .collect::<Vec<String>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    let mut lines = vec![line(headers.iter().map(|header| header.to_string()).collect())];
+    lines.extend(rows.into_iter().map(line));
+    lines.join("\n")
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::String(string) => string.clone(),
+        Value::Null => "".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Human readable form of a command result.
+fn render_table(command: &str, result: &Value) -> String {
+    match command {
+        "members" => table(
+            &["USERNAME", "PUBKEY"],
+            result["identities"]
+                .as_array()
+                .unwrap_or(&vec![])
+                .iter()
+                .map(|member| vec![cell(&member["username"]), cell(&member["pubkey"])])
+                .collect(),
+        ),
+        "refresh-members" => table(
+            &["USERNAME", "PUBKEY", "JOINED", "LEFT"],
+            result["entries"]
+                .as_array()
+                .unwrap_or(&vec![])
+                .iter()
+                .map(|entry| vec![cell(&entry["username"]), cell(&entry["pubkey"]), cell(&entry["joined"]), cell(&entry["left"])])
+                .collect(),
+        ),
+        "posts" | "chats" => {
+            let mut rows = vec![];
+            for chat in result["verified"].as_array().unwrap_or(&vec![]) {
+                let mut name = match &chat["username"] {
+                    Value::String(username) => username.clone(),
+                    _ => cell(&chat["counter_party"]),
+                };
+                let flags: Vec<String> = chat["flags"].as_array().unwrap_or(&vec![]).iter().map(cell).collect();
+                if !flags.is_empty() {
+                    name += &format!(" ({})", flags.join(","));
+                }
+                for post in chat["posts"].as_array().unwrap_or(&vec![]) {
+                    rows.push(vec![
+                        name.clone(),
+                        cell(&post["genesis"]),
+                        cell(&post["owner"]),
+                        cell(&post["post"]["payload"]["kind"]),
+                        cell(&post["post"]["payload"]["value"]),
+                    ]);
+                }
+            }
+            let mut output = table(&["CHAT", "GENESIS", "FROM", "KIND", "VALUE"], rows);
+            let corrupted = result["corrupted"].as_array().map(|corrupted| corrupted.len()).unwrap_or(0);
+            if corrupted > 0 {
+                output += &format!("\n{} corrupted post(s)", corrupted);
+            }
+            let quarantined = result["quarantined"].as_array().map(|quarantined| quarantined.len()).unwrap_or(0);
+            if quarantined > 0 {
+                output += &format!("\n{} quarantined chat(s), see --output json", quarantined);
+            }
+            output
+        }
+        "invites" | "admin-invites" => table(
+            &["INVITE_CODE", "KIND", "STATUS", "COUNT", "CLAIMED_BY"],
+            result["invitations"]
+                .as_array()
+                .unwrap_or(&vec![])
+                .iter()
+                .map(|invite| {
+                    vec![
+                        cell(&invite["invite_code"]),
+                        cell(&invite["kind"]),
+                        cell(&invite["status"]),
+                        cell(&invite["count"]),
+                        cell(&invite["claimed_by"]),
+                    ]
+                })
+                .collect(),
+        ),
+        "trades" => table(
+            &["TRADE", "STATUS", "SIDE", "AMOUNT", "PRICE", "METHOD", "MAKER", "TAKER", "BADGES TO GIVE"],
+            result["trades"]
+                .as_array()
+                .unwrap_or(&vec![])
+                .iter()
+                .map(|trade| {
+                    let prompts: Vec<String> = trade["prompts"]
+                        .as_array()
+                        .unwrap_or(&vec![])
+                        .iter()
+                        .map(|prompt| format!("{} {}", cell(&prompt["kind"]), cell(&prompt["to"])))
+                        .collect();
+                    let status = if trade["expired"] == Value::Bool(true) { "expired".to_string() } else { cell(&trade["status"]) };
+                    vec![
+                        cell(&trade["trade_id"]),
+                        status,
+                        cell(&trade["terms"]["side"]),
+                        cell(&trade["terms"]["amount"]),
+                        cell(&trade["terms"]["price"]),
+                        cell(&trade["terms"]["payment_method"]),
+                        cell(&trade["maker"]),
+                        cell(&trade["taker"]),
+                        prompts.join(","),
+                    ]
+                })
+                .collect(),
+        ),
+        "conversations" => {
+            let rows = result["conversations"]
+                .as_array()
+                .unwrap_or(&vec![])
+                .iter()
+                .map(|conversation| {
+                    let mut name = match &conversation["username"] {
+                        Value::String(username) => username.clone(),
+                        _ => cell(&conversation["counter_party"]),
+                    };
+                    if conversation["muted"] == Value::Bool(true) {
+                        name += " (muted)";
+                    }
+                    vec![
+                        name,
+                        cell(&conversation["last_activity"]),
+                        cell(&conversation["unread"]),
+                        cell(&conversation["preview"]),
+                    ]
+                })
+                .collect();
+            let mut output = table(&["CHAT", "LAST ACTIVITY", "UNREAD", "PREVIEW"], rows);
+            output += &format!("\n{} conversation(s), {} unread", cell(&result["total"]), cell(&result["unread"]));
+            output
+        }
+        "outbox" | "flush" => table(
+            &["ID", "STATE", "TO", "KIND", "POST", "ERROR"],
+            result["entries"]
+                .as_array()
+                .unwrap_or(&vec![])
+                .iter()
+                .map(|entry| {
+                    vec![
+                        cell(&entry["id"]),
+                        cell(&entry["state"]),
+                        format!("{}:{}", cell(&entry["to"]["kind"]), cell(&entry["to"]["value"])),
+                        cell(&entry["payload"]["kind"]),
+                        cell(&entry["post_id"]),
+                        cell(&entry["last_error"]),
+                    ]
+                })
+                .collect(),
+        ),
+        "recoveries" => table(
+            &["SET", "LABEL", "SHARES", "FROM", "SECRET"],
+            result["recoveries"]
+                .as_array()
+                .unwrap_or(&vec![])
+                .iter()
+                .map(|set| {
+                    let shares = set["shares"].as_array().cloned().unwrap_or_default();
+                    let from: Vec<String> = shares.iter().map(|share| cell(&share["owner"])).collect();
+                    vec![
+                        cell(&set["set_id"]),
+                        cell(&set["label"]),
+                        format!("{}/{}", shares.len(), cell(&set["threshold"])),
+                        from.join(","),
+                        cell(&set["secret"]),
+                    ]
+                })
+                .collect(),
+        ),
+        "badges" => table(
+            &["KIND", "BY", "TO", "GENESIS"],
+            result
+                .as_array()
+                .unwrap_or(&vec![])
+                .iter()
+                .map(|badge| vec![cell(&badge["kind"]), cell(&badge["by"]), cell(&badge["to"]), cell(&badge["genesis"])])
+                .collect(),
+        ),
+        "reputation" => table(
+            &["PUBKEY", "SCORE", "TRUST", "ESCROW", "SCAM", "DISTANCE"],
+            result["scores"]
+                .as_array()
+                .unwrap_or(&vec![])
+                .iter()
+                .map(|score| {
+                    vec![
+                        cell(&score["pubkey"]),
+                        cell(&score["score"]),
+                        cell(&score["trust"]),
+                        cell(&score["escrow"]),
+                        cell(&score["scam"]),
+                        cell(&score["trust_distance"]),
+                    ]
+                })
+                .collect(),
+        ),
+        "explain" => {
+            let mut rows = vec![];
+            explanation_rows(result, 0, &mut rows);
+            table(&["REASON", "CONTRIBUTION"], rows)
+        }
+        "post" => table(
+            &["FIELD", "VALUE"],
+            vec![
+                vec!["id".to_string(), cell(&result["id"])],
+                vec!["genesis".to_string(), cell(&result["genesis"])],
+                vec!["owner".to_string(), cell(&result["owner"])],
+                vec!["to".to_string(), format!("{}:{}", cell(&result["post"]["to"]["kind"]), cell(&result["post"]["to"]["value"]))],
+                vec!["kind".to_string(), cell(&result["post"]["payload"]["kind"])],
+                vec!["value".to_string(), cell(&result["post"]["payload"]["value"])],
+            ],
+        ),
+        _ => match result.as_object() {
+            Some(fields) => table(
+                &["FIELD", "VALUE"],
+                fields.iter().map(|(key, value)| vec![key.clone(), cell(value)]).collect(),
+            ),
+            None => cell(result),
+        },
+    }
+}
+
+/// One row per node of an explanation tree, children indented under their parent.
+fn explanation_rows(node: &Value, depth: usize, rows: &mut Vec<Vec<String>>) {
+    rows.push(vec![format!("{}{}", "  ".repeat(depth), cell(&node["label"])), cell(&node["contribution"])]);
+    for child in node["children"].as_array().unwrap_or(&vec![]) {
+        explanation_rows(child, depth + 1, rows);
+    }
+}
+
+/// A missing file is an empty contact list.
+fn read_contacts(path: &str) -> Result<Contacts, S5Error> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Contacts::structify(&contents),
+        Err(_) => Ok(Contacts::default()),
+    }
+}
+
+fn read_profile(path: &str) -> Result<Profile, S5Error> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| S5Error::new(ErrorKind::Input, &format!("Could not read {}: {}", path, e)))?;
+    Profile::structify(&contents)
+}
+
+fn read_terms(path: &str) -> Result<TradeTerms, S5Error> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| S5Error::new(ErrorKind::Input, &format!("Could not read {}: {}", path, e)))?;
+    TradeTerms::structify(&contents)
+}
+
+fn read_receipt_settings(path: &str) -> Result<ReceiptSettings, S5Error> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| S5Error::new(ErrorKind::Input, &format!("Could not read {}: {}", path, e)))?;
+    ReceiptSettings::structify(&contents)
+}
+
+/// A missing file is a fresh state, so the first `conversation-state` call creates it.
+fn read_conversation_state(path: &str) -> Result<ConversationState, S5Error> {
+    match fs::read_to_string(path) {
+        Ok(contents) => ConversationState::structify(&contents),
+        Err(_) => Ok(ConversationState::default()),
+    }
+}
+
+fn load_config(args: &Args) -> Result<Config, S5Error> {
+    match args.optional("config") {
+        Some(path) => {
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| S5Error::new(ErrorKind::Input, &format!("Could not read {}: {}", path, e)))?;
+            serde_json::from_str(&contents)
+                .map_err(|e| S5Error::new(ErrorKind::Input, &format!("Bad config file {}: {}", path, e)))
+        }
+        None => Ok(Config::default()),
+    }
+}
+
+fn main() {
+    let raw: Vec<String> = env::args().skip(1).collect();
+    if raw.is_empty() || raw[0] == "help" || raw[0] == "--help" || raw[0] == "-h" {
+        println!("{}", USAGE);
+        return;
+    }
+    let args = match Args::parse(&raw)
+        .and_then(|args| args.read_secrets(&mut io::stdin().lock()))
+        .and_then(|args| Ok(args.clone().with_config(&load_config(&args)?).with_env()))
+    {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    match run(&args) {
+        Ok(result) => match args.optional("output").as_deref() {
+            Some("json") => println!("{}", serde_json::to_string_pretty(&result).unwrap()),
+            _ => println!("{}", render_table(&args.command, &result)),
+        },
+        Err(e) => {
+            eprintln!("{}", serde_json::to_string(&e).unwrap());
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_cli_args() {
+        let args = Args::parse(&strings(&["send", "--index", "3", "--to", "direct:abc", "--post-id", "x"])).unwrap();
+        assert_eq!(args.command, "send");
+        assert_eq!(args.number::<u32>("index", None).unwrap(), 3);
+        assert_eq!(args.required("post_id").unwrap(), "x");
+        assert!(args.required("value").is_err());
+        assert!(Args::parse(&strings(&["send", "--index"])).is_err());
+        assert!(Args::parse(&strings(&["send", "index", "3"])).is_err());
+
+        let config = Config {
+            host: Some("http://file".to_string()),
+            social_root: Some("xprv".to_string()),
+            ..Config::default()
+        };
+        let args = Args::parse(&strings(&["members", "--host", "http://flag"])).unwrap().with_config(&config);
+        assert_eq!(args.required("host").unwrap(), "http://flag");
+        assert_eq!(args.required("social_root").unwrap(), "xprv");
+        assert!(run(&Args::parse(&strings(&["nope"])).unwrap()).is_err());
+
+        // secrets come from stdin, never argv
+        assert!(Args::parse(&strings(&["members", "--social-root", "xprv"])).is_err());
+        let args = Args::parse(&strings(&["admin-invites", "--admin-secret", "-", "--social-root", "-"]))
+            .unwrap()
+            .read_secrets(&mut "admin\nxprv\n".as_bytes())
+            .unwrap();
+        assert_eq!(args.required("admin_secret").unwrap(), "admin");
+        assert_eq!(args.required("social_root").unwrap(), "xprv");
+    }
+
+    #[test]
+    fn test_cli_tables() {
+        let members = json!({"identities": [{"username": "ishi", "pubkey": "ab"}, {"username": "sushi", "pubkey": "cd"}]});
+        assert_eq!(render_table("members", &members), "USERNAME  PUBKEY\nishi      ab\nsushi     cd");
+        assert_eq!(render_table("last-index", &json!({"last_used": 4})), "FIELD      VALUE\nlast_used  4");
+        let post = json!({"genesis": 1, "owner": "ab", "post": {"payload": {"kind": "Message", "value": "hi"}}});
+        let chats = json!({"verified": [
+            {"counter_party": "ab", "username": "ishi", "flags": ["Left"], "posts": [post]},
+            {"counter_party": "cd", "posts": [post]},
+        ], "corrupted": []});
+        assert_eq!(
+            render_table("chats", &chats),
+            "CHAT         GENESIS  FROM  KIND     VALUE\nishi (Left)  1        ab    Message  hi\ncd           1        ab    Message  hi"
+        );
+    }
+}
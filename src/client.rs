@@ -0,0 +1,1454 @@
+//! Safe Rust API over a cypherpost server.
+//!
+//! The C ABI in the crate root parses its string inputs and calls into this module.
+//! Rust callers can use it directly with typed inputs and outputs.
+//!
+//! ```no_run
+//! use cpclient::client::Client;
+//! use cpclient::network::identity::model::UserIdentity;
+//!
+//! # fn main() -> Result<(), cpclient::Error> {
+//! let me = UserIdentity::new("xprv...".to_string())?;
+//! let client = Client::new("localhost:3021");
+//! let posts = client.posts(&me, None)?;
+//! println!("{} chats", posts.verified.len());
+//! # Ok(())
+//! # }
+//! ```
+use std::fs::OpenOptions;
+use std::io::Read;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bitcoin::secp256k1::XOnlyPublicKey;
+use bitcoin::util::bip32::ExtendedPrivKey;
+
+use crate::key::child::{self, SocialRoot};
+use crate::key::ec::XOnlyPair;
+use crate::network::badge::{
+    self,
+    model::{Badge, BadgeDetails},
+    reputation::{Explanation, FilterPolicy, PostFilter, Reputation, Scores},
+};
+use crate::network::handler::{AnnouncementType, InvitePermission, NetworkConfig};
+use crate::network::identity::{
+    self,
+    contact::Contacts,
+    directory::MemberDirectory,
+    profile::{Profile, Profiles, SignedProfile},
+    model::{
+        Invitation, InvitationDetail, Invitations, JoinInvoice, MemberIdentity, Members, ServerIdentity,
+        UserIdentity, UsernameCheck,
+    },
+    username,
+};
+use crate::network::post::{
+    self,
+    attachment::{self, AttachmentChunk, AttachmentManifest},
+    conversation::{self, ConversationState, Conversations},
+    dto::ServerPostRequest,
+    payment::PaymentRequest,
+    outbox::{Outbox, OutboxState},
+    receipt::{self, Receipt, ReceiptKind, ReceiptSettings, SentReceipts},
+    recovery::{self, Recoveries, RecoverySet},
+    trade::{self, Trades},
+    model::{
+        DecryptionKey, DerivationIndex, LocalPostModel, Payload, PayloadKind, Post, PostLinks, Recipient, RecipientKind,
+        SortedPosts,
+    },
+};
+use crate::util::e::{ErrorKind, S5Error};
+
+/// How often `wait_for_payment` polls the server.
+pub const PAYMENT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// How often `drain_outbox` retries while the server is unreachable.
+pub const OUTBOX_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Derive a social root from a master xprv using the bip85 application at `account`.
+pub fn create_social_root(master_root: &ExtendedPrivKey, account: u32) -> Result<SocialRoot, S5Error> {
+    child::social_root(master_root.to_string(), account)
+}
+
+/// A connection to one cypherpost server.
+/// Calls that act as a member take that member's `UserIdentity`.
+#[derive(Debug, Clone)]
+pub struct Client {
+    pub host: String,
+    pub network: NetworkConfig,
+}
+
+impl Client {
+    /// Client with no proxy and the global network defaults.
+    pub fn new(host: &str) -> Self {
+        Client {
+            host: host.to_string(),
+            network: NetworkConfig::default(),
+        }
+    }
+    pub fn with_network(mut self, network: NetworkConfig) -> Self {
+        self.network = network;
+        self
+    }
+
+    fn keys(me: &UserIdentity) -> XOnlyPair {
+        XOnlyPair::from_xprv(me.social_root)
+    }
+
+    /// Server name and kind (private or public).
+    pub fn server_identity(&self, me: &UserIdentity) -> Result<ServerIdentity, S5Error> {
+        identity::dto::get_server_id(self.host.clone(), self.network.clone(), Client::keys(me))
+    }
+
+    /// Create an invite code using the server's admin secret.
+    pub fn admin_invite(
+        &self,
+        admin_secret: &str,
+        permission: InvitePermission,
+    ) -> Result<Invitation, S5Error> {
+        identity::dto::admin_invite(
+            self.host.clone(),
+            self.network.clone(),
+            admin_secret.to_string(),
+            permission,
+        )
+    }
+
+    /// Create a standard invite code from a privileged invite code.
+    pub fn priv_user_invite(&self, me: &UserIdentity, invite_code: &str) -> Result<Invitation, S5Error> {
+        identity::dto::user_invite(
+            self.host.clone(),
+            self.network.clone(),
+            Client::keys(me),
+            invite_code.to_string(),
+        )
+    }
+
+    /// Status of an invite code: who created it, whether it was claimed and by whom.
+    /// Does not require membership, so a code can be checked before `join`.
+    pub fn invite_detail(&self, me: &UserIdentity, invite_code: &str) -> Result<InvitationDetail, S5Error> {
+        identity::dto::invite_detail(
+            self.host.clone(),
+            self.network.clone(),
+            Client::keys(me),
+            invite_code.to_string(),
+        )
+    }
+
+    /// Invites created from my privileged invite.
+    pub fn my_invites(&self, me: &UserIdentity) -> Result<Invitations, S5Error> {
+        identity::dto::user_invites(self.host.clone(), self.network.clone(), Client::keys(me))
+    }
+
+    /// Invites created with the admin secret.
+    pub fn admin_invites(&self, admin_secret: &str) -> Result<Invitations, S5Error> {
+        identity::dto::admin_invites(self.host.clone(), self.network.clone(), admin_secret.to_string())
+    }
+
+    /// Revoke an unclaimed invite I created. Its slot goes back to my privileged invite.
+    pub fn revoke_invite(&self, me: &UserIdentity, invite_code: &str) -> Result<(), S5Error> {
+        identity::dto::revoke_invite(
+            self.host.clone(),
+            self.network.clone(),
+            Client::keys(me),
+            invite_code.to_string(),
+        )
+    }
+
+    /// Revoke an unclaimed invite created with the admin secret.
+    pub fn admin_revoke_invite(&self, admin_secret: &str, invite_code: &str) -> Result<(), S5Error> {
+        identity::dto::admin_revoke_invite(
+            self.host.clone(),
+            self.network.clone(),
+            admin_secret.to_string(),
+            invite_code.to_string(),
+        )
+    }
+
+    pub fn members(&self, me: &UserIdentity) -> Result<Members, S5Error> {
+        identity::dto::get_all(self.host.clone(), self.network.clone(), Client::keys(me))
+    }
+
+    /// Check a username against the naming policy and the server's members, e.g. before sharing an invite.
+    /// Invalid names fail locally with an Input error. The member list is only shown to members.
+    pub fn check_username(&self, me: &UserIdentity, username: &str) -> Result<UsernameCheck, S5Error> {
+        username::validate(username)?;
+        username::check(username, &self.members(me)?.identities)
+    }
+
+    /// Register `me` on a private server. The username is normalized before it is sent.
+    /// The server does not look for lookalike names, so the result lists members whose names
+    /// look like the new one in `confusable_with`; warn the user if it is not empty.
+    pub fn join(
+        &self,
+        me: &UserIdentity,
+        username: &str,
+        invite_code: &str,
+    ) -> Result<InvitationDetail, S5Error> {
+        let mut detail = identity::dto::register(
+            self.host.clone(),
+            self.network.clone(),
+            Client::keys(me),
+            invite_code.to_string(),
+            username.to_string(),
+        )?;
+        // already a member at this point, so a failed listing only loses the warning
+        if let Ok(members) = self.members(me) {
+            let pubkey = Client::keys(me).pubkey;
+            let others: Vec<MemberIdentity> =
+                members.identities.into_iter().filter(|member| member.pubkey != pubkey).collect();
+            detail.confusable_with =
+                username::confusable_members(username, &others).into_iter().map(|member| member.username).collect();
+        }
+        Ok(detail)
+    }
+
+    /// Request an invoice to join a public server. Pay it, then call `join_paid`.
+    pub fn join_invoice(&self, me: &UserIdentity) -> Result<JoinInvoice, S5Error> {
+        identity::dto::join_invoice(self.host.clone(), self.network.clone(), Client::keys(me))
+    }
+
+    pub fn invoice_status(&self, me: &UserIdentity, invoice_id: &str) -> Result<JoinInvoice, S5Error> {
+        identity::dto::invoice_status(
+            self.host.clone(),
+            self.network.clone(),
+            Client::keys(me),
+            invoice_id.to_string(),
+        )
+    }
+
+    /// Poll an invoice until it is paid. Fails if it expires or `timeout` passes first.
+    pub fn wait_for_payment(
+        &self,
+        me: &UserIdentity,
+        invoice_id: &str,
+        timeout: Duration,
+    ) -> Result<JoinInvoice, S5Error> {
+        let deadline = match Instant::now().checked_add(timeout) {
+            Some(deadline) => deadline,
+            None => return Err(S5Error::new(ErrorKind::Input, "Timeout is too large.")),
+        };
+        loop {
+            let invoice = self.invoice_status(me, invoice_id)?;
+            match invoice.status.as_str() {
+                "PAID" => return Ok(invoice),
+                "EXPIRED" => return Err(S5Error::new(ErrorKind::Input, "Invoice expired before it was paid.")),
+                _ => {}
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(S5Error::new(ErrorKind::Network, "Timed out waiting for payment."));
+            }
+            thread::sleep(std::cmp::min(PAYMENT_POLL_INTERVAL, deadline - now));
+        }
+    }
+
+    /// Register `me` on a public server with the credential from a paid invoice.
+    pub fn join_paid(
+        &self,
+        me: &UserIdentity,
+        username: &str,
+        invoice_id: &str,
+    ) -> Result<InvitationDetail, S5Error> {
+        let invoice = self.invoice_status(me, invoice_id)?;
+        match invoice.invite_code {
+            Some(invite_code) if invoice.status == "PAID" => self.join(me, username, &invite_code),
+            _ => Err(S5Error::new(
+                ErrorKind::Input,
+                &format!("Invoice is {}, it must be PAID to join.", invoice.status),
+            )),
+        }
+    }
+
+    /// Delete `me` and all my posts from the server.
+    pub fn leave(&self, me: &UserIdentity) -> Result<(), S5Error> {
+        identity::dto::delete(self.host.clone(), self.network.clone(), Client::keys(me))
+    }
+
+    /// Encrypt and upload a post using the key at derivation `index`. Returns the post id.
+    /// Recipients cannot read it until `send_keys` is called for them.
+    pub fn send_post(
+        &self,
+        me: &UserIdentity,
+        index: u32,
+        to: Recipient,
+        payload: Payload,
+    ) -> Result<String, S5Error> {
+        self.send_post_with_links(me, index, to, payload, PostLinks::default())
+    }
+
+    /// Same as `send_post`, for a post that replies to, edits or reacts to an earlier one.
+    pub fn send_post_with_links(
+        &self,
+        me: &UserIdentity,
+        index: u32,
+        to: Recipient,
+        payload: Payload,
+        links: PostLinks,
+    ) -> Result<String, S5Error> {
+        let request = Client::post_request(me, index, to, payload, links)?;
+        post::dto::create(self.host.clone(), self.network.clone(), Client::keys(me), request)
+    }
+
+    /// Check, sign and encrypt a post for upload at `index`.
+    fn post_request(
+        me: &UserIdentity,
+        index: u32,
+        to: Recipient,
+        payload: Payload,
+        links: PostLinks,
+    ) -> Result<ServerPostRequest, S5Error> {
+        payload.check()?;
+        links.check(&payload)?;
+        let xonly_pair = Client::keys(me);
+        if let PayloadKind::PaymentRequest = payload.kind {
+            let request = PaymentRequest::structify(&payload.value)?;
+            request.verify(&xonly_pair.pubkey, me.social_root.network)?;
+            if request.is_expired() {
+                return Err(S5Error::new(ErrorKind::Input, "Payment request has already expired."));
+            }
+        }
+        let post = Post::with_links(to, payload, links, xonly_pair);
+        let cypher = post.to_cypher(me.derive_encryption_key(index));
+        Ok(ServerPostRequest::new(0, index, &cypher))
+    }
+
+    /// Share the decryption key of the post made at `index` with each of `recipients`.
+    pub fn send_keys(
+        &self,
+        me: &UserIdentity,
+        index: u32,
+        post_id: &str,
+        recipients: &[XOnlyPublicKey],
+    ) -> Result<(), S5Error> {
+        if recipients.is_empty() {
+            return Err(S5Error::new(ErrorKind::Input, "Unable to parse recipients."));
+        }
+        let xonly_pair = Client::keys(me);
+        let decryption_keys = DecryptionKey::make_for_many(
+            xonly_pair.clone(),
+            recipients.to_vec(),
+            me.derive_encryption_key(index),
+        )?;
+        post::dto::keys(
+            self.host.clone(),
+            self.network.clone(),
+            xonly_pair,
+            post_id.to_string(),
+            decryption_keys,
+        )
+    }
+
+    /// Fetch and decrypt a single post.
+    pub fn post(&self, me: &UserIdentity, post_id: &str) -> Result<LocalPostModel, S5Error> {
+        let cypherpost = post::dto::single_post(
+            self.host.clone(),
+            self.network.clone(),
+            Client::keys(me),
+            post_id.to_string(),
+        )?;
+        cypherpost.decypher(me.social_root)
+    }
+
+    /// All posts visible to `me`, grouped into chats.
+    /// `genesis_filter` only fetches posts newer than the given timestamp.
+    pub fn posts(&self, me: &UserIdentity, genesis_filter: Option<u64>) -> Result<SortedPosts, S5Error> {
+        self.sorted_posts(me, genesis_filter, None)
+    }
+
+    fn sorted_posts(
+        &self,
+        me: &UserIdentity,
+        genesis_filter: Option<u64>,
+        filter: Option<&PostFilter>,
+    ) -> Result<SortedPosts, S5Error> {
+        let mut all = post::dto::get_all_posts(
+            self.host.clone(),
+            self.network.clone(),
+            me.social_root,
+            genesis_filter,
+        )?;
+        Ok(all.to_all_posts_as_chat(Client::keys(me).pubkey, me.social_root.network, filter))
+    }
+
+    /// Sign `profile` and post it, readable by `audience` or by every other member if None.
+    /// Returns the post id.
+    pub fn publish_profile(
+        &self,
+        me: &UserIdentity,
+        index: u32,
+        profile: Profile,
+        audience: Option<&[XOnlyPublicKey]>,
+    ) -> Result<String, S5Error> {
+        let xonly_pair = Client::keys(me);
+        let signed = SignedProfile::new(profile, &xonly_pair)?;
+        let audience = match audience {
+            Some(audience) => audience.to_vec(),
+            None => self
+                .members(me)?
+                .identities
+                .into_iter()
+                .map(|member| member.pubkey)
+                .filter(|pubkey| *pubkey != xonly_pair.pubkey)
+                .collect(),
+        };
+        let to = Recipient::new(RecipientKind::Group, "profiles".to_string());
+        let post_id = self.send_post(me, index, to, Payload::new(PayloadKind::Profile, signed.stringify()))?;
+        if !audience.is_empty() {
+            self.send_keys(me, index, &post_id, &audience)?;
+        }
+        Ok(post_id)
+    }
+
+    /// Latest verified profile of each member who shared one with `me`, including my own.
+    pub fn profiles(&self, me: &UserIdentity) -> Result<Profiles, S5Error> {
+        Ok(self.posts(me, None)?.profiles)
+    }
+
+    /// Bring a cached member directory up to date, fetching only the changes since it was last refreshed.
+    /// Start from `MemberDirectory::default()`.
+    pub fn refresh_directory(&self, me: &UserIdentity, directory: &mut MemberDirectory) -> Result<(), S5Error> {
+        let changes = identity::dto::get_changes(
+            self.host.clone(),
+            self.network.clone(),
+            Client::keys(me),
+            directory.latest_genesis,
+        )?;
+        directory.apply(changes.changes);
+        Ok(())
+    }
+
+    /// `posts`, with direct chats labelled from `directory` and `contacts`. Refresh the directory first.
+    /// With a `policy`, chats are also filtered by the reputation of their members, see `PostFilter`.
+    pub fn chats(
+        &self,
+        me: &UserIdentity,
+        genesis_filter: Option<u64>,
+        directory: &MemberDirectory,
+        contacts: &Contacts,
+        policy: Option<&FilterPolicy>,
+    ) -> Result<SortedPosts, S5Error> {
+        let filter = match policy {
+            Some(policy) => Some(PostFilter {
+                reputation: self.reputation(me)?,
+                policy: policy.clone(),
+            }),
+            None => None,
+        };
+        let mut sorted = self.sorted_posts(me, genesis_filter, filter.as_ref())?;
+        sorted.resolve_contacts(directory);
+        sorted.mark_verified(contacts, directory);
+        Ok(sorted)
+    }
+
+    /// One page of `chats`, most recently active first, with previews and unread counts from `state`.
+    /// A `limit` of 0 returns every conversation after `offset`.
+    pub fn conversations(
+        &self,
+        me: &UserIdentity,
+        directory: &MemberDirectory,
+        contacts: &Contacts,
+        state: &ConversationState,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Conversations, S5Error> {
+        let sorted = self.chats(me, None, directory, contacts, None)?;
+        Ok(conversation::summarize(&Client::keys(me).pubkey, &sorted, state, offset, limit))
+    }
+
+    /// Last derivation index used by `me`. Only needed when recovering local state.
+    pub fn last_index(&self, me: &UserIdentity) -> Result<DerivationIndex, S5Error> {
+        post::dto::last_derivation(self.host.clone(), self.network.clone(), Client::keys(me))
+    }
+
+    /// Split `secret` into `threshold`-of-n shares and send one to each of `holders` as a direct Secret post,
+    /// using the derivation indexes after the last one used. Keep the returned set to know who holds which share.
+    /// If a send fails the set is still returned, with the holders sent so far and the failure in `error`.
+    /// A holder whose post went up but whose keys did not is marked `keys_pending`.
+    pub fn distribute_shares(
+        &self,
+        me: &UserIdentity,
+        secret: &str,
+        threshold: u8,
+        holders: &[XOnlyPublicKey],
+        label: Option<String>,
+    ) -> Result<RecoverySet, S5Error> {
+        if holders.contains(&Client::keys(me).pubkey) {
+            return Err(S5Error::new(ErrorKind::Input, "Cannot hold a share of your own secret."));
+        }
+        let (mut set, shares) = recovery::split(secret, threshold, holders, label)?;
+        let first = self.next_index(me)?;
+        for (index, (holder, share)) in (first..).zip(set.holders.iter_mut().zip(shares.iter())) {
+            let to = Recipient::new(RecipientKind::Direct, holder.pubkey.to_string());
+            let sent = share.stringify().and_then(|share| self.send_post(me, index, to, Payload::new(PayloadKind::Secret, share)));
+            let post_id = match sent {
+                Ok(post_id) => post_id,
+                Err(e) => {
+                    set.error = Some(e);
+                    break;
+                }
+            };
+            holder.post_id = Some(post_id.clone());
+            if let Err(e) = self.send_keys(me, index, &post_id, &[holder.pubkey]) {
+                holder.keys_pending = Some(index);
+                set.error = Some(e);
+                break;
+            }
+        }
+        Ok(set)
+    }
+
+    /// Recovery shares other members have sent to `me`, grouped by set, with each secret that has enough shares rebuilt.
+    /// As a guardian these are the shares held for others; on a new identity, the shares guardians returned.
+    pub fn recoveries(&self, me: &UserIdentity) -> Result<Recoveries, S5Error> {
+        let all = post::dto::get_all_posts(self.host.clone(), self.network.clone(), me.social_root, None)?;
+        Ok(recovery::recoveries(recovery::held_shares(&Client::keys(me).pubkey, &all.posts)))
+    }
+
+    /// As a guardian, send the share of set `set_id` held by `me` to the owner's new identity `to`.
+    /// Check `to` out of band first, e.g. with safety numbers: whoever gets enough shares gets the secret.
+    pub fn return_share(&self, me: &UserIdentity, set_id: &str, to: XOnlyPublicKey) -> Result<String, S5Error> {
+        let all = post::dto::get_all_posts(self.host.clone(), self.network.clone(), me.social_root, None)?;
+        let held = match recovery::held_shares(&Client::keys(me).pubkey, &all.posts)
+            .into_iter()
+            .find(|held| held.share.set_id == set_id)
+        {
+            Some(held) => held,
+            None => return Err(S5Error::new(ErrorKind::NoResource, "No share held for this set.")),
+        };
+        let index = self.next_index(me)?;
+        let recipient = Recipient::new(RecipientKind::Direct, to.to_string());
+        let payload = Payload::new(PayloadKind::Secret, held.share.stringify()?);
+        let post_id = self.send_post(me, index, recipient, payload)?;
+        self.send_keys(me, index, &post_id, &[to])?;
+        Ok(post_id)
+    }
+
+    /// The state of every trade negotiated in posts visible to `me`.
+    /// Badge prompts for badges `me` already gave for a trade are dropped.
+    pub fn trades(&self, me: &UserIdentity) -> Result<Trades, S5Error> {
+        let pubkey = Client::keys(me).pubkey;
+        let all = post::dto::get_all_posts(self.host.clone(), self.network.clone(), me.social_root, None)?;
+        let mut trades = trade::fold(&pubkey, &all.posts);
+        if trades.trades.iter().any(|trade| !trade.prompts.is_empty()) {
+            let given: Vec<Badge> = self.badges(me)?.into_iter().filter(|badge| badge.by == pubkey).collect();
+            for trade in trades.trades.iter_mut() {
+                trade.prompts.retain(|prompt| {
+                    !given
+                        .iter()
+                        .any(|badge| badge.to == prompt.to && badge.details.reference == prompt.details.reference)
+                });
+            }
+        }
+        Ok(trades)
+    }
+
+    /// Send `kind` receipts for the posts others sent `me`, one direct post per owner and batch.
+    /// `post_ids` limits them to those posts; None acknowledges every post not yet acknowledged.
+    /// Conversations turned off in `settings` are skipped.
+    /// If a send fails the receipts sent so far are still returned, with the failure in `error`.
+    pub fn send_receipts(
+        &self,
+        me: &UserIdentity,
+        kind: ReceiptKind,
+        post_ids: Option<&[String]>,
+        settings: &ReceiptSettings,
+    ) -> Result<SentReceipts, S5Error> {
+        let all = post::dto::get_all_posts(self.host.clone(), self.network.clone(), me.social_root, None)?;
+        let pending = receipt::pending(&Client::keys(me).pubkey, &all.posts, kind, settings, post_ids);
+        let mut sent = SentReceipts::default();
+        if pending.is_empty() {
+            return Ok(sent);
+        }
+        let batches = pending
+            .into_iter()
+            .flat_map(|(owner, batches)| batches.into_iter().map(move |batch| (owner, batch)));
+        for (index, (owner, post_ids)) in (self.next_index(me)?..).zip(batches) {
+            let to = Recipient::new(RecipientKind::Direct, owner.to_string());
+            let result = Receipt { kind, post_ids }.stringify().and_then(|receipt| {
+                let post_id = self.send_post(me, index, to, Payload::new(PayloadKind::Receipt, receipt))?;
+                self.send_keys(me, index, &post_id, &[owner])?;
+                Ok(post_id)
+            });
+            match result {
+                Ok(post_id) => sent.ids.push(post_id),
+                Err(e) => {
+                    sent.error = Some(e);
+                    break;
+                }
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Encrypt everything in `reader` as an attachment and send it to `to`, readable by `recipients`.
+    /// The chunks and then the manifest use the derivation indexes after the last one used.
+    /// Returns the id of the Attachment post.
+    pub fn send_attachment(
+        &self,
+        me: &UserIdentity,
+        to: Recipient,
+        recipients: &[XOnlyPublicKey],
+        reader: &mut impl Read,
+        name: &str,
+        mime: Option<String>,
+    ) -> Result<String, S5Error> {
+        let mut index = self.next_index(me)?;
+        let manifest = attachment::seal(reader, name, mime, |chunk| {
+            let post_id = self.send_post(me, index, to.clone(), Payload::new(PayloadKind::Chunk, chunk.stringify()?))?;
+            self.send_keys(me, index, &post_id, recipients)?;
+            index += 1;
+            Ok(post_id)
+        })?;
+        let post_id = self.send_post(me, index, to, Payload::new(PayloadKind::Attachment, manifest.stringify()?))?;
+        self.send_keys(me, index, &post_id, recipients)?;
+        Ok(post_id)
+    }
+
+    /// Download and decrypt an attachment to `path`. If `path` holds part of it from an earlier attempt,
+    /// only the missing chunks are fetched.
+    pub fn download_attachment(&self, me: &UserIdentity, manifest: &AttachmentManifest, path: &Path) -> Result<(), S5Error> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(|e| S5Error::new(ErrorKind::Input, &format!("Could not open {}: {}", path.display(), e)))?;
+        attachment::download(manifest, &mut file, |chunk| {
+            AttachmentChunk::from_post(&self.post(me, &chunk.post_id)?)
+        })
+    }
+
+    /// Send the posts queued in the outbox at `path`, in order, then share their keys.
+    /// Stops at the first connection error, leaving that entry and the ones after it for the next flush.
+    /// Entries the server rejects for another reason are marked failed and skipped.
+    /// Returns every entry with its state; sent entries are then dropped from the file.
+    pub fn flush_outbox(&self, me: &UserIdentity, path: &Path) -> Result<Outbox, S5Error> {
+        let storage_key = me.derive_storage_key();
+        let mut outbox = Outbox::load(path, &storage_key)?;
+        'entries: for position in 0..outbox.entries.len() {
+            while outbox.entries[position].is_pending() {
+                let step = self.outbox_step(me, &mut outbox, position);
+                let entry = &mut outbox.entries[position];
+                match step {
+                    Ok(()) => entry.last_error = None,
+                    Err(e) if e.kind == ErrorKind::Network.to_string() => {
+                        entry.last_error = Some(e.error);
+                        outbox.save(path, &storage_key)?;
+                        break 'entries;
+                    }
+                    Err(e) => {
+                        entry.state = OutboxState::Failed;
+                        entry.last_error = Some(e.error);
+                    }
+                }
+                outbox.save(path, &storage_key)?;
+            }
+        }
+        let report = outbox.clone();
+        outbox.entries.retain(|entry| entry.state != OutboxState::Sent);
+        outbox.save(path, &storage_key)?;
+        Ok(report)
+    }
+
+    /// Move one outbox entry on by one state.
+    fn outbox_step(&self, me: &UserIdentity, outbox: &mut Outbox, position: usize) -> Result<(), S5Error> {
+        let reserved = outbox.last_reserved();
+        let entry = &mut outbox.entries[position];
+        match (entry.state, entry.index, entry.request.clone(), entry.post_id.clone()) {
+            (OutboxState::Queued, _, _, _) => {
+                let index = self.outbox_index(me, reserved)?;
+                let request =
+                    Client::post_request(me, index, entry.to.clone(), entry.payload.clone(), entry.links.clone())?;
+                entry.index = Some(index);
+                entry.request = Some(request);
+                entry.state = OutboxState::Uploading;
+            }
+            // senders outside the outbox only see the server's last index, so one may have used this one meanwhile
+            (OutboxState::Uploading, Some(index), Some(request), _) if self.next_index(me)? > index => {
+                let mine = post::dto::my_posts(self.host.clone(), self.network.clone(), Client::keys(me), None)?
+                    .into_iter()
+                    .find(|post| request.matches(post));
+                match mine {
+                    // uploaded before, the response was lost
+                    Some(post) => {
+                        entry.post_id = Some(post.id);
+                        entry.request = None;
+                        entry.state = OutboxState::KeysPending;
+                    }
+                    // taken by another post, whose key this one must not share
+                    None => {
+                        let index = self.outbox_index(me, reserved)?;
+                        let request = Client::post_request(
+                            me,
+                            index,
+                            entry.to.clone(),
+                            entry.payload.clone(),
+                            entry.links.clone(),
+                        )?;
+                        entry.index = Some(index);
+                        entry.request = Some(request);
+                    }
+                }
+            }
+            (OutboxState::Uploading, Some(_), Some(request), _) => {
+                let post_id = post::dto::create(self.host.clone(), self.network.clone(), Client::keys(me), request)?;
+                entry.post_id = Some(post_id);
+                entry.request = None;
+                entry.state = OutboxState::KeysPending;
+            }
+            (OutboxState::KeysPending, Some(index), _, Some(post_id)) => {
+                self.send_keys(me, index, &post_id, &entry.recipients)?;
+                entry.state = OutboxState::Sent;
+            }
+            _ => return Err(S5Error::new(ErrorKind::Internal, "Outbox entry is missing its index or post.")),
+        }
+        Ok(())
+    }
+
+    /// The index for an outbox entry: past both the server's last index and any other entry's reservation.
+    fn outbox_index(&self, me: &UserIdentity, reserved: Option<u32>) -> Result<u32, S5Error> {
+        match reserved {
+            Some(reserved) => Ok(std::cmp::max(reserved + 1, self.next_index(me)?)),
+            None => self.next_index(me),
+        }
+    }
+
+    /// `flush_outbox` until nothing is left to send, retrying every `OUTBOX_RETRY_INTERVAL` while the
+    /// server is unreachable. Returns the entries handled, with their last state, once empty or after `timeout`.
+    pub fn drain_outbox(&self, me: &UserIdentity, path: &Path, timeout: Duration) -> Result<Outbox, S5Error> {
+        let deadline = match Instant::now().checked_add(timeout) {
+            Some(deadline) => deadline,
+            None => return Err(S5Error::new(ErrorKind::Input, "Timeout is too large.")),
+        };
+        let mut report = Outbox::default();
+        loop {
+            let flushed = self.flush_outbox(me, path)?;
+            let done = flushed.is_empty();
+            for entry in flushed.entries {
+                match report.entries.iter_mut().find(|reported| reported.id == entry.id) {
+                    Some(reported) => *reported = entry,
+                    None => report.entries.push(entry),
+                }
+            }
+            let now = Instant::now();
+            if done || now >= deadline {
+                return Ok(report);
+            }
+            thread::sleep(std::cmp::min(OUTBOX_RETRY_INTERVAL, deadline - now));
+        }
+    }
+
+    /// The derivation index after the last one used, 0 before the first post.
+    fn next_index(&self, me: &UserIdentity) -> Result<u32, S5Error> {
+        match self.last_index(me) {
+            Ok(result) => Ok(result.last_used + 1),
+            Err(e) if e.kind == ErrorKind::NoResource.to_string() => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Badges given by or to `me`.
+    pub fn badges(&self, me: &UserIdentity) -> Result<Vec<Badge>, S5Error> {
+        badge::dto::get_all(self.host.clone(), self.network.clone(), Client::keys(me))
+    }
+
+    pub fn give_badge(
+        &self,
+        me: &UserIdentity,
+        to: XOnlyPublicKey,
+        kind: AnnouncementType,
+    ) -> Result<Badge, S5Error> {
+        self.give_badge_with_details(me, to, kind, BadgeDetails::default())
+    }
+
+    /// `give_badge` with a reason, expiry or reference signed into the badge.
+    pub fn give_badge_with_details(
+        &self,
+        me: &UserIdentity,
+        to: XOnlyPublicKey,
+        kind: AnnouncementType,
+        details: BadgeDetails,
+    ) -> Result<Badge, S5Error> {
+        let badge = Badge::with_details(kind, Client::keys(me), to, details)?;
+        badge::dto::announce(self.host.clone(), self.network.clone(), Client::keys(me), badge.clone())?;
+        Ok(badge)
+    }
+
+    pub fn revoke_badge(&self, me: &UserIdentity, badge: &Badge) -> Result<(), S5Error> {
+        badge::dto::revoke(self.host.clone(), self.network.clone(), Client::keys(me), badge.clone())
+    }
+
+    /// Badge graph as seen from `me`. Badges that fail verification are dropped.
+    pub fn reputation(&self, me: &UserIdentity) -> Result<Reputation, S5Error> {
+        Ok(Reputation::build(Client::keys(me).pubkey, self.badges(me)?))
+    }
+
+    /// Reputation of every member who has received a badge, highest first.
+    pub fn scores(&self, me: &UserIdentity) -> Result<Scores, S5Error> {
+        Ok(self.reputation(me)?.scores())
+    }
+
+    /// Which badges make up `member`'s score, and how their issuers connect to `me`.
+    pub fn explain(&self, me: &UserIdentity, member: &XOnlyPublicKey) -> Result<Explanation, S5Error> {
+        Ok(self.reputation(me)?.explain(member))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::seed::MasterKeySeed;
+    use crate::network::identity::directory::ContactFlag;
+    use crate::network::mock::{MockServer, ADMIN_SECRET};
+    use crate::network::post::model::PostsAsChat;
+    use crate::network::post::trade::{TradeAction, TradeMessage, TradeSide, TradeStatus, TradeTerms};
+
+    fn new_identity() -> UserIdentity {
+        let seed = MasterKeySeed::generate(12, "", bitcoin::network::constants::Network::Bitcoin).unwrap();
+        UserIdentity::new(create_social_root(&seed.xprv, 0).unwrap().xprv).unwrap()
+    }
+
+    /// New identities joined as `names`, the first with an admin invite and the rest invited by the first.
+    fn members<const N: usize>(client: &Client, names: &[&str; N]) -> [UserIdentity; N] {
+        let identities = [(); N].map(|_| new_identity());
+        let invite = client.admin_invite(ADMIN_SECRET, InvitePermission::Privilege(N - 1)).unwrap();
+        for (position, (who, name)) in identities.iter().zip(names).enumerate() {
+            let code = match position {
+                0 => invite.invite_code.clone(),
+                _ => client.priv_user_invite(&identities[0], &invite.invite_code).unwrap().invite_code,
+            };
+            client.join(who, name, &code).unwrap();
+        }
+        identities
+    }
+
+    #[test]
+    fn test_client_inputs() {
+        let seed = MasterKeySeed::generate(12, "", bitcoin::network::constants::Network::Bitcoin).unwrap();
+        let social_root = create_social_root(&seed.xprv, 0).unwrap();
+        let me = UserIdentity::new(social_root.xprv.clone()).unwrap();
+        assert_eq!(Client::keys(&me).pubkey.to_string(), social_root.pubkey);
+
+        // rejected before any network call is made
+        let client = Client::new("localhost:0");
+        let error = client.send_keys(&me, 0, "post_id", &[]).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Input.to_string());
+        assert_eq!(error.to_string(), "Input: Unable to parse recipients.");
+        let to = Recipient::new(RecipientKind::Group, "vault".to_string());
+        let payload = Payload::new(PayloadKind::Psbt, "not a psbt".to_string());
+        let error = client.send_post(&me, 1, to.clone(), payload).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Input.to_string());
+        // a mainnet identity cannot request testnet coins, nor send a request that has expired
+        for (address, expiry) in [("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx", None), ("bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l", Some(1))] {
+            let request = PaymentRequest::new(address, Some(1_000), None, expiry).unwrap();
+            let payload = Payload::new(PayloadKind::PaymentRequest, request.stringify().unwrap());
+            let error = client.send_post(&me, 1, to.clone(), payload).unwrap_err();
+            assert_eq!(error.kind, ErrorKind::Input.to_string());
+        }
+    }
+
+    #[test]
+    fn test_client_flow() {
+        let server = MockServer::start();
+        let client = Client::new(&server.url());
+        let alice = new_identity();
+        let bob = new_identity();
+        let alice_pubkey = Client::keys(&alice).pubkey;
+        let bob_pubkey = Client::keys(&bob).pubkey;
+
+        assert_eq!(client.server_identity(&alice).unwrap().kind, "PRIVATE");
+        let invite = client.admin_invite(ADMIN_SECRET, InvitePermission::Privilege(1)).unwrap();
+        client.join(&alice, "alice", &invite.invite_code).unwrap();
+        let invite = client.priv_user_invite(&alice, &invite.invite_code).unwrap();
+        assert_eq!(client.invite_detail(&bob, &invite.invite_code).unwrap().status, "PENDING");
+        client.join(&bob, "bob", &invite.invite_code).unwrap();
+        assert_eq!(client.members(&bob).unwrap().identities.len(), 2);
+
+        let to = Recipient::new(RecipientKind::Direct, bob_pubkey.to_string());
+        let payload = Payload::new(PayloadKind::Message, "hi bob".to_string());
+        let post_id = client.send_post(&alice, 7, to, payload).unwrap();
+        client.send_keys(&alice, 7, &post_id, &[bob_pubkey]).unwrap();
+        assert_eq!(client.post(&bob, &post_id).unwrap().post.payload.value, "hi bob");
+        let posts = client.posts(&bob, None).unwrap();
+        assert_eq!(posts.verified[0].counter_party, alice_pubkey.to_string());
+        assert_eq!(client.last_index(&alice).unwrap().last_used, 7);
+
+        let badge = client.give_badge(&bob, alice_pubkey, AnnouncementType::Trust).unwrap();
+        let badges = client.badges(&alice).unwrap();
+        assert_eq!(badges.len(), 1);
+        assert!(badges[0].verify());
+        let score = &client.scores(&bob).unwrap().scores[0];
+        assert!(score.direct_trust);
+        assert_eq!(score.score, 1.0);
+        assert_eq!(client.explain(&bob, &alice_pubkey).unwrap().children.len(), 1);
+        client.revoke_badge(&bob, &badge).unwrap();
+        assert!(client.badges(&alice).unwrap().is_empty());
+        let details = BadgeDetails {
+            reason: Some("smooth trade".to_string()),
+            expiry: None,
+            reference: Some(post_id.clone()),
+        };
+        client.give_badge_with_details(&bob, alice_pubkey, AnnouncementType::Escrow, details.clone()).unwrap();
+        let badges = client.badges(&alice).unwrap();
+        assert_eq!(badges[0].details, details);
+        assert!(badges[0].verify());
+
+        client.leave(&alice).unwrap();
+        assert!(client.post(&bob, &post_id).is_err());
+        assert_eq!(client.members(&bob).unwrap().identities.len(), 1);
+        client.leave(&bob).unwrap();
+        assert!(client.members(&bob).is_err());
+    }
+
+    #[test]
+    fn test_client_directory() {
+        let server = MockServer::start();
+        let client = Client::new(&server.url());
+        let [alice, bob] = members(&client, &["alice", "bob"]);
+        let mallory = new_identity();
+        let bob_pubkey = Client::keys(&bob).pubkey;
+        let stranger = Client::keys(&new_identity()).pubkey;
+
+        let mut directory = MemberDirectory::default();
+        client.refresh_directory(&alice, &mut directory).unwrap();
+        assert_eq!(directory.members().len(), 2);
+        let seen = directory.latest_genesis;
+        client.refresh_directory(&alice, &mut directory).unwrap();
+        assert_eq!(directory.latest_genesis, seen);
+
+        for (index, to) in [(1, bob_pubkey), (2, stranger)] {
+            let payload = Payload::new(PayloadKind::Message, "hi".to_string());
+            client.send_post(&alice, index, Recipient::new(RecipientKind::Direct, to.to_string()), payload).unwrap();
+        }
+        let mut contacts = Contacts::default();
+        contacts.verify("bob", bob_pubkey, &directory, false).unwrap();
+        client.leave(&bob).unwrap();
+        client.join(&mallory, "bob", &client.admin_invite(ADMIN_SECRET, InvitePermission::Standard).unwrap().invite_code).unwrap();
+        let payload = Payload::new(PayloadKind::Message, "it's bob, new phone".to_string());
+        let to = Recipient::new(RecipientKind::Direct, Client::keys(&alice).pubkey.to_string());
+        let post_id = client.send_post(&mallory, 1, to, payload).unwrap();
+        client.send_keys(&mallory, 1, &post_id, &[Client::keys(&alice).pubkey]).unwrap();
+        client.refresh_directory(&alice, &mut directory).unwrap();
+        assert_eq!(directory.entries.len(), 3);
+        assert_eq!(directory.members().len(), 2);
+
+        let chats = client.chats(&alice, None, &directory, &contacts, None).unwrap();
+        let chat = |pubkey: XOnlyPublicKey| chats.verified.iter().find(|chat| chat.counter_party == pubkey.to_string()).unwrap();
+        assert_eq!(chat(bob_pubkey).username.as_deref(), Some("bob"));
+        assert_eq!(
+            chat(bob_pubkey).flags,
+            [ContactFlag::Left, ContactFlag::UsernameReused, ContactFlag::Verified]
+        );
+        assert_eq!(chat(stranger).username, None);
+        assert_eq!(chat(stranger).flags, [ContactFlag::Unknown]);
+        let impostor = chat(Client::keys(&mallory).pubkey);
+        assert_eq!(impostor.flags, [ContactFlag::UsernameReused, ContactFlag::KeyChanged]);
+        assert_eq!(chats.key_changes.len(), 1);
+        assert_eq!(chats.key_changes[0].verified_pubkey, bob_pubkey);
+    }
+
+    #[test]
+    fn test_client_recovery() {
+        let server = MockServer::start();
+        let client = Client::new(&server.url());
+        let [alice, bob, carol, dave, new_alice] = members(&client, &["alice", "bob", "carol", "dave", "alice2"]);
+        let guardians = [Client::keys(&bob).pubkey, Client::keys(&carol).pubkey, Client::keys(&dave).pubkey];
+        let new_alice_pubkey = Client::keys(&new_alice).pubkey;
+
+        let mnemonic = MasterKeySeed::generate(12, "", bitcoin::network::constants::Network::Bitcoin).unwrap().mnemonic.to_string();
+        assert!(client.distribute_shares(&alice, &mnemonic, 2, &[Client::keys(&alice).pubkey], None).is_err());
+        let set = client.distribute_shares(&alice, &mnemonic, 2, &guardians, Some("wallet".to_string())).unwrap();
+        assert!(set.holders.iter().all(|holder| holder.post_id.is_some()));
+        assert!(set.error.is_none());
+        let held = client.recoveries(&bob).unwrap().recoveries;
+        assert_eq!(held.len(), 1);
+        assert_eq!(held[0].shares[0].owner, Client::keys(&alice).pubkey);
+        assert_eq!(held[0].secret, None);
+        assert!(client.recoveries(&alice).unwrap().recoveries.is_empty());
+
+        client.return_share(&bob, &set.id, new_alice_pubkey).unwrap();
+        assert_eq!(client.recoveries(&new_alice).unwrap().recoveries[0].secret, None);
+        client.return_share(&dave, &set.id, new_alice_pubkey).unwrap();
+        let recovered = &client.recoveries(&new_alice).unwrap().recoveries[0];
+        assert_eq!(recovered.secret.as_deref(), Some(mnemonic.as_str()));
+        assert_eq!(recovered.fingerprint, set.fingerprint);
+        assert_eq!(recovered.shares.len(), 2);
+        assert!(client.return_share(&alice, &set.id, new_alice_pubkey).is_err());
+
+        // the set survives a failure part way through
+        server.fail_posts_after(1);
+        let partial = client.distribute_shares(&alice, &mnemonic, 2, &guardians, None).unwrap();
+        assert!(partial.error.is_some());
+        assert!(partial.holders[0].post_id.is_some());
+        assert!(partial.holders[1..].iter().all(|holder| holder.post_id.is_none()));
+
+        // a post without its keys is kept and marked, so the keys can be sent later
+        server.restore();
+        server.fail_keys_after(1);
+        let partial = client.distribute_shares(&alice, &mnemonic, 2, &guardians, None).unwrap();
+        assert!(partial.error.is_some());
+        assert_eq!(partial.holders[0].keys_pending, None);
+        assert!(partial.holders[2].post_id.is_none());
+        let pending = &partial.holders[1];
+        assert_eq!(client.recoveries(&carol).unwrap().recoveries.len(), 1);
+        server.restore();
+        client.send_keys(&alice, pending.keys_pending.unwrap(), pending.post_id.as_ref().unwrap(), &[pending.pubkey]).unwrap();
+        assert_eq!(client.recoveries(&carol).unwrap().recoveries.len(), 2);
+    }
+
+    #[test]
+    fn test_client_trade() {
+        let server = MockServer::start();
+        let client = Client::new(&server.url());
+        let [alice, bob] = members(&client, &["alice", "bob"]);
+        let (alice_keys, bob_keys) = (Client::keys(&alice), Client::keys(&bob));
+        let send = |from: &UserIdentity, to: XOnlyPublicKey, index: u32, message: &TradeMessage| {
+            let payload = Payload::new(PayloadKind::Trade, message.stringify().unwrap());
+            let id = client.send_post(from, index, Recipient::new(RecipientKind::Direct, to.to_string()), payload).unwrap();
+            client.send_keys(from, index, &id, &[to]).unwrap();
+        };
+
+        let terms = TradeTerms {
+            side: TradeSide::Buy,
+            amount: 50_000,
+            price: "30 USD".to_string(),
+            payment_method: "cash".to_string(),
+            escrow: None,
+            deadline: u64::MAX,
+        };
+        send(&alice, bob_keys.pubkey, 1, &TradeMessage::offer(&alice_keys, terms).unwrap());
+        let trade = client.trades(&bob).unwrap().trades[0].clone();
+        send(&bob, alice_keys.pubkey, 1, &trade.respond(&bob_keys, TradeAction::Accept, None).unwrap());
+        let trade = client.trades(&alice).unwrap().trades[0].clone();
+        send(&alice, bob_keys.pubkey, 2, &trade.respond(&alice_keys, TradeAction::Complete, None).unwrap());
+        let trade = client.trades(&bob).unwrap().trades[0].clone();
+        send(&bob, alice_keys.pubkey, 2, &trade.respond(&bob_keys, TradeAction::Complete, None).unwrap());
+
+        let trade = client.trades(&bob).unwrap().get(&trade.trade_id).unwrap().clone();
+        assert_eq!(trade.status, TradeStatus::Completed);
+        assert_eq!(trade.prompts.len(), 1);
+        let prompt = trade.prompts[0].clone();
+        assert_eq!(prompt.to, alice_keys.pubkey);
+        client.give_badge_with_details(&bob, prompt.to, prompt.kind, prompt.details).unwrap();
+        assert!(client.trades(&bob).unwrap().trades[0].prompts.is_empty());
+        assert_eq!(client.trades(&alice).unwrap().trades[0].prompts.len(), 1);
+    }
+
+    #[test]
+    fn test_client_receipts() {
+        let server = MockServer::start();
+        let client = Client::new(&server.url());
+        let [alice, bob, carol] = members(&client, &["alice", "bob", "carol"]);
+        let (alice_pubkey, bob_pubkey) = (Client::keys(&alice).pubkey, Client::keys(&bob).pubkey);
+        let mut ids = vec![];
+        for (index, text) in [(1, "hi"), (2, "you there?")] {
+            let payload = Payload::new(PayloadKind::Message, text.to_string());
+            let id = client
+                .send_post(&alice, index, Recipient::new(RecipientKind::Direct, bob_pubkey.to_string()), payload)
+                .unwrap();
+            client.send_keys(&alice, index, &id, &[bob_pubkey]).unwrap();
+            ids.push(id);
+        }
+
+        let settings = ReceiptSettings::default();
+        assert_eq!(client.send_receipts(&bob, ReceiptKind::Delivered, None, &settings).unwrap().ids.len(), 1);
+        // nothing left to acknowledge
+        assert!(client.send_receipts(&bob, ReceiptKind::Delivered, None, &settings).unwrap().ids.is_empty());
+        let muted = ReceiptSettings {
+            read_off: vec![alice_pubkey.to_string()],
+            ..ReceiptSettings::default()
+        };
+        assert!(client.send_receipts(&bob, ReceiptKind::Read, None, &muted).unwrap().ids.is_empty());
+        client.send_receipts(&bob, ReceiptKind::Read, Some(&ids[..1]), &settings).unwrap();
+
+        let posts = client.posts(&alice, None).unwrap();
+        assert_eq!(posts.verified[0].posts.len(), 2);
+        assert_eq!(posts.receipts.len(), 2);
+        assert_eq!(posts.receipts[0].post_id, ids[0]);
+        assert_eq!(posts.receipts[0].read, vec![bob_pubkey]);
+        assert_eq!(posts.receipts[1].delivered, vec![bob_pubkey]);
+        assert!(posts.receipts[1].read.is_empty());
+
+        // a failure part way keeps the receipts already sent
+        for (who, index) in [(&alice, 3), (&carol, 1)] {
+            let payload = Payload::new(PayloadKind::Message, "ping".to_string());
+            let id = client
+                .send_post(who, index, Recipient::new(RecipientKind::Direct, bob_pubkey.to_string()), payload)
+                .unwrap();
+            client.send_keys(who, index, &id, &[bob_pubkey]).unwrap();
+        }
+        server.fail_posts_after(1);
+        let partial = client.send_receipts(&bob, ReceiptKind::Delivered, None, &settings).unwrap();
+        assert_eq!(partial.ids.len(), 1);
+        assert!(partial.error.is_some());
+        server.restore();
+        let rest = client.send_receipts(&bob, ReceiptKind::Delivered, None, &settings).unwrap();
+        assert_eq!(rest.ids.len(), 1);
+        assert!(rest.error.is_none());
+    }
+
+    #[test]
+    fn test_client_attachment() {
+        let server = MockServer::start();
+        let client = Client::new(&server.url());
+        let [alice, bob] = members(&client, &["alice", "bob"]);
+        let bob_pubkey = Client::keys(&bob).pubkey;
+
+        let data: Vec<u8> = (0..attachment::CHUNK_SIZE + 10).map(|i| (i % 256) as u8).collect();
+        let to = Recipient::new(RecipientKind::Direct, bob_pubkey.to_string());
+        let post_id = client
+            .send_attachment(&alice, to, &[bob_pubkey], &mut data.as_slice(), "photo.jpg", Some("image/jpeg".to_string()))
+            .unwrap();
+
+        // chunks stay out of the chat
+        let posts = client.posts(&bob, None).unwrap();
+        assert_eq!(posts.verified[0].posts.len(), 1);
+        assert!(posts.corrupted.is_empty());
+        let manifest = AttachmentManifest::structify(&client.post(&bob, &post_id).unwrap().post.payload.value).unwrap();
+        assert_eq!(manifest.chunks.len(), 2);
+
+        let path = std::env::temp_dir().join(format!("cpclient-{}", manifest.id.replace('/', "_")));
+        client.download_attachment(&bob, &manifest, &path).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_client_outbox() {
+        let server = MockServer::start();
+        let client = Client::new(&server.url());
+        let offline = Client::new("http://127.0.0.1:1")
+            .with_network(NetworkConfig::default().with_retry(crate::network::handler::RetryPolicy::none()));
+        let [alice, bob] = members(&client, &["alice", "bob"]);
+        let bob_pubkey = Client::keys(&bob).pubkey;
+
+        let path = std::env::temp_dir().join(format!("cpclient-outbox-{}", Client::keys(&alice).pubkey));
+        let mut outbox = Outbox::load(&path, &alice.derive_storage_key()).unwrap();
+        for text in ["first", "second", "third"] {
+            let to = Recipient::new(RecipientKind::Direct, bob_pubkey.to_string());
+            let payload = Payload::new(PayloadKind::Message, text.to_string());
+            outbox.queue(to, payload, PostLinks::default(), vec![bob_pubkey]).unwrap();
+        }
+        outbox.save(&path, &alice.derive_storage_key()).unwrap();
+
+        let report = offline.flush_outbox(&alice, &path).unwrap();
+        assert!(report.entries.iter().all(|entry| entry.state == OutboxState::Queued));
+        assert!(report.entries[0].last_error.is_some());
+
+        // the first post reaches the server but the response is lost
+        let mut outbox = Outbox::load(&path, &alice.derive_storage_key()).unwrap();
+        client.outbox_step(&alice, &mut outbox, 0).unwrap();
+        assert_eq!(outbox.entries[0].state, OutboxState::Uploading);
+        let request = outbox.entries[0].request.clone().unwrap();
+        let post_id = post::dto::create(client.host.clone(), client.network.clone(), Client::keys(&alice), request).unwrap();
+        outbox.save(&path, &alice.derive_storage_key()).unwrap();
+
+        let report = client.flush_outbox(&alice, &path).unwrap();
+        assert!(report.entries.iter().all(|entry| entry.state == OutboxState::Sent));
+        assert_eq!(report.entries[0].post_id, Some(post_id));
+        assert!(Outbox::load(&path, &alice.derive_storage_key()).unwrap().entries.is_empty());
+        let posts = client.posts(&bob, None).unwrap();
+        let texts: Vec<String> = posts.verified[0].posts.iter().map(|post| post.post.payload.value.clone()).collect();
+        assert_eq!(texts, vec!["first", "second", "third"]);
+
+        // a direct send takes the index reserved by an entry still uploading
+        let mut outbox = Outbox::load(&path, &alice.derive_storage_key()).unwrap();
+        let to = Recipient::new(RecipientKind::Direct, bob_pubkey.to_string());
+        let payload = Payload::new(PayloadKind::Message, "fourth".to_string());
+        outbox.queue(to.clone(), payload, PostLinks::default(), vec![bob_pubkey]).unwrap();
+        client.outbox_step(&alice, &mut outbox, 0).unwrap();
+        outbox.save(&path, &alice.derive_storage_key()).unwrap();
+        let taken = outbox.entries[0].index.unwrap();
+        let payload = Payload::new(PayloadKind::Message, "elsewhere".to_string());
+        let post_id = client.send_post(&alice, taken, to, payload).unwrap();
+        client.send_keys(&alice, taken, &post_id, &[bob_pubkey]).unwrap();
+
+        let report = client.flush_outbox(&alice, &path).unwrap();
+        assert_eq!(report.entries[0].state, OutboxState::Sent);
+        assert!(report.entries[0].index.unwrap() > taken);
+        let posts = client.posts(&bob, None).unwrap();
+        let texts: Vec<String> = posts.verified[0].posts.iter().map(|post| post.post.payload.value.clone()).collect();
+        assert_eq!(texts, vec!["first", "second", "third", "elsewhere", "fourth"]);
+        assert!(client.drain_outbox(&alice, &path, Duration::MAX).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_client_conversations() {
+        let server = MockServer::start();
+        let client = Client::new(&server.url());
+        let [alice, bob, carol] = members(&client, &["alice", "bob", "carol"]);
+        let (bob_pubkey, carol_pubkey) = (Client::keys(&bob).pubkey, Client::keys(&carol).pubkey);
+        let alice_pubkey = Client::keys(&alice).pubkey;
+        for (from, index, text) in [(&bob, 1, "hi alice"), (&carol, 1, "hello"), (&bob, 2, "lunch?")] {
+            let payload = Payload::new(PayloadKind::Message, text.to_string());
+            let id = client
+                .send_post(from, index, Recipient::new(RecipientKind::Direct, alice_pubkey.to_string()), payload)
+                .unwrap();
+            client.send_keys(from, index, &id, &[alice_pubkey]).unwrap();
+        }
+
+        let mut directory = MemberDirectory::default();
+        client.refresh_directory(&alice, &mut directory).unwrap();
+        let mut state = ConversationState::default();
+        let list = client.conversations(&alice, &directory, &Contacts::default(), &state, 0, 0).unwrap();
+        assert_eq!(list.total, 2);
+        assert_eq!(list.unread, 3);
+        assert_eq!(list.conversations[0].counter_party, bob_pubkey.to_string());
+        assert_eq!(list.conversations[0].username, Some("bob".to_string()));
+        assert_eq!(list.conversations[0].preview, "lunch?");
+
+        state.mark_read(&bob_pubkey.to_string(), list.conversations[0].last_activity);
+        let page = client.conversations(&alice, &directory, &Contacts::default(), &state, 1, 1).unwrap();
+        assert_eq!(page.unread, 1);
+        assert_eq!(page.conversations.len(), 1);
+        assert_eq!(page.conversations[0].counter_party, carol_pubkey.to_string());
+    }
+
+    #[test]
+    fn test_client_threads() {
+        let server = MockServer::start();
+        let client = Client::new(&server.url());
+        let [alice, bob] = members(&client, &["alice", "bob"]);
+        let (alice_pubkey, bob_pubkey) = (Client::keys(&alice).pubkey, Client::keys(&bob).pubkey);
+        let send = |from: &UserIdentity, to: XOnlyPublicKey, index: u32, text: &str, links: PostLinks| {
+            let payload = Payload::new(PayloadKind::Message, text.to_string());
+            let to_recipient = Recipient::new(RecipientKind::Direct, to.to_string());
+            let id = client.send_post_with_links(from, index, to_recipient, payload, links).unwrap();
+            client.send_keys(from, index, &id, &[to]).unwrap();
+            id
+        };
+
+        let root = send(&alice, bob_pubkey, 1, "lunch?", PostLinks::default());
+        let reply = PostLinks {
+            reply_to: Some(root.clone()),
+            ..PostLinks::default()
+        };
+        send(&bob, alice_pubkey, 1, "sure", reply);
+        let edit = PostLinks {
+            edits: Some(root.clone()),
+            ..PostLinks::default()
+        };
+        send(&alice, bob_pubkey, 2, "lunch at 1?", edit.clone());
+        let react = PostLinks {
+            reacts_to: Some(root.clone()),
+            ..PostLinks::default()
+        };
+        send(&bob, alice_pubkey, 2, "+1", react);
+        // bob cannot edit alice's post
+        send(&bob, alice_pubkey, 3, "no lunch", edit);
+
+        let posts = client.posts(&bob, None).unwrap();
+        assert_eq!(posts.corrupted.len(), 1);
+        let thread = posts.verified[0].threads[0].clone();
+        assert_eq!(posts.verified[0].threads.len(), 1);
+        assert_eq!(thread.root.post.id, root);
+        assert_eq!(thread.root.payload.value, "lunch at 1?");
+        assert_eq!(thread.root.history.len(), 1);
+        assert_eq!(thread.root.reactions[0].by, bob_pubkey);
+        assert_eq!(thread.replies[0].payload.value, "sure");
+
+        let bad = PostLinks {
+            edits: Some(root.clone()),
+            reacts_to: Some(root),
+            ..PostLinks::default()
+        };
+        let payload = Payload::new(PayloadKind::Message, "x".to_string());
+        let to = Recipient::new(RecipientKind::Direct, bob_pubkey.to_string());
+        assert!(client.send_post_with_links(&alice, 3, to, payload, bad).is_err());
+    }
+
+    #[test]
+    fn test_client_scam_filter() {
+        let server = MockServer::start();
+        let client = Client::new(&server.url());
+        let [alice, bob, carol, mallory] = members(&client, &["alice", "bob", "carol", "mallory"]);
+        let (alice_pubkey, bob_pubkey, carol_pubkey, mallory_pubkey) =
+            (Client::keys(&alice).pubkey, Client::keys(&bob).pubkey, Client::keys(&carol).pubkey, Client::keys(&mallory).pubkey);
+        client.give_badge(&alice, bob_pubkey, AnnouncementType::Trust).unwrap();
+        client.give_badge(&bob, mallory_pubkey, AnnouncementType::Scam).unwrap();
+        // mallory is outside alice's network, so this only flags carol
+        client.give_badge(&mallory, carol_pubkey, AnnouncementType::Scam).unwrap();
+
+        let messages = [
+            (&bob, 1, RecipientKind::Direct, alice_pubkey.to_string()),
+            (&carol, 1, RecipientKind::Direct, alice_pubkey.to_string()),
+            (&mallory, 1, RecipientKind::Direct, alice_pubkey.to_string()),
+            (&alice, 1, RecipientKind::Direct, mallory_pubkey.to_string()),
+            (&bob, 2, RecipientKind::Group, "market".to_string()),
+            (&mallory, 2, RecipientKind::Group, "market".to_string()),
+        ];
+        for (from, index, kind, to) in messages {
+            let payload = Payload::new(PayloadKind::Message, "hi".to_string());
+            let post_id = client.send_post(from, index, Recipient::new(kind, to), payload).unwrap();
+            let readers: Vec<XOnlyPublicKey> =
+                [alice_pubkey, mallory_pubkey].iter().copied().filter(|pubkey| *pubkey != Client::keys(from).pubkey).collect();
+            client.send_keys(from, index, &post_id, &readers).unwrap();
+        }
+
+        let directory = MemberDirectory::default();
+        let contacts = Contacts::default();
+        let unfiltered = client.chats(&alice, None, &directory, &contacts, None).unwrap();
+        assert_eq!(unfiltered.verified.len(), 4);
+        assert!(unfiltered.quarantined.is_empty());
+
+        let chats = client.chats(&alice, None, &directory, &contacts, Some(&FilterPolicy::default())).unwrap();
+        let chat = |chats: &[PostsAsChat], party: &str| chats.iter().find(|chat| chat.counter_party == party).cloned();
+        assert_eq!(chats.verified.len(), 3);
+        assert!(!chat(&chats.verified, &bob_pubkey.to_string()).unwrap().flags.contains(&ContactFlag::LowReputation));
+        assert!(chat(&chats.verified, &carol_pubkey.to_string()).unwrap().flags.contains(&ContactFlag::LowReputation));
+        assert_eq!(chat(&chats.verified, "market").unwrap().posts.len(), 1);
+        // both sides of the direct chat are held back
+        assert_eq!(chats.quarantined.len(), 2);
+        assert_eq!(chat(&chats.quarantined, &mallory_pubkey.to_string()).unwrap().posts.len(), 2);
+        assert_eq!(chat(&chats.quarantined, "market").unwrap().posts[0].owner, mallory_pubkey);
+    }
+
+    #[test]
+    fn test_client_profiles() {
+        let server = MockServer::start();
+        let client = Client::new(&server.url());
+        let [alice, bob, carol] = members(&client, &["alice", "bob", "carol"]);
+        let alice_pubkey = Client::keys(&alice).pubkey;
+        let profile = Profile {
+            display_name: "Alice".to_string(),
+            avatar: None,
+            bio: "".to_string(),
+            payment_methods: vec!["lightning".to_string()],
+            identities: vec![],
+        };
+        client.publish_profile(&alice, 1, profile.clone(), None).unwrap();
+        let mut update = profile.clone();
+        update.bio = "trades on weekends".to_string();
+        client
+            .publish_profile(&alice, 2, update, Some(&[Client::keys(&bob).pubkey]))
+            .unwrap();
+
+        let seen_by_bob = client.profiles(&bob).unwrap();
+        assert_eq!(seen_by_bob.profiles.len(), 1);
+        assert_eq!(seen_by_bob.get(&alice_pubkey).unwrap().profile.bio, "trades on weekends");
+        assert_eq!(client.profiles(&carol).unwrap().get(&alice_pubkey).unwrap().profile.bio, "");
+        // profiles are not chats
+        assert!(client.posts(&bob, None).unwrap().verified.is_empty());
+
+        // a profile signed by someone else is not accepted from alice
+        let forged = SignedProfile::new(profile, &Client::keys(&carol)).unwrap();
+        let payload = Payload::new(PayloadKind::Profile, forged.stringify());
+        let to = Recipient::new(RecipientKind::Group, "profiles".to_string());
+        let post_id = client.send_post(&alice, 3, to, payload).unwrap();
+        client.send_keys(&alice, 3, &post_id, &[Client::keys(&bob).pubkey]).unwrap();
+        let sorted = client.posts(&bob, None).unwrap();
+        assert_eq!(sorted.corrupted, [post_id]);
+        assert_eq!(sorted.profiles.profiles.len(), 1);
+    }
+
+    #[test]
+    fn test_client_invites() {
+        let server = MockServer::start();
+        let client = Client::new(&server.url());
+        let admin_code = client.admin_invite(ADMIN_SECRET, InvitePermission::Privilege(2)).unwrap().invite_code;
+        let spare_code = client.admin_invite(ADMIN_SECRET, InvitePermission::Standard).unwrap().invite_code;
+        let alice = new_identity();
+        client.join(&alice, "alice", &admin_code).unwrap();
+
+        let admin_list = client.admin_invites(ADMIN_SECRET).unwrap().invitations;
+        assert_eq!(admin_list.len(), 2);
+        assert_eq!(admin_list[0].status, "CLAIMED");
+        assert_eq!(admin_list[0].claimed_by, Client::keys(&alice).pubkey.to_string());
+        assert!(client.admin_invites("wrong").is_err());
+        client.admin_revoke_invite(ADMIN_SECRET, &spare_code).unwrap();
+        assert_eq!(client.invite_detail(&alice, &spare_code).unwrap().status, "REVOKED");
+        assert!(client.join(&new_identity(), "mallory", &spare_code).is_err());
+        // claimed invites cannot be revoked
+        assert!(client.admin_revoke_invite(ADMIN_SECRET, &admin_code).is_err());
+
+        let first = client.priv_user_invite(&alice, &admin_code).unwrap().invite_code;
+        let second = client.priv_user_invite(&alice, &admin_code).unwrap().invite_code;
+        assert!(client.priv_user_invite(&alice, &admin_code).is_err());
+        assert_eq!(client.my_invites(&alice).unwrap().invitations.len(), 2);
+        client.revoke_invite(&alice, &first).unwrap();
+        assert_eq!(client.invite_detail(&alice, &admin_code).unwrap().count, 1);
+        client.join(&new_identity(), "bob", &second).unwrap();
+        let statuses: Vec<String> = client
+            .my_invites(&alice)
+            .unwrap()
+            .invitations
+            .into_iter()
+            .map(|invite| invite.status)
+            .collect();
+        assert_eq!(statuses, ["REVOKED", "CLAIMED"]);
+    }
+
+    #[test]
+    fn test_client_public_join() {
+        let private = MockServer::start();
+        let client = Client::new(&private.url());
+        let alice = new_identity();
+        assert!(client.join_invoice(&alice).is_err());
+
+        let server = MockServer::start_public(1000);
+        let client = Client::new(&server.url());
+        assert_eq!(client.server_identity(&alice).unwrap().kind, "PUBLIC");
+        let invoice = client.join_invoice(&alice).unwrap();
+        assert_eq!(invoice.status, "UNPAID");
+        assert_eq!(invoice.amount, 1000);
+        assert!(invoice.invite_code.is_none());
+        // invoices are private to whoever asked for them
+        assert!(client.invoice_status(&new_identity(), &invoice.invoice_id).is_err());
+        assert!(client.join_paid(&alice, "alice", &invoice.invoice_id).is_err());
+        assert!(client
+            .wait_for_payment(&alice, &invoice.invoice_id, Duration::from_millis(200))
+            .is_err());
+        assert!(client.wait_for_payment(&alice, &invoice.invoice_id, Duration::from_secs(u64::MAX)).is_err());
+
+        let paid = thread::scope(|scope| {
+            scope.spawn(|| {
+                thread::sleep(Duration::from_millis(300));
+                server.pay(&invoice.invoice_id);
+            });
+            client.wait_for_payment(&alice, &invoice.invoice_id, Duration::from_secs(5)).unwrap()
+        });
+        assert_eq!(paid.status, "PAID");
+        client.join_paid(&alice, "alice", &invoice.invoice_id).unwrap();
+        assert_eq!(client.members(&alice).unwrap().identities.len(), 1);
+        // the credential is spent
+        assert!(client.join_paid(&new_identity(), "bob", &invoice.invoice_id).is_err());
+
+        let bob = new_identity();
+        let expired = client.join_invoice(&bob).unwrap();
+        server.expire(&expired.invoice_id);
+        assert_eq!(client.invoice_status(&bob, &expired.invoice_id).unwrap().status, "EXPIRED");
+        assert!(client.wait_for_payment(&bob, &expired.invoice_id, Duration::from_secs(5)).is_err());
+        assert!(client.join_paid(&bob, "bob", &expired.invoice_id).is_err());
+    }
+
+    #[test]
+    fn test_client_usernames() {
+        let server = MockServer::start();
+        let client = Client::new(&server.url());
+        let [alice] = members(&client, &["alice"]);
+        let mallory = new_identity();
+
+        assert!(client.check_username(&mallory, "al").is_err());
+        // only members can list members
+        assert!(client.check_username(&mallory, "mallory").is_err());
+        let check = client.check_username(&alice, "ALICE").unwrap();
+        assert_eq!(check.username, "alice");
+        assert!(check.taken && !check.available);
+        assert!(check.confusable_with.is_empty());
+        let check = client.check_username(&alice, "a1ice").unwrap();
+        assert!(!check.taken && !check.available);
+        assert_eq!(check.confusable_with, ["alice"]);
+        assert!(client.check_username(&alice, "mallory").unwrap().available);
+
+        let code = client.admin_invite(ADMIN_SECRET, InvitePermission::Standard).unwrap().invite_code;
+        assert!(client.join(&mallory, "аlice", &code).is_err()); // cyrillic a
+        assert!(client.join(&mallory, "alice", &code).is_err());
+        let joined = client.join(&mallory, " A1ice ", &code).unwrap();
+        assert_eq!(joined.confusable_with, ["alice"]);
+        let names: Vec<String> = client.members(&alice).unwrap().identities.into_iter().map(|m| m.username).collect();
+        assert_eq!(names, ["alice", "a1ice"]);
+    }
+}
@@ -1,6 +1,8 @@
 use std::{str};
 use chacha20poly1305::{XChaCha20Poly1305, Key, XNonce};
 use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::aead::generic_array::GenericArray;
+use chacha20poly1305::aead::stream::{NewStream, StreamBE32, StreamPrimitive};
 use bitcoin::secp256k1::rand::{thread_rng,Rng};
 use sha2::{Sha256, Digest};
 
@@ -27,8 +29,9 @@ pub fn cc20p1305_encrypt(plaintext:&str, key: &str)->Result<String,S5Error>{
     let key = hex::decode(key_hash256(key)).unwrap();
     let encryption_key = Key::from_slice(&key);
     let aead = XChaCha20Poly1305::new(encryption_key);
-    let nonce = nonce();
-    let nonce = XNonce::from_slice(nonce.as_bytes()); 
+    // nonce() is a variable length string; XChaCha20 needs exactly 24 bytes
+    let nonce_bytes: [u8; 24] = thread_rng().gen();
+    let nonce = XNonce::from_slice(&nonce_bytes);
     let ciphertext = match aead.encrypt(nonce, plaintext.as_bytes()){
       Ok(cipher)=>cipher,
       Err(_)=>return Err(S5Error::new(ErrorKind::Key, "Encryption Failed!"))
@@ -39,13 +42,17 @@ pub fn cc20p1305_decrypt(ciphertext:&str, key: &str)->Result<String,S5Error>{
     let key = hex::decode(key_hash256(key)).unwrap();
     let encryption_key = Key::from_slice(&key);
     let aead = XChaCha20Poly1305::new(encryption_key);
-    let iter:Vec<&str> = ciphertext.split(':').collect();
-    let nonce_slice = match base64::decode(&iter[0].as_bytes()){
-      Ok(nonce)=>nonce,
+    let (nonce_part, cipher_part) = match ciphertext.split_once(':'){
+      Some(parts)=>parts,
+      None=>return Err(S5Error::new(ErrorKind::Key, "Bad ciphertext format!"))
+    };
+    let nonce_slice = match base64::decode(nonce_part.as_bytes()){
+      Ok(nonce) if nonce.len() == 24=>nonce,
+      Ok(_)=>return Err(S5Error::new(ErrorKind::Key, "Bad nonce length!")),
       Err(_)=>return Err(S5Error::new(ErrorKind::Key, "Could not decode nonce!"))
     };
-    let nonce = XNonce::from_slice(&*nonce_slice); // 24-bytes; unique
-    let ciphertext_bytes: Vec<u8> = match base64::decode(&iter[1].as_bytes()){
+    let nonce = XNonce::from_slice(&nonce_slice); // 24-bytes; unique
+    let ciphertext_bytes: Vec<u8> = match base64::decode(cipher_part.as_bytes()){
       Ok(cipher)=>cipher,
       Err(_)=>return Err(S5Error::new(ErrorKind::Key, "Could not decode ciphertext!"))
     };
@@ -59,6 +66,39 @@ pub fn cc20p1305_decrypt(ciphertext:&str, key: &str)->Result<String,S5Error>{
     }
 }
 
+pub const STREAM_KEY_LENGTH: usize = 32;
+/// XChaCha20 nonce less the 4 byte STREAM counter and 1 byte last-chunk flag.
+pub const STREAM_NONCE_LENGTH: usize = 19;
+
+/// A random key and nonce for `stream_encrypt`, e.g. one per attachment.
+pub fn stream_key()->([u8; STREAM_KEY_LENGTH],[u8; STREAM_NONCE_LENGTH]){
+    let mut rng = thread_rng();
+    (rng.gen(), rng.gen())
+}
+
+fn stream(key: &[u8], nonce: &[u8])->Result<StreamBE32<XChaCha20Poly1305>,S5Error>{
+    if key.len() != STREAM_KEY_LENGTH || nonce.len() != STREAM_NONCE_LENGTH{
+      return Err(S5Error::new(ErrorKind::Key, "Bad stream key or nonce length!"))
+    }
+    Ok(StreamBE32::from_aead(XChaCha20Poly1305::new(Key::from_slice(key)), GenericArray::from_slice(nonce)))
+}
+
+/// Encrypt one chunk of a STREAM. `position` and `last` are authenticated,
+/// so chunks cannot be reordered, dropped or the stream cut short without decryption failing.
+pub fn stream_encrypt(key: &[u8], nonce: &[u8], position: u32, last: bool, plaintext: &[u8])->Result<Vec<u8>,S5Error>{
+    match stream(key, nonce)?.encrypt(position, last, plaintext){
+      Ok(cipher)=>Ok(cipher),
+      Err(_)=>Err(S5Error::new(ErrorKind::Key, "Encryption Failed!"))
+    }
+}
+/// Decrypt one chunk of a STREAM. Chunks can be decrypted in any order, e.g. to resume a download.
+pub fn stream_decrypt(key: &[u8], nonce: &[u8], position: u32, last: bool, ciphertext: &[u8])->Result<Vec<u8>,S5Error>{
+    match stream(key, nonce)?.decrypt(position, last, ciphertext){
+      Ok(plain)=>Ok(plain),
+      Err(_)=>Err(S5Error::new(ErrorKind::Key, "Decryption Failed!"))
+    }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -78,5 +118,35 @@ mod tests {
     let ciphertext = cc20p1305_encrypt(message, key_str).unwrap();
     let plaintext = cc20p1305_decrypt(&ciphertext, key_str).unwrap();
     assert_eq!(&plaintext, message);
+
+    // malformed input is an error, not a panic
+    let cipher_part = ciphertext.split(':').nth(1).unwrap();
+    assert!(cc20p1305_decrypt("no separator", key_str).is_err());
+    assert!(cc20p1305_decrypt(&format!("{}:{}", base64::encode(b"short"), cipher_part), key_str).is_err());
+    assert!(cc20p1305_decrypt(&format!("{}:{}", nonce(), cipher_part), key_str).is_err());
+  }
+  // made by the old encryption, whose nonce was the 24 characters of a base64 string
+  #[test]
+  fn test_legacy_ciphertext() {
+    let ciphertext = "TVRJek5EVTJOemc1TURFeU16UTFOamM0:gfwUHlNRie0xP7LoDkf2iDCg1V/oS8tfujmNLygZM9KGYHzRUKOgisFJLff+9LFqf3B4eC9ez7+qtvUJXAm/aJU=";
+    let plaintext = cc20p1305_decrypt(ciphertext, "a simple key for me to remember").unwrap();
+    assert_eq!(&plaintext, "thresh(2,wpkh([fingerprint/h/d/path]xpub/*),*,*))");
+  }
+  #[test]
+  fn test_stream_encryption() {
+    let (key, nonce) = stream_key();
+    let chunks = [b"first".to_vec(), vec![0, 159, 146, 150], b"last".to_vec()];
+    let ciphertexts: Vec<Vec<u8>> = chunks.iter().enumerate()
+      .map(|(i, chunk)| stream_encrypt(&key, &nonce, i as u32, i == chunks.len() - 1, chunk).unwrap())
+      .collect();
+    // in any order
+    assert_eq!(stream_decrypt(&key, &nonce, 2, true, &ciphertexts[2]).unwrap(), chunks[2]);
+    assert_eq!(stream_decrypt(&key, &nonce, 1, false, &ciphertexts[1]).unwrap(), chunks[1]);
+    assert_eq!(stream_decrypt(&key, &nonce, 0, false, &ciphertexts[0]).unwrap(), chunks[0]);
+    // moved, cut short or under another key
+    assert!(stream_decrypt(&key, &nonce, 0, false, &ciphertexts[1]).is_err());
+    assert!(stream_decrypt(&key, &nonce, 1, true, &ciphertexts[1]).is_err());
+    assert!(stream_decrypt(&stream_key().0, &nonce, 0, false, &ciphertexts[0]).is_err());
+    assert!(stream_decrypt(&key[1..], &nonce, 0, false, &ciphertexts[0]).is_err());
   }
 }
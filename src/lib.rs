@@ -2,21 +2,41 @@
 use std::alloc::System;
 #[global_allocator]
 static A: System = System;
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
 use std::str::FromStr;
+use std::time::Duration;
 // use bdk::bitcoin::util::bip32::ExtendedPrivKey;
+use bitcoin::network::constants::Network;
 use bitcoin::secp256k1::XOnlyPublicKey;
 use bitcoin::util::bip32::ExtendedPrivKey;
+use bitcoin::PrivateKey;
 
-mod key;
-use crate::key::{child, ec};
+pub mod key;
+use crate::key::ec;
 
-mod network;
+pub mod network;
+use crate::network::handler::{AnnouncementType, NetworkConfig, ProxyConfig};
 use crate::network::{identity, post};
-mod util;
+use crate::network::badge::model::BadgeDetails;
+use crate::network::badge::reputation::FilterPolicy;
+use crate::network::identity::contact::{self, ContactCard, ContactCode, Contacts};
+use crate::network::post::descriptor::{KeyContribution, MultisigDescriptor};
+use crate::network::post::attachment::AttachmentManifest;
+use crate::network::post::conversation::ConversationState;
+use crate::network::post::outbox::Outbox;
+use crate::network::post::payment::PaymentRequest;
+use crate::network::post::receipt::{ReceiptKind, ReceiptSettings};
+use crate::network::post::trade::{TradeAction, TradeMessage, TradeState, TradeTerms};
+use crate::network::identity::directory::MemberDirectory;
+use crate::network::identity::profile::{Profile, ProfileCard, SignedProfile};
+pub mod util;
 use crate::util::e::{ErrorKind, S5Error};
 
+pub mod client;
+pub use crate::client::Client;
+pub use crate::util::e::S5Error as Error;
+
 /// CREATE SOCIAL ROOT
 /// USES BIP85 XPRV APPLICATION TO GENERATE SOCIAL IDENTITY ROOT KEY
 /// FURTHER APPLICATION KEYS WILL BE DERIVED FROM THIS ROOT.
@@ -29,8 +49,13 @@ pub unsafe extern "C" fn create_social_root(
     account: *const c_char,
 ) -> *mut c_char {
     let master_root = CStr::from_ptr(master_root);
-    let master_root: String = match master_root.to_str() {
-        Ok(string) => string.to_string(),
+    let master_root: ExtendedPrivKey = match master_root.to_str() {
+        Ok(string) => match ExtendedPrivKey::from_str(string) {
+            Ok(result) => result,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Bad master root key string.").c_stringify()
+            }
+        },
         Err(_) => {
             return S5Error::new(ErrorKind::Input, "Could not convert master root to String")
                 .c_stringify()
@@ -55,7 +80,7 @@ pub unsafe extern "C" fn create_social_root(
         }
     };
 
-    match child::social_root(master_root, account) {
+    match client::create_social_root(&master_root, account) {
         Ok(result) => result.c_stringify(),
         Err(e) => e.c_stringify(),
     }
@@ -63,7 +88,7 @@ pub unsafe extern "C" fn create_social_root(
 /// GET SERVER IDENTITY
 /// GETS SERVER NAME & KIND (PRIVATE OR PUBLIC)
 /// PRIVATE SERVERS REQUIRE AN INVITE
-/// PUBLIC SERVERS REQUIRE A PAYMENT
+/// PUBLIC SERVERS REQUIRE A PAYMENT (SEE join_invoice)
 /// # Safety
 /// - This function is unsafe because it dereferences and a returns raw pointer.
 /// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
@@ -83,22 +108,13 @@ pub unsafe extern "C" fn server_identity(
     };
 
     let socks5 = CStr::from_ptr(socks5);
-    let socks5: Option<u32> = match socks5.to_str() {
-        Ok(string) => match string.parse::<u32>() {
-            Ok(result) => {
-                if result == 0 {
-                    None
-                } else {
-                    Some(result)
-                }
-            }
-            Err(_) => {
-                return S5Error::new(ErrorKind::Input, "Could not parse socks5 port to uint32")
-                    .c_stringify()
-            }
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
         },
         Err(_) => {
-            return S5Error::new(ErrorKind::Input, "Could not convert socks5 port to String")
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
                 .c_stringify()
         }
     };
@@ -110,13 +126,12 @@ pub unsafe extern "C" fn server_identity(
                 .c_stringify()
         }
     };
-    let keypair = match ec::keypair_from_xprv_str(&social_root) {
-        Ok(keypair) => keypair,
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
         Err(e) => return e.c_stringify(),
     };
-    let xonly_pair = ec::XOnlyPair::from_keypair(keypair);
 
-    match identity::dto::get_server_id(hostname, socks5, xonly_pair) {
+    match Client::new(&hostname).with_network(network).server_identity(&my_identity) {
         Ok(result) => result.c_stringify(),
         Err(e) => e.c_stringify(),
     }
@@ -145,22 +160,13 @@ pub unsafe extern "C" fn admin_invite(
     };
 
     let socks5 = CStr::from_ptr(socks5);
-    let socks5: Option<u32> = match socks5.to_str() {
-        Ok(string) => match string.parse::<u32>() {
-            Ok(result) => {
-                if result == 0 {
-                    None
-                } else {
-                    Some(result)
-                }
-            }
-            Err(_) => {
-                return S5Error::new(ErrorKind::Input, "Could not parse socks5 port to uint32")
-                    .c_stringify()
-            }
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
         },
         Err(_) => {
-            return S5Error::new(ErrorKind::Input, "Could not convert socks5 port to String")
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
                 .c_stringify()
         }
     };
@@ -199,7 +205,7 @@ pub unsafe extern "C" fn admin_invite(
         network::handler::InvitePermission::Standard
     };
 
-    match identity::dto::admin_invite(hostname, socks5, admin_secret, permission) {
+    match Client::new(&hostname).with_network(network).admin_invite(&admin_secret, permission) {
         Ok(result) => result.c_stringify(),
         Err(e) => e.c_stringify(),
     }
@@ -226,22 +232,13 @@ pub unsafe extern "C" fn priv_user_invite(
     };
 
     let socks5 = CStr::from_ptr(socks5);
-    let socks5: Option<u32> = match socks5.to_str() {
-        Ok(string) => match string.parse::<u32>() {
-            Ok(result) => {
-                if result == 0 {
-                    None
-                } else {
-                    Some(result)
-                }
-            }
-            Err(_) => {
-                return S5Error::new(ErrorKind::Input, "Could not parse socks5 port to uint32")
-                    .c_stringify()
-            }
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
         },
         Err(_) => {
-            return S5Error::new(ErrorKind::Input, "Could not convert socks5 port to String")
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
                 .c_stringify()
         }
     };
@@ -262,28 +259,30 @@ pub unsafe extern "C" fn priv_user_invite(
                 .c_stringify()
         }
     };
-    let keypair = match ec::keypair_from_xprv_str(&social_root) {
-        Ok(keypair) => keypair,
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
         Err(e) => return e.c_stringify(),
     };
-    let xonly_pair = ec::XOnlyPair::from_keypair(keypair);
 
-    match identity::dto::user_invite(hostname, socks5, xonly_pair, invite_code) {
+    match Client::new(&hostname)
+        .with_network(network)
+        .priv_user_invite(&my_identity, &invite_code) {
         Ok(result) => result.c_stringify(),
         Err(e) => e.c_stringify(),
     }
 }
 
-/// GET ALL MEMBERS ON THE SERVER
-/// USE TO ENSURE USERNAME OF CHOICE IS NOT TAKEN
+/// GET THE STATUS OF AN INVITE CODE
+/// USE BEFORE join TO CHECK THAT A CODE IS STILL PENDING
 /// # Safety
 /// - This function is unsafe because it dereferences and a returns raw pointer.
 /// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
 #[no_mangle]
-pub unsafe extern "C" fn get_members(
+pub unsafe extern "C" fn invite_detail(
     hostname: *const c_char,
     socks5: *const c_char,
     social_root: *const c_char,
+    invite_code: *const c_char,
 ) -> *mut c_char {
     let hostname = CStr::from_ptr(hostname);
     let hostname: String = match hostname.to_str() {
@@ -295,22 +294,13 @@ pub unsafe extern "C" fn get_members(
     };
 
     let socks5 = CStr::from_ptr(socks5);
-    let socks5: Option<u32> = match socks5.to_str() {
-        Ok(string) => match string.parse::<u32>() {
-            Ok(result) => {
-                if result == 0 {
-                    None
-                } else {
-                    Some(result)
-                }
-            }
-            Err(_) => {
-                return S5Error::new(ErrorKind::Input, "Could not parse socks5 port to uint32")
-                    .c_stringify()
-            }
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
         },
         Err(_) => {
-            return S5Error::new(ErrorKind::Input, "Could not convert socks5 port to String")
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
                 .c_stringify()
         }
     };
@@ -322,28 +312,37 @@ pub unsafe extern "C" fn get_members(
                 .c_stringify()
         }
     };
-    let keypair = match ec::keypair_from_xprv_str(&social_root) {
-        Ok(keypair) => keypair,
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
         Err(e) => return e.c_stringify(),
     };
-    let xonly_pair = ec::XOnlyPair::from_keypair(keypair);
 
-    match identity::dto::get_all(hostname, socks5, xonly_pair) {
+    let invite_code = CStr::from_ptr(invite_code);
+    let invite_code: String = match invite_code.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert invite_code to String")
+                .c_stringify()
+        }
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .invite_detail(&my_identity, &invite_code)
+    {
         Ok(result) => result.c_stringify(),
         Err(e) => e.c_stringify(),
     }
 }
-/// REGISTER TO A PRIVATE SERVER
+/// LIST INVITES CREATED FROM MY PRIVILEGED INVITE
 /// # Safety
 /// - This function is unsafe because it dereferences and a returns raw pointer.
 /// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
 #[no_mangle]
-pub unsafe extern "C" fn join(
+pub unsafe extern "C" fn my_invites(
     hostname: *const c_char,
     socks5: *const c_char,
     social_root: *const c_char,
-    username: *const c_char,
-    invite_code: *const c_char,
 ) -> *mut c_char {
     let hostname = CStr::from_ptr(hostname);
     let hostname: String = match hostname.to_str() {
@@ -355,22 +354,13 @@ pub unsafe extern "C" fn join(
     };
 
     let socks5 = CStr::from_ptr(socks5);
-    let socks5: Option<u32> = match socks5.to_str() {
-        Ok(string) => match string.parse::<u32>() {
-            Ok(result) => {
-                if result == 0 {
-                    None
-                } else {
-                    Some(result)
-                }
-            }
-            Err(_) => {
-                return S5Error::new(ErrorKind::Input, "Could not parse socks5 port to uint32")
-                    .c_stringify()
-            }
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
         },
         Err(_) => {
-            return S5Error::new(ErrorKind::Input, "Could not convert socks5 port to String")
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
                 .c_stringify()
         }
     };
@@ -382,44 +372,71 @@ pub unsafe extern "C" fn join(
                 .c_stringify()
         }
     };
-    let keypair = match ec::keypair_from_xprv_str(&social_root) {
-        Ok(keypair) => keypair,
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
         Err(e) => return e.c_stringify(),
     };
-    let xonly_pair = ec::XOnlyPair::from_keypair(keypair);
 
-    let username = CStr::from_ptr(username);
-    let username: String = match username.to_str() {
+    match Client::new(&hostname).with_network(network).my_invites(&my_identity) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// LIST INVITES CREATED AS ADMIN
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn admin_invites(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    admin_secret: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
         Ok(string) => string.to_string(),
         Err(_) => {
-            return S5Error::new(ErrorKind::Input, "Could not convert username to String")
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
                 .c_stringify()
         }
     };
 
-    let invite_code = CStr::from_ptr(invite_code);
-    let invite_code: String = match invite_code.to_str() {
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let admin_secret = CStr::from_ptr(admin_secret);
+    let admin_secret: String = match admin_secret.to_str() {
         Ok(string) => string.to_string(),
         Err(_) => {
-            return S5Error::new(ErrorKind::Input, "Could not convert invite_code to String")
+            return S5Error::new(ErrorKind::Input, "Could not convert admin_secret to String")
                 .c_stringify()
         }
     };
 
-    match identity::dto::register(hostname, socks5, xonly_pair, invite_code, username) {
+    match Client::new(&hostname).with_network(network).admin_invites(&admin_secret) {
         Ok(result) => result.c_stringify(),
         Err(e) => e.c_stringify(),
     }
 }
-/// LEAVE A SERVER
+/// REVOKE AN UNCLAIMED INVITE CREATED FROM MY PRIVILEGED INVITE
+/// THE INVITE IS ADDED BACK TO MY PRIVILEGED INVITE COUNT
 /// # Safety
 /// - This function is unsafe because it dereferences and a returns raw pointer.
 /// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
 #[no_mangle]
-pub unsafe extern "C" fn leave(
+pub unsafe extern "C" fn revoke_invite(
     hostname: *const c_char,
     socks5: *const c_char,
     social_root: *const c_char,
+    invite_code: *const c_char,
 ) -> *mut c_char {
     let hostname = CStr::from_ptr(hostname);
     let hostname: String = match hostname.to_str() {
@@ -431,22 +448,13 @@ pub unsafe extern "C" fn leave(
     };
 
     let socks5 = CStr::from_ptr(socks5);
-    let socks5: Option<u32> = match socks5.to_str() {
-        Ok(string) => match string.parse::<u32>() {
-            Ok(result) => {
-                if result == 0 {
-                    None
-                } else {
-                    Some(result)
-                }
-            }
-            Err(_) => {
-                return S5Error::new(ErrorKind::Input, "Could not parse socks5 port to uint32")
-                    .c_stringify()
-            }
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
         },
         Err(_) => {
-            return S5Error::new(ErrorKind::Input, "Could not convert socks5 port to String")
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
                 .c_stringify()
         }
     };
@@ -458,36 +466,41 @@ pub unsafe extern "C" fn leave(
                 .c_stringify()
         }
     };
-    let keypair = match ec::keypair_from_xprv_str(&social_root) {
-        Ok(keypair) => keypair,
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
         Err(e) => return e.c_stringify(),
     };
-    let xonly_pair = ec::XOnlyPair::from_keypair(keypair);
 
-    match identity::dto::delete(hostname, socks5, xonly_pair) {
+    let invite_code = CStr::from_ptr(invite_code);
+    let invite_code: String = match invite_code.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert invite_code to String")
+                .c_stringify()
+        }
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .revoke_invite(&my_identity, &invite_code)
+    {
         Ok(_) => network::handler::ServerStatusResponse::new(true).c_stringify(),
         Err(e) => e.c_stringify(),
     }
 }
-/// CREATE A POST & KEYS
-/// `to` must be colon separated `kind:value` of recipient
-/// `kind` is the kind of payload (message or secret)
-/// `value` is the value of the payload (watch out for special chars and escape chars)
+/// REVOKE AN UNCLAIMED INVITE CREATED AS ADMIN
 /// # Safety
 /// - This function is unsafe because it dereferences and a returns raw pointer.
 /// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
 #[no_mangle]
-pub unsafe extern "C" fn send_post(
+pub unsafe extern "C" fn admin_revoke_invite(
     hostname: *const c_char,
     socks5: *const c_char,
-    social_root: *const c_char,
-    index: *const c_char,
-    to: *const c_char,
-    kind: *const c_char,
-    value: *const c_char,
+    admin_secret: *const c_char,
+    invite_code: *const c_char,
 ) -> *mut c_char {
-    let hostname_cstr = CStr::from_ptr(hostname);
-    let hostname: String = match hostname_cstr.to_str() {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
         Ok(string) => string.to_string(),
         Err(_) => {
             return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
@@ -496,121 +509,56 @@ pub unsafe extern "C" fn send_post(
     };
 
     let socks5 = CStr::from_ptr(socks5);
-    let socks5: Option<u32> = match socks5.to_str() {
-        Ok(string) => match string.parse::<u32>() {
-            Ok(result) => {
-                if result == 0 {
-                    None
-                } else {
-                    Some(result)
-                }
-            }
-            Err(_) => {
-                return S5Error::new(ErrorKind::Input, "Could not parse socks5 port to uint32")
-                    .c_stringify()
-            }
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
         },
         Err(_) => {
-            return S5Error::new(ErrorKind::Input, "Could not convert socks5 port to String")
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
                 .c_stringify()
         }
     };
-    let social_root = CStr::from_ptr(social_root);
-    let social_root: String = match social_root.to_str() {
+    let admin_secret = CStr::from_ptr(admin_secret);
+    let admin_secret: String = match admin_secret.to_str() {
         Ok(string) => string.to_string(),
         Err(_) => {
-            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+            return S5Error::new(ErrorKind::Input, "Could not convert admin_secret to String")
                 .c_stringify()
         }
     };
-    let keypair = match ec::keypair_from_xprv_str(&social_root) {
-        Ok(keypair) => keypair,
-        Err(e) => return e.c_stringify(),
-    };
-    let xonly_pair = ec::XOnlyPair::from_keypair(keypair);
 
-    let index = CStr::from_ptr(index);
-    let index: u32 = match index.to_str() {
-        Ok(string) => match string.parse::<u32>() {
-            Ok(result) => result,
-            Err(_) => {
-                return S5Error::new(ErrorKind::Input, "Could not parse index to u32").c_stringify()
-            }
-        },
+    let invite_code = CStr::from_ptr(invite_code);
+    let invite_code: String = match invite_code.to_str() {
+        Ok(string) => string.to_string(),
         Err(_) => {
-            return S5Error::new(ErrorKind::Input, "Could not convert index to String")
+            return S5Error::new(ErrorKind::Input, "Could not convert invite_code to String")
                 .c_stringify()
         }
     };
-    let to = CStr::from_ptr(to);
-    let to: post::model::Recipient = match to.to_str() {
-        Ok(result) => match post::model::Recipient::from_str(result) {
-            Ok(recipient) => recipient,
-            Err(e) => return e.c_stringify(),
-        },
-        Err(_) => {
-            return S5Error::new(ErrorKind::Input, "Could not convert to into String").c_stringify()
-        }
-    };
-    let kind = match CStr::from_ptr(kind).to_str() {
-        Ok(result) => result,
-        Err(_) => {
-            return S5Error::new(
-                ErrorKind::Input,
-                "Could not convert payload kind into String",
-            )
-            .c_stringify()
-        }
-    };
-
-    let value = match CStr::from_ptr(value).to_str() {
-        Ok(result) => result,
-        Err(_) => {
-            return S5Error::new(
-                ErrorKind::Input,
-                "Could not convert payload value into String",
-            )
-            .c_stringify()
-        }
-    };
-
-    let payload = format!("{}:{}", kind, value);
-    let payload: post::model::Payload = match post::model::Payload::from_str(&payload) {
-        Ok(payload) => payload,
-        Err(e) => return e.c_stringify(),
-    };
-
-    let my_identity = match identity::model::UserIdentity::new(social_root) {
-        Ok(result) => result,
-        Err(e) => return e.c_stringify(),
-    };
 
-    let post = post::model::Post::new(to, payload, xonly_pair.clone());
-    let encryption_key = my_identity.derive_encryption_key(index);
-    let cypher = post.to_cypher(encryption_key.clone());
-
-    let request = post::dto::ServerPostRequest::new(0, index, &cypher);
-    match post::dto::create(hostname.clone(), socks5, xonly_pair.clone(), request) {
-        Ok(id) => post::model::PostId::new(id).c_stringify(),
+    match Client::new(&hostname)
+        .with_network(network)
+        .admin_revoke_invite(&admin_secret, &invite_code)
+    {
+        Ok(_) => network::handler::ServerStatusResponse::new(true).c_stringify(),
         Err(e) => e.c_stringify(),
     }
 }
-/// SEND KEYS FOR A POST's RECIPIENTS
-/// `recipients` must be a comma separated list of recipients
+
+/// GET ALL MEMBERS ON THE SERVER
+/// USE TO ENSURE USERNAME OF CHOICE IS NOT TAKEN
 /// # Safety
 /// - This function is unsafe because it dereferences and a returns raw pointer.
 /// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
 #[no_mangle]
-pub unsafe extern "C" fn send_keys(
+pub unsafe extern "C" fn get_members(
     hostname: *const c_char,
     socks5: *const c_char,
     social_root: *const c_char,
-    index: *const c_char,
-    post_id: *const c_char,
-    recipients: *const c_char,
 ) -> *mut c_char {
-    let hostname_cstr = CStr::from_ptr(hostname);
-    let hostname: String = match hostname_cstr.to_str() {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
         Ok(string) => string.to_string(),
         Err(_) => {
             return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
@@ -619,22 +567,13 @@ pub unsafe extern "C" fn send_keys(
     };
 
     let socks5 = CStr::from_ptr(socks5);
-    let socks5: Option<u32> = match socks5.to_str() {
-        Ok(string) => match string.parse::<u32>() {
-            Ok(result) => {
-                if result == 0 {
-                    None
-                } else {
-                    Some(result)
-                }
-            }
-            Err(_) => {
-                return S5Error::new(ErrorKind::Input, "Could not parse socks5 port to uint32")
-                    .c_stringify()
-            }
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
         },
         Err(_) => {
-            return S5Error::new(ErrorKind::Input, "Could not convert socks5 port to String")
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
                 .c_stringify()
         }
     };
@@ -646,102 +585,134 @@ pub unsafe extern "C" fn send_keys(
                 .c_stringify()
         }
     };
-    let keypair = match ec::keypair_from_xprv_str(&social_root) {
-        Ok(keypair) => keypair,
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
         Err(e) => return e.c_stringify(),
     };
-    let xonly_pair = ec::XOnlyPair::from_keypair(keypair);
 
-    let index = CStr::from_ptr(index);
-    let index: u32 = match index.to_str() {
-        Ok(string) => match string.parse::<u32>() {
-            Ok(result) => result,
-            Err(_) => {
-                return S5Error::new(ErrorKind::Input, "Could not parse index to u32").c_stringify()
-            }
-        },
-        Err(_) => {
-            return S5Error::new(ErrorKind::Input, "Could not convert index to String")
-                .c_stringify()
-        }
+    match Client::new(&hostname).with_network(network).members(&my_identity) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// SAFETY NUMBER FOR COMPARING KEYS WITH A CONTACT OUT OF BAND
+/// BOTH SIDES GET THE SAME 60 DIGITS
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn safety_number(
+    social_root: *const c_char,
+    pubkey: *const c_char,
+) -> *mut c_char {
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
     };
 
-    let post_id = CStr::from_ptr(post_id);
-    let post_id: String = match post_id.to_str() {
-        Ok(string) => string.to_string(),
+    let pubkey = CStr::from_ptr(pubkey);
+    let pubkey: XOnlyPublicKey = match pubkey.to_str() {
+        Ok(string) => match ec::pubkey_from_str(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
         Err(_) => {
-            return S5Error::new(ErrorKind::Input, "Could not convert post_id to String")
+            return S5Error::new(ErrorKind::Input, "Could not convert pubkey to String")
                 .c_stringify()
         }
     };
 
-    let recipients = CStr::from_ptr(recipients);
-    let recipients: Vec<XOnlyPublicKey> = match recipients.to_str() {
-        Ok(result) => {
-            let string_pubkeys: Vec<&str> = result.split(",").collect();
-            if string_pubkeys.len() == 0 {
-                return S5Error::new(ErrorKind::Input, "Unable to parse recipients.").c_stringify();
-            } else {
-                let mut xonly_vec: Vec<XOnlyPublicKey> = [].to_vec();
-                for pubkey in string_pubkeys.into_iter() {
-                    match ec::pubkey_from_str(pubkey) {
-                        Ok(result) => xonly_vec.push(result),
-                        Err(_) => {
-                            return S5Error::new(
-                                ErrorKind::Input,
-                                "One recipient pubkey is not a valid XOnlyPubKey",
-                            )
-                            .c_stringify()
-                        }
-                    };
-                }
-                xonly_vec
-            }
+    let me = ec::XOnlyPair::from_xprv(my_identity.social_root).pubkey;
+    ContactCode { code: contact::safety_number(&me, &pubkey) }.c_stringify()
+}
+/// MY CONTACT URI, TO SHOW AS A QR CODE
+/// CARRIES hostname, username AND pubkey, SIGNED WITH THE SOCIAL KEY
+/// THE username IS MY OWN ENTRY IN directory (THE refresh_members RESULT)
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn contact_uri(
+    hostname: *const c_char,
+    social_root: *const c_char,
+    directory: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
         }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
         Err(_) => {
-            return S5Error::new(ErrorKind::Input, "Could not convert recipients into String")
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
                 .c_stringify()
         }
     };
-
     let my_identity = match identity::model::UserIdentity::new(social_root) {
         Ok(result) => result,
         Err(e) => return e.c_stringify(),
     };
-    let encryption_key = my_identity.derive_encryption_key(index);
-    let decryption_keys = match post::model::DecryptionKey::make_for_many(
-        xonly_pair.clone(),
-        recipients.clone(),
-        encryption_key.clone(),
-    ) {
-        Ok(keys) => keys,
-        Err(e) => return e.c_stringify(),
+
+    let directory = CStr::from_ptr(directory);
+    let directory: MemberDirectory = match directory.to_str() {
+        Ok("") => MemberDirectory::default(),
+        Ok(string) => match MemberDirectory::structify(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert directory to String")
+                .c_stringify()
+        }
     };
 
-    match post::dto::keys(
-        hostname.clone(),
-        socks5,
-        xonly_pair.clone(),
-        post_id.clone(),
-        decryption_keys,
-    ) {
-        Ok(()) => network::handler::ServerStatusResponse::new(true).c_stringify(),
-        Err(e) => return e.c_stringify(),
+    let me = ec::XOnlyPair::from_xprv(my_identity.social_root);
+    let username = match directory.resolve(&me.pubkey) {
+        Some(entry) if entry.left.is_none() => entry.username.clone(),
+        _ => {
+            return S5Error::new(ErrorKind::NoResource, "Not a member of this server.")
+                .c_stringify()
+        }
+    };
+
+    match ContactCard::new(&hostname, &username, &me) {
+        Ok(card) => ContactCode { code: card.to_uri() }.c_stringify(),
+        Err(e) => e.c_stringify(),
     }
 }
-/// GET A SINGLE POST BY ID
+/// MARK A CONTACT AS VERIFIED
+/// PASS THE CONTACTS FROM THE LAST CALL (OR "") AND EITHER A SCANNED contact_uri, OR THE username AND pubkey
+/// WHOSE SAFETY NUMBER WAS COMPARED (WITH uri AS ""). RETURNS THE UPDATED CONTACTS TO STORE
+/// THE username MUST BELONG TO pubkey IN directory. replace IS "true" ONLY ONCE THE USER CONFIRMED
+/// REPLACING A USERNAME ALREADY VERIFIED UNDER ANOTHER KEY
 /// # Safety
 /// - This function is unsafe because it dereferences and a returns raw pointer.
 /// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
 #[no_mangle]
-pub unsafe extern "C" fn get_one_post(
+pub unsafe extern "C" fn verify_contact(
     hostname: *const c_char,
-    socks5: *const c_char,
-    social_root: *const c_char,
-    post_id: *const c_char,
+    contacts: *const c_char,
+    uri: *const c_char,
+    username: *const c_char,
+    pubkey: *const c_char,
+    directory: *const c_char,
+    replace: *const c_char,
 ) -> *mut c_char {
-    let hostname_cstr = CStr::from_ptr(hostname);
-    let hostname: String = match hostname_cstr.to_str() {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
         Ok(string) => string.to_string(),
         Err(_) => {
             return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
@@ -749,23 +720,119 @@ pub unsafe extern "C" fn get_one_post(
         }
     };
 
-    let socks5 = CStr::from_ptr(socks5);
-    let socks5: Option<u32> = match socks5.to_str() {
-        Ok(string) => match string.parse::<u32>() {
-            Ok(result) => {
-                if result == 0 {
-                    None
-                } else {
-                    Some(result)
-                }
+    let contacts = CStr::from_ptr(contacts);
+    let mut contacts: Contacts = match contacts.to_str() {
+        Ok("") => Contacts::default(),
+        Ok(string) => match Contacts::structify(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert contacts to String")
+                .c_stringify()
+        }
+    };
+
+    let uri = CStr::from_ptr(uri);
+    let uri: String = match uri.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert uri to String")
+                .c_stringify()
+        }
+    };
+
+    let directory = CStr::from_ptr(directory);
+    let directory: MemberDirectory = match directory.to_str() {
+        Ok("") => MemberDirectory::default(),
+        Ok(string) => match MemberDirectory::structify(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert directory to String")
+                .c_stringify()
+        }
+    };
+
+    let replace = CStr::from_ptr(replace);
+    let replace: bool = match replace.to_str() {
+        Ok(string) => match string.parse::<bool>() {
+            Ok(value) => value,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse replace to bool").c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert replace to String").c_stringify()
+        }
+    };
+
+    if uri.is_empty() {
+        let username = CStr::from_ptr(username);
+        let username: String = match username.to_str() {
+            Ok(string) => string.to_string(),
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not convert username to String")
+                    .c_stringify()
             }
+        };
+
+        let pubkey = CStr::from_ptr(pubkey);
+        let pubkey: XOnlyPublicKey = match pubkey.to_str() {
+            Ok(string) => match ec::pubkey_from_str(string) {
+                Ok(result) => result,
+                Err(e) => return e.c_stringify(),
+            },
             Err(_) => {
-                return S5Error::new(ErrorKind::Input, "Could not parse socks5 port to uint32")
+                return S5Error::new(ErrorKind::Input, "Could not convert pubkey to String")
                     .c_stringify()
             }
+        };
+
+        if let Err(e) = contacts.verify(&username, pubkey, &directory, replace) {
+            return e.c_stringify();
+        }
+    } else {
+        let card = match ContactCard::from_uri(&uri) {
+            Ok(card) => card,
+            Err(e) => return e.c_stringify(),
+        };
+        if let Err(e) = contacts.verify_card(&hostname, &card, &directory, replace) {
+            return e.c_stringify();
+        }
+    }
+    contacts.c_stringify()
+}
+/// REFRESH A CACHED MEMBER DIRECTORY
+/// PASS THE LAST RESULT BACK IN (OR "" THE FIRST TIME); ONLY CHANGES SINCE THEN ARE FETCHED
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn refresh_members(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    directory: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
         },
         Err(_) => {
-            return S5Error::new(ErrorKind::Input, "Could not convert socks5 port to String")
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
                 .c_stringify()
         }
     };
@@ -777,53 +844,46 @@ pub unsafe extern "C" fn get_one_post(
                 .c_stringify()
         }
     };
-    let keypair = match ec::keypair_from_xprv_str(&social_root) {
-        Ok(keypair) => keypair,
-        Err(e) => return e.c_stringify(),
-    };
-    let xonly_pair = ec::XOnlyPair::from_keypair(keypair);
-    let social_xprv = match ExtendedPrivKey::from_str(&social_root) {
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
         Ok(result) => result,
-        Err(_) => return S5Error::new(ErrorKind::Key, "BAD XPRV STRING").c_stringify(),
+        Err(e) => return e.c_stringify(),
     };
 
-    let post_id = CStr::from_ptr(post_id);
-    let post_id: String = match post_id.to_str() {
-        Ok(string) => string.to_string(),
+    let directory = CStr::from_ptr(directory);
+    let mut directory: MemberDirectory = match directory.to_str() {
+        Ok("") => MemberDirectory::default(),
+        Ok(string) => match MemberDirectory::structify(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
         Err(_) => {
-            return S5Error::new(ErrorKind::Input, "Could not convert post_id to String")
+            return S5Error::new(ErrorKind::Input, "Could not convert directory to String")
                 .c_stringify()
         }
     };
 
-    let cypherpost = match post::dto::single_post(
-        hostname.clone(),
-        socks5,
-        xonly_pair.clone(),
-        post_id.clone(),
-    ) {
-        Ok(post) => post,
-        Err(e) => return e.c_stringify(),
-    };
-
-    match cypherpost.decypher(social_xprv) {
-        Ok(post) => post.c_stringify(),
-        Err(e) => return e.c_stringify(),
+    match Client::new(&hostname)
+        .with_network(network)
+        .refresh_directory(&my_identity, &mut directory) {
+        Ok(()) => directory.c_stringify(),
+        Err(e) => e.c_stringify(),
     }
 }
-/// GET ALL POSTS FOR A USER
+/// CHECK A USERNAME AGAINST THE MEMBER LIST, E.G. BEFORE SHARING AN INVITE
+/// VALIDATES THE NAMING POLICY LOCALLY, THEN CHECKS IT IS FREE AND NOT CONFUSABLE WITH AN EXISTING MEMBER
+/// social_root MUST BE A MEMBER'S: THE MEMBER LIST IS ONLY SHOWN TO MEMBERS
 /// # Safety
 /// - This function is unsafe because it dereferences and a returns raw pointer.
 /// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
 #[no_mangle]
-pub unsafe extern "C" fn get_all_posts(
+pub unsafe extern "C" fn check_username(
     hostname: *const c_char,
     socks5: *const c_char,
     social_root: *const c_char,
-    genesis_filter: *const c_char,
+    username: *const c_char,
 ) -> *mut c_char {
-    let hostname_cstr = CStr::from_ptr(hostname);
-    let hostname: String = match hostname_cstr.to_str() {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
         Ok(string) => string.to_string(),
         Err(_) => {
             return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
@@ -832,22 +892,13 @@ pub unsafe extern "C" fn get_all_posts(
     };
 
     let socks5 = CStr::from_ptr(socks5);
-    let socks5: Option<u32> = match socks5.to_str() {
-        Ok(string) => match string.parse::<u32>() {
-            Ok(result) => {
-                if result == 0 {
-                    None
-                } else {
-                    Some(result)
-                }
-            }
-            Err(_) => {
-                return S5Error::new(ErrorKind::Input, "Could not parse socks5 port to uint32")
-                    .c_stringify()
-            }
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
         },
         Err(_) => {
-            return S5Error::new(ErrorKind::Input, "Could not convert socks5 port to String")
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
                 .c_stringify()
         }
     };
@@ -859,61 +910,41 @@ pub unsafe extern "C" fn get_all_posts(
                 .c_stringify()
         }
     };
-    let social_xprv = match ExtendedPrivKey::from_str(&social_root) {
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
         Ok(result) => result,
-        Err(_) => return S5Error::new(ErrorKind::Key, "BAD XPRV STRING").c_stringify(),
+        Err(e) => return e.c_stringify(),
     };
-    let xonly_pair = ec::XOnlyPair::from_xprv(social_xprv.clone());
 
-    let genesis_filter = CStr::from_ptr(genesis_filter);
-    let genesis_filter: Option<u64> = match genesis_filter.to_str() {
-        Ok(string) => match string.parse::<u64>() {
-            Ok(value) => {
-                if value == 0 {
-                    None
-                } else {
-                    Some(value)
-                }
-            }
-            Err(_) => {
-                return S5Error::new(ErrorKind::Key, "Could not parse genesis filter to u64")
-                    .c_stringify()
-            }
-        },
+    let username = CStr::from_ptr(username);
+    let username: String = match username.to_str() {
+        Ok(string) => string.to_string(),
         Err(_) => {
-            return S5Error::new(
-                ErrorKind::Input,
-                "Could not convert genesis filter to String",
-            )
-            .c_stringify()
+            return S5Error::new(ErrorKind::Input, "Could not convert username to String")
+                .c_stringify()
         }
     };
 
-    match post::dto::get_all_posts(
-        hostname.clone(),
-        socks5,
-        social_xprv.clone(),
-        genesis_filter,
-    ) {
-        Ok(mut all) => all.to_all_posts_as_chat(xonly_pair.pubkey).c_stringify(),
+    match Client::new(&hostname)
+        .with_network(network)
+        .check_username(&my_identity, &username) {
+        Ok(result) => result.c_stringify(),
         Err(e) => e.c_stringify(),
     }
 }
-/// GET LAST DERIVATION INDEX
-/// USERS SHOULD STORE AND UPDATE LAST USED INDEX FOR FORWARD SECRECY
-/// USE THIS FUNCTION ONLY IN CASE OF RECOVERY AND LOSS OF LOCAL DATA
-/// AVOID USING THIS BEFORE EVERY POST BY KEEPING TRACK OF INDEX LOCALLY
+/// REGISTER TO A PRIVATE SERVER
 /// # Safety
 /// - This function is unsafe because it dereferences and a returns raw pointer.
 /// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
 #[no_mangle]
-pub unsafe extern "C" fn last_index(
+pub unsafe extern "C" fn join(
     hostname: *const c_char,
     socks5: *const c_char,
     social_root: *const c_char,
+    username: *const c_char,
+    invite_code: *const c_char,
 ) -> *mut c_char {
-    let hostname_cstr = CStr::from_ptr(hostname);
-    let hostname: String = match hostname_cstr.to_str() {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
         Ok(string) => string.to_string(),
         Err(_) => {
             return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
@@ -922,22 +953,13 @@ pub unsafe extern "C" fn last_index(
     };
 
     let socks5 = CStr::from_ptr(socks5);
-    let socks5: Option<u32> = match socks5.to_str() {
-        Ok(string) => match string.parse::<u32>() {
-            Ok(result) => {
-                if result == 0 {
-                    None
-                } else {
-                    Some(result)
-                }
-            }
-            Err(_) => {
-                return S5Error::new(ErrorKind::Input, "Could not parse socks5 port to uint32")
-                    .c_stringify()
-            }
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
         },
         Err(_) => {
-            return S5Error::new(ErrorKind::Input, "Could not convert socks5 port to String")
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
                 .c_stringify()
         }
     };
@@ -949,25 +971,3489 @@ pub unsafe extern "C" fn last_index(
                 .c_stringify()
         }
     };
-    let keypair = match ec::keypair_from_xprv_str(&social_root) {
-        Ok(keypair) => keypair,
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
         Err(e) => return e.c_stringify(),
     };
-    let xonly_pair = ec::XOnlyPair::from_keypair(keypair);
-
-    match post::dto::last_derivation(hostname.clone(), socks5, xonly_pair.clone()) {
-        Ok(last_index) => last_index.c_stringify(),
-        Err(e) => return e.c_stringify(),
-    }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use bitcoin::network::constants::Network;
-    use std::ffi::{CStr, CString};
+    let username = CStr::from_ptr(username);
+    let username: String = match username.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert username to String")
+                .c_stringify()
+        }
+    };
+
+    let invite_code = CStr::from_ptr(invite_code);
+    let invite_code: String = match invite_code.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert invite_code to String")
+                .c_stringify()
+        }
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .join(&my_identity, &username, &invite_code) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// REQUEST A PAYMENT INVOICE TO JOIN A PUBLIC SERVER
+/// PAY THE invoice, WAIT FOR IT TO SETTLE, THEN COMPLETE REGISTRATION WITH join_paid
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn join_invoice(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .join_invoice(&my_identity) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// GET THE STATUS OF A JOIN INVOICE (UNPAID, PAID OR EXPIRED)
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn invoice_status(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    invoice_id: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let invoice_id = CStr::from_ptr(invoice_id);
+    let invoice_id: String = match invoice_id.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert invoice_id to String")
+                .c_stringify()
+        }
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .invoice_status(&my_identity, &invoice_id) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// BLOCK UNTIL A JOIN INVOICE IS PAID, POLLING EVERY SECOND
+/// ERRORS IF THE INVOICE EXPIRES OR timeout_secs PASSES FIRST. USE start_request("wait_for_payment", ..) TO WAIT WITHOUT BLOCKING
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn wait_for_payment(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    invoice_id: *const c_char,
+    timeout_secs: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let invoice_id = CStr::from_ptr(invoice_id);
+    let invoice_id: String = match invoice_id.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert invoice_id to String")
+                .c_stringify()
+        }
+    };
+
+    let timeout_secs = CStr::from_ptr(timeout_secs);
+    let timeout_secs: u64 = match timeout_secs.to_str() {
+        Ok(string) => match string.parse::<u64>() {
+            Ok(value) => value,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse timeout_secs to u64")
+                    .c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert timeout_secs to String")
+                .c_stringify()
+        }
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .wait_for_payment(&my_identity, &invoice_id, Duration::from_secs(timeout_secs)) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// REGISTER TO A PUBLIC SERVER USING A PAID INVOICE
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn join_paid(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    username: *const c_char,
+    invoice_id: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let username = CStr::from_ptr(username);
+    let username: String = match username.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert username to String")
+                .c_stringify()
+        }
+    };
+
+    let invoice_id = CStr::from_ptr(invoice_id);
+    let invoice_id: String = match invoice_id.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert invoice_id to String")
+                .c_stringify()
+        }
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .join_paid(&my_identity, &username, &invoice_id) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// LEAVE A SERVER
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn leave(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    match Client::new(&hostname).with_network(network).leave(&my_identity) {
+        Ok(_) => network::handler::ServerStatusResponse::new(true).c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// CREATE A POST & KEYS
+/// `to` must be colon separated `kind:value` of recipient
+/// `kind` is the kind of payload (message or secret)
+/// `value` is the value of the payload (watch out for special chars and escape chars)
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn send_post(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    index: *const c_char,
+    to: *const c_char,
+    kind: *const c_char,
+    value: *const c_char,
+) -> *mut c_char {
+    let hostname_cstr = CStr::from_ptr(hostname);
+    let hostname: String = match hostname_cstr.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let index = CStr::from_ptr(index);
+    let index: u32 = match index.to_str() {
+        Ok(string) => match string.parse::<u32>() {
+            Ok(result) => result,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse index to u32").c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert index to String")
+                .c_stringify()
+        }
+    };
+    let to = CStr::from_ptr(to);
+    let to: post::model::Recipient = match to.to_str() {
+        Ok(result) => match post::model::Recipient::from_str(result) {
+            Ok(recipient) => recipient,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert to into String").c_stringify()
+        }
+    };
+    let kind = match CStr::from_ptr(kind).to_str() {
+        Ok(result) => result,
+        Err(_) => {
+            return S5Error::new(
+                ErrorKind::Input,
+                "Could not convert payload kind into String",
+            )
+            .c_stringify()
+        }
+    };
+
+    let value = match CStr::from_ptr(value).to_str() {
+        Ok(result) => result,
+        Err(_) => {
+            return S5Error::new(
+                ErrorKind::Input,
+                "Could not convert payload value into String",
+            )
+            .c_stringify()
+        }
+    };
+
+    let payload = format!("{}:{}", kind, value);
+    let payload: post::model::Payload = match post::model::Payload::from_str(&payload) {
+        Ok(payload) => payload,
+        Err(e) => return e.c_stringify(),
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .send_post(&my_identity, index, to, payload)
+    {
+        Ok(id) => post::model::PostId::new(id).c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// SAME AS send_post, FOR A POST THAT REPLIES TO, EDITS OR REACTS TO AN EARLIER POST
+/// `links` is a PostLinks json with any of reply_to, edits or reacts_to set to a post id
+/// edits and reactions must be message posts; a reaction is at most 16 characters
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn send_post_with_links(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    index: *const c_char,
+    to: *const c_char,
+    kind: *const c_char,
+    value: *const c_char,
+    links: *const c_char,
+) -> *mut c_char {
+    let hostname_cstr = CStr::from_ptr(hostname);
+    let hostname: String = match hostname_cstr.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let index = CStr::from_ptr(index);
+    let index: u32 = match index.to_str() {
+        Ok(string) => match string.parse::<u32>() {
+            Ok(result) => result,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse index to u32").c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert index to String")
+                .c_stringify()
+        }
+    };
+    let to = CStr::from_ptr(to);
+    let to: post::model::Recipient = match to.to_str() {
+        Ok(result) => match post::model::Recipient::from_str(result) {
+            Ok(recipient) => recipient,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert to into String").c_stringify()
+        }
+    };
+    let kind = match CStr::from_ptr(kind).to_str() {
+        Ok(result) => result,
+        Err(_) => {
+            return S5Error::new(
+                ErrorKind::Input,
+                "Could not convert payload kind into String",
+            )
+            .c_stringify()
+        }
+    };
+
+    let value = match CStr::from_ptr(value).to_str() {
+        Ok(result) => result,
+        Err(_) => {
+            return S5Error::new(
+                ErrorKind::Input,
+                "Could not convert payload value into String",
+            )
+            .c_stringify()
+        }
+    };
+
+    let payload = format!("{}:{}", kind, value);
+    let payload: post::model::Payload = match post::model::Payload::from_str(&payload) {
+        Ok(payload) => payload,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let links = CStr::from_ptr(links);
+    let links: post::model::PostLinks = match links.to_str() {
+        Ok(string) => match post::model::PostLinks::structify(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert links to String").c_stringify()
+        }
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .send_post_with_links(&my_identity, index, to, payload, links)
+    {
+        Ok(id) => post::model::PostId::new(id).c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// SIGN AND PUBLISH A PROFILE AS A POST
+/// `audience` is a comma separated list of pubkeys; pass "" to share with every member
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn publish_profile(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    index: *const c_char,
+    profile: *const c_char,
+    audience: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let index = CStr::from_ptr(index);
+    let index: u32 = match index.to_str() {
+        Ok(string) => match string.parse::<u32>() {
+            Ok(result) => result,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse index to u32").c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert index to String")
+                .c_stringify()
+        }
+    };
+
+    let profile = CStr::from_ptr(profile);
+    let profile: Profile = match profile.to_str() {
+        Ok(string) => match Profile::structify(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert profile to String")
+                .c_stringify()
+        }
+    };
+
+    let audience = CStr::from_ptr(audience);
+    let audience: Option<Vec<XOnlyPublicKey>> = match audience.to_str() {
+        Ok("") => None,
+        Ok(result) => {
+            let mut xonly_vec: Vec<XOnlyPublicKey> = [].to_vec();
+            for pubkey in result.split(',') {
+                match ec::pubkey_from_str(pubkey) {
+                    Ok(result) => xonly_vec.push(result),
+                    Err(_) => {
+                        return S5Error::new(
+                            ErrorKind::Input,
+                            "One audience pubkey is not a valid XOnlyPubKey",
+                        )
+                        .c_stringify()
+                    }
+                }
+            }
+            Some(xonly_vec)
+        }
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert audience to String")
+                .c_stringify()
+        }
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .publish_profile(&my_identity, index, profile, audience.as_deref())
+    {
+        Ok(id) => post::model::PostId::new(id).c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// GET THE LATEST VERIFIED PROFILE OF EACH MEMBER WHO SHARED ONE
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn get_profiles(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    match Client::new(&hostname).with_network(network).profiles(&my_identity) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// SIGN A PROFILE AND EXPORT IT AS A CARD TO SHARE OUTSIDE THE SERVER
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn export_profile_card(
+    social_root: *const c_char,
+    profile: *const c_char,
+) -> *mut c_char {
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let profile = CStr::from_ptr(profile);
+    let profile: Profile = match profile.to_str() {
+        Ok(string) => match Profile::structify(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert profile to String")
+                .c_stringify()
+        }
+    };
+
+    match SignedProfile::new(profile, &ec::XOnlyPair::from_xprv(my_identity.social_root)) {
+        Ok(signed) => ProfileCard { card: signed.to_card() }.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// SUMMARISE A BASE64 PSBT: INPUTS, OUTPUTS, FEE AND WHO HAS SIGNED
+/// network IS bitcoin, testnet, signet OR regtest AND ONLY AFFECTS OUTPUT ADDRESSES
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn psbt_summary(psbt: *const c_char, network: *const c_char) -> *mut c_char {
+    let psbt = CStr::from_ptr(psbt);
+    let psbt = match psbt.to_str() {
+        Ok(string) => match post::psbt::parse(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert psbt to String")
+                .c_stringify()
+        }
+    };
+    let network = CStr::from_ptr(network);
+    let network: Network = match network.to_str() {
+        Ok(string) => match Network::from_str(&string.to_lowercase()) {
+            Ok(result) => result,
+            Err(_) => {
+                return S5Error::new(
+                    ErrorKind::Input,
+                    "Network must be bitcoin, testnet, signet or regtest",
+                )
+                .c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert network to String")
+                .c_stringify()
+        }
+    };
+
+    post::psbt::PsbtSummary::new(&psbt, network).c_stringify()
+}
+/// MERGE THE SIGNATURES OF SEVERAL COPIES OF ONE PSBT
+/// psbts IS A COMMA SEPARATED LIST OF BASE64 PSBTS
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn combine_psbts(psbts: *const c_char) -> *mut c_char {
+    let psbts = CStr::from_ptr(psbts);
+    let psbts: Vec<String> = match psbts.to_str() {
+        Ok(string) => string
+            .split(',')
+            .filter(|psbt| !psbt.trim().is_empty())
+            .map(|psbt| psbt.trim().to_string())
+            .collect(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert psbts to String")
+                .c_stringify()
+        }
+    };
+
+    match post::psbt::combine(&psbts) {
+        Ok(psbt) => post::psbt::CombinedPsbt { psbt }.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// ASSEMBLE A wsh(sortedmulti(...)) DESCRIPTOR FROM COSIGNER KEY CONTRIBUTIONS
+/// contributions IS A COMMA SEPARATED LIST OF [fingerprint/path]xpub
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn assemble_descriptor(
+    threshold: *const c_char,
+    contributions: *const c_char,
+) -> *mut c_char {
+    let threshold = CStr::from_ptr(threshold);
+    let threshold: usize = match threshold.to_str() {
+        Ok(string) => match string.parse::<usize>() {
+            Ok(result) => result,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse threshold to usize")
+                    .c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert threshold to String")
+                .c_stringify()
+        }
+    };
+    let contributions = CStr::from_ptr(contributions);
+    let contributions: Vec<KeyContribution> = match contributions.to_str() {
+        Ok(string) => match string
+            .split(',')
+            .filter(|key| !key.trim().is_empty())
+            .map(KeyContribution::from_str)
+            .collect()
+        {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert contributions to String")
+                .c_stringify()
+        }
+    };
+
+    match MultisigDescriptor::assemble(threshold, contributions) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// ASSEMBLE A DESCRIPTOR FROM THE LATEST xpub POST OF EACH MEMBER OF A CHAT
+/// chat IS ONE PostsAsChat FROM get_chats OR get_all_posts
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn chat_descriptor(threshold: *const c_char, chat: *const c_char) -> *mut c_char {
+    let threshold = CStr::from_ptr(threshold);
+    let threshold: usize = match threshold.to_str() {
+        Ok(string) => match string.parse::<usize>() {
+            Ok(result) => result,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse threshold to usize")
+                    .c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert threshold to String")
+                .c_stringify()
+        }
+    };
+    let chat = CStr::from_ptr(chat);
+    let chat: post::model::PostsAsChat = match chat.to_str() {
+        Ok(string) => match post::model::PostsAsChat::structify(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert chat to String").c_stringify()
+        }
+    };
+
+    match post::descriptor::assemble_from_posts(threshold, &chat.posts) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// CHECK A RECEIVED MULTISIG DESCRIPTOR AND LIST ITS KEYS
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn parse_descriptor(descriptor: *const c_char) -> *mut c_char {
+    let descriptor = CStr::from_ptr(descriptor);
+    let descriptor: String = match descriptor.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert descriptor to String")
+                .c_stringify()
+        }
+    };
+
+    match MultisigDescriptor::from_descriptor(&descriptor) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// CREATE A PAYMENT REQUEST TO POST AS A payment_request PAYLOAD
+/// amount IS IN SATS AND expiry IS A MILLISECOND TIMESTAMP; PASS "" FOR NONE
+/// wif IS THE PRIVATE KEY OF A p2wpkh OR p2tr address TO PROVE OWNERSHIP WITH; PASS "" TO SEND WITHOUT A PROOF
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn payment_request(
+    social_root: *const c_char,
+    address: *const c_char,
+    amount: *const c_char,
+    label: *const c_char,
+    expiry: *const c_char,
+    wif: *const c_char,
+) -> *mut c_char {
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+    let address = CStr::from_ptr(address);
+    let address: String = match address.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert address to String")
+                .c_stringify()
+        }
+    };
+    let amount = CStr::from_ptr(amount);
+    let amount: Option<u64> = match amount.to_str() {
+        Ok("") => None,
+        Ok(string) => match string.parse::<u64>() {
+            Ok(result) => Some(result),
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse amount to u64")
+                    .c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert amount to String")
+                .c_stringify()
+        }
+    };
+    let label = CStr::from_ptr(label);
+    let label: Option<String> = match label.to_str() {
+        Ok("") => None,
+        Ok(string) => Some(string.to_string()),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert label to String")
+                .c_stringify()
+        }
+    };
+    let expiry = CStr::from_ptr(expiry);
+    let expiry: Option<u64> = match expiry.to_str() {
+        Ok("") => None,
+        Ok(string) => match string.parse::<u64>() {
+            Ok(result) => Some(result),
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse expiry to u64")
+                    .c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert expiry to String")
+                .c_stringify()
+        }
+    };
+    let wif = CStr::from_ptr(wif);
+    let wif: Option<PrivateKey> = match wif.to_str() {
+        Ok("") => None,
+        Ok(string) => match PrivateKey::from_wif(string) {
+            Ok(result) => Some(result),
+            Err(_) => return S5Error::new(ErrorKind::Key, "Bad wif private key.").c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert wif to String").c_stringify()
+        }
+    };
+
+    let mut request = match PaymentRequest::new(&address, amount, label, expiry) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+    let owner = ec::XOnlyPair::from_xprv(my_identity.social_root).pubkey;
+    if let Some(wif) = wif {
+        if let Err(e) = request.prove(&owner, &wif.inner) {
+            return e.c_stringify();
+        }
+    }
+    match request.verify(&owner, my_identity.social_root.network) {
+        Ok(_) => request.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// CHECK A payment_request PAYLOAD POSTED BY owner AND READ ITS AMOUNT, LABEL AND EXPIRY
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn payment_request_details(
+    social_root: *const c_char,
+    owner: *const c_char,
+    request: *const c_char,
+) -> *mut c_char {
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+    let owner = CStr::from_ptr(owner);
+    let owner: XOnlyPublicKey = match owner.to_str() {
+        Ok(string) => match ec::pubkey_from_str(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert owner to String").c_stringify()
+        }
+    };
+    let request = CStr::from_ptr(request);
+    let request: PaymentRequest = match request.to_str() {
+        Ok(string) => match PaymentRequest::structify(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert request to String")
+                .c_stringify()
+        }
+    };
+
+    match request.verify(&owner, my_identity.social_root.network) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// IMPORT A PROFILE CARD
+/// ERRORS UNLESS THE CARD IS SIGNED BY THE PUBKEY IT CARRIES
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn import_profile_card(card: *const c_char) -> *mut c_char {
+    let card = CStr::from_ptr(card);
+    let card: String = match card.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert card to String")
+                .c_stringify()
+        }
+    };
+
+    match SignedProfile::from_card(&card) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// GIVE A Trust, Scam OR Escrow BADGE
+/// details IS A BadgeDetails JSON WITH ANY OF reason, expiry AND reference, OR "" FOR A PLAIN BADGE
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn give_badge(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    to: *const c_char,
+    kind: *const c_char,
+    details: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let to = CStr::from_ptr(to);
+    let to: XOnlyPublicKey = match to.to_str() {
+        Ok(string) => match ec::pubkey_from_str(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert to into String").c_stringify()
+        }
+    };
+
+    let kind = CStr::from_ptr(kind);
+    let kind: AnnouncementType = match kind.to_str() {
+        Ok(string) => match string.to_lowercase().as_str() {
+            "trust" | "scam" | "escrow" => AnnouncementType::from_str(string).unwrap(),
+            _ => {
+                return S5Error::new(ErrorKind::Input, "Badge kind must be trust, scam or escrow.")
+                    .c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert kind to String").c_stringify()
+        }
+    };
+
+    let details = CStr::from_ptr(details);
+    let details: BadgeDetails = match details.to_str() {
+        Ok("") => BadgeDetails::default(),
+        Ok(string) => match BadgeDetails::structify(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert details to String")
+                .c_stringify()
+        }
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .give_badge_with_details(&my_identity, to, kind, details)
+    {
+        Ok(_) => network::handler::ServerStatusResponse::new(true).c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// SPLIT A SECRET INTO threshold-OF-n SHARES AND SEND ONE TO EACH OF holders AS A DIRECT secret POST
+/// holders IS A COMMA SEPARATED LIST OF PUBKEYS; label IS OPTIONAL, PASS "" FOR NONE
+/// KEEP THE RETURNED RecoverySet: IT RECORDS WHO HOLDS WHICH SHARE
+/// IF SENDING FAILS PART WAY, THE SET CARRIES error AND ONLY THE HOLDERS ALREADY SENT HAVE A post_id
+/// A HOLDER WITH keys_pending HAS A post_id BUT NO KEYS: FINISH WITH send_keys USING THAT INDEX
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn distribute_shares(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    secret: *const c_char,
+    threshold: *const c_char,
+    holders: *const c_char,
+    label: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let secret = CStr::from_ptr(secret);
+    let secret: String = match secret.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert secret to String").c_stringify()
+        }
+    };
+    let threshold = CStr::from_ptr(threshold);
+    let threshold: u8 = match threshold.to_str() {
+        Ok(string) => match string.parse::<u8>() {
+            Ok(result) => result,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse threshold to u8")
+                    .c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert threshold to String")
+                .c_stringify()
+        }
+    };
+    let holders = CStr::from_ptr(holders);
+    let holders: Vec<XOnlyPublicKey> = match holders.to_str() {
+        Ok(string) => match string
+            .split(',')
+            .filter(|pubkey| !pubkey.trim().is_empty())
+            .map(|pubkey| ec::pubkey_from_str(pubkey.trim()))
+            .collect()
+        {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert holders to String")
+                .c_stringify()
+        }
+    };
+    let label = CStr::from_ptr(label);
+    let label: Option<String> = match label.to_str() {
+        Ok("") => None,
+        Ok(string) => Some(string.to_string()),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert label to String").c_stringify()
+        }
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .distribute_shares(&my_identity, &secret, threshold, &holders, label)
+    {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// RECOVERY SHARES SENT TO social_root, GROUPED BY SET, WITH THE SECRET OF EACH SET THAT HAS ENOUGH SHARES
+/// AS A GUARDIAN THESE ARE THE SHARES HELD FOR OTHERS; ON A NEW IDENTITY, THE SHARES RETURNED BY GUARDIANS
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn get_recoveries(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    match Client::new(&hostname).with_network(network).recoveries(&my_identity) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// AS A GUARDIAN, SEND THE SHARE HELD FOR set_id TO THE OWNER's NEW IDENTITY to
+/// CHECK to OUT OF BAND FIRST, E.G. WITH safety_number
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn return_share(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    set_id: *const c_char,
+    to: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let set_id = CStr::from_ptr(set_id);
+    let set_id: String = match set_id.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert set_id to String").c_stringify()
+        }
+    };
+    let to = CStr::from_ptr(to);
+    let to: XOnlyPublicKey = match to.to_str() {
+        Ok(string) => match ec::pubkey_from_str(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert to into String").c_stringify()
+        }
+    };
+
+    match Client::new(&hostname).with_network(network).return_share(&my_identity, &set_id, to) {
+        Ok(id) => post::model::PostId::new(id).c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// THE CURRENT STATE OF EVERY TRADE NEGOTIATED IN POSTS VISIBLE TO social_root
+/// prompts LISTS THE BADGES TO OFFER TO GIVE ONCE A TRADE COMPLETES
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn get_trades(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    match Client::new(&hostname).with_network(network).trades(&my_identity) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// SIGN THE NEXT MESSAGE OF A TRADE, TO SEND AS A trade PAYLOAD
+/// action IS offer, counter, accept, cancel OR complete
+/// trade IS ONE TradeState FROM get_trades, "" FOR AN offer
+/// terms IS A TradeTerms JSON FOR offer AND counter, "" OTHERWISE
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn trade_message(
+    social_root: *const c_char,
+    action: *const c_char,
+    trade: *const c_char,
+    terms: *const c_char,
+) -> *mut c_char {
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+    let action = CStr::from_ptr(action);
+    let action: TradeAction = match action.to_str() {
+        Ok(string) => match TradeAction::from_str(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert action to String").c_stringify()
+        }
+    };
+    let trade = CStr::from_ptr(trade);
+    let trade: Option<TradeState> = match trade.to_str() {
+        Ok("") => None,
+        Ok(string) => match TradeState::structify(string) {
+            Ok(result) => Some(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert trade to String").c_stringify()
+        }
+    };
+    let terms = CStr::from_ptr(terms);
+    let terms: Option<TradeTerms> = match terms.to_str() {
+        Ok("") => None,
+        Ok(string) => match TradeTerms::structify(string) {
+            Ok(result) => Some(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert terms to String").c_stringify()
+        }
+    };
+
+    let keys = ec::XOnlyPair::from_xprv(my_identity.social_root);
+    let message = match (action, trade, terms) {
+        (TradeAction::Offer, _, Some(terms)) => TradeMessage::offer(&keys, terms),
+        (TradeAction::Offer, _, None) => Err(S5Error::new(ErrorKind::Input, "An offer needs terms.")),
+        (action, Some(trade), terms) => trade.respond(&keys, action, terms),
+        (_, None, _) => Err(S5Error::new(ErrorKind::Input, "Only an offer can start a trade.")),
+    };
+    match message {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// SEND delivered OR read RECEIPTS FOR POSTS OTHERS SENT social_root, BATCHED INTO ONE POST PER OWNER
+/// post_ids IS A COMMA SEPARATED LIST, OR "" FOR EVERY POST NOT YET ACKNOWLEDGED
+/// settings IS A ReceiptSettings json OR "" TO SEND RECEIPTS IN EVERY CONVERSATION
+/// IF SENDING FAILS PART WAY, THE RESULT CARRIES error AND THE ids ALREADY SENT
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn send_receipts(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    kind: *const c_char,
+    post_ids: *const c_char,
+    settings: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let kind = CStr::from_ptr(kind);
+    let kind: ReceiptKind = match kind.to_str() {
+        Ok(string) => match ReceiptKind::from_str(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert kind to String").c_stringify()
+        }
+    };
+    let post_ids = CStr::from_ptr(post_ids);
+    let post_ids: Option<Vec<String>> = match post_ids.to_str() {
+        Ok("") => None,
+        Ok(string) => Some(string.split(',').map(|id| id.trim().to_string()).collect()),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert post_ids to String").c_stringify()
+        }
+    };
+    let settings = CStr::from_ptr(settings);
+    let settings: ReceiptSettings = match settings.to_str() {
+        Ok("") => ReceiptSettings::default(),
+        Ok(string) => match ReceiptSettings::structify(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert settings to String").c_stringify()
+        }
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .send_receipts(&my_identity, kind, post_ids.as_deref(), &settings)
+    {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// ENCRYPT THE FILE AT path AND SEND IT AS AN ATTACHMENT TO to, READABLE BY THE COMMA SEPARATED recipients
+/// CHUNKS AND THE MANIFEST USE THE DERIVATION INDEXES AFTER THE LAST ONE USED
+/// mime CAN BE ""
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn send_attachment(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    to: *const c_char,
+    recipients: *const c_char,
+    path: *const c_char,
+    name: *const c_char,
+    mime: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let to = CStr::from_ptr(to);
+    let to: post::model::Recipient = match to.to_str() {
+        Ok(result) => match post::model::Recipient::from_str(result) {
+            Ok(recipient) => recipient,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert to into String").c_stringify()
+        }
+    };
+    let recipients = CStr::from_ptr(recipients);
+    let recipients: Vec<XOnlyPublicKey> = match recipients.to_str() {
+        Ok(string) => match string
+            .split(',')
+            .map(|pubkey| ec::pubkey_from_str(pubkey.trim()))
+            .collect::<Result<Vec<XOnlyPublicKey>, S5Error>>()
+        {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert recipients to String")
+                .c_stringify()
+        }
+    };
+    let path = CStr::from_ptr(path);
+    let mut file = match path.to_str() {
+        Ok(string) => match std::fs::File::open(string) {
+            Ok(file) => file,
+            Err(e) => {
+                return S5Error::new(ErrorKind::Input, &format!("Could not open {}: {}", string, e))
+                    .c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert path to String").c_stringify()
+        }
+    };
+    let name = CStr::from_ptr(name);
+    let name: String = match name.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert name to String").c_stringify()
+        }
+    };
+    let mime = CStr::from_ptr(mime);
+    let mime: Option<String> = match mime.to_str() {
+        Ok("") => None,
+        Ok(string) => Some(string.to_string()),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert mime to String").c_stringify()
+        }
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .send_attachment(&my_identity, to, &recipients, &mut file, &name, mime)
+    {
+        Ok(id) => post::model::PostId::new(id).c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// DOWNLOAD AND DECRYPT THE ATTACHMENT IN manifest TO path
+/// IF path HOLDS PART OF IT FROM AN EARLIER CALL, ONLY THE MISSING CHUNKS ARE FETCHED
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn download_attachment(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    manifest: *const c_char,
+    path: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let manifest = CStr::from_ptr(manifest);
+    let manifest: AttachmentManifest = match manifest.to_str() {
+        Ok(string) => match AttachmentManifest::structify(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert manifest to String").c_stringify()
+        }
+    };
+    let path = CStr::from_ptr(path);
+    let path: String = match path.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert path to String").c_stringify()
+        }
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .download_attachment(&my_identity, &manifest, std::path::Path::new(&path))
+    {
+        Ok(_) => network::handler::ServerStatusResponse::new(true).c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// QUEUE A POST IN THE OUTBOX FILE AT path, TO BE SENT BY flush_outbox OR drain_outbox
+/// THE FILE IS ENCRYPTED WITH A KEY DERIVED FROM social_root
+/// links IS A PostLinks json OR ""
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn outbox_queue(
+    social_root: *const c_char,
+    path: *const c_char,
+    to: *const c_char,
+    kind: *const c_char,
+    value: *const c_char,
+    links: *const c_char,
+    recipients: *const c_char,
+) -> *mut c_char {
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+    let path = CStr::from_ptr(path);
+    let path: String = match path.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert path to String").c_stringify()
+        }
+    };
+    let to = CStr::from_ptr(to);
+    let to: post::model::Recipient = match to.to_str() {
+        Ok(result) => match post::model::Recipient::from_str(result) {
+            Ok(recipient) => recipient,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert to into String").c_stringify()
+        }
+    };
+    let kind = match CStr::from_ptr(kind).to_str() {
+        Ok(result) => result,
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert payload kind into String")
+                .c_stringify()
+        }
+    };
+    let value = match CStr::from_ptr(value).to_str() {
+        Ok(result) => result,
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert payload value into String")
+                .c_stringify()
+        }
+    };
+    let payload: post::model::Payload = match post::model::Payload::from_str(&format!("{}:{}", kind, value)) {
+        Ok(payload) => payload,
+        Err(e) => return e.c_stringify(),
+    };
+    let links = CStr::from_ptr(links);
+    let links: post::model::PostLinks = match links.to_str() {
+        Ok("") => post::model::PostLinks::default(),
+        Ok(string) => match post::model::PostLinks::structify(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert links to String").c_stringify()
+        }
+    };
+    let recipients = CStr::from_ptr(recipients);
+    let recipients: Vec<XOnlyPublicKey> = match recipients.to_str() {
+        Ok(string) => match string
+            .split(',')
+            .map(|pubkey| ec::pubkey_from_str(pubkey.trim()))
+            .collect::<Result<Vec<XOnlyPublicKey>, S5Error>>()
+        {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert recipients to String")
+                .c_stringify()
+        }
+    };
+
+    let path = std::path::Path::new(&path);
+    let storage_key = my_identity.derive_storage_key();
+    let mut outbox = match Outbox::load(path, &storage_key) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+    let entry = match outbox.queue(to, payload, links, recipients) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+    match outbox.save(path, &storage_key) {
+        Ok(_) => entry.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// EVERY POST IN THE OUTBOX FILE AT path THAT IS NOT SENT YET, WITH ITS STATE
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn outbox_status(social_root: *const c_char, path: *const c_char) -> *mut c_char {
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+    let path = CStr::from_ptr(path);
+    let path: String = match path.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert path to String").c_stringify()
+        }
+    };
+
+    match Outbox::load(std::path::Path::new(&path), &my_identity.derive_storage_key()) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// REMOVE A QUEUED OR FAILED POST FROM THE OUTBOX FILE AT path
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn outbox_remove(
+    social_root: *const c_char,
+    path: *const c_char,
+    id: *const c_char,
+) -> *mut c_char {
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+    let path = CStr::from_ptr(path);
+    let path: String = match path.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert path to String").c_stringify()
+        }
+    };
+    let id = CStr::from_ptr(id);
+    let id: String = match id.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => return S5Error::new(ErrorKind::Input, "Could not convert id to String").c_stringify(),
+    };
+
+    let path = std::path::Path::new(&path);
+    let storage_key = my_identity.derive_storage_key();
+    let mut outbox = match Outbox::load(path, &storage_key) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+    if let Err(e) = outbox.remove(&id) {
+        return e.c_stringify();
+    }
+    match outbox.save(path, &storage_key) {
+        Ok(_) => outbox.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// SEND THE POSTS QUEUED IN THE OUTBOX FILE AT path IN ORDER, STOPPING AT THE FIRST CONNECTION ERROR
+/// RETURNS EVERY ENTRY WITH ITS STATE; SENT ENTRIES ARE THEN DROPPED FROM THE FILE
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn flush_outbox(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    path: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+    let path = CStr::from_ptr(path);
+    let path: String = match path.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert path to String").c_stringify()
+        }
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .flush_outbox(&my_identity, std::path::Path::new(&path))
+    {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// flush_outbox UNTIL NOTHING IS LEFT TO SEND, RETRYING EVERY 5 SECONDS, OR UNTIL timeout_secs PASS
+/// BEST CALLED THROUGH start_request WHEN THE CONNECTION RETURNS
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn drain_outbox(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    path: *const c_char,
+    timeout_secs: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+    let path = CStr::from_ptr(path);
+    let path: String = match path.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert path to String").c_stringify()
+        }
+    };
+    let timeout_secs = CStr::from_ptr(timeout_secs);
+    let timeout_secs: u64 = match timeout_secs.to_str() {
+        Ok(string) => match string.parse::<u64>() {
+            Ok(value) => value,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse timeout_secs to u64")
+                    .c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert timeout_secs to String")
+                .c_stringify()
+        }
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .drain_outbox(&my_identity, std::path::Path::new(&path), Duration::from_secs(timeout_secs))
+    {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// REPUTATION OF EVERY MEMBER WHO HAS RECEIVED A BADGE, AS SEEN FROM social_root
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn get_reputation(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    match Client::new(&hostname).with_network(network).scores(&my_identity) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// THE BADGES BEHIND ONE MEMBER's REPUTATION, AS A TREE
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn explain_reputation(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    pubkey: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let pubkey = CStr::from_ptr(pubkey);
+    let pubkey: XOnlyPublicKey = match pubkey.to_str() {
+        Ok(string) => match ec::pubkey_from_str(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert pubkey to String")
+                .c_stringify()
+        }
+    };
+
+    match Client::new(&hostname).with_network(network).explain(&my_identity, &pubkey) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// SEND KEYS FOR A POST's RECIPIENTS
+/// `recipients` must be a comma separated list of recipients
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn send_keys(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    index: *const c_char,
+    post_id: *const c_char,
+    recipients: *const c_char,
+) -> *mut c_char {
+    let hostname_cstr = CStr::from_ptr(hostname);
+    let hostname: String = match hostname_cstr.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let index = CStr::from_ptr(index);
+    let index: u32 = match index.to_str() {
+        Ok(string) => match string.parse::<u32>() {
+            Ok(result) => result,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse index to u32").c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert index to String")
+                .c_stringify()
+        }
+    };
+
+    let post_id = CStr::from_ptr(post_id);
+    let post_id: String = match post_id.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert post_id to String")
+                .c_stringify()
+        }
+    };
+
+    let recipients = CStr::from_ptr(recipients);
+    let recipients: Vec<XOnlyPublicKey> = match recipients.to_str() {
+        Ok(result) => {
+            let string_pubkeys: Vec<&str> = result.split(",").collect();
+            if string_pubkeys.len() == 0 {
+                return S5Error::new(ErrorKind::Input, "Unable to parse recipients.").c_stringify();
+            } else {
+                let mut xonly_vec: Vec<XOnlyPublicKey> = [].to_vec();
+                for pubkey in string_pubkeys.into_iter() {
+                    match ec::pubkey_from_str(pubkey) {
+                        Ok(result) => xonly_vec.push(result),
+                        Err(_) => {
+                            return S5Error::new(
+                                ErrorKind::Input,
+                                "One recipient pubkey is not a valid XOnlyPubKey",
+                            )
+                            .c_stringify()
+                        }
+                    };
+                }
+                xonly_vec
+            }
+        }
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert recipients into String")
+                .c_stringify()
+        }
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .send_keys(&my_identity, index, &post_id, &recipients)
+    {
+        Ok(()) => network::handler::ServerStatusResponse::new(true).c_stringify(),
+        Err(e) => return e.c_stringify(),
+    }
+}
+/// GET A SINGLE POST BY ID
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn get_one_post(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    post_id: *const c_char,
+) -> *mut c_char {
+    let hostname_cstr = CStr::from_ptr(hostname);
+    let hostname: String = match hostname_cstr.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let post_id = CStr::from_ptr(post_id);
+    let post_id: String = match post_id.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert post_id to String")
+                .c_stringify()
+        }
+    };
+
+    match Client::new(&hostname).with_network(network).post(&my_identity, &post_id) {
+        Ok(post) => post.c_stringify(),
+        Err(e) => return e.c_stringify(),
+    }
+}
+/// GET ALL POSTS FOR A USER
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn get_all_posts(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    genesis_filter: *const c_char,
+) -> *mut c_char {
+    let hostname_cstr = CStr::from_ptr(hostname);
+    let hostname: String = match hostname_cstr.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let genesis_filter = CStr::from_ptr(genesis_filter);
+    let genesis_filter: Option<u64> = match genesis_filter.to_str() {
+        Ok(string) => match string.parse::<u64>() {
+            Ok(value) => {
+                if value == 0 {
+                    None
+                } else {
+                    Some(value)
+                }
+            }
+            Err(_) => {
+                return S5Error::new(ErrorKind::Key, "Could not parse genesis filter to u64")
+                    .c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(
+                ErrorKind::Input,
+                "Could not convert genesis filter to String",
+            )
+            .c_stringify()
+        }
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .posts(&my_identity, genesis_filter)
+    {
+        Ok(posts) => posts.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// GET ALL POSTS FOR A USER, WITH DIRECT CHATS LABELLED FROM A MEMBER DIRECTORY AND VERIFIED CONTACTS
+/// FLAGS COUNTER PARTIES WHO LEFT, WHOSE USERNAME WAS REUSED, WHO ARE NOT IN THE DIRECTORY,
+/// WHO ARE VERIFIED, OR WHO HOLD A VERIFIED USERNAME UNDER A NEW KEY
+/// policy FILTERS CHATS BY REPUTATION: "" FOR THE DEFAULT, "off" TO SHOW EVERYTHING
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn get_chats(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    genesis_filter: *const c_char,
+    directory: *const c_char,
+    contacts: *const c_char,
+    policy: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let genesis_filter = CStr::from_ptr(genesis_filter);
+    let genesis_filter: Option<u64> = match genesis_filter.to_str() {
+        Ok(string) => match string.parse::<u64>() {
+            Ok(value) => {
+                if value == 0 {
+                    None
+                } else {
+                    Some(value)
+                }
+            }
+            Err(_) => {
+                return S5Error::new(ErrorKind::Key, "Could not parse genesis filter to u64")
+                    .c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(
+                ErrorKind::Input,
+                "Could not convert genesis filter to String",
+            )
+            .c_stringify()
+        }
+    };
+
+    let directory = CStr::from_ptr(directory);
+    let directory: MemberDirectory = match directory.to_str() {
+        Ok("") => MemberDirectory::default(),
+        Ok(string) => match MemberDirectory::structify(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert directory to String")
+                .c_stringify()
+        }
+    };
+
+    let contacts = CStr::from_ptr(contacts);
+    let contacts: Contacts = match contacts.to_str() {
+        Ok("") => Contacts::default(),
+        Ok(string) => match Contacts::structify(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert contacts to String")
+                .c_stringify()
+        }
+    };
+
+    let policy = CStr::from_ptr(policy);
+    let policy: Option<FilterPolicy> = match policy.to_str() {
+        Ok("") => Some(FilterPolicy::default()),
+        Ok("off") => None,
+        Ok(string) => match FilterPolicy::structify(string) {
+            Ok(result) => Some(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert policy to String")
+                .c_stringify()
+        }
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .chats(&my_identity, genesis_filter, &directory, &contacts, policy.as_ref()) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// GET ONE PAGE OF CONVERSATIONS, MOST RECENTLY ACTIVE FIRST, LABELLED AS IN get_chats
+/// EACH WITH A PREVIEW OF ITS LAST MESSAGE, ITS LAST ACTIVITY AND ITS UNREAD COUNT SINCE THE READ MARKER IN state
+/// state IS KEPT BY THE APP, UPDATE IT WITH conversation_mark_read AND conversation_mute. "" FOR NONE YET
+/// limit 0 RETURNS EVERY CONVERSATION AFTER offset
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn get_conversations(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+    directory: *const c_char,
+    contacts: *const c_char,
+    state: *const c_char,
+    offset: *const c_char,
+    limit: *const c_char,
+) -> *mut c_char {
+    let hostname = CStr::from_ptr(hostname);
+    let hostname: String = match hostname.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let directory = CStr::from_ptr(directory);
+    let directory: MemberDirectory = match directory.to_str() {
+        Ok("") => MemberDirectory::default(),
+        Ok(string) => match MemberDirectory::structify(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert directory to String")
+                .c_stringify()
+        }
+    };
+
+    let contacts = CStr::from_ptr(contacts);
+    let contacts: Contacts = match contacts.to_str() {
+        Ok("") => Contacts::default(),
+        Ok(string) => match Contacts::structify(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert contacts to String")
+                .c_stringify()
+        }
+    };
+
+    let state = CStr::from_ptr(state);
+    let state: ConversationState = match state.to_str() {
+        Ok("") => ConversationState::default(),
+        Ok(string) => match ConversationState::structify(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert state to String").c_stringify()
+        }
+    };
+
+    let offset = CStr::from_ptr(offset);
+    let offset: usize = match offset.to_str() {
+        Ok(string) => match string.parse::<usize>() {
+            Ok(value) => value,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse offset to usize").c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert offset to String").c_stringify()
+        }
+    };
+    let limit = CStr::from_ptr(limit);
+    let limit: usize = match limit.to_str() {
+        Ok(string) => match string.parse::<usize>() {
+            Ok(value) => value,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse limit to usize").c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert limit to String").c_stringify()
+        }
+    };
+
+    match Client::new(&hostname)
+        .with_network(network)
+        .conversations(&my_identity, &directory, &contacts, &state, offset, limit) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// MARK A CONVERSATION AS READ UP TO THE POST WITH genesis, USUALLY ITS last_activity
+/// RETURNS THE UPDATED state TO STORE. "" FOR NONE YET
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn conversation_mark_read(
+    state: *const c_char,
+    counter_party: *const c_char,
+    genesis: *const c_char,
+) -> *mut c_char {
+    let state = CStr::from_ptr(state);
+    let mut state: ConversationState = match state.to_str() {
+        Ok("") => ConversationState::default(),
+        Ok(string) => match ConversationState::structify(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert state to String").c_stringify()
+        }
+    };
+    let counter_party = CStr::from_ptr(counter_party);
+    let counter_party: String = match counter_party.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert counter party to String")
+                .c_stringify()
+        }
+    };
+    let genesis = CStr::from_ptr(genesis);
+    let genesis: u64 = match genesis.to_str() {
+        Ok(string) => match string.parse::<u64>() {
+            Ok(value) => value,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse genesis to u64").c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert genesis to String").c_stringify()
+        }
+    };
+
+    state.mark_read(&counter_party, genesis);
+    state.c_stringify()
+}
+/// MUTE OR UNMUTE A CONVERSATION. muted IS "true" OR "false"
+/// MUTED CONVERSATIONS KEEP THEIR UNREAD COUNT BUT ARE LEFT OUT OF THE TOTAL unread
+/// RETURNS THE UPDATED state TO STORE. "" FOR NONE YET
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn conversation_mute(
+    state: *const c_char,
+    counter_party: *const c_char,
+    muted: *const c_char,
+) -> *mut c_char {
+    let state = CStr::from_ptr(state);
+    let mut state: ConversationState = match state.to_str() {
+        Ok("") => ConversationState::default(),
+        Ok(string) => match ConversationState::structify(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert state to String").c_stringify()
+        }
+    };
+    let counter_party = CStr::from_ptr(counter_party);
+    let counter_party: String = match counter_party.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert counter party to String")
+                .c_stringify()
+        }
+    };
+    let muted = CStr::from_ptr(muted);
+    let muted: bool = match muted.to_str() {
+        Ok(string) => match string.parse::<bool>() {
+            Ok(value) => value,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse muted to bool").c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert muted to String").c_stringify()
+        }
+    };
+
+    state.set_muted(&counter_party, muted);
+    state.c_stringify()
+}
+/// GET LAST DERIVATION INDEX
+/// USERS SHOULD STORE AND UPDATE LAST USED INDEX FOR FORWARD SECRECY
+/// USE THIS FUNCTION ONLY IN CASE OF RECOVERY AND LOSS OF LOCAL DATA
+/// AVOID USING THIS BEFORE EVERY POST BY KEEPING TRACK OF INDEX LOCALLY
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn last_index(
+    hostname: *const c_char,
+    socks5: *const c_char,
+    social_root: *const c_char,
+) -> *mut c_char {
+    let hostname_cstr = CStr::from_ptr(hostname);
+    let hostname: String = match hostname_cstr.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert hostname to String")
+                .c_stringify()
+        }
+    };
+
+    let socks5 = CStr::from_ptr(socks5);
+    let network: NetworkConfig = match socks5.to_str() {
+        Ok(string) => match ProxyConfig::from_ffi_str(string) {
+            Ok(result) => NetworkConfig::new(result),
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert socks5 proxy to String")
+                .c_stringify()
+        }
+    };
+    let social_root = CStr::from_ptr(social_root);
+    let social_root: String = match social_root.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert social root to String")
+                .c_stringify()
+        }
+    };
+    let my_identity = match identity::model::UserIdentity::new(social_root) {
+        Ok(result) => result,
+        Err(e) => return e.c_stringify(),
+    };
+
+    match Client::new(&hostname).with_network(network).last_index(&my_identity) {
+        Ok(last_index) => last_index.c_stringify(),
+        Err(e) => return e.c_stringify(),
+    }
+}
+
+/// SET GLOBAL NETWORK DEFAULTS
+/// APPLIES TO EVERY CALL MADE AFTER IT
+/// `connect_timeout` and `read_timeout` are in milliseconds (use 0 to disable)
+/// `max_retries` only applies to requests that are safe to repeat (reads and idempotent puts)
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn set_network_defaults(
+    connect_timeout: *const c_char,
+    read_timeout: *const c_char,
+    max_retries: *const c_char,
+) -> *mut c_char {
+    let connect_timeout = CStr::from_ptr(connect_timeout);
+    let connect_timeout: u64 = match connect_timeout.to_str() {
+        Ok(string) => match string.parse::<u64>() {
+            Ok(result) => result,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse connect timeout to u64")
+                    .c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert connect timeout to String")
+                .c_stringify()
+        }
+    };
+
+    let read_timeout = CStr::from_ptr(read_timeout);
+    let read_timeout: u64 = match read_timeout.to_str() {
+        Ok(string) => match string.parse::<u64>() {
+            Ok(result) => result,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse read timeout to u64")
+                    .c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert read timeout to String")
+                .c_stringify()
+        }
+    };
+
+    let max_retries = CStr::from_ptr(max_retries);
+    let max_retries: u32 = match max_retries.to_str() {
+        Ok(string) => match string.parse::<u32>() {
+            Ok(result) => result,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse max retries to u32")
+                    .c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert max retries to String")
+                .c_stringify()
+        }
+    };
+
+    let retry = NetworkConfig::default().retry;
+    network::handler::set_network_defaults(
+        network::handler::Timeouts::new(connect_timeout, read_timeout),
+        network::handler::RetryPolicy::new(max_retries, retry.base_delay_ms, retry.max_delay_ms),
+    );
+    network::handler::ServerStatusResponse::new(true).c_stringify()
+}
+
+/// FREE A STRING RETURNED BY ANY FUNCTION OR CALLBACK IN THIS LIBRARY
+/// # Safety
+/// - ONLY pass pointers returned by this library, and only once.
+#[no_mangle]
+pub unsafe extern "C" fn cstring_free(ptr: *mut c_char) {
+    if ptr.is_null() {
+        return;
+    }
+    drop(CString::from_raw(ptr));
+}
+
+// Runs one of the blocking exports with its arguments taken from a JSON object.
+// Argument names match the parameter names of the export.
+unsafe fn dispatch(operation: &str, args: &serde_json::Value) -> Result<String, S5Error> {
+    let arg = |name: &str| -> Result<CString, S5Error> {
+        let value = match args.get(name) {
+            Some(serde_json::Value::String(value)) => value.to_string(),
+            Some(serde_json::Value::Number(value)) => value.to_string(),
+            _ => {
+                return Err(S5Error::new(
+                    ErrorKind::Input,
+                    &format!("Missing string argument: {}", name),
+                ))
+            }
+        };
+        match CString::new(value) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(
+                ErrorKind::Input,
+                &format!("Argument contains a nul byte: {}", name),
+            )),
+        }
+    };
+    let result = match operation {
+        "create_social_root" => {
+            create_social_root(arg("master_root")?.as_ptr(), arg("account")?.as_ptr())
+        }
+        "safety_number" => safety_number(arg("social_root")?.as_ptr(), arg("pubkey")?.as_ptr()),
+        "contact_uri" => contact_uri(
+            arg("hostname")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("directory")?.as_ptr(),
+        ),
+        "verify_contact" => verify_contact(
+            arg("hostname")?.as_ptr(),
+            arg("contacts")?.as_ptr(),
+            arg("uri")?.as_ptr(),
+            arg("username")?.as_ptr(),
+            arg("pubkey")?.as_ptr(),
+            arg("directory")?.as_ptr(),
+            arg("replace")?.as_ptr(),
+        ),
+        "export_profile_card" => {
+            export_profile_card(arg("social_root")?.as_ptr(), arg("profile")?.as_ptr())
+        }
+        "import_profile_card" => import_profile_card(arg("card")?.as_ptr()),
+        "psbt_summary" => psbt_summary(arg("psbt")?.as_ptr(), arg("network")?.as_ptr()),
+        "combine_psbts" => combine_psbts(arg("psbts")?.as_ptr()),
+        "assemble_descriptor" => {
+            assemble_descriptor(arg("threshold")?.as_ptr(), arg("contributions")?.as_ptr())
+        }
+        "chat_descriptor" => chat_descriptor(arg("threshold")?.as_ptr(), arg("chat")?.as_ptr()),
+        "parse_descriptor" => parse_descriptor(arg("descriptor")?.as_ptr()),
+        "payment_request" => payment_request(
+            arg("social_root")?.as_ptr(),
+            arg("address")?.as_ptr(),
+            arg("amount")?.as_ptr(),
+            arg("label")?.as_ptr(),
+            arg("expiry")?.as_ptr(),
+            arg("wif")?.as_ptr(),
+        ),
+        "payment_request_details" => payment_request_details(
+            arg("social_root")?.as_ptr(),
+            arg("owner")?.as_ptr(),
+            arg("request")?.as_ptr(),
+        ),
+        "server_identity" => server_identity(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+        ),
+        "admin_invite" => admin_invite(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("admin_secret")?.as_ptr(),
+            arg("kind")?.as_ptr(),
+            arg("count")?.as_ptr(),
+        ),
+        "priv_user_invite" => priv_user_invite(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("invite_code")?.as_ptr(),
+        ),
+        "invite_detail" => invite_detail(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("invite_code")?.as_ptr(),
+        ),
+        "my_invites" => my_invites(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+        ),
+        "admin_invites" => admin_invites(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("admin_secret")?.as_ptr(),
+        ),
+        "revoke_invite" => revoke_invite(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("invite_code")?.as_ptr(),
+        ),
+        "admin_revoke_invite" => admin_revoke_invite(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("admin_secret")?.as_ptr(),
+            arg("invite_code")?.as_ptr(),
+        ),
+        "get_members" => get_members(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+        ),
+        "refresh_members" => refresh_members(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("directory")?.as_ptr(),
+        ),
+        "check_username" => check_username(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("username")?.as_ptr(),
+        ),
+        "join" => join(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("username")?.as_ptr(),
+            arg("invite_code")?.as_ptr(),
+        ),
+        "join_invoice" => join_invoice(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+        ),
+        "invoice_status" => invoice_status(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("invoice_id")?.as_ptr(),
+        ),
+        "wait_for_payment" => wait_for_payment(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("invoice_id")?.as_ptr(),
+            arg("timeout_secs")?.as_ptr(),
+        ),
+        "join_paid" => join_paid(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("username")?.as_ptr(),
+            arg("invoice_id")?.as_ptr(),
+        ),
+        "leave" => leave(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+        ),
+        "send_post" => send_post(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("index")?.as_ptr(),
+            arg("to")?.as_ptr(),
+            arg("kind")?.as_ptr(),
+            arg("value")?.as_ptr(),
+        ),
+        "send_post_with_links" => send_post_with_links(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("index")?.as_ptr(),
+            arg("to")?.as_ptr(),
+            arg("kind")?.as_ptr(),
+            arg("value")?.as_ptr(),
+            arg("links")?.as_ptr(),
+        ),
+        "send_keys" => send_keys(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("index")?.as_ptr(),
+            arg("post_id")?.as_ptr(),
+            arg("recipients")?.as_ptr(),
+        ),
+        "get_one_post" => get_one_post(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("post_id")?.as_ptr(),
+        ),
+        "get_all_posts" => get_all_posts(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("genesis_filter")?.as_ptr(),
+        ),
+        "publish_profile" => publish_profile(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("index")?.as_ptr(),
+            arg("profile")?.as_ptr(),
+            arg("audience")?.as_ptr(),
+        ),
+        "get_profiles" => get_profiles(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+        ),
+        "give_badge" => give_badge(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("to")?.as_ptr(),
+            arg("kind")?.as_ptr(),
+            arg("details")?.as_ptr(),
+        ),
+        "distribute_shares" => distribute_shares(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("secret")?.as_ptr(),
+            arg("threshold")?.as_ptr(),
+            arg("holders")?.as_ptr(),
+            arg("label")?.as_ptr(),
+        ),
+        "get_recoveries" => get_recoveries(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+        ),
+        "return_share" => return_share(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("set_id")?.as_ptr(),
+            arg("to")?.as_ptr(),
+        ),
+        "get_trades" => get_trades(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+        ),
+        "trade_message" => trade_message(
+            arg("social_root")?.as_ptr(),
+            arg("action")?.as_ptr(),
+            arg("trade")?.as_ptr(),
+            arg("terms")?.as_ptr(),
+        ),
+        "send_receipts" => send_receipts(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("kind")?.as_ptr(),
+            arg("post_ids")?.as_ptr(),
+            arg("settings")?.as_ptr(),
+        ),
+        "send_attachment" => send_attachment(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("to")?.as_ptr(),
+            arg("recipients")?.as_ptr(),
+            arg("path")?.as_ptr(),
+            arg("name")?.as_ptr(),
+            arg("mime")?.as_ptr(),
+        ),
+        "download_attachment" => download_attachment(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("manifest")?.as_ptr(),
+            arg("path")?.as_ptr(),
+        ),
+        "outbox_queue" => outbox_queue(
+            arg("social_root")?.as_ptr(),
+            arg("path")?.as_ptr(),
+            arg("to")?.as_ptr(),
+            arg("kind")?.as_ptr(),
+            arg("value")?.as_ptr(),
+            arg("links")?.as_ptr(),
+            arg("recipients")?.as_ptr(),
+        ),
+        "outbox_status" => outbox_status(arg("social_root")?.as_ptr(), arg("path")?.as_ptr()),
+        "outbox_remove" => outbox_remove(
+            arg("social_root")?.as_ptr(),
+            arg("path")?.as_ptr(),
+            arg("id")?.as_ptr(),
+        ),
+        "flush_outbox" => flush_outbox(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("path")?.as_ptr(),
+        ),
+        "drain_outbox" => drain_outbox(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("path")?.as_ptr(),
+            arg("timeout_secs")?.as_ptr(),
+        ),
+        "get_reputation" => get_reputation(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+        ),
+        "explain_reputation" => explain_reputation(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("pubkey")?.as_ptr(),
+        ),
+        "get_chats" => get_chats(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("genesis_filter")?.as_ptr(),
+            arg("directory")?.as_ptr(),
+            arg("contacts")?.as_ptr(),
+            arg("policy")?.as_ptr(),
+        ),
+        "get_conversations" => get_conversations(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+            arg("directory")?.as_ptr(),
+            arg("contacts")?.as_ptr(),
+            arg("state")?.as_ptr(),
+            arg("offset")?.as_ptr(),
+            arg("limit")?.as_ptr(),
+        ),
+        "conversation_mark_read" => conversation_mark_read(
+            arg("state")?.as_ptr(),
+            arg("counter_party")?.as_ptr(),
+            arg("genesis")?.as_ptr(),
+        ),
+        "conversation_mute" => conversation_mute(
+            arg("state")?.as_ptr(),
+            arg("counter_party")?.as_ptr(),
+            arg("muted")?.as_ptr(),
+        ),
+        "last_index" => last_index(
+            arg("hostname")?.as_ptr(),
+            arg("socks5")?.as_ptr(),
+            arg("social_root")?.as_ptr(),
+        ),
+        _ => {
+            return Err(S5Error::new(
+                ErrorKind::Input,
+                &format!("Unknown operation: {}", operation),
+            ))
+        }
+    };
+    match CString::from_raw(result).into_string() {
+        Ok(result) => Ok(result),
+        Err(_) => Err(S5Error::new(ErrorKind::Internal, "Result was not valid UTF-8")),
+    }
+}
+
+/// START A NON-BLOCKING REQUEST
+/// `operation` is the name of any blocking function above (eg. "get_all_posts")
+/// `args` is a JSON object of that function's inputs, keyed by parameter name
+/// Returns a `RequestId` immediately. The JSON the blocking call would have returned is either
+/// passed to `callback` (from a worker thread) or, if callback is null, read with `poll_request`.
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+/// - `callback` receives ownership of its result string and must pass it into cstring_free.
+#[no_mangle]
+pub unsafe extern "C" fn start_request(
+    operation: *const c_char,
+    args: *const c_char,
+    callback: Option<util::task::ResultCallback>,
+) -> *mut c_char {
+    let operation = CStr::from_ptr(operation);
+    let operation: String = match operation.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert operation to String")
+                .c_stringify()
+        }
+    };
+    let args = CStr::from_ptr(args);
+    let args: serde_json::Value = match args.to_str() {
+        Ok(string) => match serde_json::from_str(string) {
+            Ok(value) => value,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse args as a JSON object")
+                    .c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert args to String").c_stringify()
+        }
+    };
+
+    let request_id = util::task::spawn(
+        move || match dispatch(&operation, &args) {
+            Ok(result) => result,
+            Err(e) => serde_json::to_string(&e).unwrap_or_default(),
+        },
+        callback,
+    );
+    util::task::RequestId::new(request_id).c_stringify()
+}
+/// POLL A NON-BLOCKING REQUEST
+/// `status` is "pending", "done" or "cancelled". `result` is set once done.
+/// Done and cancelled requests are forgotten after they are polled.
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn poll_request(request_id: *const c_char) -> *mut c_char {
+    let request_id = CStr::from_ptr(request_id);
+    let request_id: u64 = match request_id.to_str() {
+        Ok(string) => match string.parse::<u64>() {
+            Ok(result) => result,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse request id to u64")
+                    .c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert request id to String")
+                .c_stringify()
+        }
+    };
+    match util::task::poll(request_id) {
+        Ok(status) => status.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+/// CANCEL A NON-BLOCKING REQUEST
+/// A request already sent to the server cannot be recalled; its result is discarded and
+/// its callback never fires.
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn cancel_request(request_id: *const c_char) -> *mut c_char {
+    let request_id = CStr::from_ptr(request_id);
+    let request_id: u64 = match request_id.to_str() {
+        Ok(string) => match string.parse::<u64>() {
+            Ok(result) => result,
+            Err(_) => {
+                return S5Error::new(ErrorKind::Input, "Could not parse request id to u64")
+                    .c_stringify()
+            }
+        },
+        Err(_) => {
+            return S5Error::new(ErrorKind::Input, "Could not convert request id to String")
+                .c_stringify()
+        }
+    };
+    match util::task::cancel(request_id) {
+        Ok(()) => network::handler::ServerStatusResponse::new(true).c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::mock::{MockServer, ADMIN_SECRET};
+    use bitcoin::network::constants::Network;
+    use std::ffi::{CStr, CString};
+    #[test]
+    fn test_ffi_async() {
+        unsafe {
+            let seed = key::seed::MasterKeySeed::generate(12, "", Network::Bitcoin).unwrap();
+            let operation_cstr = CString::new("create_social_root").unwrap().into_raw();
+            let args = format!(
+                "{{\"master_root\":\"{}\",\"account\":0}}",
+                seed.xprv
+            );
+            let args_cstr = CString::new(args).unwrap().into_raw();
+            let result_ptr = start_request(operation_cstr, args_cstr, None);
+            let result_str = CStr::from_ptr(result_ptr).to_str().unwrap();
+            let request_id = util::task::RequestId::structify(result_str).unwrap().request_id;
+            cstring_free(result_ptr);
+
+            let request_id_cstr = CString::new(request_id.to_string()).unwrap().into_raw();
+            let mut status = util::task::RequestStatus::new(request_id, "pending", None);
+            for _ in 0..200 {
+                let result_ptr = poll_request(request_id_cstr);
+                status = util::task::RequestStatus::structify(CStr::from_ptr(result_ptr).to_str().unwrap()).unwrap();
+                cstring_free(result_ptr);
+                if status.status != "pending" {
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+            assert_eq!(status.status, "done");
+            let social_root = key::child::SocialRoot::structify(&status.result.clone().unwrap().to_string());
+            assert!(social_root.is_ok());
+
+            let operation_cstr = CString::new("not_an_export").unwrap().into_raw();
+            let args_cstr = CString::new("{}").unwrap().into_raw();
+            let result_ptr = start_request(operation_cstr, args_cstr, None);
+            let request_id = util::task::RequestId::structify(CStr::from_ptr(result_ptr).to_str().unwrap()).unwrap().request_id;
+            let request_id_cstr = CString::new(request_id.to_string()).unwrap().into_raw();
+            for _ in 0..200 {
+                let result_ptr = poll_request(request_id_cstr);
+                status = util::task::RequestStatus::structify(CStr::from_ptr(result_ptr).to_str().unwrap()).unwrap();
+                if status.status != "pending" {
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+            assert_eq!(status.result.unwrap()["kind"], "Input");
+        }
+    }
+
+    #[test]
+    fn test_ffi_profile_card() {
+        unsafe {
+            let seed = key::seed::MasterKeySeed::generate(12, "", Network::Bitcoin).unwrap();
+            let social_root = key::child::social_root(seed.xprv.to_string(), 0).unwrap().xprv;
+            let social_root_cstr = CString::new(social_root).unwrap().into_raw();
+            let profile_cstr = CString::new("{\"display_name\":\"Ishi\",\"bio\":\"hi\"}").unwrap().into_raw();
+            let result_ptr = export_profile_card(social_root_cstr, profile_cstr);
+            let card: ProfileCard = serde_json::from_str(CStr::from_ptr(result_ptr).to_str().unwrap()).unwrap();
+            cstring_free(result_ptr);
+
+            let card_cstr = CString::new(card.card.clone()).unwrap().into_raw();
+            let result_ptr = import_profile_card(card_cstr);
+            let imported = SignedProfile::structify(CStr::from_ptr(result_ptr).to_str().unwrap()).unwrap();
+            cstring_free(result_ptr);
+            assert_eq!(imported.profile.display_name, "Ishi");
+
+            let tampered = card.card.replace("cypherpost:profile:", "cypherpost:profile:x");
+            let result_ptr = import_profile_card(CString::new(tampered).unwrap().into_raw());
+            let error: serde_json::Value = serde_json::from_str(CStr::from_ptr(result_ptr).to_str().unwrap()).unwrap();
+            assert_eq!(error["kind"], "Input");
+        }
+    }
+
     #[test]
-    // #[ignore]
     fn test_ffi_composite() {
         unsafe {
             //
@@ -1003,7 +4489,8 @@ mod tests {
             // GET SERVER IDENTITY
             //
             //
-            let hostname = "http://localhost:3021".to_string();
+            let server = MockServer::start();
+            let hostname = server.url();
             let hostname_cstr = CString::new(hostname.clone()).unwrap().into_raw();
             let socks5 = "0";
             let socks5_cstr = CString::new(socks5).unwrap().into_raw();
@@ -1018,7 +4505,7 @@ mod tests {
             // ADMIN OPS (GET INVITE)
             //098f6bcd4621d373cade4e832627b4f6
             //9caff0735bc6e80121cedcb98ca51821
-            let admin_secret = "098f6bcd4621d373cade4e832627b4f6";
+            let admin_secret = ADMIN_SECRET;
             let admin_secret_cstr = CString::new(admin_secret).unwrap().into_raw();
             let kind = "priv";
             let kind_cstr = CString::new(kind).unwrap().into_raw();
@@ -1,10 +1,9 @@
-use ureq::{Proxy, AgentBuilder};
 use serde::{Deserialize, Serialize};
 
 use crate::key::encryption::{nonce};
 use crate::key::ec::{XOnlyPair};
-use crate::network::handler::{HttpHeader,HttpMethod,APIEndPoint,OwnedBy,ServerStatusResponse, sign_request};
-use crate::network::badge::model::{Badge};
+use crate::network::handler::{HttpHeader,HttpMethod,APIEndPoint,OwnedBy,ServerStatusResponse, NetworkConfig, build_agent, call_with_retry, sign_request, read_body};
+use crate::network::badge::model::{Badge,BadgeDetails};
 use crate::util::e::{ErrorKind, S5Error};
 use bitcoin::secp256k1::{XOnlyPublicKey};
 use bitcoin::secp256k1::schnorr::Signature;
@@ -13,46 +12,35 @@ use bitcoin::secp256k1::schnorr::Signature;
 pub struct AnnouncementRequest{
     recipient: String,
     nonce: String, 
-    signature: String
+    signature: String,
+    /// Left out for legacy badges, so older servers see the same request.
+    #[serde(flatten)]
+    details: BadgeDetails,
 }
 impl AnnouncementRequest{
-    pub fn new(recipient: XOnlyPublicKey, nonce: String, signature: Signature)->AnnouncementRequest{
+    pub fn new(recipient: XOnlyPublicKey, nonce: String, signature: Signature, details: BadgeDetails)->AnnouncementRequest{
         AnnouncementRequest {
             recipient: recipient.to_string(),
             nonce: nonce,
-            signature: signature.to_string()
-
+            signature: signature.to_string(),
+            details,
         }
     }
 }
 
-pub fn announce(host: String, socks5: Option<u32>, keypair: XOnlyPair, badge: Badge)->Result<(), S5Error>{
+pub fn announce(host: String, network: NetworkConfig, keypair: XOnlyPair, badge: Badge)->Result<(), S5Error>{
     let full_url = host + &APIEndPoint::Announce(badge.clone().kind).to_string();
     let nonce = nonce();
     let signature = sign_request(keypair.clone(), HttpMethod::Post, APIEndPoint::Announce(badge.kind), &nonce).unwrap();
-    let body = AnnouncementRequest::new(badge.to, badge.nonce, badge.signature);
-    let proxy = if socks5.is_some(){ 
-        Some(Proxy::new(&format!("socks5://localhost:{}",socks5.unwrap().to_string())).unwrap())
-    }
-    else{
-        None
-    };
-    let agent = if proxy.is_some(){
-        AgentBuilder::new()
-        .proxy(proxy.unwrap())
-        .build()
-    }
-    else{
-        AgentBuilder::new()
-        .build()
-    };
+    let body = AnnouncementRequest::new(badge.to, badge.nonce, badge.signature, badge.details);
+    let agent = build_agent(&network, Some(keypair.pubkey))?;
     match agent.post(&full_url)
         .set(&HttpHeader::Signature.to_string(), &signature)
         .set(&HttpHeader::Pubkey.to_string(), &keypair.pubkey.to_string())
         .set(&HttpHeader::Nonce.to_string(), &nonce)
         .send_json(body){
             Ok(response)=>  
-                match ServerStatusResponse::structify(&response.into_string().unwrap())
+                match ServerStatusResponse::structify(&read_body(response)?)
                 {
                     Ok(result)=>{
                         if result.status {
@@ -88,32 +76,19 @@ impl AllBadgesResponse{
     }
 }
 
-pub fn get_all(host: String, socks5: Option<u32>, keypair: XOnlyPair)->Result<Vec<Badge>, S5Error>{
+pub fn get_all(host: String, network: NetworkConfig, keypair: XOnlyPair)->Result<Vec<Badge>, S5Error>{
     let full_url = host + &APIEndPoint::Announcements(OwnedBy::Others).to_string();
-    let nonce = nonce();
-    let signature = sign_request(keypair.clone(), HttpMethod::Get, APIEndPoint::Announcements(OwnedBy::Others), &nonce).unwrap();
-    let proxy = if socks5.is_some(){ 
-        Some(Proxy::new(&format!("socks5://localhost:{}",socks5.unwrap().to_string())).unwrap())
-    }
-    else{
-        None
-    };
-    let agent = if proxy.is_some(){
-        AgentBuilder::new()
-        .proxy(proxy.unwrap())
-        .build()
-    }
-    else{
-        AgentBuilder::new()
-        .build()
-    };
-    match agent.get(&full_url)
-        .set(&HttpHeader::Signature.to_string(), &signature)
-        .set(&HttpHeader::Pubkey.to_string(), &keypair.pubkey.to_string())
-        .set(&HttpHeader::Nonce.to_string(), &nonce)
-        .call(){
+    let agent = build_agent(&network, Some(keypair.pubkey))?;
+    match call_with_retry(network.retry, ||{
+        let nonce = nonce();
+        let signature = sign_request(keypair.clone(), HttpMethod::Get, APIEndPoint::Announcements(OwnedBy::Others), &nonce).unwrap();
+        agent.get(&full_url)
+            .set(&HttpHeader::Signature.to_string(), &signature)
+            .set(&HttpHeader::Pubkey.to_string(), &keypair.pubkey.to_string())
+            .set(&HttpHeader::Nonce.to_string(), &nonce)
+        }){
             Ok(response)=>{
-                match AllBadgesResponse::structify(&response.into_string().unwrap())
+                match AllBadgesResponse::structify(&read_body(response)?)
                 {
                     Ok(result)=>Ok(result.announcements),
                     Err(e) =>{
@@ -138,32 +113,18 @@ impl BadgeRevokeRequest{
         }
     }
 }
-pub fn revoke(host: String, socks5: Option<u32>, keypair: XOnlyPair, badge: Badge)->Result<(), S5Error>{
+pub fn revoke(host: String, network: NetworkConfig, keypair: XOnlyPair, badge: Badge)->Result<(), S5Error>{
     let full_url = host + &APIEndPoint::Revoke(badge.clone().kind).to_string();
     let nonce = nonce();
     let signature = sign_request(keypair.clone(), HttpMethod::Post, APIEndPoint::Revoke(badge.clone().kind), &nonce).unwrap();
     let body = BadgeRevokeRequest::new(badge.to);
-    let proxy = if socks5.is_some(){ 
-        Some(Proxy::new(&format!("socks5://localhost:{}",socks5.unwrap().to_string())).unwrap())
-    }
-    else{
-        None
-    };
-    let agent = if proxy.is_some(){
-        AgentBuilder::new()
-        .proxy(proxy.unwrap())
-        .build()
-    }
-    else{
-        AgentBuilder::new()
-        .build()
-    };
+    let agent = build_agent(&network, Some(keypair.pubkey))?;
     match agent.post(&full_url)
         .set(&HttpHeader::Signature.to_string(), &signature)
         .set(&HttpHeader::Pubkey.to_string(), &keypair.pubkey.to_string())
         .set(&HttpHeader::Nonce.to_string(), &nonce)
         .send_json(body){
-            Ok(response)=> match ServerStatusResponse::structify(&response.into_string().unwrap())
+            Ok(response)=> match ServerStatusResponse::structify(&read_body(response)?)
                 {
                     Ok(result)=>{
                         if result.status {
@@ -186,6 +147,7 @@ pub fn revoke(host: String, socks5: Option<u32>, keypair: XOnlyPair, badge: Badg
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::network::mock::{MockServer, ADMIN_SECRET};
     use crate::network::identity::dto::{admin_invite,register};
     use crate::key::ec;
     use crate::key::seed;
@@ -197,15 +159,15 @@ mod tests {
     use std::str::FromStr;
 
     #[test]
-    #[ignore]
     fn test_badges_dto(){
-        let url = "http://localhost:3021".to_string();
+        let server = MockServer::start();
+        let url = server.url();
         // ADMIN INVITE
-        let admin_invite_code = "098f6bcd4621d373cade4e832627b4f6".to_string();
-        let client_invite_code1 = admin_invite(url.clone(), None,admin_invite_code.clone(),InvitePermission::Standard).unwrap();
+        let admin_invite_code = ADMIN_SECRET.to_string();
+        let client_invite_code1 = admin_invite(url.clone(), NetworkConfig::default(), admin_invite_code.clone(),InvitePermission::Standard).unwrap();
         assert_eq!(client_invite_code1.invite_code.len() , 32);
         
-        let client_invite_code2 = admin_invite(url.clone(), None,admin_invite_code.clone(),InvitePermission::Standard).unwrap();
+        let client_invite_code2 = admin_invite(url.clone(), NetworkConfig::default(), admin_invite_code.clone(),InvitePermission::Standard).unwrap();
         assert_eq!(client_invite_code1.invite_code.len() , 32);
 
         // REGISTER USERS
@@ -216,27 +178,27 @@ mod tests {
         let xonly_pair1 = ec::XOnlyPair::from_xprv(social_child1);
         let user1 = "builder".to_string() + &nonce[0..3];
 
-        assert!(register(url.clone(), None, xonly_pair1.clone(), client_invite_code1.invite_code, user1).is_ok());
+        assert!(register(url.clone(), NetworkConfig::default(), xonly_pair1.clone(), client_invite_code1.invite_code, user1).is_ok());
         
         let seed2 = seed::MasterKeySeed::generate(24, "", Network::Bitcoin).unwrap();
         let social_child2 = ExtendedPrivKey::from_str(&child::social_root(seed2.xprv.to_string(),0).unwrap().xprv).unwrap();
         let xonly_pair2 = ec::XOnlyPair::from_xprv(social_child2);
         let user2 = "facilitator".to_string() + &nonce[0..3];
         
-        assert!(register(url.clone(), None, xonly_pair2.clone(), client_invite_code2.invite_code, user2).is_ok());
+        assert!(register(url.clone(), NetworkConfig::default(), xonly_pair2.clone(), client_invite_code2.invite_code, user2).is_ok());
 
         let badge1to2 = Badge::new(AnnouncementType::Trust,xonly_pair1.clone(),xonly_pair2.pubkey);
         assert!(badge1to2.verify());
 
-        assert!(announce(url.clone(), None, xonly_pair1.clone(), badge1to2.clone()).is_ok());
+        assert!(announce(url.clone(), NetworkConfig::default(), xonly_pair1.clone(), badge1to2.clone()).is_ok());
 
-        let badges: Vec<Badge> = get_all(url.clone(), None, xonly_pair2.clone()).unwrap();
+        let badges: Vec<Badge> = get_all(url.clone(), NetworkConfig::default(), xonly_pair2.clone()).unwrap();
         let count = badges.len();
         assert!(count > 0);
         
-        revoke(url.clone(), None, xonly_pair1.clone(), badge1to2.clone()).unwrap();
+        revoke(url.clone(), NetworkConfig::default(), xonly_pair1.clone(), badge1to2.clone()).unwrap();
         
-        let badges: Vec<Badge> = get_all(url.clone(), None, xonly_pair2).unwrap();
+        let badges: Vec<Badge> = get_all(url.clone(), NetworkConfig::default(), xonly_pair2).unwrap();
         let count_update = badges.len();
         assert!(count - count_update == 1);
 
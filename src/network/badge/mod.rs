@@ -1,2 +1,3 @@
 pub mod model;
-pub mod dto;
\ No newline at end of file
+pub mod dto;
+pub mod reputation;
\ No newline at end of file
@@ -3,7 +3,46 @@ use bitcoin::secp256k1::{XOnlyPublicKey};
 use crate::key::ec::{XOnlyPair, schnorr_verify};
 use bitcoin::secp256k1::schnorr::Signature;
 use crate::network::handler::{AnnouncementType};
-use crate::key::encryption::{nonce};
+use crate::key::encryption::{nonce,key_hash256};
+use crate::util::e::{ErrorKind, S5Error};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const MAX_BADGE_REASON_LENGTH: usize = 280;
+pub const MAX_BADGE_REFERENCE_LENGTH: usize = 128;
+
+/// Optional signed context for a badge. A badge with none of these set is signed in the legacy format.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct BadgeDetails{
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Millisecond timestamp after which the badge no longer counts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiry: Option<u64>,
+    /// What the badge is about, e.g. a post id or a txid.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+}
+
+impl BadgeDetails {
+    pub fn structify(stringified: &str) -> Result<BadgeDetails, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Input, "Error structifying BadgeDetails")),
+        }
+    }
+    pub fn is_empty(&self)->bool{
+        self.reason.is_none() && self.expiry.is_none() && self.reference.is_none()
+    }
+    pub fn check(&self)->Result<(), S5Error>{
+        if self.reason.as_ref().is_some_and(|reason| reason.chars().count() > MAX_BADGE_REASON_LENGTH){
+            return Err(S5Error::new(ErrorKind::Input, &format!("Badge reason must be at most {} characters.", MAX_BADGE_REASON_LENGTH)));
+        }
+        if self.reference.as_ref().is_some_and(|reference| reference.chars().count() > MAX_BADGE_REFERENCE_LENGTH){
+            return Err(S5Error::new(ErrorKind::Input, &format!("Badge reference must be at most {} characters.", MAX_BADGE_REFERENCE_LENGTH)));
+        }
+        Ok(())
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Badge{
@@ -13,7 +52,9 @@ pub struct Badge{
     pub to : XOnlyPublicKey,
     pub nonce: String,
     pub signature: Signature,
-    pub hash: Option<String>
+    pub hash: Option<String>,
+    #[serde(flatten)]
+    pub details: BadgeDetails,
 }
 
 impl Badge {
@@ -22,23 +63,88 @@ impl Badge {
         creator: XOnlyPair,
         to: XOnlyPublicKey,
     )->Self{
+        Badge::with_details(kind, creator, to, BadgeDetails::default()).unwrap()
+    }
+    /// A badge that also signs `details`. Errors if they are over the length limits.
+    pub fn with_details(
+        kind: AnnouncementType,
+        creator: XOnlyPair,
+        to: XOnlyPublicKey,
+        details: BadgeDetails,
+    )->Result<Self, S5Error>{
+        details.check()?;
         let nonce = nonce();
-        let message = format!("{}:{}:{}:{}",creator.pubkey.to_string(),to.to_string(),kind.to_string(),nonce);
-        let signature = creator.schnorr_sign(&message).unwrap();
-        Badge{
+        let message = Badge::message(&creator.pubkey, &to, &kind, &nonce, &details);
+        let signature = creator.schnorr_sign(&message)?;
+        Ok(Badge{
             genesis: None,
             kind,
             by:creator.pubkey,
             to,
             nonce,
             signature,
-            hash: None
+            hash: None,
+            details,
+        })
+    }
+    /// Legacy badges sign "by:to:kind:nonce". Badges with details append the sha256 of the details json.
+    fn message(by: &XOnlyPublicKey, to: &XOnlyPublicKey, kind: &AnnouncementType, nonce: &str, details: &BadgeDetails)->String{
+        let legacy = format!("{}:{}:{}:{}",by,to,kind.to_string(),nonce);
+        if details.is_empty(){
+            legacy
+        }
+        else{
+            format!("{}:{}",legacy,key_hash256(&serde_json::to_string(details).unwrap()))
         }
     }
 
     pub fn verify(&self)->bool{
-        let message = format!("{}:{}:{}:{}",self.by.to_string(),self.to.to_string(),self.kind.to_string(),self.nonce);
+        let message = Badge::message(&self.by, &self.to, &self.kind, &self.nonce, &self.details);
         schnorr_verify(self.signature,&message,self.by).is_ok()
     }
+
+    pub fn is_expired(&self)->bool{
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        self.details.expiry.is_some_and(|expiry| expiry <= now)
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing::keys;
+
+    #[test]
+    fn test_badge_details() {
+        let (ishi, sushi) = (keys(), keys());
+        let legacy = Badge::new(AnnouncementType::Trust, ishi.clone(), sushi.pubkey);
+        assert!(legacy.verify());
+        assert!(!legacy.is_expired());
+        // legacy badges from the server carry no details
+        let stored = serde_json::to_string(&legacy).unwrap();
+        assert!(!stored.contains("reason"));
+        assert!(serde_json::from_str::<Badge>(&stored).unwrap().verify());
+
+        let details = BadgeDetails {
+            reason: Some("never sent the sats".to_string()),
+            expiry: Some(1),
+            reference: Some("a1b2c3".to_string()),
+        };
+        let badge = Badge::with_details(AnnouncementType::Scam, ishi.clone(), sushi.pubkey, details).unwrap();
+        assert!(badge.verify());
+        assert!(badge.is_expired());
+        let parsed: Badge = serde_json::from_str(&serde_json::to_string(&badge).unwrap()).unwrap();
+        assert_eq!(parsed.details.reference.as_deref(), Some("a1b2c3"));
+        assert!(parsed.verify());
+
+        let mut stripped = badge.clone();
+        stripped.details = BadgeDetails::default();
+        assert!(!stripped.verify());
+        let mut extended = badge.clone();
+        extended.details.expiry = None;
+        assert!(!extended.verify());
+
+        let too_long = BadgeDetails { reason: Some("x".repeat(MAX_BADGE_REASON_LENGTH + 1)), ..Default::default() };
+        assert!(Badge::with_details(AnnouncementType::Trust, ishi, sushi.pubkey, too_long).is_err());
+    }
+}
@@ -0,0 +1,368 @@
+//! Web of trust scoring from badges, as seen from one member.
+//!
+//! Every badge issuer gets a weight from their distance to `me` along Trust badges:
+//! 1 for me, halved at every hop, and `STRANGER_WEIGHT` if they are not reachable within
+//! `MAX_TRUST_DEPTH` hops. A member's score adds up the weights of those who trust them,
+//! adds escrow history at `ESCROW_FACTOR` and takes away scam flags at `SCAM_FACTOR`.
+//! Only trust from inside my network counts; escrow and scam badges from strangers count a little.
+use crate::network::badge::model::Badge;
+use crate::network::handler::AnnouncementType;
+use crate::util::e::{ErrorKind, S5Error};
+use bitcoin::secp256k1::XOnlyPublicKey;
+use serde::{Deserialize, Serialize};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::ffi::CString;
+use std::os::raw::c_char;
+
+pub const MAX_TRUST_DEPTH: u32 = 3;
+pub const TRUST_DECAY: f64 = 0.5;
+pub const STRANGER_WEIGHT: f64 = 0.1;
+pub const ESCROW_FACTOR: f64 = 0.5;
+pub const SCAM_FACTOR: f64 = 2.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MemberScore {
+    pub pubkey: XOnlyPublicKey,
+    pub score: f64,
+    /// I gave them a Trust badge.
+    pub direct_trust: bool,
+    /// Trust hops from me, if within `MAX_TRUST_DEPTH`.
+    pub trust_distance: Option<u32>,
+    pub trust: f64,
+    pub escrow: f64,
+    pub scam: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Scores {
+    /// Highest score first.
+    pub scores: Vec<MemberScore>,
+}
+
+impl Scores {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+/// Why a member has their score. The root holds the total; each child is one badge,
+/// and the children of a badge are the trust path from me to its issuer.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Explanation {
+    pub label: String,
+    pub pubkey: Option<XOnlyPublicKey>,
+    pub contribution: f64,
+    /// Reason and reference signed into the badge, if any.
+    #[serde(default)]
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub reference: Option<String>,
+    pub children: Vec<Explanation>,
+}
+
+impl Explanation {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+/// How `AllPosts::to_all_posts_as_chat` treats posts from members with a poor reputation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FilterPolicy {
+    /// Quarantine senders with a Scam badge from anyone this many trust hops from me or closer.
+    /// 0 means only my own Scam badges.
+    #[serde(default = "FilterPolicy::default_scam_within")]
+    pub scam_within: u32,
+    /// Quarantine senders scoring below this.
+    #[serde(default = "FilterPolicy::default_quarantine_below")]
+    pub quarantine_below: f64,
+    /// Flag senders scoring below this, or carrying any Scam badge.
+    #[serde(default)]
+    pub flag_below: f64,
+}
+
+impl Default for FilterPolicy {
+    fn default() -> Self {
+        FilterPolicy {
+            scam_within: FilterPolicy::default_scam_within(),
+            quarantine_below: FilterPolicy::default_quarantine_below(),
+            flag_below: 0.0,
+        }
+    }
+}
+
+impl FilterPolicy {
+    fn default_scam_within() -> u32 {
+        1
+    }
+    fn default_quarantine_below() -> f64 {
+        -1.0
+    }
+    pub fn structify(stringified: &str) -> Result<FilterPolicy, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Input, "Error structifying FilterPolicy")),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum FilterAction {
+    Allow,
+    Flag,
+    Quarantine,
+}
+
+/// A policy and the reputation it is judged against.
+pub struct PostFilter {
+    pub reputation: Reputation,
+    pub policy: FilterPolicy,
+}
+
+impl PostFilter {
+    pub fn action(&self, sender: &XOnlyPublicKey) -> FilterAction {
+        let score = self.reputation.score(sender);
+        let close_reporter = self.reputation.badges.iter().any(|badge| {
+            badge.to == *sender
+                && matches!(badge.kind, AnnouncementType::Scam)
+                && matches!(self.reputation.network.get(&badge.by), Some((hops, _)) if *hops <= self.policy.scam_within)
+        });
+        if close_reporter || score.score < self.policy.quarantine_below {
+            FilterAction::Quarantine
+        } else if score.scam > 0.0 || score.score < self.policy.flag_below {
+            FilterAction::Flag
+        } else {
+            FilterAction::Allow
+        }
+    }
+}
+
+pub struct Reputation {
+    me: XOnlyPublicKey,
+    badges: Vec<Badge>,
+    /// Pubkey => (hops from me, who they were reached through).
+    network: BTreeMap<XOnlyPublicKey, (u32, Option<XOnlyPublicKey>)>,
+}
+
+impl Reputation {
+    /// Badges that fail `Badge::verify`, badges to oneself and expired badges are dropped.
+    pub fn build(me: XOnlyPublicKey, badges: Vec<Badge>) -> Reputation {
+        let badges: Vec<Badge> = badges
+            .into_iter()
+            .filter(|badge| badge.by != badge.to && !badge.is_expired() && badge.verify())
+            .collect();
+        let mut network = BTreeMap::new();
+        network.insert(me, (0, None));
+        let mut queue = VecDeque::from([me]);
+        while let Some(issuer) = queue.pop_front() {
+            let hops = network[&issuer].0;
+            if hops == MAX_TRUST_DEPTH {
+                continue;
+            }
+            for badge in badges.iter().filter(|badge| badge.by == issuer) {
+                if let (AnnouncementType::Trust, Entry::Vacant(entry)) = (&badge.kind, network.entry(badge.to)) {
+                    entry.insert((hops + 1, Some(issuer)));
+                    queue.push_back(badge.to);
+                }
+            }
+        }
+        Reputation { me, badges, network }
+    }
+
+    fn weight(&self, pubkey: &XOnlyPublicKey) -> f64 {
+        match self.network.get(pubkey) {
+            Some((hops, _)) => TRUST_DECAY.powi(*hops as i32),
+            None => STRANGER_WEIGHT,
+        }
+    }
+    fn in_network(&self, pubkey: &XOnlyPublicKey) -> bool {
+        self.network.contains_key(pubkey)
+    }
+
+    pub fn score(&self, member: &XOnlyPublicKey) -> MemberScore {
+        let mut score = MemberScore {
+            pubkey: *member,
+            score: 0.0,
+            direct_trust: false,
+            trust_distance: self.network.get(member).map(|(hops, _)| *hops),
+            trust: 0.0,
+            escrow: 0.0,
+            scam: 0.0,
+        };
+        for badge in self.badges.iter().filter(|badge| badge.to == *member) {
+            match badge.kind {
+                AnnouncementType::Trust => {
+                    if badge.by == self.me {
+                        score.direct_trust = true;
+                    }
+                    if self.in_network(&badge.by) {
+                        score.trust += self.weight(&badge.by);
+                    }
+                }
+                AnnouncementType::Escrow => score.escrow += self.weight(&badge.by),
+                AnnouncementType::Scam => score.scam += self.weight(&badge.by),
+            }
+        }
+        score.score = score.trust + ESCROW_FACTOR * score.escrow - SCAM_FACTOR * score.scam;
+        score
+    }
+
+    /// Everyone who has received a badge, highest score first.
+    pub fn scores(&self) -> Scores {
+        let members: BTreeSet<XOnlyPublicKey> =
+            self.badges.iter().map(|badge| badge.to).filter(|pubkey| *pubkey != self.me).collect();
+        let mut scores: Vec<MemberScore> = members.iter().map(|member| self.score(member)).collect();
+        scores.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+        Scores { scores }
+    }
+
+    /// Trust path from me to `pubkey`, one node per hop, nested.
+    fn path(&self, pubkey: &XOnlyPublicKey) -> Vec<Explanation> {
+        let mut hops = vec![];
+        let mut current = *pubkey;
+        while let Some((_, Some(through))) = self.network.get(&current) {
+            hops.push((*through, current));
+            current = *through;
+        }
+        hops.into_iter().fold(vec![], |children, (by, to)| {
+            let label = if by == self.me { format!("You trust {}", to) } else { format!("{} trusts {}", by, to) };
+            vec![Explanation {
+                label,
+                pubkey: Some(to),
+                contribution: self.weight(&to),
+                reason: None,
+                reference: None,
+                children,
+            }]
+        })
+    }
+
+    pub fn explain(&self, member: &XOnlyPublicKey) -> Explanation {
+        let score = self.score(member);
+        let mut children = vec![];
+        for badge in self.badges.iter().filter(|badge| badge.to == *member) {
+            let weight = self.weight(&badge.by);
+            let (label, contribution) = match badge.kind {
+                AnnouncementType::Trust if self.in_network(&badge.by) => (format!("Trusted by {}", badge.by), weight),
+                AnnouncementType::Trust => (format!("Trusted by {}, outside your network", badge.by), 0.0),
+                AnnouncementType::Escrow => (format!("Escrow with {}", badge.by), ESCROW_FACTOR * weight),
+                AnnouncementType::Scam => (format!("Flagged as scam by {}", badge.by), -SCAM_FACTOR * weight),
+            };
+            children.push(Explanation {
+                label,
+                pubkey: Some(badge.by),
+                contribution,
+                reason: badge.details.reason.clone(),
+                reference: badge.details.reference.clone(),
+                children: self.path(&badge.by),
+            });
+        }
+        Explanation {
+            label: format!("Reputation of {}", member),
+            pubkey: Some(*member),
+            contribution: score.score,
+            reason: None,
+            reference: None,
+            children,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing::keys;
+    use crate::network::badge::model::BadgeDetails;
+
+    #[test]
+    fn test_reputation() {
+        let (me, alice, bob, carol, mallory, stranger) = (keys(), keys(), keys(), keys(), keys(), keys());
+        let details = |expiry| BadgeDetails {
+            reason: Some("paid on time".to_string()),
+            expiry: Some(expiry),
+            reference: Some("txid".to_string()),
+        };
+        let mut forged = Badge::new(AnnouncementType::Trust, stranger.clone(), mallory.pubkey);
+        forged.by = me.pubkey;
+        let badges = vec![
+            Badge::new(AnnouncementType::Trust, me.clone(), alice.pubkey),
+            Badge::new(AnnouncementType::Trust, alice.clone(), bob.pubkey),
+            Badge::new(AnnouncementType::Trust, bob.clone(), carol.pubkey),
+            Badge::with_details(AnnouncementType::Escrow, alice.clone(), carol.pubkey, details(u64::MAX)).unwrap(),
+            Badge::with_details(AnnouncementType::Trust, me.clone(), mallory.pubkey, details(1)).unwrap(),
+            Badge::new(AnnouncementType::Trust, stranger.clone(), mallory.pubkey),
+            Badge::new(AnnouncementType::Trust, mallory.clone(), mallory.pubkey),
+            Badge::new(AnnouncementType::Scam, alice.clone(), mallory.pubkey),
+            Badge::new(AnnouncementType::Scam, stranger.clone(), carol.pubkey),
+            forged,
+        ];
+        let reputation = Reputation::build(me.pubkey, badges);
+
+        let alice_score = reputation.score(&alice.pubkey);
+        assert!(alice_score.direct_trust);
+        assert_eq!(alice_score.trust_distance, Some(1));
+        assert_eq!(alice_score.trust, 1.0);
+
+        let bob_score = reputation.score(&bob.pubkey);
+        assert!(!bob_score.direct_trust);
+        assert_eq!(bob_score.trust, 0.5);
+
+        let carol_score = reputation.score(&carol.pubkey);
+        assert_eq!(carol_score.trust_distance, Some(3));
+        assert_eq!(carol_score.trust, 0.25);
+        assert_eq!(carol_score.escrow, 0.5);
+        assert_eq!(carol_score.scam, STRANGER_WEIGHT);
+        assert!((carol_score.score - (0.25 + 0.25 - 0.2)).abs() < 1e-9);
+
+        // stranger trust, self trust, my expired trust and the forged badge count for nothing
+        let mallory_score = reputation.score(&mallory.pubkey);
+        assert_eq!(mallory_score.trust, 0.0);
+        assert_eq!(mallory_score.trust_distance, None);
+        assert_eq!(mallory_score.scam, 0.5);
+        assert_eq!(mallory_score.score, -1.0);
+
+        let scores = reputation.scores().scores;
+        assert_eq!(scores.len(), 4);
+        assert_eq!(scores[0].pubkey, alice.pubkey);
+        assert_eq!(scores[3].pubkey, mallory.pubkey);
+
+        let mut filter = PostFilter { reputation, policy: FilterPolicy::default() };
+        assert_eq!(filter.action(&alice.pubkey), FilterAction::Allow);
+        // scam flag from a stranger
+        assert_eq!(filter.action(&carol.pubkey), FilterAction::Flag);
+        // scam flag from someone I trust
+        assert_eq!(filter.action(&mallory.pubkey), FilterAction::Quarantine);
+        assert_eq!(filter.action(&stranger.pubkey), FilterAction::Allow);
+        filter.policy = FilterPolicy::structify(r#"{"scam_within":0,"quarantine_below":-5.0}"#).unwrap();
+        assert_eq!(filter.action(&mallory.pubkey), FilterAction::Flag);
+        let reputation = filter.reputation;
+
+        let explanation = reputation.explain(&carol.pubkey);
+        assert_eq!(explanation.children.len(), 3);
+        let escrow = explanation.children.iter().find(|child| child.pubkey == Some(alice.pubkey)).unwrap();
+        assert_eq!(escrow.reason.as_deref(), Some("paid on time"));
+        let from_bob = explanation.children.iter().find(|child| child.pubkey == Some(bob.pubkey)).unwrap();
+        // you trust alice, who trusts bob
+        assert_eq!(from_bob.children[0].label, format!("You trust {}", alice.pubkey));
+        assert_eq!(from_bob.children[0].children[0].label, format!("{} trusts {}", alice.pubkey, bob.pubkey));
+    }
+}
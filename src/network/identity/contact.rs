@@ -0,0 +1,297 @@
+//! Out of band contact verification.
+//! Two members either compare a safety number derived from both pubkeys, or one scans the
+//! other's contact uri, which carries a signature made with the key it names.
+//! Verified contacts are kept on the device, like the member directory.
+use crate::key::ec::{schnorr_verify, signature_from_str, XOnlyPair};
+use crate::key::encryption::nonce;
+use crate::network::identity::directory::MemberDirectory;
+use crate::network::identity::username;
+use crate::util::e::{ErrorKind, S5Error};
+use bitcoin::secp256k1::schnorr::Signature;
+use bitcoin::secp256k1::XOnlyPublicKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+
+pub const CONTACT_URI_PREFIX: &str = "cypherpost://contact";
+
+/// 60 digits in 12 groups of 5, the same whichever side computes it.
+pub fn safety_number(a: &XOnlyPublicKey, b: &XOnlyPublicKey) -> String {
+    let (first, second) = if a.serialize() <= b.serialize() { (a, b) } else { (b, a) };
+    let mut hasher = Sha512::new();
+    hasher.update(b"cypherpost-safety-number");
+    hasher.update(first.serialize());
+    hasher.update(second.serialize());
+    let hash = hasher.finalize();
+    hash[..60]
+        .chunks(5)
+        .map(|chunk| {
+            let value = chunk.iter().fold(0u64, |acc, byte| (acc << 8) | *byte as u64);
+            format!("{:05}", value % 100_000)
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// What a contact QR code carries: where to find a member, and proof they hold the key.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ContactCard {
+    pub host: String,
+    pub username: String,
+    pub pubkey: XOnlyPublicKey,
+    pub nonce: String,
+    pub signature: Signature,
+}
+
+impl ContactCard {
+    pub fn new(host: &str, username: &str, owner: &XOnlyPair) -> Result<ContactCard, S5Error> {
+        let nonce = nonce();
+        let message = ContactCard::message(host, username, &owner.pubkey, &nonce);
+        Ok(ContactCard {
+            host: host.to_string(),
+            username: username.to_string(),
+            pubkey: owner.pubkey,
+            nonce,
+            signature: owner.schnorr_sign(&message)?,
+        })
+    }
+    fn message(host: &str, username: &str, pubkey: &XOnlyPublicKey, nonce: &str) -> String {
+        format!("{}:{}:{}:{}", host, username, pubkey, nonce)
+    }
+    pub fn verify(&self) -> Result<(), S5Error> {
+        schnorr_verify(
+            self.signature,
+            &ContactCard::message(&self.host, &self.username, &self.pubkey, &self.nonce),
+            self.pubkey,
+        )
+    }
+
+    pub fn to_uri(&self) -> String {
+        let mut uri = Url::parse(CONTACT_URI_PREFIX).unwrap();
+        uri.query_pairs_mut()
+            .append_pair("host", &self.host)
+            .append_pair("username", &self.username)
+            .append_pair("pubkey", &self.pubkey.to_string())
+            .append_pair("nonce", &self.nonce)
+            .append_pair("signature", &self.signature.to_string());
+        uri.to_string()
+    }
+    /// Parses and verifies a uri made with `to_uri`.
+    pub fn from_uri(uri: &str) -> Result<ContactCard, S5Error> {
+        let parsed = match Url::parse(uri.trim()) {
+            Ok(parsed) if parsed.as_str().starts_with(CONTACT_URI_PREFIX) => parsed,
+            _ => return Err(S5Error::new(ErrorKind::Input, "Not a contact uri.")),
+        };
+        let field = |name: &str| -> Result<String, S5Error> {
+            match parsed.query_pairs().find(|(key, _)| key == name) {
+                Some((_, value)) => Ok(value.to_string()),
+                None => Err(S5Error::new(ErrorKind::Input, &format!("Contact uri is missing {}.", name))),
+            }
+        };
+        let card = ContactCard {
+            host: field("host")?,
+            username: field("username")?,
+            pubkey: XOnlyPublicKey::from_str(&field("pubkey")?)
+                .map_err(|_| S5Error::new(ErrorKind::Input, "Contact uri has a bad pubkey."))?,
+            nonce: field("nonce")?,
+            signature: signature_from_str(&field("signature")?)?,
+        };
+        card.verify()?;
+        Ok(card)
+    }
+}
+
+/// Returned by `contact_uri` and `safety_number`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ContactCode {
+    pub code: String,
+}
+
+impl ContactCode {
+    pub fn c_stringify(&self) -> *mut c_char {
+        CString::new(serde_json::to_string(self).unwrap()).unwrap().into_raw()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VerifiedContact {
+    pub username: String,
+    pub pubkey: XOnlyPublicKey,
+    pub verified_at: u64,
+}
+
+/// A verified username now belongs to a different key.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KeyChange {
+    pub username: String,
+    pub verified_pubkey: XOnlyPublicKey,
+    pub current_pubkey: XOnlyPublicKey,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Contacts {
+    pub verified: Vec<VerifiedContact>,
+}
+
+impl Contacts {
+    pub fn structify(stringified: &str) -> Result<Contacts, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Input, "Error structifying Contacts")),
+        }
+    }
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+
+    /// Mark `pubkey` as the verified key for `username`. `directory` must list `username` for `pubkey`.
+    /// A username already verified under another key is only moved to `pubkey` with `replace`,
+    /// once the user has confirmed the change.
+    pub fn verify(
+        &mut self,
+        username: &str,
+        pubkey: XOnlyPublicKey,
+        directory: &MemberDirectory,
+        replace: bool,
+    ) -> Result<(), S5Error> {
+        let name = username::normalize(username);
+        match directory.resolve(&pubkey) {
+            Some(entry) if username::normalize(&entry.username) == name => {}
+            Some(_) => return Err(S5Error::new(ErrorKind::Input, "This key holds another username on this server.")),
+            None => return Err(S5Error::new(ErrorKind::NoResource, "This key is not a member of this server.")),
+        }
+        let taken = self
+            .verified
+            .iter()
+            .any(|contact| username::normalize(&contact.username) == name && contact.pubkey != pubkey);
+        if taken && !replace {
+            return Err(S5Error::new(
+                ErrorKind::Input,
+                "This username is verified under another key. Confirm to replace it.",
+            ));
+        }
+        self.verified.retain(|contact| username::normalize(&contact.username) != name && contact.pubkey != pubkey);
+        self.verified.push(VerifiedContact {
+            username: name,
+            pubkey,
+            verified_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64,
+        });
+        Ok(())
+    }
+    /// Verify from a scanned contact uri. The uri must be for `host`, see `verify` for the rest.
+    pub fn verify_card(
+        &mut self,
+        host: &str,
+        card: &ContactCard,
+        directory: &MemberDirectory,
+        replace: bool,
+    ) -> Result<(), S5Error> {
+        card.verify()?;
+        if card.host.trim_end_matches('/') != host.trim_end_matches('/') {
+            return Err(S5Error::new(ErrorKind::Input, "Contact is for another server."));
+        }
+        self.verify(&card.username, card.pubkey, directory, replace)
+    }
+    pub fn remove(&mut self, pubkey: &XOnlyPublicKey) {
+        self.verified.retain(|contact| contact.pubkey != *pubkey);
+    }
+
+    pub fn is_verified(&self, pubkey: &XOnlyPublicKey) -> bool {
+        self.verified.iter().any(|contact| contact.pubkey == *pubkey)
+    }
+    /// The verified contact whose username `pubkey` now holds under another key.
+    pub fn key_change(&self, pubkey: &XOnlyPublicKey, directory: &MemberDirectory) -> Option<KeyChange> {
+        let entry = directory.resolve(pubkey)?;
+        let name = username::normalize(&entry.username);
+        self.verified
+            .iter()
+            .find(|contact| username::normalize(&contact.username) == name && contact.pubkey != *pubkey)
+            .map(|contact| KeyChange {
+                username: contact.username.clone(),
+                verified_pubkey: contact.pubkey,
+                current_pubkey: *pubkey,
+            })
+    }
+    /// Every verified username that a current member holds under a different key.
+    pub fn key_changes(&self, directory: &MemberDirectory) -> Vec<KeyChange> {
+        directory
+            .members()
+            .into_iter()
+            .filter_map(|entry| self.key_change(&entry.pubkey, directory))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing::keys;
+    use crate::network::identity::directory::{MemberChange, MemberChangeKind};
+
+    #[test]
+    fn test_contact_verification() {
+        let (ishi, sushi, mallory) = (keys(), keys(), keys());
+        let number = safety_number(&ishi.pubkey, &sushi.pubkey);
+        assert_eq!(number, safety_number(&sushi.pubkey, &ishi.pubkey));
+        assert_ne!(number, safety_number(&ishi.pubkey, &mallory.pubkey));
+        assert_eq!(number.len(), 12 * 5 + 11);
+
+        let card = ContactCard::new("https://cypherpost.io", "sushi", &sushi).unwrap();
+        let uri = card.to_uri();
+        assert!(uri.starts_with("cypherpost://contact?host=https%3A%2F%2Fcypherpost.io"));
+        let scanned = ContactCard::from_uri(&uri).unwrap();
+        assert_eq!(scanned.pubkey, sushi.pubkey);
+        let forged = uri.replace(&sushi.pubkey.to_string(), &mallory.pubkey.to_string());
+        assert!(ContactCard::from_uri(&forged).is_err());
+        assert!(ContactCard::from_uri("https://example.com").is_err());
+
+        let mut directory = MemberDirectory::default();
+        let change = |genesis, kind, pubkey| MemberChange { genesis, kind, username: "sushi".to_string(), pubkey };
+        directory.apply(vec![change(1, MemberChangeKind::Joined, sushi.pubkey)]);
+        let mut contacts = Contacts::default();
+        assert!(contacts.verify_card("https://other.io", &scanned, &directory, false).is_err());
+        contacts.verify_card("https://cypherpost.io/", &scanned, &directory, false).unwrap();
+        assert!(contacts.is_verified(&sushi.pubkey));
+        assert!(contacts.key_changes(&directory).is_empty());
+
+        // a validly signed card claiming someone else's username
+        let impostor = ContactCard::new("https://cypherpost.io", "sushi", &mallory).unwrap();
+        assert!(contacts.verify_card("https://cypherpost.io", &impostor, &directory, true).is_err());
+        let mut elsewhere = directory.clone();
+        elsewhere.apply(vec![MemberChange {
+            genesis: 2,
+            kind: MemberChangeKind::Joined,
+            username: "mallory".to_string(),
+            pubkey: mallory.pubkey,
+        }]);
+        assert!(contacts.verify_card("https://cypherpost.io", &impostor, &elsewhere, true).is_err());
+        assert!(contacts.is_verified(&sushi.pubkey));
+        directory.apply(vec![
+            change(2, MemberChangeKind::Left, sushi.pubkey),
+            change(3, MemberChangeKind::Joined, mallory.pubkey),
+        ]);
+        let changes = contacts.key_changes(&directory);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].verified_pubkey, sushi.pubkey);
+        assert_eq!(changes[0].current_pubkey, mallory.pubkey);
+
+        // verifying the new key replaces the old one, once confirmed
+        assert!(contacts.verify("sushi", mallory.pubkey, &directory, false).is_err());
+        contacts.verify("sushi", mallory.pubkey, &directory, true).unwrap();
+        assert!(!contacts.is_verified(&sushi.pubkey));
+        assert!(contacts.key_changes(&directory).is_empty());
+    }
+}
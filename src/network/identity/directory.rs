@@ -0,0 +1,210 @@
+//! Local cache of the server's member list, kept up to date from the membership change feed.
+//! Apps persist the stringified directory and pass it back in, so each refresh only fetches
+//! what changed since `latest_genesis`.
+use crate::network::identity::username;
+use crate::util::e::{ErrorKind, S5Error};
+use bitcoin::secp256k1::XOnlyPublicKey;
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+use std::os::raw::c_char;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum MemberChangeKind {
+    Joined,
+    Left,
+}
+
+/// One entry of the server's membership log.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MemberChange {
+    pub genesis: u64,
+    pub kind: MemberChangeKind,
+    pub username: String,
+    pub pubkey: XOnlyPublicKey,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MemberChanges {
+    pub changes: Vec<MemberChange>,
+}
+
+impl MemberChanges {
+    pub fn structify(stringified: &str) -> Result<MemberChanges, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Internal, "Error structifying MemberChanges")),
+        }
+    }
+}
+
+/// A membership period. A member who leaves and rejoins gets a second entry.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DirectoryEntry {
+    pub username: String,
+    pub pubkey: XOnlyPublicKey,
+    pub joined: u64,
+    pub left: Option<u64>,
+}
+
+/// How a chat's counter party relates to the member directory.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ContactFlag {
+    /// Was a member, but has since left the server.
+    Left,
+    /// Another pubkey has held this username, now or in the past.
+    UsernameReused,
+    /// Never seen in the directory.
+    Unknown,
+    /// Key confirmed out of band, see `Contacts`.
+    Verified,
+    /// Holds the username of a verified contact, but not the verified key. Warn loudly.
+    KeyChanged,
+    /// Carries a Scam badge, or scores below the filter policy, see `PostFilter`.
+    LowReputation,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MemberDirectory {
+    /// In join order.
+    pub entries: Vec<DirectoryEntry>,
+    pub latest_genesis: u64,
+}
+
+impl MemberDirectory {
+    pub fn structify(stringified: &str) -> Result<MemberDirectory, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Input, "Error structifying MemberDirectory")),
+        }
+    }
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+
+    /// Apply changes in genesis order. Changes at or before `latest_genesis` were already applied and are skipped.
+    pub fn apply(&mut self, mut changes: Vec<MemberChange>) {
+        changes.sort_by_key(|change| change.genesis);
+        let applied = self.latest_genesis;
+        for change in changes.into_iter().filter(|change| change.genesis > applied) {
+            match change.kind {
+                MemberChangeKind::Joined => self.entries.push(DirectoryEntry {
+                    username: change.username,
+                    pubkey: change.pubkey,
+                    joined: change.genesis,
+                    left: None,
+                }),
+                MemberChangeKind::Left => {
+                    if let Some(entry) = self
+                        .entries
+                        .iter_mut()
+                        .find(|entry| entry.pubkey == change.pubkey && entry.left.is_none())
+                    {
+                        entry.left = Some(change.genesis);
+                    }
+                }
+            }
+            self.latest_genesis = change.genesis;
+        }
+    }
+
+    /// Current members, in join order.
+    pub fn members(&self) -> Vec<&DirectoryEntry> {
+        self.entries.iter().filter(|entry| entry.left.is_none()).collect()
+    }
+
+    /// The current membership of `pubkey`, or its most recent one if it has left.
+    pub fn resolve(&self, pubkey: &XOnlyPublicKey) -> Option<&DirectoryEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|entry| entry.pubkey == *pubkey)
+            .min_by_key(|entry| entry.left.is_some())
+    }
+
+    /// True if more than one pubkey has registered `name`.
+    pub fn username_reused(&self, name: &str) -> bool {
+        let name = username::normalize(name);
+        let mut holders = self
+            .entries
+            .iter()
+            .filter(|entry| username::normalize(&entry.username) == name)
+            .map(|entry| entry.pubkey);
+        match holders.next() {
+            Some(first) => holders.any(|pubkey| pubkey != first),
+            None => false,
+        }
+    }
+
+    /// Username and flags for a chat counter party.
+    pub fn contact(&self, pubkey: &XOnlyPublicKey) -> (Option<String>, Vec<ContactFlag>) {
+        match self.resolve(pubkey) {
+            Some(entry) => {
+                let mut flags = vec![];
+                if entry.left.is_some() {
+                    flags.push(ContactFlag::Left);
+                }
+                if self.username_reused(&entry.username) {
+                    flags.push(ContactFlag::UsernameReused);
+                }
+                (Some(entry.username.clone()), flags)
+            }
+            None => (None, vec![ContactFlag::Unknown]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing::pubkey;
+
+    fn change(genesis: u64, kind: MemberChangeKind, username: &str, pubkey: XOnlyPublicKey) -> MemberChange {
+        MemberChange { genesis, kind, username: username.to_string(), pubkey }
+    }
+
+    #[test]
+    fn test_directory() {
+        let (ishi, sushi, mallory, stranger) = (pubkey(), pubkey(), pubkey(), pubkey());
+        let mut directory = MemberDirectory::default();
+        directory.apply(vec![
+            change(2, MemberChangeKind::Joined, "sushi", sushi),
+            change(1, MemberChangeKind::Joined, "ishi", ishi),
+        ]);
+        assert_eq!(directory.latest_genesis, 2);
+        assert_eq!(directory.members()[0].username, "ishi");
+        assert_eq!(directory.contact(&ishi), (Some("ishi".to_string()), vec![]));
+
+        directory.apply(vec![
+            // already applied
+            change(2, MemberChangeKind::Joined, "sushi", sushi),
+            change(3, MemberChangeKind::Left, "ishi", ishi),
+            change(4, MemberChangeKind::Joined, "ishi", mallory),
+        ]);
+        assert_eq!(directory.entries.len(), 3);
+        assert_eq!(directory.members().len(), 2);
+        assert_eq!(
+            directory.contact(&ishi),
+            (Some("ishi".to_string()), vec![ContactFlag::Left, ContactFlag::UsernameReused])
+        );
+        assert_eq!(directory.contact(&mallory), (Some("ishi".to_string()), vec![ContactFlag::UsernameReused]));
+        assert_eq!(directory.contact(&sushi).1, vec![]);
+        assert_eq!(directory.contact(&stranger), (None, vec![ContactFlag::Unknown]));
+
+        // rejoining reuses nothing
+        directory.apply(vec![
+            change(5, MemberChangeKind::Left, "sushi", sushi),
+            change(6, MemberChangeKind::Joined, "sushi", sushi),
+        ]);
+        assert_eq!(directory.contact(&sushi).1, vec![]);
+        assert!(directory.resolve(&sushi).unwrap().left.is_none());
+    }
+}
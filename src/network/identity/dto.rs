@@ -1,38 +1,26 @@
 use serde::{Deserialize, Serialize};
-use ureq::{Proxy, AgentBuilder};
 use crate::key::encryption::{nonce};
 use crate::key::ec::{XOnlyPair};
-use crate::network::handler::{HttpHeader,HttpMethod,APIEndPoint, InvitePermission, ServerStatusResponse, sign_request};
-use crate::network::identity::model::{ServerIdentity,Invitation,Members,InvitationDetail};
+use crate::network::handler::{HttpHeader,HttpMethod,APIEndPoint, InvitePermission, ServerStatusResponse, NetworkConfig, build_agent, call_with_retry, sign_request, read_body};
+use crate::network::identity::model::{ServerIdentity,Invitation,Invitations,Members,InvitationDetail,JoinInvoice};
+use crate::network::identity::username;
+use crate::network::identity::directory::MemberChanges;
 use crate::util::e::{ErrorKind, S5Error};
 
 
-pub fn get_server_id(host: String,socks5: Option<u32>, xonly_pair: XOnlyPair)->Result<ServerIdentity, S5Error>{
+pub fn get_server_id(host: String,network: NetworkConfig, xonly_pair: XOnlyPair)->Result<ServerIdentity, S5Error>{
     let full_url = host + &APIEndPoint::ServerIdentity.to_string();
-    let nonce = nonce();
-    let signature = sign_request(xonly_pair.clone(), HttpMethod::Get, APIEndPoint::ServerIdentity, &nonce).unwrap();
-    let proxy = if socks5.is_some(){ 
-        Some(Proxy::new(&format!("socks5://localhost:{}",socks5.unwrap().to_string())).unwrap())
-    }
-    else{
-        None
-    };
-    let agent = if proxy.is_some(){
-        AgentBuilder::new()
-        .proxy(proxy.unwrap())
-        .build()
-    }
-    else{
-        AgentBuilder::new()
-        .build()
-    };
-    match agent.get(&full_url)
-        .set(&HttpHeader::Signature.to_string(), &signature)
-        .set(&HttpHeader::Pubkey.to_string(), &xonly_pair.pubkey.to_string())
-        .set(&HttpHeader::Nonce.to_string(), &nonce)
-        .call(){
+    let agent = build_agent(&network, Some(xonly_pair.pubkey))?;
+    match call_with_retry(network.retry, ||{
+        let nonce = nonce();
+        let signature = sign_request(xonly_pair.clone(), HttpMethod::Get, APIEndPoint::ServerIdentity, &nonce).unwrap();
+        agent.get(&full_url)
+            .set(&HttpHeader::Signature.to_string(), &signature)
+            .set(&HttpHeader::Pubkey.to_string(), &xonly_pair.pubkey.to_string())
+            .set(&HttpHeader::Nonce.to_string(), &nonce)
+        }){
             Ok(response)=>{
-                let response = response.into_string().unwrap();
+                let response = read_body(response)?;
                 match ServerIdentity::structify(&response)
                 {
                     Ok(result)=>return Ok(result),
@@ -47,29 +35,15 @@ pub fn get_server_id(host: String,socks5: Option<u32>, xonly_pair: XOnlyPair)->R
         }
 }
 
-pub fn admin_invite(host: String,socks5: Option<u32>, admin_secret: String, permission: InvitePermission)->Result<Invitation, S5Error>{
+pub fn admin_invite(host: String,network: NetworkConfig, admin_secret: String, permission: InvitePermission)->Result<Invitation, S5Error>{
     let full_url = host + &APIEndPoint::AdminInvite(permission).to_string();
-    let proxy = if socks5.is_some(){ 
-        Some(Proxy::new(&format!("socks5://localhost:{}",socks5.unwrap().to_string())).unwrap())
-    }
-    else{
-        None
-    };
-    let agent = if proxy.is_some(){
-        AgentBuilder::new()
-        .proxy(proxy.unwrap())
-        .build()
-    }
-    else{
-        AgentBuilder::new()
-        .build()
-    };
+    let agent = build_agent(&network, None)?;
     match agent.get(&full_url)
         .set(&HttpHeader::AdminInvite.to_string(), &admin_secret)
         .call()
         {
             Ok(response)=>  Ok(
-                match Invitation::structify(&response.into_string().unwrap()){
+                match Invitation::structify(&read_body(response)?){
                     Ok(result)=>result,
                     Err(e) =>{
                         return Err(e);
@@ -82,25 +56,11 @@ pub fn admin_invite(host: String,socks5: Option<u32>, admin_secret: String, perm
         }
 }
 
-pub fn user_invite(host: String,socks5: Option<u32>,xonly_pair: XOnlyPair,  priv_invite_code: String)->Result<Invitation, S5Error>{
+pub fn user_invite(host: String,network: NetworkConfig,xonly_pair: XOnlyPair,  priv_invite_code: String)->Result<Invitation, S5Error>{
     let full_url = host + &APIEndPoint::UserInvite.to_string();
     let nonce = nonce();
     let signature = sign_request(xonly_pair.clone(), HttpMethod::Get, APIEndPoint::UserInvite, &nonce).unwrap();
-    let proxy = if socks5.is_some(){ 
-        Some(Proxy::new(&format!("socks5://localhost:{}",socks5.unwrap().to_string())).unwrap())
-    }
-    else{
-        None
-    };
-    let agent = if proxy.is_some(){
-        AgentBuilder::new()
-        .proxy(proxy.unwrap())
-        .build()
-    }
-    else{
-        AgentBuilder::new()
-        .build()
-    };
+    let agent = build_agent(&network, Some(xonly_pair.pubkey))?;
     match agent.get(&full_url)
         .set(&HttpHeader::UserInvite.to_string(), &priv_invite_code)
         .set(&HttpHeader::Signature.to_string(), &signature)
@@ -109,7 +69,7 @@ pub fn user_invite(host: String,socks5: Option<u32>,xonly_pair: XOnlyPair,  priv
         .call()
         {
             Ok(response)=>  Ok(
-                match Invitation::structify(&response.into_string().unwrap()){
+                match Invitation::structify(&read_body(response)?){
                     Ok(result)=>result,
                     Err(e) =>{
                         return Err(e);
@@ -122,6 +82,157 @@ pub fn user_invite(host: String,socks5: Option<u32>,xonly_pair: XOnlyPair,  priv
         }
 }
 
+/// Status of any invite code. Works before joining, so a code can be checked before it is used.
+pub fn invite_detail(host: String, network: NetworkConfig, xonly_pair: XOnlyPair, invite_code: String)->Result<InvitationDetail, S5Error>{
+    let full_url = host + &APIEndPoint::UserInviteDetail.to_string();
+    let agent = build_agent(&network, Some(xonly_pair.pubkey))?;
+    match call_with_retry(network.retry, ||{
+        let nonce = nonce();
+        let signature = sign_request(xonly_pair.clone(), HttpMethod::Get, APIEndPoint::UserInviteDetail, &nonce).unwrap();
+        agent.get(&full_url)
+            .set(&HttpHeader::InviteCode.to_string(), &invite_code)
+            .set(&HttpHeader::Signature.to_string(), &signature)
+            .set(&HttpHeader::Pubkey.to_string(), &xonly_pair.pubkey.to_string())
+            .set(&HttpHeader::Nonce.to_string(), &nonce)
+        }){
+            Ok(response)=>InvitationDetail::structify(&read_body(response)?),
+            Err(e)=>{
+                Err(S5Error::from_ureq(e))
+            }
+        }
+}
+
+/// Standard invites created from my privileged invite.
+pub fn user_invites(host: String, network: NetworkConfig, xonly_pair: XOnlyPair)->Result<Invitations, S5Error>{
+    let full_url = host + &APIEndPoint::UserInvites.to_string();
+    let agent = build_agent(&network, Some(xonly_pair.pubkey))?;
+    match call_with_retry(network.retry, ||{
+        let nonce = nonce();
+        let signature = sign_request(xonly_pair.clone(), HttpMethod::Get, APIEndPoint::UserInvites, &nonce).unwrap();
+        agent.get(&full_url)
+            .set(&HttpHeader::Signature.to_string(), &signature)
+            .set(&HttpHeader::Pubkey.to_string(), &xonly_pair.pubkey.to_string())
+            .set(&HttpHeader::Nonce.to_string(), &nonce)
+        }){
+            Ok(response)=>Invitations::structify(&read_body(response)?),
+            Err(e)=>{
+                Err(S5Error::from_ureq(e))
+            }
+        }
+}
+
+/// Every invite created with the admin secret.
+pub fn admin_invites(host: String, network: NetworkConfig, admin_secret: String)->Result<Invitations, S5Error>{
+    let full_url = host + &APIEndPoint::AdminInvites.to_string();
+    let agent = build_agent(&network, None)?;
+    match call_with_retry(network.retry, ||{
+        agent.get(&full_url)
+            .set(&HttpHeader::AdminInvite.to_string(), &admin_secret)
+        }){
+            Ok(response)=>Invitations::structify(&read_body(response)?),
+            Err(e)=>{
+                Err(S5Error::from_ureq(e))
+            }
+        }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InviteRevokeRequest{
+    invite_code: String
+}
+impl InviteRevokeRequest{
+    pub fn new(invite_code: &str)->InviteRevokeRequest{
+        InviteRevokeRequest {
+            invite_code: invite_code.to_string()
+        }
+    }
+}
+
+fn revoke_status(response: Result<ureq::Response, ureq::Error>)->Result<(), S5Error>{
+    match response {
+        Ok(response)=> match ServerStatusResponse::structify(&read_body(response)?)
+            {
+                Ok(result)=>{
+                    if result.status {
+                        Ok(())
+                    }
+                    else {
+                        Err(S5Error::new(ErrorKind::Network, "Server returned a false status. This invite might already be claimed."))
+                    }
+                },
+                Err(e) =>{
+                    Err(e)
+                }
+            },
+        Err(e)=>{
+            Err(S5Error::from_ureq(e))
+        }
+    }
+}
+
+/// Revoke an unclaimed standard invite made from my privileged invite. The server returns it to my count.
+pub fn revoke_invite(host: String, network: NetworkConfig, xonly_pair: XOnlyPair, invite_code: String)->Result<(), S5Error>{
+    let full_url = host + &APIEndPoint::RevokeInvite.to_string();
+    let nonce = nonce();
+    let signature = sign_request(xonly_pair.clone(), HttpMethod::Post, APIEndPoint::RevokeInvite, &nonce).unwrap();
+    let agent = build_agent(&network, Some(xonly_pair.pubkey))?;
+    revoke_status(
+        agent.post(&full_url)
+            .set(&HttpHeader::Signature.to_string(), &signature)
+            .set(&HttpHeader::Pubkey.to_string(), &xonly_pair.pubkey.to_string())
+            .set(&HttpHeader::Nonce.to_string(), &nonce)
+            .send_json(InviteRevokeRequest::new(&invite_code))
+    )
+}
+
+/// Revoke any unclaimed invite created with the admin secret.
+pub fn admin_revoke_invite(host: String, network: NetworkConfig, admin_secret: String, invite_code: String)->Result<(), S5Error>{
+    let full_url = host + &APIEndPoint::AdminRevokeInvite.to_string();
+    let agent = build_agent(&network, None)?;
+    revoke_status(
+        agent.post(&full_url)
+            .set(&HttpHeader::AdminInvite.to_string(), &admin_secret)
+            .send_json(InviteRevokeRequest::new(&invite_code))
+    )
+}
+
+/// Ask a PUBLIC server for a payment invoice to join. Not retried, every call creates a new invoice.
+pub fn join_invoice(host: String, network: NetworkConfig, xonly_pair: XOnlyPair)->Result<JoinInvoice, S5Error>{
+    let full_url = host + &APIEndPoint::JoinInvoice(None).to_string();
+    let nonce = nonce();
+    let signature = sign_request(xonly_pair.clone(), HttpMethod::Post, APIEndPoint::JoinInvoice(None), &nonce).unwrap();
+    let agent = build_agent(&network, Some(xonly_pair.pubkey))?;
+    match agent.post(&full_url)
+        .set(&HttpHeader::Signature.to_string(), &signature)
+        .set(&HttpHeader::Pubkey.to_string(), &xonly_pair.pubkey.to_string())
+        .set(&HttpHeader::Nonce.to_string(), &nonce)
+        .call(){
+            Ok(response)=>JoinInvoice::structify(&read_body(response)?),
+            Err(e)=>{
+                Err(S5Error::from_ureq(e))
+            }
+        }
+}
+
+/// Current state of an invoice I requested.
+pub fn invoice_status(host: String, network: NetworkConfig, xonly_pair: XOnlyPair, invoice_id: String)->Result<JoinInvoice, S5Error>{
+    let full_url = host + &APIEndPoint::JoinInvoice(Some(invoice_id.clone())).to_string();
+    let agent = build_agent(&network, Some(xonly_pair.pubkey))?;
+    match call_with_retry(network.retry, ||{
+        let nonce = nonce();
+        let signature = sign_request(xonly_pair.clone(), HttpMethod::Get, APIEndPoint::JoinInvoice(Some(invoice_id.clone())), &nonce).unwrap();
+        agent.get(&full_url)
+            .set(&HttpHeader::Signature.to_string(), &signature)
+            .set(&HttpHeader::Pubkey.to_string(), &xonly_pair.pubkey.to_string())
+            .set(&HttpHeader::Nonce.to_string(), &nonce)
+        }){
+            Ok(response)=>JoinInvoice::structify(&read_body(response)?),
+            Err(e)=>{
+                Err(S5Error::from_ureq(e))
+            }
+        }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct IdentityRegisterRequest{
     username: String
@@ -134,26 +245,12 @@ impl IdentityRegisterRequest{
     }
 }
 
-pub fn register(host: String, socks5: Option<u32>, xonly_pair: XOnlyPair, invite_code: String, username: String)->Result<InvitationDetail, S5Error>{
+pub fn register(host: String, network: NetworkConfig, xonly_pair: XOnlyPair, invite_code: String, username: String)->Result<InvitationDetail, S5Error>{
     let full_url = host + &APIEndPoint::Identity.to_string();
     let nonce = nonce();
     let signature = sign_request(xonly_pair.clone(), HttpMethod::Post, APIEndPoint::Identity, &nonce).unwrap();
-    let body = IdentityRegisterRequest::new(&username.to_lowercase());
-    let proxy = if socks5.is_some(){ 
-        Some(Proxy::new(&format!("socks5://localhost:{}",socks5.unwrap().to_string())).unwrap())
-    }
-    else{
-        None
-    };
-    let agent = if proxy.is_some(){
-        AgentBuilder::new()
-        .proxy(proxy.unwrap())
-        .build()
-    }
-    else{
-        AgentBuilder::new()
-        .build()
-    };
+    let body = IdentityRegisterRequest::new(&username::validate(&username)?);
+    let agent = build_agent(&network, Some(xonly_pair.pubkey))?;
     match agent.post(&full_url)
         .set(&HttpHeader::InviteCode.to_string(), &invite_code)
         .set(&HttpHeader::Signature.to_string(), &signature)
@@ -161,7 +258,7 @@ pub fn register(host: String, socks5: Option<u32>, xonly_pair: XOnlyPair, invite
         .set(&HttpHeader::Nonce.to_string(), &nonce)
         .send_json(body){
             Ok(response)=>  Ok(
-                match InvitationDetail::structify(&response.into_string().unwrap()){
+                match InvitationDetail::structify(&read_body(response)?){
                     Ok(result)=>result,
                     Err(e) =>{
                         return Err(e);
@@ -175,32 +272,19 @@ pub fn register(host: String, socks5: Option<u32>, xonly_pair: XOnlyPair, invite
 }
 
 
-pub fn get_all(host: String,socks5: Option<u32>, xonly_pair: XOnlyPair)->Result<Members, S5Error>{
+pub fn get_all(host: String,network: NetworkConfig, xonly_pair: XOnlyPair)->Result<Members, S5Error>{
     let full_url = host + &APIEndPoint::AllIdentities.to_string();
-    let nonce = nonce();
-    let signature = sign_request(xonly_pair.clone(), HttpMethod::Get, APIEndPoint::AllIdentities, &nonce).unwrap();
-    let proxy = if socks5.is_some(){ 
-        Some(Proxy::new(&format!("socks5://localhost:{}",socks5.unwrap().to_string())).unwrap())
-    }
-    else{
-        None
-    };
-    let agent = if proxy.is_some(){
-        AgentBuilder::new()
-        .proxy(proxy.unwrap())
-        .build()
-    }
-    else{
-        AgentBuilder::new()
-        .build()
-    };
-    match agent.get(&full_url)
-        .set(&HttpHeader::Signature.to_string(), &signature)
-        .set(&HttpHeader::Pubkey.to_string(), &xonly_pair.pubkey.to_string())
-        .set(&HttpHeader::Nonce.to_string(), &nonce)
-        .call(){
+    let agent = build_agent(&network, Some(xonly_pair.pubkey))?;
+    match call_with_retry(network.retry, ||{
+        let nonce = nonce();
+        let signature = sign_request(xonly_pair.clone(), HttpMethod::Get, APIEndPoint::AllIdentities, &nonce).unwrap();
+        agent.get(&full_url)
+            .set(&HttpHeader::Signature.to_string(), &signature)
+            .set(&HttpHeader::Pubkey.to_string(), &xonly_pair.pubkey.to_string())
+            .set(&HttpHeader::Nonce.to_string(), &nonce)
+        }){
             Ok(response)=>
-                match Members::structify(&response.into_string().unwrap())
+                match Members::structify(&read_body(response)?)
                 {
                     Ok(result)=>Ok(result),
                     Err(e) =>{
@@ -213,31 +297,36 @@ pub fn get_all(host: String,socks5: Option<u32>, xonly_pair: XOnlyPair)->Result<
         }
 }
 
-pub fn delete(host: String, socks5: Option<u32>, xonly_pair: XOnlyPair)->Result<(), S5Error>{
+/// Members who joined or left after `genesis_filter`, oldest first.
+pub fn get_changes(host: String, network: NetworkConfig, xonly_pair: XOnlyPair, genesis_filter: u64)->Result<MemberChanges, S5Error>{
+    let full_url = host + &APIEndPoint::IdentityChanges.to_string() + "?genesis_filter=" + &genesis_filter.to_string();
+    let agent = build_agent(&network, Some(xonly_pair.pubkey))?;
+    match call_with_retry(network.retry, ||{
+        let nonce = nonce();
+        let signature = sign_request(xonly_pair.clone(), HttpMethod::Get, APIEndPoint::IdentityChanges, &nonce).unwrap();
+        agent.get(&full_url)
+            .set(&HttpHeader::Signature.to_string(), &signature)
+            .set(&HttpHeader::Pubkey.to_string(), &xonly_pair.pubkey.to_string())
+            .set(&HttpHeader::Nonce.to_string(), &nonce)
+        }){
+            Ok(response)=>MemberChanges::structify(&read_body(response)?),
+            Err(e)=>{
+                Err(S5Error::from_ureq(e))
+            }
+        }
+}
+
+pub fn delete(host: String, network: NetworkConfig, xonly_pair: XOnlyPair)->Result<(), S5Error>{
     let full_url = host + &APIEndPoint::Identity.to_string();
     let nonce = nonce();
     let signature = sign_request(xonly_pair.clone(), HttpMethod::Delete, APIEndPoint::Identity, &nonce).unwrap();
-    let proxy = if socks5.is_some(){ 
-        Some(Proxy::new(&format!("socks5://localhost:{}",socks5.unwrap().to_string())).unwrap())
-    }
-    else{
-        None
-    };
-    let agent = if proxy.is_some(){
-        AgentBuilder::new()
-        .proxy(proxy.unwrap())
-        .build()
-    }
-    else{
-        AgentBuilder::new()
-        .build()
-    };
+    let agent = build_agent(&network, Some(xonly_pair.pubkey))?;
     match agent.delete(&full_url)
         .set(&HttpHeader::Signature.to_string(), &signature)
         .set(&HttpHeader::Pubkey.to_string(), &xonly_pair.pubkey.to_string())
         .set(&HttpHeader::Nonce.to_string(), &nonce)
         .call(){
-            Ok(response)=> match ServerStatusResponse::structify(&response.into_string().unwrap())
+            Ok(response)=> match ServerStatusResponse::structify(&read_body(response)?)
                 {
                     Ok(result)=>{
                         if result.status {
@@ -260,30 +349,31 @@ pub fn delete(host: String, socks5: Option<u32>, xonly_pair: XOnlyPair)->Result<
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::network::mock::{MockServer, ADMIN_SECRET};
     use crate::key::ec;
     use crate::key::seed;
     use bitcoin::network::constants::Network;
 
     #[test]
-    #[ignore]
     fn test_identities_dto(){
-        let url = "http://localhost:3021".to_string();
+        let server = MockServer::start();
+        let url = server.url();
         // ADMIN INVITE
-        let admin_invite_code = "098f6bcd4621d373cade4e832627b4f6".to_string();
-        let client_invite_code = admin_invite(url.clone(),None, admin_invite_code,InvitePermission::Standard).unwrap();
+        let admin_invite_code = ADMIN_SECRET.to_string();
+        let client_invite_code = admin_invite(url.clone(), NetworkConfig::default(), admin_invite_code,InvitePermission::Standard).unwrap();
         assert_eq!(client_invite_code.invite_code.len() , 32);
         // REGISTER USER
         let seed = seed::MasterKeySeed::generate(24, "", Network::Bitcoin).unwrap();
         let keys = XOnlyPair::from_keypair(ec::keypair_from_xprv_str(&seed.xprv.to_string()).unwrap());
         let nonce = nonce();
         let username = "ishi".to_string() + &nonce[0..5].to_lowercase();
-        register(url.clone(),None,  keys.clone(), client_invite_code.invite_code, username).unwrap();
+        register(url.clone(), NetworkConfig::default(), keys.clone(), client_invite_code.invite_code, username).unwrap();
         // GET ALL USERS
-        let members = get_all(url.clone(),None,  keys.clone()).unwrap();
+        let members = get_all(url.clone(), NetworkConfig::default(), keys.clone()).unwrap();
         // println!("{:#?}",members);
         let user_count = members.identities.len();
         assert!(user_count>0);
         // leave the network
-        delete(url.clone(),None,  keys).unwrap();
+        delete(url.clone(), NetworkConfig::default(), keys).unwrap();
     }
 }
\ No newline at end of file
@@ -1,2 +1,6 @@
+pub mod contact;
+pub mod directory;
 pub mod dto;
 pub mod model;
+pub mod profile;
+pub mod username;
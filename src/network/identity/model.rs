@@ -77,6 +77,9 @@ pub struct InvitationDetail{
     pub status: String,
     pub kind: String,
     pub count: u128,
+    /// Set by `Client::join`: members whose usernames look like the one just registered.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub confusable_with: Vec<String>,
 }
 
 impl InvitationDetail{
@@ -102,6 +105,36 @@ impl InvitationDetail{
     }
 }
 
+/// Invites created by the caller (or by ADMIN), newest last.
+/// `status` is PENDING, CLAIMED or REVOKED. For privileged invites `count` is how many invites remain.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Invitations{
+    pub invitations: Vec<InvitationDetail>
+}
+
+impl Invitations{
+    pub fn structify(stringified: &str) -> Result<Invitations, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => {
+                Err(S5Error::new(ErrorKind::Internal, "Error structifying Invitations"))
+            }
+        }
+    }
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+          Ok(result) => result,
+          Err(_) => {
+            return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+              .unwrap()
+              .into_raw()
+          }
+        };
+    
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MemberIdentity{
     pub username: String,
@@ -136,6 +169,75 @@ impl Members{
 }
 
 
+/// A payment request for joining a PUBLIC server. `amount` is in sats and `expiry` is a millisecond timestamp.
+/// `status` is UNPAID, PAID or EXPIRED. Once PAID, `invite_code` is the credential that completes the join.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JoinInvoice{
+    pub invoice_id: String,
+    pub invoice: String,
+    pub amount: u64,
+    pub expiry: u64,
+    pub status: String,
+    pub invite_code: Option<String>
+}
+
+impl JoinInvoice{
+    pub fn structify(stringified: &str) -> Result<JoinInvoice, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => {
+                Err(S5Error::new(ErrorKind::Internal, "Error structifying JoinInvoice"))
+            }
+        }
+    }
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+          Ok(result) => result,
+          Err(_) => {
+            return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+              .unwrap()
+              .into_raw()
+          }
+        };
+    
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+/// Result of checking a username against the member list, see `username::check`.
+/// `username` is the normalized form that join will register. It is not `available` if taken,
+/// or if it looks like an existing member's name; those members are listed in `confusable_with`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UsernameCheck{
+    pub username: String,
+    pub available: bool,
+    pub taken: bool,
+    pub confusable_with: Vec<String>
+}
+
+impl UsernameCheck{
+    pub fn structify(stringified: &str) -> Result<UsernameCheck, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => {
+                Err(S5Error::new(ErrorKind::Internal, "Error structifying UsernameCheck"))
+            }
+        }
+    }
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+          Ok(result) => result,
+          Err(_) => {
+            return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+              .unwrap()
+              .into_raw()
+          }
+        };
+    
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UserIdentity{
     pub social_root: ExtendedPrivKey,
@@ -185,5 +287,11 @@ impl UserIdentity {
         let enc_source = child::hex(self.social_root.to_string(), index).unwrap();
         encryption::key_hash256(&enc_source)
     }
+    /// Key for data kept on the device, e.g. the outbox. Derived with the bip85 wif application,
+    /// so it never matches a post encryption key.
+    pub fn derive_storage_key(&self)->String{
+        let storage_source = child::secret_key(self.social_root.to_string(), 0).unwrap();
+        encryption::key_hash256(&storage_source)
+    }
 }
 
@@ -0,0 +1,247 @@
+//! Member profiles, signed with the social key so they can be checked wherever they turn up:
+//! in a profile post, or in a card shared outside the server.
+use crate::key::ec::{schnorr_verify, XOnlyPair};
+use crate::key::encryption::key_hash256;
+use crate::util::e::{ErrorKind, S5Error};
+use bitcoin::secp256k1::schnorr::Signature;
+use bitcoin::secp256k1::XOnlyPublicKey;
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const MAX_DISPLAY_NAME_LENGTH: usize = 64;
+pub const MAX_BIO_LENGTH: usize = 280;
+/// Decoded avatar bytes. Enough for a small thumbnail.
+pub const MAX_AVATAR_BYTES: usize = 32 * 1024;
+pub const MAX_PROFILE_LINKS: usize = 8;
+/// Cards are this prefix followed by the base64 of the signed profile json.
+pub const PROFILE_CARD_PREFIX: &str = "cypherpost:profile:";
+
+/// An account elsewhere, e.g. {"kind": "nostr", "value": "npub1..."}.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExternalIdentity {
+    pub kind: String,
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub display_name: String,
+    /// Base64 encoded image.
+    #[serde(default)]
+    pub avatar: Option<String>,
+    #[serde(default)]
+    pub bio: String,
+    /// e.g. "lightning", "onchain", "sepa"
+    #[serde(default)]
+    pub payment_methods: Vec<String>,
+    #[serde(default)]
+    pub identities: Vec<ExternalIdentity>,
+}
+
+impl Profile {
+    pub fn structify(stringified: &str) -> Result<Profile, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Input, "Error structifying Profile")),
+        }
+    }
+    pub fn check(&self) -> Result<(), S5Error> {
+        if self.display_name.trim().is_empty() || self.display_name.chars().count() > MAX_DISPLAY_NAME_LENGTH {
+            return Err(S5Error::new(
+                ErrorKind::Input,
+                &format!("Display name must be 1 to {} characters.", MAX_DISPLAY_NAME_LENGTH),
+            ));
+        }
+        if self.bio.chars().count() > MAX_BIO_LENGTH {
+            return Err(S5Error::new(
+                ErrorKind::Input,
+                &format!("Bio must be at most {} characters.", MAX_BIO_LENGTH),
+            ));
+        }
+        if let Some(avatar) = &self.avatar {
+            match base64::decode(avatar) {
+                Ok(bytes) if bytes.len() <= MAX_AVATAR_BYTES => {}
+                Ok(_) => {
+                    return Err(S5Error::new(
+                        ErrorKind::Input,
+                        &format!("Avatar must be at most {} bytes.", MAX_AVATAR_BYTES),
+                    ))
+                }
+                Err(_) => return Err(S5Error::new(ErrorKind::Input, "Avatar must be base64 encoded.")),
+            }
+        }
+        if self.payment_methods.len() > MAX_PROFILE_LINKS || self.identities.len() > MAX_PROFILE_LINKS {
+            return Err(S5Error::new(
+                ErrorKind::Input,
+                &format!("At most {} payment methods and {} identities.", MAX_PROFILE_LINKS, MAX_PROFILE_LINKS),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A profile with the owner's schnorr signature over "pubkey:updated:sha256(profile json)".
+/// `updated` is a millisecond timestamp; the newest valid profile for a pubkey wins.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SignedProfile {
+    pub pubkey: XOnlyPublicKey,
+    pub updated: u64,
+    pub profile: Profile,
+    pub signature: Signature,
+}
+
+impl SignedProfile {
+    pub fn new(profile: Profile, owner: &XOnlyPair) -> Result<SignedProfile, S5Error> {
+        profile.check()?;
+        let updated = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        let message = SignedProfile::message(&owner.pubkey, updated, &profile);
+        Ok(SignedProfile {
+            pubkey: owner.pubkey,
+            updated,
+            signature: owner.schnorr_sign(&message)?,
+            profile,
+        })
+    }
+    fn message(pubkey: &XOnlyPublicKey, updated: u64, profile: &Profile) -> String {
+        let profile = serde_json::to_string(profile).unwrap();
+        format!("{}:{}:{}", pubkey, updated, key_hash256(&profile))
+    }
+    pub fn verify(&self) -> Result<(), S5Error> {
+        self.profile.check()?;
+        schnorr_verify(
+            self.signature,
+            &SignedProfile::message(&self.pubkey, self.updated, &self.profile),
+            self.pubkey,
+        )
+    }
+
+    pub fn stringify(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+    /// Parses and verifies.
+    pub fn structify(stringified: &str) -> Result<SignedProfile, S5Error> {
+        let profile: SignedProfile = match serde_json::from_str(stringified) {
+            Ok(result) => result,
+            Err(_) => return Err(S5Error::new(ErrorKind::Input, "Error structifying SignedProfile")),
+        };
+        profile.verify()?;
+        Ok(profile)
+    }
+    pub fn c_stringify(&self) -> *mut c_char {
+        CString::new(self.stringify()).unwrap().into_raw()
+    }
+
+    /// A single line to share as text or a QR code.
+    pub fn to_card(&self) -> String {
+        PROFILE_CARD_PREFIX.to_string() + &base64::encode(self.stringify())
+    }
+    /// Parses and verifies a card made with `to_card`.
+    pub fn from_card(card: &str) -> Result<SignedProfile, S5Error> {
+        let encoded = match card.trim().strip_prefix(PROFILE_CARD_PREFIX) {
+            Some(encoded) => encoded,
+            None => return Err(S5Error::new(ErrorKind::Input, "Not a profile card.")),
+        };
+        let decoded = match base64::decode(encoded).ok().and_then(|bytes| String::from_utf8(bytes).ok()) {
+            Some(decoded) => decoded,
+            None => return Err(S5Error::new(ErrorKind::Input, "Profile card is not valid base64.")),
+        };
+        SignedProfile::structify(&decoded)
+    }
+}
+
+/// Latest verified profile per member.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Profiles {
+    pub profiles: Vec<SignedProfile>,
+}
+
+impl Profiles {
+    /// Keep `profile` if it is newer than what is held for its pubkey.
+    pub fn insert(&mut self, profile: SignedProfile) {
+        match self.profiles.iter_mut().find(|existing| existing.pubkey == profile.pubkey) {
+            Some(existing) if existing.updated < profile.updated => *existing = profile,
+            Some(_) => {}
+            None => self.profiles.push(profile),
+        }
+    }
+    pub fn get(&self, pubkey: &XOnlyPublicKey) -> Option<&SignedProfile> {
+        self.profiles.iter().find(|profile| profile.pubkey == *pubkey)
+    }
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+/// Returned by `export_profile_card`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProfileCard {
+    pub card: String,
+}
+
+impl ProfileCard {
+    pub fn c_stringify(&self) -> *mut c_char {
+        CString::new(serde_json::to_string(self).unwrap()).unwrap().into_raw()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing::keys;
+
+    #[test]
+    fn test_profile_signing() {
+        let ishi = keys();
+        let profile = Profile {
+            display_name: "Ishi".to_string(),
+            avatar: Some(base64::encode([0u8; 64])),
+            bio: "bitcoin only".to_string(),
+            payment_methods: vec!["lightning".to_string()],
+            identities: vec![ExternalIdentity { kind: "nostr".to_string(), value: "npub1xyz".to_string() }],
+        };
+        let signed = SignedProfile::new(profile.clone(), &ishi).unwrap();
+        signed.verify().unwrap();
+
+        let card = signed.to_card();
+        assert!(card.starts_with(PROFILE_CARD_PREFIX));
+        let imported = SignedProfile::from_card(&card).unwrap();
+        assert_eq!(imported.profile, profile);
+        assert_eq!(imported.pubkey, ishi.pubkey);
+        assert!(SignedProfile::from_card("cypherpost:profile:@@").is_err());
+        assert!(SignedProfile::from_card(&base64::encode(signed.stringify())).is_err());
+
+        let mut forged = signed.clone();
+        forged.profile.display_name = "Sushi".to_string();
+        assert!(forged.verify().is_err());
+        assert!(SignedProfile::from_card(&forged.to_card()).is_err());
+        let mut stolen = signed.clone();
+        stolen.pubkey = keys().pubkey;
+        assert!(stolen.verify().is_err());
+
+        let mut too_big = profile.clone();
+        too_big.avatar = Some(base64::encode(vec![0u8; MAX_AVATAR_BYTES + 1]));
+        assert!(SignedProfile::new(too_big, &ishi).is_err());
+        let mut blank = profile;
+        blank.display_name = " ".to_string();
+        assert!(SignedProfile::new(blank, &ishi).is_err());
+
+        let mut profiles = Profiles::default();
+        let mut older = signed.clone();
+        older.updated -= 1;
+        profiles.insert(signed.clone());
+        profiles.insert(older);
+        assert_eq!(profiles.profiles.len(), 1);
+        assert_eq!(profiles.get(&ishi.pubkey).unwrap().updated, signed.updated);
+    }
+}
@@ -0,0 +1,145 @@
+//! Username policy, shared by join, the availability check and the mock server.
+use crate::network::identity::model::{MemberIdentity, UsernameCheck};
+use crate::util::e::{ErrorKind, S5Error};
+use unicode_normalization::UnicodeNormalization;
+
+pub const MIN_USERNAME_LENGTH: usize = 3;
+pub const MAX_USERNAME_LENGTH: usize = 24;
+
+/// NFKC, trimmed and lowercased. Fullwidth and other compatibility forms fold to ASCII here.
+pub fn normalize(username: &str) -> String {
+    username.nfkc().collect::<String>().trim().to_lowercase()
+}
+
+/// The normalized username, or why it is not allowed.
+/// After normalization a username is 3-24 of a-z, 0-9 and _, starting with a letter.
+pub fn validate(username: &str) -> Result<String, S5Error> {
+    let normalized = normalize(username);
+    let length = normalized.chars().count();
+    if !(MIN_USERNAME_LENGTH..=MAX_USERNAME_LENGTH).contains(&length) {
+        return Err(S5Error::new(
+            ErrorKind::Input,
+            &format!(
+                "Username must be between {} and {} characters.",
+                MIN_USERNAME_LENGTH, MAX_USERNAME_LENGTH
+            ),
+        ));
+    }
+    if let Some(bad) = normalized
+        .chars()
+        .find(|c| !(c.is_ascii_lowercase() || c.is_ascii_digit() || *c == '_'))
+    {
+        return Err(S5Error::new(
+            ErrorKind::Input,
+            &format!("Username may only contain a-z, 0-9 and _. Found '{}'.", bad),
+        ));
+    }
+    if !normalized.starts_with(|c: char| c.is_ascii_lowercase()) {
+        return Err(S5Error::new(ErrorKind::Input, "Username must start with a letter."));
+    }
+    Ok(normalized)
+}
+
+// Latin lookalikes from the UTS #39 confusables table, limited to what can appear in usernames.
+fn homoglyph(c: char) -> char {
+    match c {
+        '0' | 'о' | 'ο' | 'օ' => 'o',
+        '1' | 'i' | '!' | '|' | 'ı' | 'і' | 'ӏ' | 'ι' | 'ɩ' => 'l',
+        'з' => 'e',
+        '5' | 'ѕ' => 's',
+        'а' | 'α' | 'ɑ' => 'a',
+        'с' | 'ϲ' => 'c',
+        'ԁ' => 'd',
+        'е' | 'ε' => 'e',
+        'һ' => 'h',
+        'ј' | 'ϳ' => 'j',
+        'κ' | 'к' => 'k',
+        'р' | 'ρ' => 'p',
+        'ԛ' => 'q',
+        'τ' | 'т' => 't',
+        'υ' | 'ս' => 'u',
+        'ν' | 'ѵ' => 'v',
+        'ԝ' | 'ѡ' => 'w',
+        'х' | 'χ' => 'x',
+        'у' | 'γ' => 'y',
+        other => other,
+    }
+}
+
+/// Usernames that look alike share a skeleton: "ishi", "ish1", "ish_i" and "іshі" (cyrillic) all collide.
+pub fn skeleton(username: &str) -> String {
+    let folded: String = normalize(username)
+        .chars()
+        .filter(|c| !matches!(c, '_' | '.' | '-'))
+        .map(homoglyph)
+        .collect();
+    folded.replace("rn", "m").replace("vv", "w").replace("cl", "d")
+}
+
+/// Members whose username looks like `username` without being the same name.
+pub fn confusable_members(username: &str, members: &[MemberIdentity]) -> Vec<MemberIdentity> {
+    let normalized = normalize(username);
+    let skeleton = skeleton(username);
+    members
+        .iter()
+        .filter(|member| {
+            let existing = normalize(&member.username);
+            existing != normalized && self::skeleton(&existing) == skeleton
+        })
+        .cloned()
+        .collect()
+}
+
+/// Check `username` against the policy and the current `members`.
+pub fn check(username: &str, members: &[MemberIdentity]) -> Result<UsernameCheck, S5Error> {
+    let username = validate(username)?;
+    let taken = members.iter().any(|member| normalize(&member.username) == username);
+    let confusable_with: Vec<String> =
+        confusable_members(&username, members).into_iter().map(|member| member.username).collect();
+    Ok(UsernameCheck { available: !taken && confusable_with.is_empty(), username, taken, confusable_with })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+
+    #[test]
+    fn test_username_policy() {
+        assert_eq!(validate("  Ishi_5 ").unwrap(), "ishi_5");
+        // fullwidth folds to ascii under NFKC
+        assert_eq!(validate("ｉｓｈｉ").unwrap(), "ishi");
+        assert!(validate("is").is_err());
+        assert!(validate(&"a".repeat(25)).is_err());
+        assert!(validate("5ishi").is_err());
+        assert!(validate("ishi-5").is_err());
+        assert!(validate("іshі").is_err()); // cyrillic i
+
+        assert_eq!(skeleton("ishi"), skeleton("ISH1"));
+        assert_eq!(skeleton("ishi"), skeleton("ish_i"));
+        assert_eq!(skeleton("ishi"), skeleton("іshі"));
+        assert_eq!(skeleton("modern"), skeleton("rnodem"));
+        assert_ne!(skeleton("ishi"), skeleton("sushi"));
+        assert_ne!(skeleton("b3n"), skeleton("ben"));
+
+        let pubkey = testing::pubkey();
+        let members = vec![
+            MemberIdentity { username: "ishi".to_string(), pubkey },
+            MemberIdentity { username: "sushi".to_string(), pubkey },
+        ];
+        let flagged = confusable_members("ish1", &members);
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].username, "ishi");
+        // the same name is taken, not confusable
+        assert!(confusable_members("Ishi", &members).is_empty());
+
+        let result = check("Ishi", &members).unwrap();
+        assert!(result.taken && !result.available);
+        assert!(result.confusable_with.is_empty());
+        let result = check("ish1", &members).unwrap();
+        assert!(!result.taken && !result.available);
+        assert_eq!(result.confusable_with, ["ishi"]);
+        assert!(check("mallory", &members).unwrap().available);
+        assert!(check("is", &members).is_err());
+    }
+}
@@ -0,0 +1,720 @@
+//! In-process cypherpost server for tests.
+//! Implements the /api/v2 identity, post and announcement endpoints and /api/v3/notifications,
+//! checking request signatures and nonces the same way the real server does.
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bitcoin::secp256k1::XOnlyPublicKey;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tiny_http::{Header, Response, Server};
+
+use crate::key::ec::{schnorr_verify, signature_from_str, XOnlyPair};
+use crate::key::encryption::{key_hash256, nonce};
+use crate::key::seed::MasterKeySeed;
+use crate::network::badge::model::{Badge, BadgeDetails};
+use crate::network::handler::{AnnouncementType, HttpHeader, ServerErrorResponse};
+use crate::network::identity::model::{InvitationDetail, JoinInvoice, MemberIdentity};
+use crate::network::identity::directory::{MemberChange, MemberChangeKind};
+use crate::network::identity::username;
+use crate::network::post::dto::ServerPostModel;
+use crate::network::post::model::DecryptionKey;
+
+/// Same secret the old live-server tests used.
+pub const ADMIN_SECRET: &str = "098f6bcd4621d373cade4e832627b4f6";
+
+/// A server on a random local port. Shut down when dropped.
+pub struct MockServer {
+    url: String,
+    server: Arc<Server>,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockServer {
+    /// A PRIVATE server, joined with invite codes.
+    pub fn start() -> Self {
+        MockServer::serve(State::new(None))
+    }
+    /// A PUBLIC server that charges `price` sats to join, through fake invoices settled with `pay`.
+    pub fn start_public(price: u64) -> Self {
+        MockServer::serve(State::new(Some(price)))
+    }
+    fn serve(state: State) -> Self {
+        let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+        let url = format!("http://{}", server.server_addr());
+        let state = Arc::new(Mutex::new(state));
+        let handle_state = state.clone();
+        let incoming = server.clone();
+        thread::spawn(move || {
+            for mut request in incoming.incoming_requests() {
+                let mut body = String::new();
+                let _ = request.as_reader().read_to_string(&mut body);
+                let mock_request = MockRequest::new(&request, body);
+                let (code, reply) = match handle(&mut handle_state.lock().unwrap(), &mock_request) {
+                    Ok(value) => (200, value.to_string()),
+                    Err((code, message)) => {
+                        let error = ServerErrorResponse { code: code as i32, message };
+                        (code, serde_json::to_string(&error).unwrap())
+                    }
+                };
+                let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+                let response = Response::from_string(reply)
+                    .with_status_code(code)
+                    .with_header(content_type);
+                let _ = request.respond(response);
+            }
+        });
+        MockServer { url, server, state }
+    }
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+    /// Settle an invoice as if the payment arrived. The payer gets a standard invite code.
+    pub fn pay(&self, invoice_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        let invite_code = state.new_invite("STANDARD", 0, "PAYMENT");
+        let invoice = state.invoice(invoice_id).unwrap();
+        invoice.status = "PAID".to_string();
+        invoice.invite_code = Some(invite_code);
+    }
+    pub fn expire(&self, invoice_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.invoice(invoice_id).unwrap().expiry = 0;
+    }
+    /// Accept only `count` more posts, then fail the rest as if the server went down.
+    pub fn fail_posts_after(&self, count: usize) {
+        self.state.lock().unwrap().post_budget = Some(count);
+    }
+    /// Accept only `count` more key uploads, then fail the rest, leaving their posts unreadable.
+    pub fn fail_keys_after(&self, count: usize) {
+        self.state.lock().unwrap().keys_budget = Some(count);
+    }
+    /// Undo `fail_posts_after` and `fail_keys_after`.
+    pub fn restore(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.post_budget = None;
+        state.keys_budget = None;
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+    }
+}
+
+type Reply = Result<Value, (u16, String)>;
+
+struct MockRequest {
+    method: String,
+    path: String,
+    query: BTreeMap<String, String>,
+    headers: BTreeMap<String, String>,
+    body: String,
+}
+
+impl MockRequest {
+    fn new(request: &tiny_http::Request, body: String) -> Self {
+        let (path, query) = match request.url().split_once('?') {
+            Some((path, query)) => (path.to_string(), query.to_string()),
+            None => (request.url().to_string(), "".to_string()),
+        };
+        MockRequest {
+            method: request.method().to_string().to_uppercase(),
+            path,
+            query: query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            headers: request
+                .headers()
+                .iter()
+                .map(|header| (header.field.as_str().as_str().to_lowercase(), header.value.as_str().to_string()))
+                .collect(),
+            body,
+        }
+    }
+    fn header(&self, header: HttpHeader) -> Result<String, (u16, String)> {
+        match self.headers.get(&header.to_string()) {
+            Some(value) => Ok(value.clone()),
+            None => Err((400, format!("Missing header {}", header.to_string()))),
+        }
+    }
+    fn json<T: for<'de> Deserialize<'de>>(&self) -> Result<T, (u16, String)> {
+        serde_json::from_str(&self.body).map_err(|e| (400, format!("Bad request body: {}", e)))
+    }
+    fn genesis_filter(&self) -> u64 {
+        self.query
+            .get("genesis_filter")
+            .and_then(|filter| filter.parse::<u64>().ok())
+            .unwrap_or(0)
+    }
+}
+
+struct StoredPost {
+    id: String,
+    genesis: u64,
+    expiry: u64,
+    owner: XOnlyPublicKey,
+    cypher_json: String,
+    derivation_index: u32,
+    // receiver => decryption key
+    keys: BTreeMap<XOnlyPublicKey, String>,
+}
+
+impl StoredPost {
+    fn to_model(&self, viewer: XOnlyPublicKey) -> ServerPostModel {
+        ServerPostModel {
+            id: self.id.clone(),
+            genesis: self.genesis,
+            expiry: self.expiry,
+            owner: self.owner,
+            cypher_json: self.cypher_json.clone(),
+            derivation_index: self.derivation_index,
+            decryption_key: self.keys.get(&viewer).cloned(),
+        }
+    }
+}
+
+struct StoredInvoice {
+    requested_by: XOnlyPublicKey,
+    invoice: JoinInvoice,
+}
+
+struct State {
+    server_keys: XOnlyPair,
+    // join price in sats, for PUBLIC servers
+    price: Option<u64>,
+    invoices: Vec<StoredInvoice>,
+    invites: Vec<InvitationDetail>,
+    members: Vec<MemberIdentity>,
+    membership_log: Vec<MemberChange>,
+    posts: Vec<StoredPost>,
+    // (owner, idempotency key) => post id
+    idempotent_posts: BTreeMap<(XOnlyPublicKey, String), String>,
+    badges: Vec<Badge>,
+    seen_nonces: BTreeSet<String>,
+    last_genesis: u64,
+    // posts left before uploads fail, see fail_posts_after
+    post_budget: Option<usize>,
+    // key uploads left before they fail, see fail_keys_after
+    keys_budget: Option<usize>,
+}
+
+impl State {
+    fn new(price: Option<u64>) -> Self {
+        let seed = MasterKeySeed::generate(12, "", bitcoin::Network::Bitcoin).unwrap();
+        State {
+            server_keys: XOnlyPair::from_xprv(seed.xprv),
+            price,
+            invoices: vec![],
+            invites: vec![],
+            members: vec![],
+            membership_log: vec![],
+            posts: vec![],
+            idempotent_posts: BTreeMap::new(),
+            badges: vec![],
+            seen_nonces: BTreeSet::new(),
+            last_genesis: 0,
+            post_budget: None,
+            keys_budget: None,
+        }
+    }
+    /// Millisecond timestamps, strictly increasing so that posts keep their order.
+    fn genesis(&mut self) -> u64 {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        self.last_genesis = std::cmp::max(now, self.last_genesis + 1);
+        self.last_genesis
+    }
+    fn random_id(length: usize) -> String {
+        key_hash256(&nonce())[0..length].to_string()
+    }
+
+    /// Checks the schnorr signature over "METHOD /path nonce" and that the nonce is fresh.
+    fn authenticate(&mut self, request: &MockRequest) -> Result<XOnlyPublicKey, (u16, String)> {
+        let pubkey = XOnlyPublicKey::from_str(&request.header(HttpHeader::Pubkey)?)
+            .map_err(|_| (401, "Bad pubkey".to_string()))?;
+        let signature = signature_from_str(&request.header(HttpHeader::Signature)?)
+            .map_err(|_| (401, "Bad signature".to_string()))?;
+        let nonce = request.header(HttpHeader::Nonce)?;
+        let message = format!("{} {} {}", request.method, request.path, nonce);
+        if schnorr_verify(signature, &message, pubkey).is_err() {
+            return Err((401, "Signature verification failed".to_string()));
+        }
+        if !self.seen_nonces.insert(format!("{}:{}", pubkey, nonce)) {
+            return Err((401, "Nonce already used".to_string()));
+        }
+        Ok(pubkey)
+    }
+    fn authenticate_member(&mut self, request: &MockRequest) -> Result<XOnlyPublicKey, (u16, String)> {
+        let pubkey = self.authenticate(request)?;
+        if self.members.iter().any(|member| member.pubkey == pubkey) {
+            Ok(pubkey)
+        } else {
+            Err((401, "Not a member of this server".to_string()))
+        }
+    }
+    fn new_invite(&mut self, kind: &str, count: u128, created_by: &str) -> String {
+        let invite_code = State::random_id(32);
+        let genesis = self.genesis() as u128;
+        self.invites.push(InvitationDetail {
+            genesis,
+            invite_code: invite_code.clone(),
+            claimed_by: "".to_string(),
+            created_by: created_by.to_string(),
+            status: "PENDING".to_string(),
+            kind: kind.to_string(),
+            count,
+            confusable_with: vec![],
+        });
+        invite_code
+    }
+    fn invoice(&mut self, invoice_id: &str) -> Option<&mut JoinInvoice> {
+        self.invoices
+            .iter_mut()
+            .map(|stored| &mut stored.invoice)
+            .find(|invoice| invoice.invoice_id == invoice_id)
+    }
+    fn revoke_invite(&mut self, invite_code: &str, created_by: &str) -> Result<(), (u16, String)> {
+        let invite = self
+            .invites
+            .iter_mut()
+            .find(|invite| invite.invite_code == invite_code && invite.created_by == created_by)
+            .ok_or((404, "No invite with this code was created by you".to_string()))?;
+        if invite.status != "PENDING" {
+            return Err((409, format!("Invite is already {}", invite.status)));
+        }
+        invite.status = "REVOKED".to_string();
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RevokeInviteBody {
+    invite_code: String,
+}
+#[derive(Serialize, Deserialize)]
+struct RegisterBody {
+    username: String,
+}
+#[derive(Serialize, Deserialize)]
+struct PostBody {
+    expiry: u64,
+    derivation_index: u32,
+    cypher_json: String,
+}
+#[derive(Serialize, Deserialize)]
+struct KeysBody {
+    post_id: String,
+    decryption_keys: Vec<DecryptionKey>,
+}
+#[derive(Serialize, Deserialize)]
+struct AnnounceBody {
+    recipient: String,
+    nonce: String,
+    signature: String,
+    #[serde(flatten)]
+    details: BadgeDetails,
+}
+#[derive(Serialize, Deserialize)]
+struct RevokeBody {
+    revoking: String,
+}
+
+fn check_admin(request: &MockRequest) -> Result<(), (u16, String)> {
+    if request.header(HttpHeader::AdminInvite)? == ADMIN_SECRET {
+        Ok(())
+    } else {
+        Err((401, "Bad admin secret".to_string()))
+    }
+}
+
+fn status() -> Reply {
+    Ok(json!({ "status": true }))
+}
+
+fn announcement_kind(kind: &str) -> Result<AnnouncementType, (u16, String)> {
+    match kind {
+        "trust" | "scam" | "escrow" => Ok(AnnouncementType::from_str(kind).unwrap()),
+        _ => Err((404, "No such announcement kind".to_string())),
+    }
+}
+
+fn handle(state: &mut State, request: &MockRequest) -> Reply {
+    let segments: Vec<&str> = request.path.trim_start_matches('/').split('/').collect();
+    match (request.method.as_str(), &segments[..]) {
+        ("GET", ["api", "v2", "identity", "server"]) => {
+            state.authenticate(request)?;
+            Ok(json!({
+                "kind": if state.price.is_some() { "PUBLIC" } else { "PRIVATE" },
+                "name": "cypherpost-mock",
+                "pubkey": state.server_keys.pubkey.to_string(),
+            }))
+        }
+        ("GET", ["api", "v2", "identity", "admin", "invitation"]) => {
+            check_admin(request)?;
+            let invite_code = match request.query.get("type").map(|kind| kind.as_str()) {
+                Some("standard") => state.new_invite("STANDARD", 0, "ADMIN"),
+                Some("priv") => {
+                    let count = request
+                        .query
+                        .get("count")
+                        .and_then(|count| count.parse::<u128>().ok())
+                        .ok_or((400, "Bad invite count".to_string()))?;
+                    state.new_invite("PRIVILEGED", count, "ADMIN")
+                }
+                _ => return Err((400, "Bad invite type".to_string())),
+            };
+            Ok(json!({ "invite_code": invite_code }))
+        }
+        ("GET", ["api", "v2", "identity", "invitation"]) => {
+            state.authenticate(request)?;
+            let secret = request.header(HttpHeader::UserInvite)?;
+            let invite = state
+                .invites
+                .iter_mut()
+                .find(|invite| invite.invite_code == secret && invite.kind == "PRIVILEGED")
+                .ok_or((401, "Invalid privileged invite code".to_string()))?;
+            if invite.status != "CLAIMED" {
+                return Err((401, "Privileged invite has not been claimed".to_string()));
+            }
+            if invite.count == 0 {
+                return Err((403, "Privileged invite has no invites left".to_string()));
+            }
+            invite.count -= 1;
+            let created_by = invite.claimed_by.clone();
+            let invite_code = state.new_invite("STANDARD", 0, &created_by);
+            Ok(json!({ "invite_code": invite_code }))
+        }
+        ("GET", ["api", "v2", "identity", "invitation", "detail"]) => {
+            state.authenticate(request)?;
+            let invite_code = request.header(HttpHeader::InviteCode)?;
+            match state.invites.iter().find(|invite| invite.invite_code == invite_code) {
+                Some(invite) => Ok(serde_json::to_value(invite).unwrap()),
+                None => Err((404, "No invite with this code".to_string())),
+            }
+        }
+        ("GET", ["api", "v2", "identity", "invitation", "self"]) => {
+            let me = state.authenticate_member(request)?.to_string();
+            let invitations: Vec<&InvitationDetail> =
+                state.invites.iter().filter(|invite| invite.created_by == me).collect();
+            Ok(json!({ "invitations": invitations }))
+        }
+        ("GET", ["api", "v2", "identity", "admin", "invitation", "all"]) => {
+            check_admin(request)?;
+            let invitations: Vec<&InvitationDetail> =
+                state.invites.iter().filter(|invite| invite.created_by == "ADMIN").collect();
+            Ok(json!({ "invitations": invitations }))
+        }
+        ("POST", ["api", "v2", "identity", "invitation", "revoke"]) => {
+            let me = state.authenticate(request)?.to_string();
+            let body: RevokeInviteBody = request.json()?;
+            state.revoke_invite(&body.invite_code, &me)?;
+            // the code goes back to the privileged invite it was made from
+            if let Some(invite) = state
+                .invites
+                .iter_mut()
+                .find(|invite| invite.kind == "PRIVILEGED" && invite.claimed_by == me)
+            {
+                invite.count += 1;
+            }
+            status()
+        }
+        ("POST", ["api", "v2", "identity", "admin", "invitation", "revoke"]) => {
+            check_admin(request)?;
+            let body: RevokeInviteBody = request.json()?;
+            state.revoke_invite(&body.invite_code, "ADMIN")?;
+            status()
+        }
+        ("POST", ["api", "v2", "identity"]) => {
+            let pubkey = state.authenticate(request)?;
+            let invite_code = request.header(HttpHeader::InviteCode)?;
+            let body: RegisterBody = request.json()?;
+            if state.members.iter().any(|member| member.pubkey == pubkey) {
+                return Err((409, "Identity already registered".to_string()));
+            }
+            let name = username::validate(&body.username).map_err(|e| (400, e.error))?;
+            if state.members.iter().any(|member| username::normalize(&member.username) == name) {
+                return Err((409, "Username already taken".to_string()));
+            }
+            let invite = state
+                .invites
+                .iter_mut()
+                .find(|invite| invite.invite_code == invite_code && invite.status == "PENDING")
+                .ok_or((401, "Invalid invite code".to_string()))?;
+            invite.status = "CLAIMED".to_string();
+            invite.claimed_by = pubkey.to_string();
+            let detail = invite.clone();
+            let genesis = state.genesis();
+            state.membership_log.push(MemberChange {
+                genesis,
+                kind: MemberChangeKind::Joined,
+                username: name.clone(),
+                pubkey,
+            });
+            state.members.push(MemberIdentity { username: name, pubkey });
+            Ok(serde_json::to_value(detail).unwrap())
+        }
+        ("POST", ["api", "v2", "identity", "invoice"]) => {
+            let requested_by = state.authenticate(request)?;
+            let amount = state.price.ok_or((403, "Server is invite only".to_string()))?;
+            let invoice_id = State::random_id(16);
+            let expiry = state.genesis() + 600_000;
+            let invoice = JoinInvoice {
+                invoice: format!("lnmock{}n1{}", amount, invoice_id),
+                invoice_id,
+                amount,
+                expiry,
+                status: "UNPAID".to_string(),
+                invite_code: None,
+            };
+            state.invoices.push(StoredInvoice { requested_by, invoice: invoice.clone() });
+            Ok(serde_json::to_value(invoice).unwrap())
+        }
+        ("GET", ["api", "v2", "identity", "invoice", invoice_id]) => {
+            let pubkey = state.authenticate(request)?;
+            let now = state.genesis();
+            let stored = state
+                .invoices
+                .iter_mut()
+                .find(|stored| stored.invoice.invoice_id == *invoice_id && stored.requested_by == pubkey)
+                .ok_or((404, "No invoice with this id was requested by you".to_string()))?;
+            if stored.invoice.status == "UNPAID" && stored.invoice.expiry < now {
+                stored.invoice.status = "EXPIRED".to_string();
+            }
+            Ok(serde_json::to_value(&stored.invoice).unwrap())
+        }
+        ("GET", ["api", "v2", "identity", "changes"]) => {
+            state.authenticate_member(request)?;
+            let filter = request.genesis_filter();
+            let changes: Vec<&MemberChange> =
+                state.membership_log.iter().filter(|change| change.genesis > filter).collect();
+            Ok(json!({ "changes": changes }))
+        }
+        ("GET", ["api", "v2", "identity", "all"]) => {
+            state.authenticate_member(request)?;
+            Ok(json!({ "identities": state.members }))
+        }
+        ("DELETE", ["api", "v2", "identity"]) => {
+            let pubkey = state.authenticate_member(request)?;
+            let genesis = state.genesis();
+            let username = state.members.iter().find(|member| member.pubkey == pubkey).unwrap().username.clone();
+            state.membership_log.push(MemberChange { genesis, kind: MemberChangeKind::Left, username, pubkey });
+            state.members.retain(|member| member.pubkey != pubkey);
+            state.posts.retain(|post| post.owner != pubkey);
+            for post in state.posts.iter_mut() {
+                post.keys.remove(&pubkey);
+            }
+            state.badges.retain(|badge| badge.by != pubkey);
+            status()
+        }
+        ("PUT", ["api", "v2", "post"]) => {
+            let owner = state.authenticate_member(request)?;
+            let body: PostBody = request.json()?;
+            let idempotency_key = request.headers.get(&HttpHeader::IdempotencyKey.to_string()).cloned();
+            if let Some(key) = idempotency_key.clone() {
+                if let Some(id) = state.idempotent_posts.get(&(owner, key)) {
+                    return Ok(json!({ "id": id }));
+                }
+            }
+            match state.post_budget {
+                Some(0) => return Err((503, "Service unavailable".to_string())),
+                Some(left) => state.post_budget = Some(left - 1),
+                None => {}
+            }
+            let id = State::random_id(24);
+            let genesis = state.genesis();
+            state.posts.push(StoredPost {
+                id: id.clone(),
+                genesis,
+                expiry: body.expiry,
+                owner,
+                cypher_json: body.cypher_json,
+                derivation_index: body.derivation_index,
+                keys: BTreeMap::new(),
+            });
+            if let Some(key) = idempotency_key {
+                state.idempotent_posts.insert((owner, key), id.clone());
+            }
+            Ok(json!({ "id": id }))
+        }
+        ("PUT", ["api", "v2", "post", "keys"]) => {
+            let owner = state.authenticate_member(request)?;
+            let body: KeysBody = request.json()?;
+            match state.keys_budget {
+                Some(0) => return Err((503, "Service unavailable".to_string())),
+                Some(left) => state.keys_budget = Some(left - 1),
+                None => {}
+            }
+            let post = state
+                .posts
+                .iter_mut()
+                .find(|post| post.id == body.post_id && post.owner == owner)
+                .ok_or((404, "No post with this id".to_string()))?;
+            for key in body.decryption_keys {
+                post.keys.insert(key.receiver, key.decryption_key);
+            }
+            status()
+        }
+        ("GET", ["api", "v2", "post", "self"]) => {
+            let me = state.authenticate_member(request)?;
+            let filter = request.genesis_filter();
+            let posts: Vec<ServerPostModel> = state
+                .posts
+                .iter()
+                .filter(|post| post.owner == me && post.genesis > filter)
+                .map(|post| post.to_model(me))
+                .collect();
+            Ok(json!({ "posts": posts }))
+        }
+        ("GET", ["api", "v2", "post", "others"]) => {
+            let me = state.authenticate_member(request)?;
+            let filter = request.genesis_filter();
+            let posts: Vec<ServerPostModel> = state
+                .posts
+                .iter()
+                .filter(|post| post.keys.contains_key(&me) && post.genesis > filter)
+                .map(|post| post.to_model(me))
+                .collect();
+            Ok(json!({ "posts": posts }))
+        }
+        ("GET", ["api", "v2", "post", "last", "derivation"]) => {
+            let me = state.authenticate_member(request)?;
+            match state.posts.iter().filter(|post| post.owner == me).map(|post| post.derivation_index).max() {
+                Some(last_used) => Ok(json!({ "last_used": last_used })),
+                None => Err((404, "No posts made yet".to_string())),
+            }
+        }
+        ("GET", ["api", "v2", "post", id]) => {
+            let me = state.authenticate_member(request)?;
+            match state
+                .posts
+                .iter()
+                .find(|post| post.id == *id && (post.owner == me || post.keys.contains_key(&me)))
+            {
+                Some(post) => Ok(json!({ "post": post.to_model(me) })),
+                None => Err((404, "No post with this id".to_string())),
+            }
+        }
+        ("DELETE", ["api", "v2", "post", id]) => {
+            let me = state.authenticate_member(request)?;
+            let count = state.posts.len();
+            state.posts.retain(|post| !(post.id == *id && post.owner == me));
+            if state.posts.len() == count {
+                return Err((404, "No post with this id".to_string()));
+            }
+            status()
+        }
+        ("GET", ["api", "v2", "announcement", "all"]) => {
+            state.authenticate_member(request)?;
+            Ok(json!({ "announcements": state.badges }))
+        }
+        ("GET", ["api", "v2", "announcement", "self"]) => {
+            let me = state.authenticate_member(request)?;
+            let badges: Vec<&Badge> = state.badges.iter().filter(|badge| badge.by == me).collect();
+            Ok(json!({ "announcements": badges }))
+        }
+        ("POST", ["api", "v2", "announcement", kind]) => {
+            let by = state.authenticate_member(request)?;
+            let kind = announcement_kind(kind)?;
+            let body: AnnounceBody = request.json()?;
+            let to = XOnlyPublicKey::from_str(&body.recipient).map_err(|_| (400, "Bad recipient".to_string()))?;
+            let signature = signature_from_str(&body.signature).map_err(|_| (400, "Bad badge signature".to_string()))?;
+            if !state.members.iter().any(|member| member.pubkey == to) {
+                return Err((404, "Recipient is not a member".to_string()));
+            }
+            if state
+                .badges
+                .iter()
+                .any(|badge| badge.by == by && badge.to == to && badge.kind.to_string() == kind.to_string())
+            {
+                return Err((409, "Badge already given".to_string()));
+            }
+            let genesis = state.genesis();
+            let mut badge = Badge {
+                genesis: Some(genesis),
+                kind,
+                by,
+                to,
+                nonce: body.nonce,
+                signature,
+                hash: None,
+                details: body.details,
+            };
+            if !badge.verify() {
+                return Err((400, "Badge signature verification failed".to_string()));
+            }
+            badge.hash = Some(key_hash256(&format!("{}:{}:{}:{}", by, to, badge.kind.to_string(), badge.nonce)));
+            state.badges.push(badge);
+            status()
+        }
+        ("POST", ["api", "v2", "announcement", kind, "revoke"]) => {
+            let by = state.authenticate_member(request)?;
+            let kind = announcement_kind(kind)?;
+            let body: RevokeBody = request.json()?;
+            let to = XOnlyPublicKey::from_str(&body.revoking).map_err(|_| (400, "Bad revoking pubkey".to_string()))?;
+            let count = state.badges.len();
+            state
+                .badges
+                .retain(|badge| !(badge.by == by && badge.to == to && badge.kind.to_string() == kind.to_string()));
+            if state.badges.len() == count {
+                return Err((404, "No such badge".to_string()));
+            }
+            status()
+        }
+        ("GET", ["api", "v3", "notifications"]) => {
+            let me = state.authenticate_member(request)?;
+            let filter = request.genesis_filter();
+            let mut notifications: Vec<Value> = state
+                .posts
+                .iter()
+                .filter(|post| post.keys.contains_key(&me) && post.genesis > filter)
+                .map(|post| json!({ "kind": "post", "id": post.id, "by": post.owner.to_string(), "genesis": post.genesis }))
+                .collect();
+            notifications.extend(
+                state
+                    .badges
+                    .iter()
+                    .filter(|badge| badge.to == me && badge.genesis.unwrap_or(0) > filter)
+                    .map(|badge| json!({ "kind": "badge", "id": badge.hash, "by": badge.by.to_string(), "genesis": badge.genesis })),
+            );
+            Ok(json!({ "notifications": notifications }))
+        }
+        _ => Err((404, format!("No route for {} {}", request.method, request.path))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::handler::{sign_request, APIEndPoint, HttpMethod};
+
+    #[test]
+    #[allow(clippy::result_large_err)]
+    fn test_mock_signature_checks() {
+        let server = MockServer::start();
+        let seed = MasterKeySeed::generate(12, "", bitcoin::Network::Bitcoin).unwrap();
+        let keys = XOnlyPair::from_xprv(seed.xprv);
+        let url = server.url() + &APIEndPoint::ServerIdentity.to_string();
+        let call = |method: HttpMethod, nonce: &str| {
+            let signature = sign_request(keys.clone(), method, APIEndPoint::ServerIdentity, nonce).unwrap();
+            ureq::get(&url)
+                .set(&HttpHeader::Signature.to_string(), &signature)
+                .set(&HttpHeader::Pubkey.to_string(), &keys.pubkey.to_string())
+                .set(&HttpHeader::Nonce.to_string(), nonce)
+                .call()
+        };
+        let nonce = nonce();
+        assert!(call(HttpMethod::Get, &nonce).is_ok());
+        // replayed nonce
+        assert_eq!(call(HttpMethod::Get, &nonce).unwrap_err().into_response().unwrap().status(), 401);
+        // signed for a different method
+        assert_eq!(call(HttpMethod::Post, "fresh").unwrap_err().into_response().unwrap().status(), 401);
+        // unsigned
+        assert_eq!(ureq::get(&url).call().unwrap_err().into_response().unwrap().status(), 400);
+    }
+}
@@ -2,3 +2,5 @@ pub mod handler;
 pub mod identity;
 pub mod badge;
 pub mod post;
+#[cfg(test)]
+pub mod mock;
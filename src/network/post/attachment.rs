@@ -0,0 +1,334 @@
+//! Encrypted attachments, e.g. images, PDFs or signed transaction files.
+//! The file is split into `CHUNK_SIZE` chunks, each encrypted with STREAM under a key made for the attachment,
+//! and each chunk is sent as its own `PayloadKind::Chunk` post. The message carrying the attachment is a
+//! `PayloadKind::Attachment` post holding the `AttachmentManifest`: the key, and the size and hash of every chunk.
+//! Only recipients of the manifest can decrypt the chunks; a download checks each chunk before writing it,
+//! so an interrupted download can resume from the last complete chunk.
+use crate::key::encryption::{
+    nonce, stream_decrypt, stream_encrypt, stream_key, STREAM_KEY_LENGTH, STREAM_NONCE_LENGTH,
+};
+use crate::network::post::model::{LocalPostModel, PayloadKind};
+use crate::util::e::{ErrorKind, S5Error};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::raw::c_char;
+
+/// Plaintext bytes per chunk.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+pub const MAX_ATTACHMENT_SIZE: u64 = 16 * 1024 * 1024;
+pub const MAX_ATTACHMENT_NAME_LENGTH: usize = 128;
+/// Poly1305 tag added to each chunk.
+const TAG_SIZE: u64 = 16;
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// Where to find one encrypted chunk and how to check it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChunkRef {
+    pub post_id: String,
+    /// Ciphertext bytes.
+    pub size: u64,
+    /// Hex sha256 of the ciphertext.
+    pub sha256: String,
+}
+
+/// The value of an Attachment post.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AttachmentManifest {
+    pub id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime: Option<String>,
+    /// Plaintext bytes.
+    pub size: u64,
+    /// Hex sha256 of the plaintext.
+    pub sha256: String,
+    /// Hex STREAM key and nonce, see `encryption::stream_encrypt`.
+    pub key: String,
+    pub nonce: String,
+    pub chunk_size: u64,
+    pub chunks: Vec<ChunkRef>,
+}
+
+impl AttachmentManifest {
+    pub fn structify(stringified: &str) -> Result<AttachmentManifest, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Input, "Error structifying AttachmentManifest")),
+        }
+    }
+    pub fn stringify(&self) -> Result<String, S5Error> {
+        match serde_json::to_string(self) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Internal, "Error stringifying AttachmentManifest")),
+        }
+    }
+    pub fn c_stringify(&self) -> *mut c_char {
+        CString::new(serde_json::to_string(self).unwrap()).unwrap().into_raw()
+    }
+    pub fn check(&self) -> Result<(), S5Error> {
+        check_name(&self.name)?;
+        if self.size > MAX_ATTACHMENT_SIZE {
+            return Err(S5Error::new(
+                ErrorKind::Input,
+                &format!("Attachments can be at most {} bytes.", MAX_ATTACHMENT_SIZE),
+            ));
+        }
+        let keys = (hex::decode(&self.key), hex::decode(&self.nonce));
+        if !matches!(keys, (Ok(key), Ok(nonce)) if key.len() == STREAM_KEY_LENGTH && nonce.len() == STREAM_NONCE_LENGTH) {
+            return Err(S5Error::new(ErrorKind::Input, "Bad attachment key."));
+        }
+        if self.chunk_size == 0 {
+            return Err(S5Error::new(ErrorKind::Input, "Attachment chunks do not match its size."));
+        }
+        // every chunk but the last is full, and even an empty file has one chunk
+        let chunks = std::cmp::max(1, self.size.div_ceil(self.chunk_size));
+        if self.chunks.len() as u64 != chunks {
+            return Err(S5Error::new(ErrorKind::Input, "Attachment chunks do not match its size."));
+        }
+        let ciphertext: u64 = self.chunks.iter().map(|chunk| chunk.size).sum();
+        if ciphertext != self.size + TAG_SIZE * chunks {
+            return Err(S5Error::new(ErrorKind::Input, "Attachment chunks do not match its size."));
+        }
+        Ok(())
+    }
+    fn stream_key(&self) -> Result<(Vec<u8>, Vec<u8>), S5Error> {
+        match (hex::decode(&self.key), hex::decode(&self.nonce)) {
+            (Ok(key), Ok(nonce)) => Ok((key, nonce)),
+            _ => Err(S5Error::new(ErrorKind::Input, "Bad attachment key.")),
+        }
+    }
+    /// Check chunk `index` against the manifest and decrypt it.
+    pub fn open(&self, index: usize, chunk: &AttachmentChunk) -> Result<Vec<u8>, S5Error> {
+        let expected = match self.chunks.get(index) {
+            Some(expected) => expected,
+            None => return Err(S5Error::new(ErrorKind::Input, "No such attachment chunk.")),
+        };
+        if chunk.attachment_id != self.id || chunk.index as usize != index {
+            return Err(S5Error::new(ErrorKind::Input, "Chunk belongs to another attachment."));
+        }
+        let data = base64::decode(&chunk.data).map_err(|_| S5Error::new(ErrorKind::Input, "Bad chunk data."))?;
+        if data.len() as u64 != expected.size || sha256_hex(&data) != expected.sha256 {
+            return Err(S5Error::new(ErrorKind::Key, "Chunk does not match the attachment manifest."));
+        }
+        let (key, nonce) = self.stream_key()?;
+        stream_decrypt(&key, &nonce, chunk.index, index + 1 == self.chunks.len(), &data)
+    }
+}
+
+pub fn check_name(name: &str) -> Result<(), S5Error> {
+    if name.trim().is_empty() || name.chars().count() > MAX_ATTACHMENT_NAME_LENGTH {
+        return Err(S5Error::new(
+            ErrorKind::Input,
+            &format!("Attachment names must be 1 to {} characters.", MAX_ATTACHMENT_NAME_LENGTH),
+        ));
+    }
+    Ok(())
+}
+
+/// The value of a Chunk post.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AttachmentChunk {
+    pub attachment_id: String,
+    pub index: u32,
+    /// Base64 ciphertext.
+    pub data: String,
+}
+
+impl AttachmentChunk {
+    pub fn structify(stringified: &str) -> Result<AttachmentChunk, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Input, "Error structifying AttachmentChunk")),
+        }
+    }
+    pub fn stringify(&self) -> Result<String, S5Error> {
+        match serde_json::to_string(self) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Internal, "Error stringifying AttachmentChunk")),
+        }
+    }
+    pub fn from_post(post: &LocalPostModel) -> Result<AttachmentChunk, S5Error> {
+        if !matches!(post.post.payload.kind, PayloadKind::Chunk) {
+            return Err(S5Error::new(ErrorKind::Input, "Not an attachment chunk."));
+        }
+        post.verify()?;
+        AttachmentChunk::structify(&post.post.payload.value)
+    }
+}
+
+/// Fill `buffer` from `reader` until it is full or the reader is done. Returns the bytes read.
+fn read_chunk(reader: &mut impl Read, buffer: &mut [u8]) -> Result<usize, S5Error> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(S5Error::new(ErrorKind::Input, &format!("Could not read attachment: {}", e))),
+        }
+    }
+    Ok(filled)
+}
+
+/// Encrypt everything in `reader` under a new key, one chunk at a time. `upload` sends each chunk
+/// and returns its post id. Only two chunks are held in memory at once.
+pub fn seal(
+    reader: &mut impl Read,
+    name: &str,
+    mime: Option<String>,
+    mut upload: impl FnMut(AttachmentChunk) -> Result<String, S5Error>,
+) -> Result<AttachmentManifest, S5Error> {
+    check_name(name)?;
+    let (key, stream_nonce) = stream_key();
+    let mut manifest = AttachmentManifest {
+        id: nonce(),
+        name: name.to_string(),
+        mime,
+        size: 0,
+        sha256: String::new(),
+        key: hex::encode(key),
+        nonce: hex::encode(stream_nonce),
+        chunk_size: CHUNK_SIZE as u64,
+        chunks: vec![],
+    };
+    let mut hasher = Sha256::new();
+    let mut current = vec![0; CHUNK_SIZE];
+    let mut next = vec![0; CHUNK_SIZE];
+    let mut current_length = read_chunk(reader, &mut current)?;
+    loop {
+        // read ahead to know whether this is the last chunk
+        let next_length = if current_length == CHUNK_SIZE { read_chunk(reader, &mut next)? } else { 0 };
+        let plaintext = &current[..current_length];
+        manifest.size += current_length as u64;
+        if manifest.size > MAX_ATTACHMENT_SIZE {
+            return Err(S5Error::new(
+                ErrorKind::Input,
+                &format!("Attachments can be at most {} bytes.", MAX_ATTACHMENT_SIZE),
+            ));
+        }
+        hasher.update(plaintext);
+        let index = manifest.chunks.len() as u32;
+        let ciphertext = stream_encrypt(&key, &stream_nonce, index, next_length == 0, plaintext)?;
+        let post_id = upload(AttachmentChunk {
+            attachment_id: manifest.id.clone(),
+            index,
+            data: base64::encode(&ciphertext),
+        })?;
+        manifest.chunks.push(ChunkRef {
+            post_id,
+            size: ciphertext.len() as u64,
+            sha256: sha256_hex(&ciphertext),
+        });
+        if next_length == 0 {
+            break;
+        }
+        std::mem::swap(&mut current, &mut next);
+        current_length = next_length;
+    }
+    manifest.sha256 = hex::encode(hasher.finalize());
+    Ok(manifest)
+}
+
+/// Download an attachment into `file`, keeping the complete chunks already in it from an earlier attempt.
+/// `fetch` gets one chunk. Each chunk is checked before it is written and the whole file once done;
+/// a file that does not match is emptied so the next attempt starts over.
+pub fn download(
+    manifest: &AttachmentManifest,
+    file: &mut File,
+    mut fetch: impl FnMut(&ChunkRef) -> Result<AttachmentChunk, S5Error>,
+) -> Result<(), S5Error> {
+    manifest.check()?;
+    let io_error = |e: std::io::Error| S5Error::new(ErrorKind::Internal, &format!("Could not write attachment: {}", e));
+    let length = file.metadata().map_err(io_error)?.len();
+    let done = std::cmp::min(length / manifest.chunk_size, manifest.chunks.len() as u64 - 1) as usize;
+    file.set_len(done as u64 * manifest.chunk_size).map_err(io_error)?;
+    file.seek(SeekFrom::End(0)).map_err(io_error)?;
+    for (index, chunk) in manifest.chunks.iter().enumerate().skip(done) {
+        let plaintext = manifest.open(index, &fetch(chunk)?)?;
+        file.write_all(&plaintext).map_err(io_error)?;
+        file.flush().map_err(io_error)?;
+    }
+    let mut hasher = Sha256::new();
+    file.seek(SeekFrom::Start(0)).map_err(io_error)?;
+    std::io::copy(file, &mut hasher).map_err(io_error)?;
+    if hex::encode(hasher.finalize()) == manifest.sha256 && file.metadata().map_err(io_error)?.len() == manifest.size {
+        return Ok(());
+    }
+    file.set_len(0).map_err(io_error)?;
+    Err(S5Error::new(ErrorKind::Key, "Attachment does not match its manifest."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::encryption::key_hash256;
+    use std::collections::BTreeMap;
+    use std::fs::OpenOptions;
+
+    fn temp_file(name: &str) -> (std::path::PathBuf, File) {
+        let path = std::env::temp_dir().join(format!("cpclient-{}-{}", name, key_hash256(&nonce())));
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path).unwrap();
+        (path, file)
+    }
+
+    #[test]
+    fn test_seal_and_download() {
+        let data: Vec<u8> = (0..(2 * CHUNK_SIZE + 100)).map(|i| (i % 251) as u8).collect();
+        let mut uploaded = BTreeMap::<String, AttachmentChunk>::new();
+        let manifest = seal(&mut data.as_slice(), "tx.psbt", None, |chunk| {
+            let id = format!("post{}", chunk.index);
+            uploaded.insert(id.clone(), chunk);
+            Ok(id)
+        })
+        .unwrap();
+        assert_eq!(manifest.chunks.len(), 3);
+        assert_eq!(manifest.size, data.len() as u64);
+        assert_eq!(manifest.sha256, sha256_hex(&data));
+        manifest.check().unwrap();
+        assert!(AttachmentManifest::structify(&manifest.stringify().unwrap()).unwrap() == manifest);
+
+        // interrupted after the first chunk and part of the second
+        let (path, mut file) = temp_file("attachment");
+        let mut fetched = 0;
+        let result = download(&manifest, &mut file, |chunk| {
+            fetched += 1;
+            if fetched == 2 {
+                return Err(S5Error::new(ErrorKind::Network, "offline"));
+            }
+            Ok(uploaded[&chunk.post_id].clone())
+        });
+        assert!(result.is_err());
+        file.write_all(&data[CHUNK_SIZE..CHUNK_SIZE + 10]).unwrap();
+        // resumes from the second chunk
+        let mut fetched = vec![];
+        download(&manifest, &mut file, |chunk| {
+            fetched.push(chunk.post_id.clone());
+            Ok(uploaded[&chunk.post_id].clone())
+        })
+        .unwrap();
+        assert_eq!(fetched, vec!["post1", "post2"]);
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+
+        // a chunk swapped for another, or from another attachment, is rejected
+        let mut swapped = uploaded["post2"].clone();
+        swapped.index = 1;
+        assert!(manifest.open(1, &swapped).is_err());
+        let mut tampered = manifest.clone();
+        tampered.chunks[2].sha256 = tampered.chunks[1].sha256.clone();
+        file.set_len(0).unwrap();
+        assert!(download(&tampered, &mut file, |chunk| Ok(uploaded[&chunk.post_id].clone())).is_err());
+        assert_eq!(file.metadata().unwrap().len(), 2 * CHUNK_SIZE as u64);
+        std::fs::remove_file(&path).unwrap();
+
+        let empty = seal(&mut [].as_slice(), "empty.txt", None, |_| Ok("post".to_string())).unwrap();
+        assert_eq!(empty.chunks.len(), 1);
+        empty.check().unwrap();
+        assert!(seal(&mut data.as_slice(), "", None, |_| Ok("post".to_string())).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+//! Conversation list. Summarizes the chats of a `SortedPosts` into one row per counter party,
+//! most recently active first, with a preview of the last message and the number of unread posts.
+//! What has been read and which chats are muted is kept on the device in a `ConversationState`.
+use crate::network::identity::directory::ContactFlag;
+use crate::network::post::attachment::AttachmentManifest;
+use crate::network::post::model::{LocalPostModel, PayloadKind, PostsAsChat, SortedPosts};
+use crate::util::e::{ErrorKind, S5Error};
+use bitcoin::secp256k1::XOnlyPublicKey;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::os::raw::c_char;
+
+/// Characters of the last message shown in a preview.
+pub const PREVIEW_LENGTH: usize = 80;
+
+/// Read markers and mutes, by counter party pubkey or group id. Kept by the app.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ConversationState {
+    /// Genesis of the latest post read in each chat.
+    #[serde(default)]
+    pub read_up_to: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub muted: Vec<String>,
+}
+
+impl ConversationState {
+    pub fn structify(stringified: &str) -> Result<ConversationState, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Input, "Error structifying ConversationState")),
+        }
+    }
+    pub fn c_stringify(&self) -> *mut c_char {
+        CString::new(serde_json::to_string(self).unwrap()).unwrap().into_raw()
+    }
+    /// Mark every post in `counter_party`'s chat up to `genesis` as read. Markers only move forward.
+    pub fn mark_read(&mut self, counter_party: &str, genesis: u64) {
+        let marker = self.read_up_to.entry(counter_party.to_string()).or_insert(0);
+        *marker = std::cmp::max(*marker, genesis);
+    }
+    pub fn set_muted(&mut self, counter_party: &str, muted: bool) {
+        self.muted.retain(|entry| entry != counter_party);
+        if muted {
+            self.muted.push(counter_party.to_string());
+        }
+    }
+    pub fn is_muted(&self, counter_party: &str) -> bool {
+        self.muted.iter().any(|entry| entry == counter_party)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConversationSummary {
+    pub counter_party: String,
+    pub username: Option<String>,
+    pub flags: Vec<ContactFlag>,
+    pub last_post_id: String,
+    /// Genesis of the latest post, pass it to `ConversationState::mark_read` once the chat is opened.
+    pub last_activity: u64,
+    pub preview: String,
+    /// Posts from others after the read marker. Edits and reactions are not counted.
+    pub unread: usize,
+    pub muted: bool,
+}
+
+/// One page of conversations, most recently active first.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Conversations {
+    pub conversations: Vec<ConversationSummary>,
+    /// Conversations on all pages.
+    pub total: usize,
+    /// Unread posts in chats that are not muted, on all pages.
+    pub unread: usize,
+}
+
+impl Conversations {
+    pub fn c_stringify(&self) -> *mut c_char {
+        CString::new(serde_json::to_string(self).unwrap()).unwrap().into_raw()
+    }
+}
+
+/// Short text for a post in a conversation list.
+pub fn preview(post: &LocalPostModel) -> String {
+    let text = match post.post.payload.kind {
+        PayloadKind::Message => post.post.payload.value.clone(),
+        PayloadKind::Attachment => match AttachmentManifest::structify(&post.post.payload.value) {
+            Ok(manifest) => format!("[attachment] {}", manifest.name),
+            Err(_) => "[attachment]".to_string(),
+        },
+        _ => format!("[{}]", post.post.payload.kind.to_string()),
+    };
+    let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if text.chars().count() > PREVIEW_LENGTH {
+        format!("{}...", text.chars().take(PREVIEW_LENGTH).collect::<String>())
+    } else {
+        text
+    }
+}
+
+fn summarize_chat(me: &XOnlyPublicKey, chat: &PostsAsChat, state: &ConversationState) -> Option<ConversationSummary> {
+    let last = chat.posts.iter().max_by_key(|post| post.genesis)?;
+    // a reaction is activity, but the message it reacts to is the better preview
+    let shown = chat
+        .posts
+        .iter()
+        .filter(|post| post.post.links.reacts_to.is_none())
+        .max_by_key(|post| post.genesis)
+        .unwrap_or(last);
+    let read_up_to = state.read_up_to.get(&chat.counter_party).copied().unwrap_or(0);
+    let unread = chat
+        .posts
+        .iter()
+        .filter(|post| post.owner != *me && post.genesis > read_up_to)
+        .filter(|post| post.post.links.edits.is_none() && post.post.links.reacts_to.is_none())
+        .count();
+    Some(ConversationSummary {
+        counter_party: chat.counter_party.clone(),
+        username: chat.username.clone(),
+        flags: chat.flags.clone(),
+        last_post_id: last.id.clone(),
+        last_activity: last.genesis,
+        preview: preview(shown),
+        unread,
+        muted: state.is_muted(&chat.counter_party),
+    })
+}
+
+/// The verified chats of `sorted` as a conversation list, most recently active first.
+/// Skips `offset` conversations and returns at most `limit`, or all of them with a limit of 0.
+pub fn summarize(
+    me: &XOnlyPublicKey,
+    sorted: &SortedPosts,
+    state: &ConversationState,
+    offset: usize,
+    limit: usize,
+) -> Conversations {
+    let mut conversations: Vec<ConversationSummary> =
+        sorted.verified.iter().filter_map(|chat| summarize_chat(me, chat, state)).collect();
+    conversations.sort_by(|a, b| b.last_activity.cmp(&a.last_activity).then_with(|| a.counter_party.cmp(&b.counter_party)));
+    let total = conversations.len();
+    let unread = conversations.iter().filter(|conversation| !conversation.muted).map(|conversation| conversation.unread).sum();
+    let limit = if limit == 0 { total } else { limit };
+    Conversations {
+        conversations: conversations.into_iter().skip(offset).take(limit).collect(),
+        total,
+        unread,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::ec::XOnlyPair;
+    use crate::util::testing::keys;
+    use crate::network::post::model::{Payload, Post, PostLinks, Recipient, RecipientKind};
+
+    fn chat(counter_party: &str, posts: Vec<LocalPostModel>) -> PostsAsChat {
+        PostsAsChat {
+            counter_party: counter_party.to_string(),
+            posts,
+            username: None,
+            flags: vec![],
+            threads: vec![],
+        }
+    }
+
+    #[test]
+    fn test_summarize() {
+        let (me, bob, carol) = (keys(), keys(), keys());
+        let post = |from: &XOnlyPair, id: &str, genesis: u64, kind: PayloadKind, value: &str, links: PostLinks| LocalPostModel {
+            id: id.to_string(),
+            genesis,
+            expiry: 0,
+            owner: from.pubkey,
+            post: Post::with_links(
+                Recipient::new(RecipientKind::Group, "market".to_string()),
+                Payload::new(kind, value.to_string()),
+                links,
+                from.clone(),
+            ),
+        };
+        let message = |from: &XOnlyPair, id: &str, genesis: u64, value: &str| {
+            post(from, id, genesis, PayloadKind::Message, value, PostLinks::default())
+        };
+        let reaction = PostLinks {
+            reacts_to: Some("b1".to_string()),
+            ..PostLinks::default()
+        };
+        let long = "word ".repeat(40);
+        let sorted = SortedPosts {
+            verified: vec![
+                chat(&bob.pubkey.to_string(), vec![
+                    message(&bob, "b1", 10, "hi"),
+                    message(&me, "b2", 20, "hey"),
+                    message(&bob, "b3", 30, &long),
+                    post(&me, "b4", 60, PayloadKind::Message, "+1", reaction),
+                ]),
+                chat(&carol.pubkey.to_string(), vec![message(&carol, "c1", 50, "new\nline")]),
+                chat("market", vec![
+                    message(&bob, "m1", 40, "selling"),
+                    post(&carol, "m2", 45, PayloadKind::Psbt, "cHNidP8B", PostLinks::default()),
+                ]),
+            ],
+            ..SortedPosts::default()
+        };
+
+        let mut state = ConversationState::default();
+        let all = summarize(&me.pubkey, &sorted, &state, 0, 0);
+        assert_eq!(all.total, 3);
+        let order: Vec<&str> = all.conversations.iter().map(|conversation| conversation.counter_party.as_str()).collect();
+        assert_eq!(order, vec![bob.pubkey.to_string().as_str(), carol.pubkey.to_string().as_str(), "market"]);
+        assert_eq!(all.conversations[0].last_activity, 60);
+        assert_eq!(all.conversations[0].preview, format!("{}...", &long[..PREVIEW_LENGTH]));
+        assert_eq!(all.conversations[0].unread, 2);
+        assert_eq!(all.conversations[1].preview, "new line");
+        assert_eq!(all.conversations[2].preview, "[psbt]");
+        assert_eq!(all.unread, 5);
+
+        state.mark_read(&bob.pubkey.to_string(), 30);
+        state.mark_read(&bob.pubkey.to_string(), 10);
+        state.set_muted("market", true);
+        let page = summarize(&me.pubkey, &sorted, &state, 1, 5);
+        assert_eq!(page.total, 3);
+        assert_eq!(page.conversations.len(), 2);
+        assert!(page.conversations[1].muted);
+        assert_eq!(page.unread, 1);
+        assert_eq!(summarize(&me.pubkey, &sorted, &state, 0, 1).conversations[0].unread, 0);
+        state.set_muted("market", false);
+        assert!(state.muted.is_empty());
+    }
+}
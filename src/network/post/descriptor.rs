@@ -0,0 +1,303 @@
+//! Multisig wallet setup over posts.
+//! Each cosigner posts a `PayloadKind::Xpub` contribution, "[fingerprint/path]xpub". A coordinator
+//! collects them from the chat, assembles a `wsh(sortedmulti(...))` descriptor and posts it back
+//! as `PayloadKind::Descriptor`, for every cosigner to check their key is in it.
+use crate::network::post::model::{LocalPostModel, PayloadKind};
+use crate::util::e::{ErrorKind, S5Error};
+use bitcoin::secp256k1::XOnlyPublicKey;
+use bitcoin::util::bip32::{DerivationPath, ExtendedPubKey, Fingerprint};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::ffi::CString;
+use std::fmt;
+use std::os::raw::c_char;
+use std::str::FromStr;
+
+/// Most keys a standard P2WSH multisig can hold.
+pub const MAX_MULTISIG_KEYS: usize = 20;
+
+const INPUT_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+fn polymod(c: u64, value: u64) -> u64 {
+    let c0 = c >> 35;
+    let mut c = ((c & 0x7ffffffff) << 5) ^ value;
+    for (bit, generator) in [0xf5dee51989, 0xa9fdca3312, 0x1bab10e32d, 0x3706b1677a, 0x644d626ffd].iter().enumerate() {
+        if c0 & (1 << bit) != 0 {
+            c ^= generator;
+        }
+    }
+    c
+}
+
+/// The 8 character BIP380 checksum of a descriptor without one.
+pub fn checksum(descriptor: &str) -> Result<String, S5Error> {
+    let mut c = 1;
+    let mut class = 0;
+    let mut class_count = 0;
+    for ch in descriptor.chars() {
+        let position = match INPUT_CHARSET.find(ch) {
+            Some(position) => position as u64,
+            None => return Err(S5Error::new(ErrorKind::Input, &format!("Invalid descriptor character {}", ch))),
+        };
+        c = polymod(c, position & 31);
+        class = class * 3 + (position >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            c = polymod(c, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        c = polymod(c, class);
+    }
+    for _ in 0..8 {
+        c = polymod(c, 0);
+    }
+    c ^= 1;
+    Ok((0..8).map(|j| CHECKSUM_CHARSET[((c >> (5 * (7 - j))) & 31) as usize] as char).collect())
+}
+
+/// The descriptor without its checksum, if the checksum is present and correct.
+pub fn verify_checksum(descriptor: &str) -> Result<&str, S5Error> {
+    match descriptor.trim().rsplit_once('#') {
+        Some((body, sum)) if checksum(body)? == sum => Ok(body),
+        Some(_) => Err(S5Error::new(ErrorKind::Input, "Descriptor checksum mismatch.")),
+        None => Err(S5Error::new(ErrorKind::Input, "Descriptor has no checksum.")),
+    }
+}
+
+/// One cosigner's key: where it was derived from and the xpub at that path.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KeyContribution {
+    pub fingerprint: Fingerprint,
+    pub path: DerivationPath,
+    pub xpub: ExtendedPubKey,
+}
+
+impl fmt::Display for KeyContribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path.to_string();
+        write!(f, "[{}{}]{}", self.fingerprint, path.trim_start_matches('m'), self.xpub)
+    }
+}
+
+impl FromStr for KeyContribution {
+    type Err = S5Error;
+
+    /// "[d34db33f/48'/0'/0'/2']xpub..." , with ' or h for hardened steps.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = |message: &str| S5Error::new(ErrorKind::Input, &format!("Bad key contribution: {}", message));
+        let (origin, xpub) = match s.trim().strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+            Some(parts) => parts,
+            None => return Err(bad("must be [fingerprint/path]xpub")),
+        };
+        let (fingerprint, path) = origin.split_once('/').unwrap_or((origin, ""));
+        let fingerprint = Fingerprint::from_str(fingerprint).map_err(|_| bad("fingerprint must be 8 hex characters"))?;
+        let path = DerivationPath::from_str(format!("m/{}", path).trim_end_matches('/'))
+            .map_err(|_| bad("invalid derivation path"))?;
+        let xpub = ExtendedPubKey::from_str(xpub).map_err(|_| bad("invalid xpub"))?;
+        if xpub.depth as usize != path.len() {
+            return Err(bad("xpub depth does not match the path"));
+        }
+        Ok(KeyContribution { fingerprint, path, xpub })
+    }
+}
+
+/// A sorted multisig wallet, with the receive (/0/*) and change (/1/*) descriptors checksummed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MultisigDescriptor {
+    pub threshold: usize,
+    /// Sorted, so every cosigner assembles the same descriptor.
+    pub keys: Vec<KeyContribution>,
+    pub receive: String,
+    pub change: String,
+}
+
+impl MultisigDescriptor {
+    pub fn assemble(threshold: usize, mut keys: Vec<KeyContribution>) -> Result<MultisigDescriptor, S5Error> {
+        if keys.is_empty() || keys.len() > MAX_MULTISIG_KEYS {
+            return Err(S5Error::new(
+                ErrorKind::Input,
+                &format!("A multisig needs 1 to {} keys.", MAX_MULTISIG_KEYS),
+            ));
+        }
+        if threshold == 0 || threshold > keys.len() {
+            return Err(S5Error::new(ErrorKind::Input, "Threshold must be between 1 and the number of keys."));
+        }
+        if keys.iter().any(|key| key.xpub.network != keys[0].xpub.network) {
+            return Err(S5Error::new(ErrorKind::Input, "Keys are for different networks."));
+        }
+        // dropping the copy would leave fewer distinct cosigners than the group agreed on
+        let mut seen = HashSet::new();
+        if !keys.iter().all(|key| seen.insert(key.xpub)) {
+            return Err(S5Error::new(ErrorKind::Input, "The same xpub was contributed more than once."));
+        }
+        keys.sort_by_key(|key| key.to_string());
+        let branch = |branch: u32| -> Result<String, S5Error> {
+            let keys: Vec<String> = keys.iter().map(|key| format!("{}/{}/*", key, branch)).collect();
+            let body = format!("wsh(sortedmulti({},{}))", threshold, keys.join(","));
+            Ok(format!("{}#{}", body, checksum(&body)?))
+        };
+        Ok(MultisigDescriptor { threshold, receive: branch(0)?, change: branch(1)?, keys })
+    }
+
+    /// Parses and checks a receive or change descriptor made by `assemble`.
+    pub fn from_descriptor(descriptor: &str) -> Result<MultisigDescriptor, S5Error> {
+        let body = verify_checksum(descriptor)?;
+        let inner = match body.strip_prefix("wsh(sortedmulti(").and_then(|rest| rest.strip_suffix("))")) {
+            Some(inner) => inner,
+            None => return Err(S5Error::new(ErrorKind::Input, "Not a wsh(sortedmulti(...)) descriptor.")),
+        };
+        let mut parts = inner.split(',');
+        let threshold = parts
+            .next()
+            .and_then(|threshold| threshold.parse::<usize>().ok())
+            .ok_or_else(|| S5Error::new(ErrorKind::Input, "Bad descriptor threshold."))?;
+        let keys = parts
+            .map(|key| match key.strip_suffix("/0/*").or_else(|| key.strip_suffix("/1/*")) {
+                Some(key) => KeyContribution::from_str(key),
+                None => Err(S5Error::new(ErrorKind::Input, "Descriptor keys must end in /0/* or /1/*.")),
+            })
+            .collect::<Result<Vec<KeyContribution>, S5Error>>()?;
+        let assembled = MultisigDescriptor::assemble(threshold, keys)?;
+        if assembled.receive != descriptor.trim() && assembled.change != descriptor.trim() {
+            return Err(S5Error::new(ErrorKind::Input, "Descriptor keys are not sorted or mix branches."));
+        }
+        Ok(assembled)
+    }
+
+    pub fn includes(&self, key: &KeyContribution) -> bool {
+        self.keys.contains(key)
+    }
+
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+/// Checks a `PayloadKind::Descriptor` value: any descriptor with a valid checksum,
+/// and for sorted multisig, keys that parse.
+pub fn check(descriptor: &str) -> Result<(), S5Error> {
+    let body = verify_checksum(descriptor)?;
+    if body.starts_with("wsh(sortedmulti(") {
+        MultisigDescriptor::from_descriptor(descriptor)?;
+    }
+    Ok(())
+}
+
+/// The latest key contribution from each poster in `posts`, e.g. the posts of one chat.
+pub fn collect(posts: &[LocalPostModel]) -> Vec<(XOnlyPublicKey, KeyContribution)> {
+    let mut posts: Vec<&LocalPostModel> = posts.iter().collect();
+    posts.sort_by_key(|post| post.genesis);
+    let mut contributions: Vec<(XOnlyPublicKey, KeyContribution)> = vec![];
+    for post in posts.into_iter().filter(|post| matches!(post.post.payload.kind, PayloadKind::Xpub)) {
+        if let Ok(key) = KeyContribution::from_str(&post.post.payload.value) {
+            contributions.retain(|(owner, _)| *owner != post.owner);
+            contributions.push((post.owner, key));
+        }
+    }
+    contributions
+}
+
+/// Assemble a descriptor from the key contributions in `posts`.
+pub fn assemble_from_posts(threshold: usize, posts: &[LocalPostModel]) -> Result<MultisigDescriptor, S5Error> {
+    MultisigDescriptor::assemble(threshold, collect(posts).into_iter().map(|(_, key)| key).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+    use crate::key::ec::XOnlyPair;
+    use crate::network::post::model::{Payload, Post, Recipient, RecipientKind};
+    use bitcoin::network::constants::Network;
+    use bitcoin::secp256k1::Secp256k1;
+    use bitcoin::util::bip32::ExtendedPrivKey;
+
+    fn contribution(seed: u8) -> KeyContribution {
+        let secp = Secp256k1::new();
+        let master = ExtendedPrivKey::new_master(Network::Testnet, &[seed; 32]).unwrap();
+        let path = DerivationPath::from_str("m/48'/1'/0'/2'").unwrap();
+        let xpub = ExtendedPubKey::from_priv(&secp, &master.derive_priv(&secp, &path).unwrap());
+        KeyContribution { fingerprint: master.fingerprint(&secp), path, xpub }
+    }
+
+    #[test]
+    fn test_descriptor_checksum() {
+        // BIP380 test vector
+        assert_eq!(checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+        assert_eq!(verify_checksum("raw(deadbeef)#89f8spxm").unwrap(), "raw(deadbeef)");
+        assert!(verify_checksum("raw(deadbeef)#89f8spxx").is_err());
+        assert!(verify_checksum("raw(deadbeef)").is_err());
+        assert!(checksum("raw(dead\u{e9})").is_err());
+    }
+
+    #[test]
+    fn test_multisig_descriptor() {
+        let (a, b, c) = (contribution(1), contribution(2), contribution(3));
+        let text = a.to_string();
+        assert!(text.starts_with(&format!("[{}/48'/1'/0'/2']tpub", a.fingerprint)));
+        assert_eq!(KeyContribution::from_str(&text).unwrap(), a);
+        assert_eq!(KeyContribution::from_str(&text.replace('\'', "h")).unwrap(), a);
+        assert!(KeyContribution::from_str(&text.replace("/2']", "]")).is_err());
+        assert!(KeyContribution::from_str("xpub").is_err());
+
+        let wallet = MultisigDescriptor::assemble(2, vec![c.clone(), a.clone(), b.clone()]).unwrap();
+        assert_eq!(wallet, MultisigDescriptor::assemble(2, vec![b.clone(), c.clone(), a.clone()]).unwrap());
+        assert!(wallet.receive.starts_with("wsh(sortedmulti(2,["));
+        assert!(wallet.change.contains("/1/*"));
+        assert_eq!(MultisigDescriptor::from_descriptor(&wallet.receive).unwrap(), wallet);
+        assert_eq!(MultisigDescriptor::from_descriptor(&wallet.change).unwrap(), wallet);
+        check(&wallet.receive).unwrap();
+        assert!(check(&wallet.receive.replace("sortedmulti(2", "sortedmulti(3")).is_err());
+        assert!(wallet.includes(&b));
+        assert!(MultisigDescriptor::assemble(4, vec![a.clone(), b.clone(), c.clone()]).is_err());
+        assert!(MultisigDescriptor::assemble(2, vec![a.clone(), a.clone()]).is_err());
+        // one xpub under two fingerprints, sorting on either side of another key
+        let low = KeyContribution { fingerprint: Fingerprint::from_str("00000000").unwrap(), ..a.clone() };
+        let high = KeyContribution { fingerprint: Fingerprint::from_str("ffffffff").unwrap(), ..a.clone() };
+        assert!(MultisigDescriptor::assemble(3, vec![low.clone(), b.clone(), high.clone()]).is_err());
+        assert!(MultisigDescriptor::assemble(2, vec![high, b.clone(), low]).is_err());
+        assert!(MultisigDescriptor::assemble(2, vec![a.clone(), b.clone(), c.clone(), b.clone()]).is_err());
+
+        let cosigners: Vec<XOnlyPair> = (0..3)
+            .map(|_| testing::keys())
+            .collect();
+        let post = |genesis: u64, from: &XOnlyPair, key: &KeyContribution| LocalPostModel {
+            id: genesis.to_string(),
+            genesis,
+            expiry: 0,
+            owner: from.pubkey,
+            post: Post::new(
+                Recipient::new(RecipientKind::Group, "vault".to_string()),
+                Payload::new(PayloadKind::Xpub, key.to_string()),
+                from.clone(),
+            ),
+        };
+        // the first cosigner replaced their key
+        let posts = vec![
+            post(1, &cosigners[0], &c),
+            post(2, &cosigners[0], &a),
+            post(3, &cosigners[1], &b),
+            post(4, &cosigners[2], &c),
+        ];
+        assert_eq!(collect(&posts).len(), 3);
+        assert_eq!(assemble_from_posts(2, &posts).unwrap(), wallet);
+        // a cosigner reposting someone else's key does not shrink the wallet to 2-of-2
+        let mut copied = posts.clone();
+        copied.push(post(5, &cosigners[2], &b));
+        assert!(assemble_from_posts(2, &copied).is_err());
+    }
+}
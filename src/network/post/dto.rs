@@ -1,8 +1,7 @@
 use crate::util::e::{ErrorKind, S5Error};
 use serde::{Deserialize, Serialize};
 
-use ureq::{Proxy, AgentBuilder};
-use crate::network::handler::{HttpHeader,HttpMethod,APIEndPoint,ServerStatusResponse, OwnedBy, sign_request};
+use crate::network::handler::{HttpHeader,HttpMethod,APIEndPoint,ServerStatusResponse, OwnedBy, NetworkConfig, build_agent, call_with_retry, send_json_with_retry, sign_request, read_body};
 use crate::network::post::model::{LocalPostModel, Post, DecryptionKey, DerivationIndex,AllPosts};
 use bitcoin::util::bip32::ExtendedPrivKey;
 use crate::key::encryption::{nonce,key_hash256,cc20p1305_decrypt};
@@ -25,6 +24,15 @@ impl ServerPostRequest{
             cypher_json: cypher_json.to_string()
         }
     }
+    /// Same request => same key, so the server can drop a resubmission after a lost response.
+    /// The cypher_json carries a random nonce, so two sends of the same message never collide.
+    pub fn idempotency_key(&self)->String{
+        key_hash256(&format!("{}:{}:{}", self.expiry, self.derivation_index, self.cypher_json))
+    }
+    /// True if `post` is what the server stored for this request.
+    pub fn matches(&self, post: &ServerPostModel)->bool{
+        post.derivation_index == self.derivation_index && post.cypher_json == self.cypher_json
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -42,32 +50,21 @@ impl ServerPostIdResponse{
     }
 }
 
-pub fn create(host: String, socks5: Option<u32>, key_pair: XOnlyPair, cpost_req: ServerPostRequest)->Result<String, S5Error>{
+pub fn create(host: String, network: NetworkConfig, key_pair: XOnlyPair, cpost_req: ServerPostRequest)->Result<String, S5Error>{
     let full_url = host + &APIEndPoint::Post(None).to_string();
-    let nonce = nonce();
-    let signature = sign_request(key_pair.clone(), HttpMethod::Put, APIEndPoint::Post(None), &nonce).unwrap();
-    let proxy = if socks5.is_some(){ 
-        Some(Proxy::new(&format!("socks5://localhost:{}",socks5.unwrap().to_string())).unwrap())
-    }
-    else{
-        None
-    };
-    let agent = if proxy.is_some(){
-        AgentBuilder::new()
-        .proxy(proxy.unwrap())
-        .build()
-    }
-    else{
-        AgentBuilder::new()
-        .build()
-    };
-    match agent.put(&full_url)
-        .set(&HttpHeader::Signature.to_string(), &signature)
-        .set(&HttpHeader::Pubkey.to_string(), &key_pair.pubkey.to_string())
-        .set(&HttpHeader::Nonce.to_string(), &nonce)
-        .send_json(cpost_req){
+    let idempotency_key = cpost_req.idempotency_key();
+    let agent = build_agent(&network, Some(key_pair.pubkey))?;
+    match send_json_with_retry(network.retry, &cpost_req, ||{
+        let nonce = nonce();
+        let signature = sign_request(key_pair.clone(), HttpMethod::Put, APIEndPoint::Post(None), &nonce).unwrap();
+        agent.put(&full_url)
+            .set(&HttpHeader::Signature.to_string(), &signature)
+            .set(&HttpHeader::Pubkey.to_string(), &key_pair.pubkey.to_string())
+            .set(&HttpHeader::Nonce.to_string(), &nonce)
+            .set(&HttpHeader::IdempotencyKey.to_string(), &idempotency_key)
+        }){
             Ok(response)=>  
-                match ServerPostIdResponse::structify(&response.into_string().unwrap())
+                match ServerPostIdResponse::structify(&read_body(response)?)
                 {
                     Ok(result)=>{
                         Ok(result.id)
@@ -83,31 +80,17 @@ pub fn create(host: String, socks5: Option<u32>, key_pair: XOnlyPair, cpost_req:
 
 }
 
-pub fn remove(host: String, socks5: Option<u32>, key_pair: XOnlyPair, id: String)->Result<(), S5Error>{
+pub fn remove(host: String, network: NetworkConfig, key_pair: XOnlyPair, id: String)->Result<(), S5Error>{
     let full_url = host + &APIEndPoint::Post(Some(id.clone())).to_string();
     let nonce = nonce();
     let signature = sign_request(key_pair.clone(), HttpMethod::Delete, APIEndPoint::Post(Some(id)), &nonce).unwrap();
-    let proxy = if socks5.is_some(){ 
-        Some(Proxy::new(&format!("socks5://localhost:{}",socks5.unwrap().to_string())).unwrap())
-    }
-    else{
-        None
-    };
-    let agent = if proxy.is_some(){
-        AgentBuilder::new()
-        .proxy(proxy.unwrap())
-        .build()
-    }
-    else{
-        AgentBuilder::new()
-        .build()
-    };
+    let agent = build_agent(&network, Some(key_pair.pubkey))?;
     match agent.delete(&full_url)
         .set(&HttpHeader::Signature.to_string(), &signature)
         .set(&HttpHeader::Pubkey.to_string(), &key_pair.pubkey.to_string())
         .set(&HttpHeader::Nonce.to_string(), &nonce)
         .call(){
-            Ok(response)=> match ServerStatusResponse::structify(&response.into_string().unwrap())
+            Ok(response)=> match ServerStatusResponse::structify(&read_body(response)?)
             {
                 Ok(result)=>{
                     if result.status {
@@ -141,34 +124,28 @@ impl ServerPostKeysRequest{
             decryption_keys
         }
     }
+    pub fn idempotency_key(&self)->String{
+        let mut receivers: Vec<String> = self.decryption_keys.iter().map(|key| key.receiver.to_string()).collect();
+        receivers.sort();
+        key_hash256(&format!("{}:{}", self.post_id, receivers.join(",")))
+    }
 }
 
-pub fn keys(host: String, socks5: Option<u32>, key_pair: XOnlyPair, post_id: String, decryption_keys: Vec<DecryptionKey>)->Result<(), S5Error>{
+pub fn keys(host: String, network: NetworkConfig, key_pair: XOnlyPair, post_id: String, decryption_keys: Vec<DecryptionKey>)->Result<(), S5Error>{
     let full_url = host + &APIEndPoint::PostKeys.to_string();
-    let nonce = nonce();
-    let signature = sign_request(key_pair.clone(), HttpMethod::Put, APIEndPoint::PostKeys, &nonce).unwrap();
     let body = ServerPostKeysRequest::new(&post_id, decryption_keys);
-    let proxy = if socks5.is_some(){ 
-        Some(Proxy::new(&format!("socks5://localhost:{}",socks5.unwrap().to_string())).unwrap())
-    }
-    else{
-        None
-    };
-    let agent = if proxy.is_some(){
-        AgentBuilder::new()
-        .proxy(proxy.unwrap())
-        .build()
-    }
-    else{
-        AgentBuilder::new()
-        .build()
-    };
-    match agent.put(&full_url)
-        .set(&HttpHeader::Signature.to_string(), &signature)
-        .set(&HttpHeader::Pubkey.to_string(), &key_pair.pubkey.to_string())
-        .set(&HttpHeader::Nonce.to_string(), &nonce)
-        .send_json(body){
-            Ok(response)=> match ServerStatusResponse::structify(&response.into_string().unwrap())
+    let idempotency_key = body.idempotency_key();
+    let agent = build_agent(&network, Some(key_pair.pubkey))?;
+    match send_json_with_retry(network.retry, &body, ||{
+        let nonce = nonce();
+        let signature = sign_request(key_pair.clone(), HttpMethod::Put, APIEndPoint::PostKeys, &nonce).unwrap();
+        agent.put(&full_url)
+            .set(&HttpHeader::Signature.to_string(), &signature)
+            .set(&HttpHeader::Pubkey.to_string(), &key_pair.pubkey.to_string())
+            .set(&HttpHeader::Nonce.to_string(), &nonce)
+            .set(&HttpHeader::IdempotencyKey.to_string(), &idempotency_key)
+        }){
+            Ok(response)=> match ServerStatusResponse::structify(&read_body(response)?)
             {
                 Ok(result)=>{
                     if result.status {
@@ -207,7 +184,7 @@ impl ServerPostModel{
 
         // check if reponse owner is self or other
         if self.owner == my_xonly_pair.pubkey {
-            let decryption_key_root = child::hex(social_root.to_string(), self.clone().derivation_index).unwrap();
+            let decryption_key_root = child::hex(social_root.to_string(), self.derivation_index)?;
             let decryption_key = key_hash256(&decryption_key_root);
             let plain_json_string = match cc20p1305_decrypt(&self.clone().cypher_json, &decryption_key){
                 Ok(result)=>result,
@@ -219,12 +196,15 @@ impl ServerPostModel{
                 genesis: self.genesis,
                 expiry: self.expiry,
                 owner:  self.owner,
-                post: Post::structify(&plain_json_string).unwrap(),
+                post: Post::structify(&plain_json_string)?,
             })
         }
         else {
-            let shared_secret = my_xonly_pair.compute_shared_secret(xonly_to_public_key(self.clone().owner)).unwrap();
-            let decryption_key = cc20p1305_decrypt(&self.clone().decryption_key.unwrap(), &shared_secret).unwrap_or("Bad Key".to_string());
+            let shared_secret = my_xonly_pair.compute_shared_secret(xonly_to_public_key(self.owner))?;
+            let decryption_key = match &self.decryption_key {
+                Some(decryption_key)=>cc20p1305_decrypt(decryption_key, &shared_secret).unwrap_or("Bad Key".to_string()),
+                None=>return Err(S5Error::new(ErrorKind::Key, "No decryption key for this post"))
+            };
             let plain_json_string = match cc20p1305_decrypt(&self.cypher_json, &decryption_key){
                 Ok(result)=>result,
                 Err(_)=>return Err(S5Error::new(ErrorKind::Key, "Decryption Error"))
@@ -235,7 +215,7 @@ impl ServerPostModel{
                 genesis: self.genesis,
                 expiry: self.expiry,
                 owner: self.owner,
-                post: Post::structify(&plain_json_string).unwrap(),
+                post: Post::structify(&plain_json_string)?,
             })
         }
 
@@ -257,32 +237,19 @@ impl ServerPostModelResponse{
     }
 }
 
-fn my_posts(host: String, socks5: Option<u32>, key_pair: XOnlyPair, filter: Option<u64>)->Result<Vec<ServerPostModel>, S5Error>{
+pub fn my_posts(host: String, network: NetworkConfig, key_pair: XOnlyPair, filter: Option<u64>)->Result<Vec<ServerPostModel>, S5Error>{
     let filter = if filter.is_some(){"?genesis_filter=".to_string() + &filter.unwrap().to_string()}else{"".to_string()};
     let full_url = host.to_string() + &APIEndPoint::Posts(OwnedBy::Me).to_string() + &filter;
-    let nonce = nonce();
-    let signature = sign_request(key_pair.clone(), HttpMethod::Get, APIEndPoint::Posts(OwnedBy::Me), &nonce).unwrap();
-    let proxy = if socks5.is_some(){ 
-        Some(Proxy::new(&format!("socks5://localhost:{}",socks5.unwrap().to_string())).unwrap())
-    }
-    else{
-        None
-    };
-    let agent = if proxy.is_some(){
-        AgentBuilder::new()
-        .proxy(proxy.unwrap())
-        .build()
-    }
-    else{
-        AgentBuilder::new()
-        .build()
-    };
-    match agent.get(&full_url)
-        .set(&HttpHeader::Signature.to_string(), &signature)
-        .set(&HttpHeader::Pubkey.to_string(), &key_pair.pubkey.to_string())
-        .set(&HttpHeader::Nonce.to_string(), &nonce)
-        .call(){
-            Ok(response)=> match ServerPostModelResponse::structify(&response.into_string().unwrap())
+    let agent = build_agent(&network, Some(key_pair.pubkey))?;
+    match call_with_retry(network.retry, ||{
+        let nonce = nonce();
+        let signature = sign_request(key_pair.clone(), HttpMethod::Get, APIEndPoint::Posts(OwnedBy::Me), &nonce).unwrap();
+        agent.get(&full_url)
+            .set(&HttpHeader::Signature.to_string(), &signature)
+            .set(&HttpHeader::Pubkey.to_string(), &key_pair.pubkey.to_string())
+            .set(&HttpHeader::Nonce.to_string(), &nonce)
+        }){
+            Ok(response)=> match ServerPostModelResponse::structify(&read_body(response)?)
             {
                 Ok(result)=>{
                     Ok(result.posts)
@@ -297,33 +264,20 @@ fn my_posts(host: String, socks5: Option<u32>, key_pair: XOnlyPair, filter: Opti
         }
 }
 
-fn others_posts(host: String, socks5: Option<u32>, key_pair: XOnlyPair, filter: Option<u64>)->Result<Vec<ServerPostModel>, S5Error>{
+fn others_posts(host: String, network: NetworkConfig, key_pair: XOnlyPair, filter: Option<u64>)->Result<Vec<ServerPostModel>, S5Error>{
     let filter = if filter.is_some(){"?genesis_filter=".to_string() + &filter.unwrap().to_string()}else{"".to_string()};
     let full_url = host.to_string() + &APIEndPoint::Posts(OwnedBy::Others).to_string() + &filter;
 
-    let nonce = nonce();
-    let signature = sign_request(key_pair.clone(), HttpMethod::Get, APIEndPoint::Posts(OwnedBy::Others), &nonce).unwrap();
-    let proxy = if socks5.is_some(){ 
-        Some(Proxy::new(&format!("socks5://localhost:{}",socks5.unwrap().to_string())).unwrap())
-    }
-    else{
-        None
-    };
-    let agent = if proxy.is_some(){
-        AgentBuilder::new()
-        .proxy(proxy.unwrap())
-        .build()
-    }
-    else{
-        AgentBuilder::new()
-        .build()
-    };
-    match agent.get(&full_url)
-        .set(&HttpHeader::Signature.to_string(), &signature)
-        .set(&HttpHeader::Pubkey.to_string(), &key_pair.pubkey.to_string())
-        .set(&HttpHeader::Nonce.to_string(), &nonce)
-        .call(){
-            Ok(response)=> match ServerPostModelResponse::structify(&response.into_string().unwrap())
+    let agent = build_agent(&network, Some(key_pair.pubkey))?;
+    match call_with_retry(network.retry, ||{
+        let nonce = nonce();
+        let signature = sign_request(key_pair.clone(), HttpMethod::Get, APIEndPoint::Posts(OwnedBy::Others), &nonce).unwrap();
+        agent.get(&full_url)
+            .set(&HttpHeader::Signature.to_string(), &signature)
+            .set(&HttpHeader::Pubkey.to_string(), &key_pair.pubkey.to_string())
+            .set(&HttpHeader::Nonce.to_string(), &nonce)
+        }){
+            Ok(response)=> match ServerPostModelResponse::structify(&read_body(response)?)
             {
                 Ok(result)=>{
                     Ok(result.posts)
@@ -349,10 +303,10 @@ fn process_cypherposts(social_root: ExtendedPrivKey,posts: Vec<ServerPostModel>)
     Ok(AllPosts::new(plains))
 }
 
-pub fn get_all_posts(host: String, socks5: Option<u32>,  social_root: ExtendedPrivKey, filter: Option<u64>)->Result<AllPosts,S5Error>{
+pub fn get_all_posts(host: String, network: NetworkConfig,  social_root: ExtendedPrivKey, filter: Option<u64>)->Result<AllPosts,S5Error>{
     let xonly_pair = XOnlyPair::from_xprv(social_root.clone());
-    let mut all_posts = my_posts(host.clone(),socks5, xonly_pair.clone(), filter).unwrap();
-    all_posts.append(&mut others_posts(host,socks5, xonly_pair, filter).unwrap());
+    let mut all_posts = my_posts(host.clone(),network.clone(), xonly_pair.clone(), filter)?;
+    all_posts.append(&mut others_posts(host,network, xonly_pair, filter)?);
     process_cypherposts(social_root, all_posts)
 }
 
@@ -371,31 +325,18 @@ impl ServerPostSingleResponse{
     }
 }
 
-pub fn single_post(host: String, socks5: Option<u32>, xonly_pair: XOnlyPair,post_id: String)->Result<ServerPostModel, S5Error>{
+pub fn single_post(host: String, network: NetworkConfig, xonly_pair: XOnlyPair,post_id: String)->Result<ServerPostModel, S5Error>{
     let full_url = host + &APIEndPoint::Post(Some(post_id.to_string())).to_string();
-    let nonce = nonce();
-    let signature = sign_request(xonly_pair.clone(), HttpMethod::Get, APIEndPoint::Post(Some(post_id.to_string())), &nonce).unwrap();
-    let proxy = if socks5.is_some(){ 
-        Some(Proxy::new(&format!("socks5://localhost:{}",socks5.unwrap().to_string())).unwrap())
-    }
-    else{
-        None
-    };
-    let agent = if proxy.is_some(){
-        AgentBuilder::new()
-        .proxy(proxy.unwrap())
-        .build()
-    }
-    else{
-        AgentBuilder::new()
-        .build()
-    };
-    match agent.get(&full_url)
-        .set(&HttpHeader::Signature.to_string(), &signature)
-        .set(&HttpHeader::Pubkey.to_string(), &xonly_pair.pubkey.to_string())
-        .set(&HttpHeader::Nonce.to_string(), &nonce)
-        .call(){
-            Ok(response)=> match ServerPostSingleResponse::structify(&response.into_string().unwrap())
+    let agent = build_agent(&network, Some(xonly_pair.pubkey))?;
+    match call_with_retry(network.retry, ||{
+        let nonce = nonce();
+        let signature = sign_request(xonly_pair.clone(), HttpMethod::Get, APIEndPoint::Post(Some(post_id.to_string())), &nonce).unwrap();
+        agent.get(&full_url)
+            .set(&HttpHeader::Signature.to_string(), &signature)
+            .set(&HttpHeader::Pubkey.to_string(), &xonly_pair.pubkey.to_string())
+            .set(&HttpHeader::Nonce.to_string(), &nonce)
+        }){
+            Ok(response)=> match ServerPostSingleResponse::structify(&read_body(response)?)
             {
                 Ok(result)=>{
                     Ok(result.post)
@@ -411,31 +352,18 @@ pub fn single_post(host: String, socks5: Option<u32>, xonly_pair: XOnlyPair,post
 }
 
 
-pub fn last_derivation(host: String, socks5: Option<u32>, xonly_pair: XOnlyPair)->Result<DerivationIndex, S5Error>{
+pub fn last_derivation(host: String, network: NetworkConfig, xonly_pair: XOnlyPair)->Result<DerivationIndex, S5Error>{
     let full_url = host + &APIEndPoint::LastDerivation.to_string();
-    let nonce = nonce();
-    let signature = sign_request(xonly_pair.clone(), HttpMethod::Get, APIEndPoint::LastDerivation, &nonce).unwrap();
-    let proxy = if socks5.is_some(){ 
-        Some(Proxy::new(&format!("socks5://localhost:{}",socks5.unwrap().to_string())).unwrap())
-    }
-    else{
-        None
-    };
-    let agent = if proxy.is_some(){
-        AgentBuilder::new()
-        .proxy(proxy.unwrap())
-        .build()
-    }
-    else{
-        AgentBuilder::new()
-        .build()
-    };
-    match agent.get(&full_url)
-        .set(&HttpHeader::Signature.to_string(), &signature)
-        .set(&HttpHeader::Pubkey.to_string(), &xonly_pair.pubkey.to_string())
-        .set(&HttpHeader::Nonce.to_string(), &nonce)
-        .call(){
-            Ok(response)=> match DerivationIndex::structify(&response.into_string().unwrap())
+    let agent = build_agent(&network, Some(xonly_pair.pubkey))?;
+    match call_with_retry(network.retry, ||{
+        let nonce = nonce();
+        let signature = sign_request(xonly_pair.clone(), HttpMethod::Get, APIEndPoint::LastDerivation, &nonce).unwrap();
+        agent.get(&full_url)
+            .set(&HttpHeader::Signature.to_string(), &signature)
+            .set(&HttpHeader::Pubkey.to_string(), &xonly_pair.pubkey.to_string())
+            .set(&HttpHeader::Nonce.to_string(), &nonce)
+        }){
+            Ok(response)=> match DerivationIndex::structify(&read_body(response)?)
             {
                 Ok(result)=>{
                     Ok(result)
@@ -454,6 +382,7 @@ pub fn last_derivation(host: String, socks5: Option<u32>, xonly_pair: XOnlyPair)
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::network::mock::{MockServer, ADMIN_SECRET};
     use crate::network::identity::dto::{admin_invite,user_invite,register,get_all};
     use crate::key::ec;
     use crate::key::seed;
@@ -465,15 +394,67 @@ mod tests {
     use bitcoin::util::bip32::{ExtendedPrivKey};
     use std::str::FromStr;
     #[test]
-    #[ignore]
+    fn test_idempotency_keys(){
+        let request = ServerPostRequest::new(0, 3, "nonce:cypher");
+        assert_eq!(request.idempotency_key(), request.clone().idempotency_key());
+        assert_ne!(request.idempotency_key(), ServerPostRequest::new(0, 4, "nonce:cypher").idempotency_key());
+
+        let seed1 = seed::MasterKeySeed::generate(24, "", Network::Bitcoin).unwrap();
+        let xonly_pair1 = ec::XOnlyPair::from_xprv(seed1.xprv);
+        let seed2 = seed::MasterKeySeed::generate(24, "", Network::Bitcoin).unwrap();
+        let xonly_pair2 = ec::XOnlyPair::from_xprv(seed2.xprv);
+        let seed3 = seed::MasterKeySeed::generate(24, "", Network::Bitcoin).unwrap();
+        let xonly_pair3 = ec::XOnlyPair::from_xprv(seed3.xprv);
+        let keys_a = DecryptionKey::make_for_many(xonly_pair1.clone(), [xonly_pair2.pubkey, xonly_pair3.pubkey].to_vec(), "key".to_string()).unwrap();
+        let keys_b = DecryptionKey::make_for_many(xonly_pair1, [xonly_pair3.pubkey, xonly_pair2.pubkey].to_vec(), "key".to_string()).unwrap();
+        // order of recipients does not matter
+        assert_eq!(
+            ServerPostKeysRequest::new("post1", keys_a.clone()).idempotency_key(),
+            ServerPostKeysRequest::new("post1", keys_b).idempotency_key()
+        );
+        assert_ne!(
+            ServerPostKeysRequest::new("post1", keys_a.clone()).idempotency_key(),
+            ServerPostKeysRequest::new("post2", keys_a).idempotency_key()
+        );
+    }
+
+    #[test]
+    fn test_decypher_malformed_post(){
+        let seed = seed::MasterKeySeed::generate(24, "", Network::Bitcoin).unwrap();
+        let social_root = ExtendedPrivKey::from_str(&child::social_root(seed.xprv.to_string(),0).unwrap().xprv).unwrap();
+        let owner = ec::XOnlyPair::from_xprv(social_root).pubkey;
+        let encryption_key = key_hash256(&child::hex(social_root.to_string(), 1).unwrap());
+        let server_post = |plain: &str, decryption_key: Option<String>| ServerPostModel{
+            id: "s5id".to_string(),
+            genesis: 1,
+            expiry: 0,
+            owner,
+            cypher_json: crate::key::encryption::cc20p1305_encrypt(plain, &encryption_key).unwrap(),
+            derivation_index: 1,
+            decryption_key,
+        };
+        assert!(server_post("not a post", None).decypher(social_root).is_err());
+        assert!(server_post("{\"to\":{\"kind\":\"direct\",\"value\":\"x\"},\"payload\":{\"kind\":\"unknown\",\"value\":\"\"}}", None)
+            .decypher(social_root)
+            .is_err());
+        let other = seed::MasterKeySeed::generate(24, "", Network::Bitcoin).unwrap();
+        let other_root = ExtendedPrivKey::from_str(&child::social_root(other.xprv.to_string(),0).unwrap().xprv).unwrap();
+        assert!(server_post("not a post", None).decypher(other_root).is_err());
+
+        let mut all = process_cypherposts(social_root, vec![server_post("not a post", None)]).unwrap();
+        assert!(all.to_all_posts_as_chat(owner, Network::Bitcoin, None).verified.is_empty());
+    }
+
+    #[test]
     fn test_post_dto(){
-        let url = "http://localhost:3021".to_string();
+        let server = MockServer::start();
+        let url = server.url();
         // ADMIN INVITE
-        let admin_invite_code = "098f6bcd4621d373cade4e832627b4f6".to_string();
-        let client_invite_code1 = admin_invite(url.clone(), None,admin_invite_code.clone(),InvitePermission::Standard).unwrap();
+        let admin_invite_code = ADMIN_SECRET.to_string();
+        let client_invite_code1 = admin_invite(url.clone(), NetworkConfig::default(), admin_invite_code.clone(),InvitePermission::Standard).unwrap();
         assert_eq!(client_invite_code1.invite_code.len() , 32);
 
-        let client_invite_code2 = admin_invite(url.clone(), None,admin_invite_code.clone(),InvitePermission::Privilege(1)).unwrap();
+        let client_invite_code2 = admin_invite(url.clone(), NetworkConfig::default(), admin_invite_code.clone(),InvitePermission::Privilege(1)).unwrap();
         assert_eq!(client_invite_code2.invite_code.len() , 32);
 
         // REGISTER USERS
@@ -484,7 +465,7 @@ mod tests {
         let social_child1 = ExtendedPrivKey::from_str(&child::social_root(seed1.xprv.to_string(),0).unwrap().xprv).unwrap();
         let my_identity = UserIdentity::new(social_child1.to_string()).unwrap();
         let xonly_pair1 = ec::XOnlyPair::from_xprv(my_identity.social_root);
-        assert!(register(url.clone(), None, xonly_pair1.clone(), client_invite_code1.invite_code, user1).is_ok());
+        assert!(register(url.clone(), NetworkConfig::default(), xonly_pair1.clone(), client_invite_code1.invite_code, user1).is_ok());
         
         let seed2 = seed::MasterKeySeed::generate(24, "", Network::Bitcoin).unwrap();
         let social_child2 = ExtendedPrivKey::from_str(&child::social_root(seed2.xprv.to_string(),0).unwrap().xprv).unwrap();
@@ -492,9 +473,9 @@ mod tests {
 
         let xonly_pair2 = ec::XOnlyPair::from_xprv(social_child2);
         let user2 = "facilitator".to_string() + &nonce[0..3];
-        assert!(register(url.clone(), None, xonly_pair2.clone(), client_invite_code2.invite_code.clone(), user2).is_ok());
+        assert!(register(url.clone(), NetworkConfig::default(), xonly_pair2.clone(), client_invite_code2.invite_code.clone(), user2).is_ok());
 
-        let client_invite_code3 = user_invite(url.clone(), None, xonly_pair2.clone(),client_invite_code2.invite_code.clone()).unwrap();
+        let client_invite_code3 = user_invite(url.clone(), NetworkConfig::default(), xonly_pair2.clone(),client_invite_code2.invite_code.clone()).unwrap();
         assert_eq!(client_invite_code3.invite_code.len() , 32);
 
         let seed3 = seed::MasterKeySeed::generate(24, "", Network::Bitcoin).unwrap();
@@ -503,10 +484,10 @@ mod tests {
 
         let xonly_pair3 = ec::XOnlyPair::from_xprv(social_child3);
         let user3 = "escrow".to_string() + &nonce[0..3];
-        assert!(register(url.clone(), None, xonly_pair3.clone(), client_invite_code3.invite_code, user3).is_ok());
+        assert!(register(url.clone(), NetworkConfig::default(), xonly_pair3.clone(), client_invite_code3.invite_code, user3).is_ok());
 
         // GET ALL USERS
-        let members = get_all(url.clone(), None,xonly_pair3.clone()).unwrap();
+        let members = get_all(url.clone(), NetworkConfig::default(), xonly_pair3.clone()).unwrap();
         let user_count = members.identities.len();
         assert!(user_count>0);
 
@@ -517,10 +498,11 @@ mod tests {
         let encryption_key = my_identity.derive_encryption_key(index);
         let cypher_json = post.to_cypher(encryption_key.clone());
         let cpost_req = ServerPostRequest::new(0, index,&cypher_json);
-        let post_id = create(url.clone(), None,xonly_pair1.clone(), cpost_req).unwrap();
+        let post_id = create(url.clone(), NetworkConfig::default(), xonly_pair1.clone(), cpost_req).unwrap();
         assert_eq!(post_id.len(), 24);
         let decrypkeys = DecryptionKey::make_for_many(xonly_pair1.clone(),[xonly_pair3.clone().pubkey].to_vec(), encryption_key).unwrap();
-        assert!(keys(url.clone(), None, xonly_pair1.clone(), post_id.clone(),decrypkeys).is_ok());
+        assert!(keys(url.clone(), NetworkConfig::default(), xonly_pair1.clone(), post_id.clone(),decrypkeys).is_ok());
+        let user1_post_id = post_id.clone();
 
         // Create a struct to share as user3 to user1
         let message_to_share = Payload::new(PayloadKind::Message,"Hi guy :)".to_string());
@@ -529,10 +511,10 @@ mod tests {
         let encryption_key = e_identity.derive_encryption_key(index);
         let cypher_json = post.to_cypher(encryption_key.clone());
         let cpost_req = ServerPostRequest::new(0, index,&cypher_json);
-        let post_id = create(url.clone(), None,xonly_pair3.clone(), cpost_req).unwrap();
+        let post_id = create(url.clone(), NetworkConfig::default(), xonly_pair3.clone(), cpost_req).unwrap();
         assert_eq!(post_id.len(), 24);
         let decrypkeys = DecryptionKey::make_for_many(xonly_pair3.clone(),[xonly_pair1.clone().pubkey].to_vec(), encryption_key).unwrap();
-        assert!(keys(url.clone(), None, xonly_pair3.clone(), post_id.clone(),decrypkeys).is_ok());
+        assert!(keys(url.clone(), NetworkConfig::default(), xonly_pair3.clone(), post_id.clone(),decrypkeys).is_ok());
 
         // Create a struct to share as user2 to group
         let message_to_share = Payload::new(PayloadKind::Message,"hey fren :)".to_string());
@@ -542,23 +524,26 @@ mod tests {
         let encryption_key = fac_identity.derive_encryption_key(index);
         let cypher_json = post.to_cypher(encryption_key.clone());
         let cpost_req = ServerPostRequest::new(0, index,&cypher_json);
-        let post_id = create(url.clone(), None,xonly_pair2.clone(), cpost_req).unwrap();
+        let post_id = create(url.clone(), NetworkConfig::default(), xonly_pair2.clone(), cpost_req).unwrap();
         assert_eq!(post_id.len(), 24);
         let decrypkeys = DecryptionKey::make_for_many(xonly_pair2.clone(),[xonly_pair1.clone().pubkey,xonly_pair3.clone().pubkey].to_vec(), encryption_key).unwrap();
-        assert!(keys(url.clone(), None, xonly_pair2.clone(), post_id.clone(),decrypkeys).is_ok());
+        assert!(keys(url.clone(), NetworkConfig::default(), xonly_pair2.clone(), post_id.clone(),decrypkeys).is_ok());
 
         // Get posts & keys as user2
-        let all = get_all_posts(url.clone(), None, social_child2.clone(), None).unwrap();
+        let all = get_all_posts(url.clone(), NetworkConfig::default(), social_child2.clone(), None).unwrap();
         assert_eq!(all.posts.len(),1);
         // Get posts & keys as user3
-        let all = get_all_posts(url.clone(), None, social_child3.clone(), None).unwrap();
+        let all = get_all_posts(url.clone(), NetworkConfig::default(), social_child3.clone(), None).unwrap();
         assert_eq!(all.posts.len(),3);
         // Get posts as self
-        let mut all = get_all_posts(url.clone(), None, my_identity.social_root, None).unwrap();
+        let mut all = get_all_posts(url.clone(), NetworkConfig::default(), my_identity.social_root, None).unwrap();
         assert_eq!(all.posts.len(),3);
-        println!("{:#?}",all.to_all_posts_as_chat(xonly_pair1.pubkey));
-        // Delete post
-        assert!(remove(url.clone(), None,xonly_pair1.clone(), post_id.clone()).is_ok());
+        println!("{:#?}",all.to_all_posts_as_chat(xonly_pair1.pubkey, my_identity.social_root.network, None));
+        // Delete post: only the owner can
+        assert!(remove(url.clone(), NetworkConfig::default(), xonly_pair1.clone(), post_id.clone()).is_err());
+        assert!(remove(url.clone(), NetworkConfig::default(), xonly_pair1.clone(), user1_post_id).is_ok());
+        let all = get_all_posts(url.clone(), NetworkConfig::default(), social_child3, None).unwrap();
+        assert_eq!(all.posts.len(),2);
         // KEEP BUILDING!
     }
 }
\ No newline at end of file
@@ -1,2 +1,11 @@
 pub mod model;
-pub mod dto;
\ No newline at end of file
+pub mod dto;
+pub mod psbt;
+pub mod descriptor;
+pub mod payment;
+pub mod recovery;
+pub mod trade;
+pub mod receipt;
+pub mod attachment;
+pub mod outbox;
+pub mod conversation;
@@ -2,12 +2,22 @@ use crate::key::encryption::{self,key_hash256};
 use crate::key::ec::{XOnlyPair,xonly_to_public_key,schnorr_verify};
 use bitcoin::secp256k1::schnorr::Signature;
 use bitcoin::secp256k1::{XOnlyPublicKey};
+use bitcoin::network::constants::Network;
 use serde::{Deserialize, Serialize};
 use crate::util::e::{S5Error,ErrorKind};
+use crate::network::identity::directory::{ContactFlag,MemberDirectory};
+use crate::network::identity::profile::{Profiles,SignedProfile};
+use crate::network::identity::contact::{Contacts,KeyChange};
+use crate::network::badge::reputation::{FilterAction,PostFilter};
+use crate::network::post::{descriptor,psbt};
+use crate::network::post::payment::PaymentRequest;
+use crate::network::post::trade::TradeMessage;
+use crate::network::post::receipt::{self,PostStatus,Receipt};
+use crate::network::post::attachment::{AttachmentChunk,AttachmentManifest};
 use::std::str::FromStr;
 use std::ffi::CString;
 use std::os::raw::c_char;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap,BTreeSet};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PostId {
@@ -70,8 +80,7 @@ impl LocalPostModel{
         }
     }
     pub fn verify(&self)->Result<(),S5Error>{
-      let checksum_message = self.post.to.to_string() + ":" + &self.post.payload.to_string();
-      let checksum = key_hash256(&checksum_message);
+      let checksum = Post::checksum(&self.post.to, &self.post.payload, &self.post.links);
       if checksum != self.post.checksum{
         Err(S5Error::new(ErrorKind::Post,"Checksum Mismatch! Cannot trust this message"))
       }
@@ -79,12 +88,154 @@ impl LocalPostModel{
         schnorr_verify(self.post.signature, &self.post.checksum, self.owner)    
       } 
     }
+    /// Payload checks that depend on the owner and the reader's `network`, on top of `Payload::check`.
+    pub fn check(&self, network: Network)->Result<(),S5Error>{
+      self.post.payload.check()?;
+      self.post.links.check(&self.post.payload)?;
+      match self.post.payload.kind{
+        PayloadKind::PaymentRequest=>{
+          PaymentRequest::structify(&self.post.payload.value)?.verify(&self.owner, network)?;
+        }
+        PayloadKind::Receipt if !matches!(self.post.to.kind, RecipientKind::Direct)=>{
+          return Err(S5Error::new(ErrorKind::Post,"Receipts must be direct posts."));
+        }
+        PayloadKind::Trade=>{
+          let message = TradeMessage::structify(&self.post.payload.value)?;
+          if message.by != self.owner{
+            return Err(S5Error::new(ErrorKind::Post,"Trade message was signed by someone else."));
+          }
+        }
+        _=>()
+      }
+      Ok(())
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PostsAsChat{
     pub counter_party: String,
     pub posts: Vec<LocalPostModel>,
+    /// Filled by `SortedPosts::resolve_contacts` for direct chats.
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub flags: Vec<ContactFlag>,
+    /// `posts` with edits applied and reactions attached, grouped by the post each thread starts from.
+    #[serde(default)]
+    pub threads: Vec<Thread>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Reaction{
+    pub id: String,
+    pub by: XOnlyPublicKey,
+    pub genesis: u64,
+    pub reaction: String,
+}
+
+/// A post as shown in a chat.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChatMessage{
+    /// As first posted, so it still verifies.
+    pub post: LocalPostModel,
+    /// The latest version.
+    pub payload: Payload,
+    /// Edit posts, earliest first.
+    pub history: Vec<LocalPostModel>,
+    pub reactions: Vec<Reaction>,
+}
+
+impl ChatMessage{
+    fn new(post: LocalPostModel)->Self{
+        ChatMessage{
+            payload: post.post.payload.clone(),
+            post,
+            history: [].to_vec(),
+            reactions: [].to_vec(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Thread{
+    pub root: ChatMessage,
+    /// Replies to the root or to other replies, earliest first.
+    pub replies: Vec<ChatMessage>,
+}
+
+/// Build the threads of one chat from its posts, earliest first. Edits of posts we do not own
+/// and edits or reactions whose target is not in the chat are pushed to `corrupted`.
+fn to_threads(posts: &[LocalPostModel], corrupted: &mut Vec<String>)->Vec<Thread>{
+    let mut messages = Vec::<ChatMessage>::new();
+    let mut positions = BTreeMap::<String, usize>::new();
+    for post in posts.iter().filter(|post| post.post.links.edits.is_none() && post.post.links.reacts_to.is_none()){
+        positions.insert(post.id.clone(), messages.len());
+        messages.push(ChatMessage::new(post.clone()));
+    }
+    for post in posts.iter(){
+        if let Some(target) = &post.post.links.edits{
+            match positions.get(target).map(|position| &mut messages[*position]){
+                Some(message) if message.post.owner == post.owner && matches!(message.post.post.payload.kind, PayloadKind::Message)=>{
+                    message.payload = post.post.payload.clone();
+                    message.history.push(post.clone());
+                }
+                _=>corrupted.push(post.id.clone()),
+            }
+        }
+        if let Some(target) = &post.post.links.reacts_to{
+            match positions.get(target).map(|position| &mut messages[*position]){
+                Some(message)=>{
+                    let repeated = message.reactions.iter().any(|reaction| reaction.by == post.owner && reaction.reaction == post.post.payload.value);
+                    if !repeated{
+                        message.reactions.push(Reaction{
+                            id: post.id.clone(),
+                            by: post.owner,
+                            genesis: post.genesis,
+                            reaction: post.post.payload.value.clone(),
+                        });
+                    }
+                }
+                None=>corrupted.push(post.id.clone()),
+            }
+        }
+    }
+    // a reply joins the thread of the post it answers; replies to unknown posts start their own
+    let mut roots = Vec::<usize>::new();
+    for position in 0..messages.len(){
+        let mut root = position;
+        for _ in 0..messages.len(){
+            match messages[root].post.post.links.reply_to.as_ref().and_then(|target| positions.get(target)){
+                Some(parent) if *parent != root=>root = *parent,
+                _=>break,
+            }
+        }
+        roots.push(root);
+    }
+    let mut threads = BTreeMap::<usize, Thread>::new();
+    for (position, message) in messages.iter().enumerate(){
+        if roots[position] == position{
+            threads.insert(position, Thread{ root: message.clone(), replies: [].to_vec() });
+        }
+    }
+    for (position, message) in messages.into_iter().enumerate(){
+        if roots[position] != position{
+            if let Some(thread) = threads.get_mut(&roots[position]){
+                thread.replies.push(message);
+            }
+        }
+    }
+    threads.into_values().collect()
+}
+
+impl PostsAsChat{
+    pub fn structify(stringified: &str) -> Result<PostsAsChat, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => {
+                Err(S5Error::new(ErrorKind::Input, "Error structifying PostsAsChat"))
+            }
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -92,17 +243,35 @@ pub struct SortedPosts{
     pub verified: Vec<PostsAsChat>,
     pub corrupted: Vec<String>,
     pub latest_genesis: u64,
-
+    /// Profile posts are collected here instead of into chats.
+    #[serde(default)]
+    pub profiles: Profiles,
+    /// Set by `mark_verified`. Verified usernames now held by another key.
+    #[serde(default)]
+    pub key_changes: Vec<KeyChange>,
+    /// Chats held back by a `PostFilter`. Group chats only hold the posts from quarantined senders.
+    #[serde(default)]
+    pub quarantined: Vec<PostsAsChat>,
+    /// Receipts for my posts, see `post::receipt`. With a genesis filter, only from the receipts fetched.
+    #[serde(default)]
+    pub receipts: Vec<PostStatus>,
 }
 
-impl SortedPosts{
-    pub fn default()->Self{
+impl Default for SortedPosts{
+    fn default()->Self{
       SortedPosts{
         verified: [].to_vec(),
         corrupted: [].to_vec(),
         latest_genesis:0,
+        profiles: Profiles::default(),
+        key_changes: [].to_vec(),
+        quarantined: [].to_vec(),
+        receipts: [].to_vec(),
       }
     }
+}
+
+impl SortedPosts{
     pub fn c_stringify(&self) -> *mut c_char {
         let stringified = match serde_json::to_string(self) {
           Ok(result) => result,
@@ -123,6 +292,31 @@ impl SortedPosts{
             }
         }
     }
+    /// Label direct chats with the counter party's username and flag leavers, reused usernames and strangers.
+    /// Group chats are left as they are.
+    pub fn resolve_contacts(&mut self, directory: &MemberDirectory){
+        for chat in self.verified.iter_mut().chain(self.quarantined.iter_mut()){
+            if let Ok(pubkey) = XOnlyPublicKey::from_str(&chat.counter_party){
+                let (username, flags) = directory.contact(&pubkey);
+                chat.username = username;
+                chat.flags.extend(flags);
+            }
+        }
+    }
+    /// Flag direct chats with verified contacts, and with members holding a verified username under a new key.
+    pub fn mark_verified(&mut self, contacts: &Contacts, directory: &MemberDirectory){
+        for chat in self.verified.iter_mut().chain(self.quarantined.iter_mut()){
+            if let Ok(pubkey) = XOnlyPublicKey::from_str(&chat.counter_party){
+                if contacts.is_verified(&pubkey){
+                    chat.flags.push(ContactFlag::Verified);
+                }
+                if contacts.key_change(&pubkey, directory).is_some(){
+                    chat.flags.push(ContactFlag::KeyChanged);
+                }
+            }
+        }
+        self.key_changes = contacts.key_changes(directory);
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -155,16 +349,36 @@ impl AllPosts{
             }
         }
     }
-    pub fn to_all_posts_as_chat(&mut self, my_pubkey: XOnlyPublicKey)->SortedPosts{
-        if self.posts.len() == 0 {
-          SortedPosts::default();
+    /// Group verified posts into chats. With a `filter`, direct chats with members it quarantines and
+    /// group posts from them go to `SortedPosts::quarantined`; chats with members it flags get `ContactFlag::LowReputation`.
+    /// Posts that fail `LocalPostModel::check` for `network`, e.g. payment requests to another network, are corrupted.
+    pub fn to_all_posts_as_chat(&mut self, my_pubkey: XOnlyPublicKey, network: Network, filter: Option<&PostFilter>)->SortedPosts{
+        if self.posts.is_empty() {
+            return SortedPosts::default();
         }
         // earliest first
         self.posts.sort_by_key(|post| post.genesis);
         let mut btree = BTreeMap::<String, Vec<LocalPostModel>>::new();
+        let mut quarantine = BTreeMap::<String, Vec<LocalPostModel>>::new();
+        let mut flagged = BTreeSet::<String>::new();
         let mut corrupted:Vec<String> = [].to_vec();
+        let mut profiles = Profiles::default();
         for item in self.clone().posts.into_iter(){
-            if item.clone().verify().is_ok(){
+            if let PayloadKind::Profile = item.post.payload.kind{
+                match SignedProfile::structify(&item.post.payload.value){
+                    Ok(profile) if profile.pubkey == item.owner && item.verify().is_ok()=>profiles.insert(profile),
+                    _=>corrupted.push(item.id.clone()),
+                }
+                continue;
+            }
+            // receipts are merged into `receipts` below, and chunks are fetched through their attachment
+            if let PayloadKind::Receipt | PayloadKind::Chunk = item.post.payload.kind{
+                if item.verify().is_err() || item.check(network).is_err(){
+                    corrupted.push(item.id.clone());
+                }
+                continue;
+            }
+            if item.verify().is_ok() && item.check(network).is_ok(){
               let counter_party = match item.clone().post.to.kind{
                   RecipientKind::Direct=>{
                       if item.clone().owner == my_pubkey {
@@ -179,11 +393,26 @@ impl AllPosts{
                   }
               };
 
-              if btree.contains_key(&counter_party){
-                  btree.entry(counter_party).and_modify(|value| value.push(item));
-              }
-              else{
-                  btree.insert(counter_party,[item].to_vec());
+              // direct chats are judged by the counter party, so both sides stay together
+              let sender = match item.post.to.kind{
+                  RecipientKind::Direct=>XOnlyPublicKey::from_str(&counter_party).ok(),
+                  RecipientKind::Group=>Some(item.owner),
+              };
+              let action = match (filter, sender){
+                  (Some(filter), Some(sender)) if sender != my_pubkey=>filter.action(&sender),
+                  _=>FilterAction::Allow,
+              };
+              match action{
+                  FilterAction::Quarantine=>{
+                      quarantine.entry(counter_party).or_default().push(item);
+                  }
+                  FilterAction::Flag=>{
+                      flagged.insert(counter_party.clone());
+                      btree.entry(counter_party).or_default().push(item);
+                  }
+                  FilterAction::Allow=>{
+                      btree.entry(counter_party).or_default().push(item);
+                  }
               }
             }
             else{
@@ -191,28 +420,79 @@ impl AllPosts{
                 ()
             }
         };
-        let mut all_pas: Vec<PostsAsChat> = [].to_vec();
-        for (key, value) in btree.iter() {
-            all_pas.push(PostsAsChat{
-                counter_party: key.to_string(),
-                posts: value.clone(),
-            });
-        }
+        let mut as_chats = |chats: BTreeMap<String, Vec<LocalPostModel>>, quarantined: bool| -> Vec<PostsAsChat> {
+            chats.into_iter().map(|(key, value)| PostsAsChat{
+                flags: if quarantined || flagged.contains(&key) { [ContactFlag::LowReputation].to_vec() } else { [].to_vec() },
+                counter_party: key,
+                threads: to_threads(&value, &mut corrupted),
+                posts: value,
+                username: None,
+            }).collect()
+        };
+        let verified = as_chats(btree, false);
+        let quarantined = as_chats(quarantine, true);
         SortedPosts{
-            verified: all_pas,
+            verified,
             corrupted,
             latest_genesis:self.posts[self.posts.len() - 1].genesis,
+            profiles,
+            key_changes: [].to_vec(),
+            quarantined,
+            receipts: receipt::statuses(&my_pubkey, &self.posts),
         }
     }
 
 }
 
+pub const MAX_REACTION_LENGTH: usize = 16;
+
+/// Optional links to an earlier post, covered by the checksum. At most one of `edits` and `reacts_to` is set.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PostLinks{
+    /// Id of the post this one answers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+    /// Id of a message post of ours that this one replaces.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edits: Option<String>,
+    /// Id of the post this one reacts to; the payload is a short message, e.g. an emoji.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reacts_to: Option<String>,
+}
+
+impl PostLinks {
+    pub fn structify(stringified: &str) -> Result<PostLinks, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Input, "Error structifying PostLinks")),
+        }
+    }
+    pub fn is_empty(&self)->bool{
+        self.reply_to.is_none() && self.edits.is_none() && self.reacts_to.is_none()
+    }
+    /// Checks the links fit `payload`. Whether the linked post exists and, for edits, is ours is checked when read.
+    pub fn check(&self, payload: &Payload)->Result<(),S5Error>{
+        if self.edits.is_some() && self.reacts_to.is_some(){
+            return Err(S5Error::new(ErrorKind::Input, "A post cannot both edit and react."));
+        }
+        if (self.edits.is_some() || self.reacts_to.is_some()) && !matches!(payload.kind, PayloadKind::Message){
+            return Err(S5Error::new(ErrorKind::Input, "Only messages can be edits or reactions."));
+        }
+        if self.reacts_to.is_some() && (payload.value.is_empty() || payload.value.chars().count() > MAX_REACTION_LENGTH){
+            return Err(S5Error::new(ErrorKind::Input, &format!("A reaction must be 1 to {} characters.", MAX_REACTION_LENGTH)));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Post {
     pub to: Recipient,
     pub payload: Payload,
     pub checksum: String,
     pub signature : Signature,
+    #[serde(flatten)]
+    pub links: PostLinks,
 }
 
 impl Post{
@@ -221,13 +501,32 @@ impl Post{
         payload: Payload, 
         xonly_pair: XOnlyPair
     )->Self{
-        let checksum_message = to.to_string() + ":" + &payload.to_string();
-        let checksum = key_hash256(&checksum_message);
+        Post::with_links(to, payload, PostLinks::default(), xonly_pair)
+    }
+    /// A post that replies to, edits or reacts to another. Check `links` first with `PostLinks::check`.
+    pub fn with_links(
+        to: Recipient,
+        payload: Payload,
+        links: PostLinks,
+        xonly_pair: XOnlyPair
+    )->Self{
+        let checksum = Post::checksum(&to, &payload, &links);
         Post {
             to,
             payload,
             checksum:checksum.clone(),
             signature: xonly_pair.schnorr_sign(&checksum).unwrap(),
+            links,
+        }
+    }
+    /// Posts without links use the legacy "to:payload" checksum. Posts with links append the links json.
+    fn checksum(to: &Recipient, payload: &Payload, links: &PostLinks)->String{
+        let checksum_message = to.to_string() + ":" + &payload.to_string();
+        if links.is_empty(){
+            key_hash256(&checksum_message)
+        }
+        else{
+            key_hash256(&format!("{}:{}", checksum_message, serde_json::to_string(links).unwrap()))
         }
     }
     pub fn stringify(&self) -> Result<String, S5Error> {
@@ -270,8 +569,8 @@ impl ToString for RecipientKind {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Recipient {
-    kind: RecipientKind,
-    value: String,
+    pub kind: RecipientKind,
+    pub value: String,
 }
 impl Recipient {
     pub fn new(kind: RecipientKind, value: String)->Self{
@@ -308,12 +607,39 @@ impl FromStr for Recipient{
 pub enum PayloadKind {
     Message,
     Secret,
+    /// value is a SignedProfile json
+    Profile,
+    /// value is a base64 PSBT, see `post::psbt`
+    Psbt,
+    /// value is a multisig key contribution, "[fingerprint/path]xpub", see `post::descriptor`
+    Xpub,
+    /// value is a checksummed output descriptor
+    Descriptor,
+    /// value is a PaymentRequest json, see `post::payment`
+    PaymentRequest,
+    /// value is a signed TradeMessage json, see `post::trade`
+    Trade,
+    /// value is a Receipt json, see `post::receipt`
+    Receipt,
+    /// value is an AttachmentManifest json, see `post::attachment`
+    Attachment,
+    /// value is an AttachmentChunk json
+    Chunk,
 }
 impl ToString for PayloadKind {
     fn to_string(&self)->String{
         match self{
             PayloadKind::Message=>"message".to_string(),
-            PayloadKind::Secret=>"secret".to_string()
+            PayloadKind::Secret=>"secret".to_string(),
+            PayloadKind::Profile=>"profile".to_string(),
+            PayloadKind::Psbt=>"psbt".to_string(),
+            PayloadKind::Xpub=>"xpub".to_string(),
+            PayloadKind::Descriptor=>"descriptor".to_string(),
+            PayloadKind::PaymentRequest=>"payment_request".to_string(),
+            PayloadKind::Trade=>"trade".to_string(),
+            PayloadKind::Receipt=>"receipt".to_string(),
+            PayloadKind::Attachment=>"attachment".to_string(),
+            PayloadKind::Chunk=>"chunk".to_string()
         }
     }
 }
@@ -333,23 +659,45 @@ impl Payload {
     pub fn to_string(&self)->String{
         format!("{}:{}",self.kind.to_string(), self.value)
     }
+    /// Checks values that must parse, e.g. PSBTs.
+    pub fn check(&self)->Result<(),S5Error>{
+        match self.kind{
+            PayloadKind::Psbt=>psbt::parse(&self.value).map(|_| ()),
+            PayloadKind::Xpub=>descriptor::KeyContribution::from_str(&self.value).map(|_| ()),
+            PayloadKind::Descriptor=>descriptor::check(&self.value),
+            PayloadKind::PaymentRequest=>PaymentRequest::structify(&self.value)?.details().map(|_| ()),
+            PayloadKind::Trade=>TradeMessage::structify(&self.value)?.verify(),
+            PayloadKind::Receipt=>Receipt::structify(&self.value)?.check(),
+            PayloadKind::Attachment=>AttachmentManifest::structify(&self.value)?.check(),
+            PayloadKind::Chunk=>AttachmentChunk::structify(&self.value).map(|_| ()),
+            _=>Ok(())
+        }
+    }
 }
 impl FromStr for Payload{
     type Err = S5Error;
 
     fn from_str(s: &str)->Result<Self,Self::Err>{
-        let parts: Vec<&str> = s.split(":").collect();
-        if parts.len() != 2{
-            Err(S5Error::new(ErrorKind::Input,"Bad Payload str format. Must be format => \'kind:value\'"))
-        }
-        else{
-            let kind = match parts[0].to_lowercase().as_str(){
-                "message"=>PayloadKind::Message,
-                "secret"=>PayloadKind::Secret,
-                _=> return Err(S5Error::new(ErrorKind::Input,"Bad Payload kind. Must be message or secret."))
-            };
-            let value = parts[1].to_string();
-            Ok(Payload::new(kind,value))
+        // the value may itself contain ':'
+        match s.split_once(':'){
+            None=>Err(S5Error::new(ErrorKind::Input,"Bad Payload str format. Must be format => \'kind:value\'")),
+            Some((kind, value))=>{
+                let kind = match kind.to_lowercase().as_str(){
+                    "message"=>PayloadKind::Message,
+                    "secret"=>PayloadKind::Secret,
+                    "profile"=>PayloadKind::Profile,
+                    "psbt"=>PayloadKind::Psbt,
+                    "xpub"=>PayloadKind::Xpub,
+                    "descriptor"=>PayloadKind::Descriptor,
+                    "payment_request"=>PayloadKind::PaymentRequest,
+                    "trade"=>PayloadKind::Trade,
+                    "receipt"=>PayloadKind::Receipt,
+                    "attachment"=>PayloadKind::Attachment,
+                    "chunk"=>PayloadKind::Chunk,
+                    _=> return Err(S5Error::new(ErrorKind::Input,"Bad Payload kind. Must be message, secret, profile, psbt, xpub, descriptor, payment_request, trade, receipt, attachment or chunk."))
+                };
+                Ok(Payload::new(kind,value.to_string()))
+            }
         }
     }
 }
@@ -412,6 +760,7 @@ impl DerivationIndex{
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::util::testing;
     use crate::key::seed;
     use crate::key::child;
     use crate::key::ec;
@@ -435,4 +784,62 @@ mod tests {
         );
         println!("{:#?}",one_post.stringify());
     }
+
+    #[test]
+    fn test_no_posts() {
+        let pubkey = testing::pubkey();
+        let sorted = AllPosts::new(vec![]).to_all_posts_as_chat(pubkey, Network::Bitcoin, None);
+        assert!(sorted.verified.is_empty());
+        assert_eq!(sorted.latest_genesis, 0);
+    }
+
+    #[test]
+    fn test_threads_edits_and_reactions() {
+        let (alice, bob) = (testing::keys(), testing::keys());
+        let to = Recipient::new(RecipientKind::Group, "market".to_string());
+        let post = |from: &ec::XOnlyPair, id: &str, genesis: u64, value: &str, links: PostLinks| LocalPostModel {
+            id: id.to_string(),
+            genesis,
+            expiry: 0,
+            owner: from.pubkey,
+            post: Post::with_links(to.clone(), Payload::new(PayloadKind::Message, value.to_string()), links, from.clone()),
+        };
+        let reply = |id: &str| PostLinks { reply_to: Some(id.to_string()), ..Default::default() };
+        let edit = |id: &str| PostLinks { edits: Some(id.to_string()), ..Default::default() };
+        let react = |id: &str| PostLinks { reacts_to: Some(id.to_string()), ..Default::default() };
+
+        let legacy = post(&alice, "1", 1, "selling 1M sats", PostLinks::default());
+        // posts without links keep the legacy checksum
+        assert_eq!(legacy.post.checksum, key_hash256(&format!("{}:{}", to.to_string(), legacy.post.payload.to_string())));
+        let mut all = AllPosts::new(vec![
+            legacy,
+            post(&bob, "2", 2, "how much?", reply("1")),
+            post(&alice, "3", 3, "620 EUR", reply("2")),
+            post(&alice, "4", 4, "selling 2M sats", edit("1")),
+            post(&bob, "5", 5, "👍", react("3")),
+            post(&bob, "6", 6, "👍", react("3")),
+            post(&bob, "7", 7, "selling 9M sats", edit("1")),
+            post(&bob, "8", 8, "👀", react("404")),
+            post(&alice, "9", 9, "anyone else?", PostLinks::default()),
+        ]);
+        let mut tampered = post(&alice, "10", 10, "hi", reply("1"));
+        tampered.post.links.reply_to = Some("9".to_string());
+        all.posts.push(tampered);
+
+        let sorted = all.to_all_posts_as_chat(alice.pubkey, Network::Bitcoin, None);
+        assert_eq!(sorted.corrupted, vec!["10".to_string(), "7".to_string(), "8".to_string()]);
+        let threads = &sorted.verified[0].threads;
+        assert_eq!(threads.len(), 2);
+        assert_eq!(threads[0].root.payload.value, "selling 2M sats");
+        assert_eq!(threads[0].root.post.post.payload.value, "selling 1M sats");
+        assert_eq!(threads[0].root.history.len(), 1);
+        assert_eq!(threads[0].replies.iter().map(|reply| reply.post.id.as_str()).collect::<Vec<&str>>(), vec!["2", "3"]);
+        assert_eq!(threads[0].replies[1].reactions.len(), 1);
+        assert_eq!(threads[1].root.post.id, "9");
+
+        assert!(edit("1").check(&Payload::new(PayloadKind::Psbt, "".to_string())).is_err());
+        assert!(react("1").check(&Payload::new(PayloadKind::Message, "x".repeat(MAX_REACTION_LENGTH + 1))).is_err());
+        let both = PostLinks { edits: Some("1".to_string()), reacts_to: Some("1".to_string()), reply_to: None };
+        assert!(both.check(&Payload::new(PayloadKind::Message, "x".to_string())).is_err());
+    }
 }
\ No newline at end of file
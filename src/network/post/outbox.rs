@@ -0,0 +1,201 @@
+//! Offline outbox. Posts composed while the server is unreachable are queued in a local file,
+//! encrypted with `UserIdentity::derive_storage_key`, and sent in order by `Client::flush_outbox`.
+//! Each entry is saved after every step, so an interrupted flush picks up where it stopped: the post request
+//! is built once, before uploading, and resending it is recognised by the server's idempotency key.
+use crate::key::encryption::{cc20p1305_decrypt, cc20p1305_encrypt, nonce};
+use crate::network::post::dto::ServerPostRequest;
+use crate::network::post::model::{Payload, PostLinks, Recipient};
+use crate::util::e::{ErrorKind, S5Error};
+use bitcoin::secp256k1::XOnlyPublicKey;
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+use std::fs;
+use std::os::raw::c_char;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxState {
+    /// Not sent yet.
+    Queued,
+    /// A derivation index is reserved and the post may already be on the server.
+    Uploading,
+    /// The post is on the server, the recipients do not have its key yet.
+    KeysPending,
+    Sent,
+    /// Rejected for a reason other than the connection, see `last_error`. Not retried.
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OutboxEntry {
+    /// Local id, to follow or remove the entry.
+    pub id: String,
+    pub to: Recipient,
+    pub payload: Payload,
+    #[serde(default)]
+    pub links: PostLinks,
+    pub recipients: Vec<XOnlyPublicKey>,
+    pub queued_at: u64,
+    pub state: OutboxState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<u32>,
+    /// Fixed once uploading starts, so a retry sends the very same request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<ServerPostRequest>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+impl OutboxEntry {
+    pub fn c_stringify(&self) -> *mut c_char {
+        CString::new(serde_json::to_string(self).unwrap()).unwrap().into_raw()
+    }
+    /// Still waiting to be sent, as opposed to sent or failed.
+    pub fn is_pending(&self) -> bool {
+        matches!(self.state, OutboxState::Queued | OutboxState::Uploading | OutboxState::KeysPending)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Outbox {
+    pub entries: Vec<OutboxEntry>,
+}
+
+impl Outbox {
+    /// Read the outbox at `path`, or an empty one if there is no file yet.
+    pub fn load(path: &Path, storage_key: &str) -> Result<Outbox, S5Error> {
+        let cipher = match fs::read_to_string(path) {
+            Ok(cipher) => cipher,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Outbox::default()),
+            Err(e) => return Err(S5Error::new(ErrorKind::Input, &format!("Could not read outbox: {}", e))),
+        };
+        let plain = cc20p1305_decrypt(&cipher, storage_key)?;
+        match serde_json::from_str(&plain) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Internal, "Error structifying Outbox")),
+        }
+    }
+    /// Write the outbox to `path`, replacing the old file in one step.
+    pub fn save(&self, path: &Path, storage_key: &str) -> Result<(), S5Error> {
+        let plain = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => return Err(S5Error::new(ErrorKind::Internal, "Error stringifying Outbox")),
+        };
+        let cipher = cc20p1305_encrypt(&plain, storage_key)?;
+        let temporary = path.with_extension("tmp");
+        let io_error = |e: std::io::Error| S5Error::new(ErrorKind::Internal, &format!("Could not write outbox: {}", e));
+        fs::write(&temporary, cipher).map_err(io_error)?;
+        fs::rename(&temporary, path).map_err(io_error)
+    }
+    pub fn c_stringify(&self) -> *mut c_char {
+        CString::new(serde_json::to_string(self).unwrap()).unwrap().into_raw()
+    }
+    /// Add a post to the end of the queue, checked as `Client::send_post_with_links` would.
+    pub fn queue(
+        &mut self,
+        to: Recipient,
+        payload: Payload,
+        links: PostLinks,
+        recipients: Vec<XOnlyPublicKey>,
+    ) -> Result<OutboxEntry, S5Error> {
+        payload.check()?;
+        links.check(&payload)?;
+        if recipients.is_empty() {
+            return Err(S5Error::new(ErrorKind::Input, "Unable to parse recipients."));
+        }
+        let entry = OutboxEntry {
+            id: nonce(),
+            to,
+            payload,
+            links,
+            recipients,
+            queued_at: now(),
+            state: OutboxState::Queued,
+            index: None,
+            request: None,
+            post_id: None,
+            last_error: None,
+        };
+        self.entries.push(entry.clone());
+        Ok(entry)
+    }
+    /// Drop a queued or failed entry. Entries already on their way to the server cannot be taken back.
+    pub fn remove(&mut self, id: &str) -> Result<(), S5Error> {
+        match self.entries.iter().position(|entry| entry.id == id) {
+            Some(position) if matches!(self.entries[position].state, OutboxState::Queued | OutboxState::Failed) => {
+                self.entries.remove(position);
+                Ok(())
+            }
+            Some(_) => Err(S5Error::new(ErrorKind::Input, "This post is already being sent.")),
+            None => Err(S5Error::new(ErrorKind::NoResource, "No outbox entry with this id.")),
+        }
+    }
+    /// The highest derivation index reserved by an entry still being sent.
+    pub fn last_reserved(&self) -> Option<u32> {
+        self.entries.iter().filter(|entry| entry.is_pending()).filter_map(|entry| entry.index).max()
+    }
+    pub fn is_empty(&self) -> bool {
+        !self.entries.iter().any(|entry| entry.is_pending())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::encryption::key_hash256;
+    use crate::network::post::model::{PayloadKind, RecipientKind};
+
+    #[test]
+    fn test_outbox_file() {
+        let path = std::env::temp_dir().join(format!("cpclient-outbox-{}", key_hash256(&nonce())));
+        let key = key_hash256("storage key");
+        let to = Recipient::new(RecipientKind::Group, "market".to_string());
+        let recipient = XOnlyPublicKey::from_keypair(&bitcoin::secp256k1::KeyPair::from_seckey_slice(
+            &bitcoin::secp256k1::Secp256k1::new(),
+            &[7u8; 32],
+        )
+        .unwrap());
+
+        let mut outbox = Outbox::load(&path, &key).unwrap();
+        assert!(outbox.is_empty());
+        let first = outbox
+            .queue(to.clone(), Payload::new(PayloadKind::Message, "hi".to_string()), PostLinks::default(), vec![recipient])
+            .unwrap();
+        outbox
+            .queue(to.clone(), Payload::new(PayloadKind::Message, "there".to_string()), PostLinks::default(), vec![recipient])
+            .unwrap();
+        assert!(outbox
+            .queue(to.clone(), Payload::new(PayloadKind::Psbt, "nope".to_string()), PostLinks::default(), vec![recipient])
+            .is_err());
+        assert!(outbox
+            .queue(to, Payload::new(PayloadKind::Message, "hi".to_string()), PostLinks::default(), vec![])
+            .is_err());
+        outbox.save(&path, &key).unwrap();
+
+        // encrypted at rest
+        assert!(!fs::read_to_string(&path).unwrap().contains("there"));
+        assert!(Outbox::load(&path, &key_hash256("another key")).is_err());
+        let mut outbox = Outbox::load(&path, &key).unwrap();
+        assert_eq!(outbox.entries.len(), 2);
+        assert_eq!(outbox.entries[1].payload.value, "there");
+
+        outbox.entries[1].state = OutboxState::Uploading;
+        outbox.entries[1].index = Some(4);
+        assert_eq!(outbox.last_reserved(), Some(4));
+        assert!(outbox.remove(&outbox.entries[1].id.clone()).is_err());
+        outbox.remove(&first.id).unwrap();
+        assert!(outbox.remove(&first.id).is_err());
+        assert_eq!(outbox.entries.len(), 1);
+        fs::write(&path, "not an outbox").unwrap();
+        assert!(Outbox::load(&path, &key).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,379 @@
+//! Payment requests shared as `PayloadKind::PaymentRequest` posts.
+//! The payload value is a `PaymentRequest` json holding a BIP21 uri. An optional BIP322 simple
+//! signature from the address key proves the sender controls the address and ties it to the post owner,
+//! so a copy reposted by someone else or edited in transit fails the check.
+use crate::util::e::{ErrorKind, S5Error};
+use bitcoin::blockdata::opcodes::all::OP_RETURN;
+use bitcoin::blockdata::script::Builder;
+use bitcoin::consensus::{deserialize, serialize};
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::{KeyPair, Message, Secp256k1, SecretKey, XOnlyPublicKey};
+use bitcoin::util::address::{AddressType, Payload as AddressPayload};
+use bitcoin::util::ecdsa::EcdsaSig;
+use bitcoin::util::schnorr::{SchnorrSig, TapTweak};
+use bitcoin::util::sighash::{Prevouts, SighashCache};
+use bitcoin::{
+    Address, Amount, Denomination, EcdsaSighashType, Network, OutPoint, PublicKey, SchnorrSighashType, Script,
+    Transaction, TxIn, TxOut, Txid, Witness,
+};
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const MAX_PAYMENT_LABEL_LENGTH: usize = 140;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PaymentRequest {
+    /// bitcoin:<address>?amount=<btc>&label=<text>
+    pub uri: String,
+    /// Millisecond timestamp after which the request should not be paid.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiry: Option<u64>,
+    /// Base64 BIP322 simple signature of `proof_message` by the address key. p2wpkh and p2tr only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proof: Option<String>,
+}
+
+/// What a reader needs to show a payment request. Returned by `payment_request_details`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PaymentDetails {
+    pub address: String,
+    /// In sats.
+    pub amount: Option<u64>,
+    pub label: Option<String>,
+    pub message: Option<String>,
+    pub expiry: Option<u64>,
+    pub expired: bool,
+    /// Carries an ownership proof. Posts whose proof fails are dropped into `corrupted`, so this is only
+    /// true for a request read from a verified post or checked with `PaymentRequest::verify`.
+    pub proven: bool,
+}
+
+impl PaymentDetails {
+    pub fn c_stringify(&self) -> *mut c_char {
+        CString::new(serde_json::to_string(self).unwrap()).unwrap().into_raw()
+    }
+}
+
+impl PaymentRequest {
+    /// An unproven request. Add a proof with `prove` or by setting `proof` to one made by an external wallet.
+    pub fn new(
+        address: &str,
+        amount: Option<u64>,
+        label: Option<String>,
+        expiry: Option<u64>,
+    ) -> Result<PaymentRequest, S5Error> {
+        let address = parse_address(address)?;
+        let mut params: Vec<String> = vec![];
+        if let Some(amount) = amount {
+            params.push(format!("amount={}", Amount::from_sat(amount).to_string_in(Denomination::Bitcoin)));
+        }
+        if let Some(label) = label {
+            params.push(format!("label={}", encode(&label)));
+        }
+        let uri = if params.is_empty() {
+            format!("bitcoin:{}", address)
+        } else {
+            format!("bitcoin:{}?{}", address, params.join("&"))
+        };
+        let request = PaymentRequest { uri, expiry, proof: None };
+        request.details()?;
+        Ok(request)
+    }
+    pub fn structify(stringified: &str) -> Result<PaymentRequest, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Input, "Error structifying PaymentRequest")),
+        }
+    }
+    pub fn stringify(&self) -> Result<String, S5Error> {
+        match serde_json::to_string(self) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Internal, "Error stringifying PaymentRequest")),
+        }
+    }
+    pub fn c_stringify(&self) -> *mut c_char {
+        CString::new(serde_json::to_string(self).unwrap()).unwrap().into_raw()
+    }
+
+    /// Parses the uri. Fails on a bad address, amount or an unknown `req-` parameter.
+    pub fn details(&self) -> Result<PaymentDetails, S5Error> {
+        let (scheme, rest) = match self.uri.split_once(':') {
+            Some(parts) => parts,
+            None => return Err(S5Error::new(ErrorKind::Input, "Payment uri must start with bitcoin:")),
+        };
+        if !scheme.eq_ignore_ascii_case("bitcoin") {
+            return Err(S5Error::new(ErrorKind::Input, "Payment uri must start with bitcoin:"));
+        }
+        let (address, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let address = parse_address(address)?;
+        let mut details = PaymentDetails {
+            address: address.to_string(),
+            amount: None,
+            label: None,
+            message: None,
+            expiry: self.expiry,
+            expired: self.is_expired(),
+            proven: self.proof.is_some(),
+        };
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "amount" => {
+                    let amount = Amount::from_str_in(&value, Denomination::Bitcoin)
+                        .map_err(|_| S5Error::new(ErrorKind::Input, "Bad payment amount."))?;
+                    details.amount = Some(amount.as_sat());
+                }
+                "label" => details.label = Some(value.to_string()),
+                "message" => details.message = Some(value.to_string()),
+                key if key.starts_with("req-") => {
+                    return Err(S5Error::new(ErrorKind::Input, &format!("Unsupported required payment parameter {}.", key)))
+                }
+                _ => (),
+            }
+        }
+        if details.label.as_ref().is_some_and(|label| label.chars().count() > MAX_PAYMENT_LABEL_LENGTH) {
+            return Err(S5Error::new(
+                ErrorKind::Input,
+                &format!("Payment label must be at most {} characters.", MAX_PAYMENT_LABEL_LENGTH),
+            ));
+        }
+        Ok(details)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        self.expiry.is_some_and(|expiry| expiry <= now)
+    }
+
+    /// Binds the proof to the post owner, so it cannot be lifted into someone else's post.
+    pub fn proof_message(&self, owner: &XOnlyPublicKey) -> String {
+        format!("{}:{}:{}", owner, self.uri, self.expiry.unwrap_or(0))
+    }
+
+    /// Sign the ownership proof with the secret key of a p2wpkh or key path p2tr address.
+    pub fn prove(&mut self, owner: &XOnlyPublicKey, secret: &SecretKey) -> Result<(), S5Error> {
+        let address = parse_address(&self.details()?.address)?;
+        let message = self.proof_message(owner);
+        self.proof = Some(bip322_sign(&address, &message, secret)?);
+        Ok(())
+    }
+
+    /// Checks a request read from a post made by `owner` by an identity on `network`:
+    /// the uri parses, the address is for the same network and the proof, if any, is valid.
+    pub fn verify(&self, owner: &XOnlyPublicKey, network: Network) -> Result<PaymentDetails, S5Error> {
+        let details = self.details()?;
+        let address = parse_address(&details.address)?;
+        if !same_network(&address, network) {
+            return Err(S5Error::new(ErrorKind::Input, "Payment address is for a different network."));
+        }
+        if let Some(proof) = &self.proof {
+            bip322_verify(&address, &self.proof_message(owner), proof)?;
+        }
+        Ok(details)
+    }
+}
+
+fn parse_address(address: &str) -> Result<Address, S5Error> {
+    Address::from_str(address.trim()).map_err(|_| S5Error::new(ErrorKind::Input, "Bad payment address."))
+}
+
+/// Testnet identities pay to any test network, as tprv keys do not say which one.
+fn same_network(address: &Address, network: Network) -> bool {
+    match network {
+        Network::Bitcoin => address.network == Network::Bitcoin,
+        _ => address.network != Network::Bitcoin,
+    }
+}
+
+/// Percent encoding for uri values; form encoding would turn spaces into '+'.
+fn encode(value: &str) -> String {
+    url::form_urlencoded::byte_serialize(value.as_bytes()).collect::<String>().replace('+', "%20")
+}
+
+fn message_hash(message: &str) -> sha256::Hash {
+    let tag = sha256::Hash::hash(b"BIP0322-signed-message");
+    let mut engine = sha256::Hash::engine();
+    engine.input(&tag[..]);
+    engine.input(&tag[..]);
+    engine.input(message.as_bytes());
+    sha256::Hash::from_engine(engine)
+}
+
+fn to_spend(address: &Address, message: &str) -> Transaction {
+    Transaction {
+        version: 0,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint { txid: Txid::default(), vout: 0xFFFFFFFF },
+            script_sig: Builder::new().push_int(0).push_slice(&message_hash(message)[..]).into_script(),
+            sequence: 0,
+            witness: Witness::default(),
+        }],
+        output: vec![TxOut { value: 0, script_pubkey: address.script_pubkey() }],
+    }
+}
+
+fn to_sign(to_spend: &Transaction, witness: Witness) -> Transaction {
+    Transaction {
+        version: 0,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint { txid: to_spend.txid(), vout: 0 },
+            script_sig: Script::new(),
+            sequence: 0,
+            witness,
+        }],
+        output: vec![TxOut { value: 0, script_pubkey: Builder::new().push_opcode(OP_RETURN).into_script() }],
+    }
+}
+
+fn bad_proof() -> S5Error {
+    S5Error::new(ErrorKind::Key, "Bad payment address proof.")
+}
+
+fn unsupported() -> S5Error {
+    S5Error::new(ErrorKind::Input, "Payment address proofs need a p2wpkh or p2tr address.")
+}
+
+fn bip322_sign(address: &Address, message: &str, secret: &SecretKey) -> Result<String, S5Error> {
+    let secp = Secp256k1::new();
+    let to_spend = to_spend(address, message);
+    let mut to_sign = to_sign(&to_spend, Witness::default());
+    let witness = match address.address_type() {
+        Some(AddressType::P2wpkh) => {
+            let pubkey = PublicKey::new(bitcoin::secp256k1::PublicKey::from_secret_key(&secp, secret));
+            if Address::p2wpkh(&pubkey, address.network).ok().as_ref() != Some(address) {
+                return Err(S5Error::new(ErrorKind::Key, "Key does not match the payment address."));
+            }
+            let sighash = SighashCache::new(&to_sign)
+                .segwit_signature_hash(0, &Script::new_p2pkh(&pubkey.pubkey_hash()), 0, EcdsaSighashType::All)
+                .map_err(|_| bad_proof())?;
+            let signature = secp.sign_ecdsa(&Message::from_slice(&sighash[..]).unwrap(), secret);
+            Witness::from_vec(vec![EcdsaSig::sighash_all(signature).to_vec(), pubkey.to_bytes()])
+        }
+        Some(AddressType::P2tr) => {
+            let keypair = KeyPair::from_secret_key(&secp, *secret);
+            if Address::p2tr(&secp, XOnlyPublicKey::from_keypair(&keypair), None, address.network) != *address {
+                return Err(S5Error::new(ErrorKind::Key, "Key does not match the payment address."));
+            }
+            let sighash = SighashCache::new(&to_sign)
+                .taproot_key_spend_signature_hash(0, &Prevouts::All(&to_spend.output), SchnorrSighashType::Default)
+                .map_err(|_| bad_proof())?;
+            let tweaked = keypair.tap_tweak(&secp, None).into_inner();
+            let signature = secp.sign_schnorr(&Message::from_slice(&sighash[..]).unwrap(), &tweaked);
+            Witness::from_vec(vec![signature.as_ref().to_vec()])
+        }
+        _ => return Err(unsupported()),
+    };
+    to_sign.input[0].witness = witness;
+    Ok(base64::encode(serialize(&to_sign.input[0].witness)))
+}
+
+fn bip322_verify(address: &Address, message: &str, proof: &str) -> Result<(), S5Error> {
+    let secp = Secp256k1::verification_only();
+    let witness: Witness = base64::decode(proof.trim())
+        .ok()
+        .and_then(|bytes| deserialize(&bytes).ok())
+        .ok_or_else(bad_proof)?;
+    let to_spend = to_spend(address, message);
+    let to_sign = to_sign(&to_spend, witness.clone());
+    let stack = witness.to_vec();
+    match address.address_type() {
+        Some(AddressType::P2wpkh) => {
+            if stack.len() != 2 {
+                return Err(bad_proof());
+            }
+            let signature = EcdsaSig::from_slice(&stack[0]).map_err(|_| bad_proof())?;
+            let pubkey = PublicKey::from_slice(&stack[1]).map_err(|_| bad_proof())?;
+            if Address::p2wpkh(&pubkey, address.network).ok().as_ref() != Some(address) {
+                return Err(bad_proof());
+            }
+            let sighash = SighashCache::new(&to_sign)
+                .segwit_signature_hash(0, &Script::new_p2pkh(&pubkey.pubkey_hash()), 0, signature.hash_ty)
+                .map_err(|_| bad_proof())?;
+            secp.verify_ecdsa(&Message::from_slice(&sighash[..]).unwrap(), &signature.sig, &pubkey.inner)
+                .map_err(|_| bad_proof())
+        }
+        Some(AddressType::P2tr) => {
+            if stack.len() != 1 {
+                return Err(bad_proof());
+            }
+            let signature = SchnorrSig::from_slice(&stack[0]).map_err(|_| bad_proof())?;
+            let output_key = match &address.payload {
+                AddressPayload::WitnessProgram { program, .. } => {
+                    XOnlyPublicKey::from_slice(program).map_err(|_| bad_proof())?
+                }
+                _ => return Err(bad_proof()),
+            };
+            let sighash = SighashCache::new(&to_sign)
+                .taproot_key_spend_signature_hash(0, &Prevouts::All(&to_spend.output), signature.hash_ty)
+                .map_err(|_| bad_proof())?;
+            secp.verify_schnorr(&signature.sig, &Message::from_slice(&sighash[..]).unwrap(), &output_key)
+                .map_err(|_| bad_proof())
+        }
+        _ => Err(unsupported()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bip322_vectors() {
+        let address = parse_address("bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l").unwrap();
+        assert_eq!(
+            message_hash("Hello World").to_string(),
+            "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a"
+        );
+        bip322_verify(&address, "", "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=").unwrap();
+        bip322_verify(&address, "Hello World", "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=").unwrap();
+        assert!(bip322_verify(&address, "Hello World!", "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=").is_err());
+    }
+
+    #[test]
+    fn test_payment_request() {
+        let secp = Secp256k1::new();
+        let secret = SecretKey::from_slice(&[3u8; 32]).unwrap();
+        let owner = XOnlyPublicKey::from_keypair(&KeyPair::from_secret_key(&secp, SecretKey::from_slice(&[4u8; 32]).unwrap()));
+        let other = XOnlyPublicKey::from_keypair(&KeyPair::from_secret_key(&secp, SecretKey::from_slice(&[5u8; 32]).unwrap()));
+        let pubkey = PublicKey::new(bitcoin::secp256k1::PublicKey::from_secret_key(&secp, &secret));
+        let segwit = Address::p2wpkh(&pubkey, Network::Testnet).unwrap().to_string();
+        let taproot = Address::p2tr(&secp, XOnlyPublicKey::from_keypair(&KeyPair::from_secret_key(&secp, secret)), None, Network::Testnet).to_string();
+
+        let mut request = PaymentRequest::new(&segwit, Some(150_000), Some("trade 42 & co".to_string()), None).unwrap();
+        assert!(request.uri.contains("amount=0.00150000"));
+        assert!(request.uri.contains("label=trade%2042%20%26%20co"));
+        let details = request.verify(&owner, Network::Testnet).unwrap();
+        assert_eq!(details.amount, Some(150_000));
+        assert_eq!(details.label.as_deref(), Some("trade 42 & co"));
+        assert!(!details.proven);
+        assert!(request.verify(&owner, Network::Bitcoin).is_err());
+
+        request.prove(&owner, &secret).unwrap();
+        assert!(request.verify(&owner, Network::Testnet).unwrap().proven);
+        // lifted into another member's post
+        assert!(request.verify(&other, Network::Testnet).is_err());
+        // edited in transit
+        let mut edited = request.clone();
+        edited.uri = edited.uri.replace("0.0015", "0.0025");
+        assert!(edited.verify(&owner, Network::Testnet).is_err());
+        let mut extended = request.clone();
+        extended.expiry = Some(u64::MAX);
+        assert!(extended.verify(&owner, Network::Testnet).is_err());
+
+        let mut request = PaymentRequest::new(&taproot, None, None, Some(1)).unwrap();
+        request.prove(&owner, &secret).unwrap();
+        let details = request.verify(&owner, Network::Testnet).unwrap();
+        assert!(details.expired && details.proven);
+        assert!(request.clone().prove(&owner, &SecretKey::from_slice(&[6u8; 32]).unwrap()).is_err());
+
+        let parsed = PaymentRequest { uri: format!("BITCOIN:{}?req-somethingyoudontunderstand=50", segwit), expiry: None, proof: None };
+        assert!(parsed.details().is_err());
+        let parsed = PaymentRequest { uri: format!("bitcoin:{}?amount=abc", segwit), expiry: None, proof: None };
+        assert!(parsed.details().is_err());
+        assert!(PaymentRequest::new("not an address", None, None, None).is_err());
+    }
+}
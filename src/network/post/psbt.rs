@@ -0,0 +1,246 @@
+//! PSBTs shared between multisig cosigners as `PayloadKind::Psbt` posts.
+//! The payload value is the base64 PSBT. Each cosigner posts their signed copy;
+//! any of them can combine the copies and finalize once enough signatures are in.
+use crate::network::post::model::{LocalPostModel, PayloadKind};
+use crate::util::e::{ErrorKind, S5Error};
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{Address, Network};
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::str::FromStr;
+
+pub fn parse(psbt: &str) -> Result<PartiallySignedTransaction, S5Error> {
+    PartiallySignedTransaction::from_str(psbt.trim())
+        .map_err(|e| S5Error::new(ErrorKind::Input, &format!("Bad PSBT: {}", e)))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PsbtInputSummary {
+    pub outpoint: String,
+    /// None if the PSBT does not carry the spent utxo.
+    pub value: Option<u64>,
+    /// Pubkeys with a signature on this input.
+    pub signed_by: Vec<String>,
+    pub finalized: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PsbtOutputSummary {
+    /// None for scripts without an address form, e.g. OP_RETURN.
+    pub address: Option<String>,
+    pub script_pubkey: String,
+    pub value: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PsbtSummary {
+    /// Of the unsigned transaction, the same in every cosigner's copy.
+    pub txid: String,
+    pub inputs: Vec<PsbtInputSummary>,
+    pub outputs: Vec<PsbtOutputSummary>,
+    /// None unless every input carries its utxo, or if the values do not add up.
+    pub fee: Option<u64>,
+    /// Every input is finalized.
+    pub complete: bool,
+}
+
+impl PsbtSummary {
+    pub fn new(psbt: &PartiallySignedTransaction, network: Network) -> PsbtSummary {
+        let tx = &psbt.unsigned_tx;
+        let inputs: Vec<PsbtInputSummary> = tx
+            .input
+            .iter()
+            .zip(psbt.inputs.iter())
+            .map(|(txin, input)| {
+                let value = match (&input.witness_utxo, &input.non_witness_utxo) {
+                    (Some(utxo), _) => Some(utxo.value),
+                    (None, Some(prev)) => prev.output.get(txin.previous_output.vout as usize).map(|utxo| utxo.value),
+                    (None, None) => None,
+                };
+                let mut signed_by: Vec<String> = input.partial_sigs.keys().map(|pubkey| pubkey.to_string()).collect();
+                signed_by.extend(input.tap_script_sigs.keys().map(|(pubkey, _)| pubkey.to_string()));
+                if let (Some(_), Some(internal_key)) = (input.tap_key_sig, input.tap_internal_key) {
+                    signed_by.push(internal_key.to_string());
+                }
+                PsbtInputSummary {
+                    outpoint: txin.previous_output.to_string(),
+                    value,
+                    signed_by,
+                    finalized: input.final_script_sig.is_some() || input.final_script_witness.is_some(),
+                }
+            })
+            .collect();
+        let outputs: Vec<PsbtOutputSummary> = tx
+            .output
+            .iter()
+            .map(|txout| PsbtOutputSummary {
+                address: Address::from_script(&txout.script_pubkey, network).map(|address| address.to_string()),
+                script_pubkey: txout.script_pubkey.to_string(),
+                value: txout.value,
+            })
+            .collect();
+        // values come from whoever built the PSBT, so the totals may overflow
+        let spent = inputs
+            .iter()
+            .try_fold(0u64, |total, input| input.value.and_then(|value| total.checked_add(value)));
+        let sent = outputs.iter().try_fold(0u64, |total, output| total.checked_add(output.value));
+        PsbtSummary {
+            txid: tx.txid().to_string(),
+            fee: spent.zip(sent).and_then(|(spent, sent)| spent.checked_sub(sent)),
+            complete: !inputs.is_empty() && inputs.iter().all(|input| input.finalized),
+            inputs,
+            outputs,
+        }
+    }
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+/// Merge the signatures of several copies of the same PSBT. Returns the combined base64 PSBT.
+pub fn combine(psbts: &[String]) -> Result<String, S5Error> {
+    let mut psbts = psbts.iter();
+    let mut combined = match psbts.next() {
+        Some(first) => parse(first)?,
+        None => return Err(S5Error::new(ErrorKind::Input, "No PSBTs to combine.")),
+    };
+    for psbt in psbts {
+        let psbt = parse(psbt)?;
+        if psbt.unsigned_tx.txid() != combined.unsigned_tx.txid() {
+            return Err(S5Error::new(ErrorKind::Input, "PSBTs are for different transactions."));
+        }
+        combined
+            .combine(psbt)
+            .map_err(|e| S5Error::new(ErrorKind::Input, &format!("Could not combine PSBTs: {}", e)))?;
+    }
+    Ok(combined.to_string())
+}
+
+/// Combine every PSBT post in `posts` whose unsigned transaction has `txid`, e.g. the posts of one chat.
+pub fn combine_posts(posts: &[LocalPostModel], txid: &str) -> Result<String, S5Error> {
+    let copies: Vec<String> = posts
+        .iter()
+        .filter(|post| matches!(post.post.payload.kind, PayloadKind::Psbt))
+        .filter(|post| matches!(parse(&post.post.payload.value), Ok(psbt) if psbt.unsigned_tx.txid().to_string() == txid))
+        .map(|post| post.post.payload.value.clone())
+        .collect();
+    if copies.is_empty() {
+        return Err(S5Error::new(ErrorKind::NoResource, "No PSBT posts for this txid."));
+    }
+    combine(&copies)
+}
+
+/// Returned by `combine_psbts`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CombinedPsbt {
+    pub psbt: String,
+}
+
+impl CombinedPsbt {
+    pub fn c_stringify(&self) -> *mut c_char {
+        CString::new(serde_json::to_string(self).unwrap()).unwrap().into_raw()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+    use crate::network::post::model::{Payload, Post, Recipient, RecipientKind};
+    use bitcoin::blockdata::script::Builder;
+    use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+    use bitcoin::util::ecdsa::EcdsaSig;
+    use bitcoin::{OutPoint, PublicKey, Transaction, TxIn, TxOut, Witness};
+
+    fn unsigned() -> PartiallySignedTransaction {
+        let prev = OutPoint::from_str("ce9c4a3c6e1ae1a3b4b6c5d2b3e5e2b7b3c7f2a1e5d4c3b2a1f0e9d8c7b6a594:1").unwrap();
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn { previous_output: prev, script_sig: Default::default(), sequence: 0xFFFFFFFD, witness: Witness::default() }],
+            output: vec![
+                TxOut { value: 90_000, script_pubkey: Builder::new().push_int(0).push_slice(&[7u8; 32]).into_script() },
+                TxOut { value: 0, script_pubkey: Builder::new().push_opcode(bitcoin::blockdata::opcodes::all::OP_RETURN).into_script() },
+            ],
+        };
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(tx).unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: 100_000,
+            script_pubkey: Builder::new().push_int(0).push_slice(&[9u8; 32]).into_script(),
+        });
+        psbt
+    }
+
+    fn signed_by(seed: u8) -> (PartiallySignedTransaction, PublicKey) {
+        let secp = Secp256k1::new();
+        let secret = SecretKey::from_slice(&[seed; 32]).unwrap();
+        let pubkey = PublicKey::new(bitcoin::secp256k1::PublicKey::from_secret_key(&secp, &secret));
+        let sig = secp.sign_ecdsa(&Message::from_slice(&[1u8; 32]).unwrap(), &secret);
+        let mut psbt = unsigned();
+        psbt.inputs[0].partial_sigs.insert(pubkey, EcdsaSig::sighash_all(sig));
+        (psbt, pubkey)
+    }
+
+    #[test]
+    fn test_psbt_summary_and_combine() {
+        let summary = PsbtSummary::new(&unsigned(), Network::Testnet);
+        assert_eq!(summary.fee, Some(10_000));
+        assert_eq!(summary.inputs[0].value, Some(100_000));
+        assert!(summary.inputs[0].signed_by.is_empty());
+        assert!(summary.outputs[0].address.as_ref().unwrap().starts_with("tb1q"));
+        assert_eq!(summary.outputs[1].address, None);
+        assert!(!summary.complete);
+
+        let (first, ishi) = signed_by(1);
+        let (second, sushi) = signed_by(2);
+        let combined = combine(&[first.to_string(), second.to_string()]).unwrap();
+        let summary = PsbtSummary::new(&parse(&combined).unwrap(), Network::Testnet);
+        assert_eq!(summary.inputs[0].signed_by.len(), 2);
+        assert!(summary.inputs[0].signed_by.contains(&ishi.to_string()));
+        assert!(summary.inputs[0].signed_by.contains(&sushi.to_string()));
+        assert_eq!(summary.txid, unsigned().unsigned_tx.txid().to_string());
+
+        let mut other = unsigned();
+        other.unsigned_tx.output[0].value = 80_000;
+        assert!(combine(&[first.to_string(), other.to_string()]).is_err());
+        assert!(combine(&[]).is_err());
+        assert!(parse("not a psbt").is_err());
+
+        let mut overflow = unsigned();
+        overflow.unsigned_tx.output[1].value = u64::MAX;
+        assert_eq!(PsbtSummary::new(&overflow, Network::Testnet).fee, None);
+        let mut overflow = unsigned();
+        overflow.unsigned_tx.input.push(overflow.unsigned_tx.input[0].clone());
+        overflow.inputs.push(overflow.inputs[0].clone());
+        overflow.inputs[1].witness_utxo.as_mut().unwrap().value = u64::MAX;
+        assert_eq!(PsbtSummary::new(&overflow, Network::Testnet).fee, None);
+
+        // copies posted by each cosigner, plus an unrelated one
+        let cosigner = testing::keys();
+        let post = |psbt: &PartiallySignedTransaction| LocalPostModel {
+            id: "id".to_string(),
+            genesis: 0,
+            expiry: 0,
+            owner: cosigner.pubkey,
+            post: Post::new(
+                Recipient::new(RecipientKind::Group, "vault".to_string()),
+                Payload::new(PayloadKind::Psbt, psbt.to_string()),
+                cosigner.clone(),
+            ),
+        };
+        let posts = vec![post(&first), post(&other), post(&second)];
+        let combined = combine_posts(&posts, &summary.txid).unwrap();
+        assert_eq!(PsbtSummary::new(&parse(&combined).unwrap(), Network::Testnet).inputs[0].signed_by.len(), 2);
+        assert!(combine_posts(&posts[..0], &summary.txid).is_err());
+    }
+}
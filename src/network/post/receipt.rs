@@ -0,0 +1,294 @@
+//! Delivery and read receipts. A receipt is a direct `PayloadKind::Receipt` post to the owner of the posts it
+//! acknowledges, signed like any other post. One receipt covers up to `MAX_RECEIPT_BATCH` posts, so a member
+//! catching up on a conversation sends one post instead of one per message.
+//! `statuses` merges the receipts others sent `me` into the status of each of `me`'s posts.
+use crate::network::post::model::{LocalPostModel, PayloadKind, RecipientKind};
+use crate::util::e::{ErrorKind, S5Error};
+use bitcoin::secp256k1::XOnlyPublicKey;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::CString;
+use std::fmt;
+use std::os::raw::c_char;
+use std::str::FromStr;
+
+pub const MAX_RECEIPT_BATCH: usize = 100;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReceiptKind {
+    /// The decryption key was fetched.
+    Delivered,
+    /// The post was shown to the reader. Implies delivered.
+    Read,
+}
+
+impl fmt::Display for ReceiptKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReceiptKind::Delivered => write!(f, "delivered"),
+            ReceiptKind::Read => write!(f, "read"),
+        }
+    }
+}
+
+impl FromStr for ReceiptKind {
+    type Err = S5Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "delivered" => Ok(ReceiptKind::Delivered),
+            "read" => Ok(ReceiptKind::Read),
+            _ => Err(S5Error::new(ErrorKind::Input, "Bad receipt kind. Must be delivered or read.")),
+        }
+    }
+}
+
+/// The value of a Receipt post.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Receipt {
+    pub kind: ReceiptKind,
+    pub post_ids: Vec<String>,
+}
+
+impl Receipt {
+    pub fn structify(stringified: &str) -> Result<Receipt, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Input, "Error structifying Receipt")),
+        }
+    }
+    pub fn stringify(&self) -> Result<String, S5Error> {
+        match serde_json::to_string(self) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Internal, "Error stringifying Receipt")),
+        }
+    }
+    pub fn check(&self) -> Result<(), S5Error> {
+        if self.post_ids.is_empty() || self.post_ids.len() > MAX_RECEIPT_BATCH {
+            return Err(S5Error::new(
+                ErrorKind::Input,
+                &format!("A receipt covers 1 to {} posts.", MAX_RECEIPT_BATCH),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Conversations, by counter party pubkey or group id, that get no receipts from `me`.
+/// Kept by the app and passed in when sending receipts.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ReceiptSettings {
+    /// No receipts at all.
+    #[serde(default)]
+    pub off: Vec<String>,
+    /// Delivered receipts only.
+    #[serde(default)]
+    pub read_off: Vec<String>,
+}
+
+impl ReceiptSettings {
+    pub fn structify(stringified: &str) -> Result<ReceiptSettings, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Input, "Error structifying ReceiptSettings")),
+        }
+    }
+    pub fn allows(&self, counter_party: &str, kind: ReceiptKind) -> bool {
+        let blocked = |list: &Vec<String>| list.iter().any(|entry| entry == counter_party);
+        match kind {
+            ReceiptKind::Delivered => !blocked(&self.off),
+            ReceiptKind::Read => !blocked(&self.off) && !blocked(&self.read_off),
+        }
+    }
+}
+
+/// Who acknowledged one of `me`'s posts. Members in `read` are also in `delivered`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PostStatus {
+    pub post_id: String,
+    pub delivered: Vec<XOnlyPublicKey>,
+    pub read: Vec<XOnlyPublicKey>,
+}
+
+/// Ids of the receipt posts sent.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SentReceipts {
+    pub ids: Vec<String>,
+    /// Why sending stopped early. The receipts in `ids` went out, the rest did not.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<S5Error>,
+}
+
+impl SentReceipts {
+    pub fn c_stringify(&self) -> *mut c_char {
+        CString::new(serde_json::to_string(self).unwrap()).unwrap().into_raw()
+    }
+}
+
+fn receipts<'a>(
+    posts: &'a [LocalPostModel],
+    by: impl Fn(&XOnlyPublicKey) -> bool + 'a,
+) -> impl Iterator<Item = (&'a LocalPostModel, Receipt)> + 'a {
+    posts
+        .iter()
+        .filter(move |post| by(&post.owner))
+        .filter(|post| matches!(post.post.payload.kind, PayloadKind::Receipt))
+        .filter(|post| matches!(post.post.to.kind, RecipientKind::Direct) && post.verify().is_ok())
+        .filter_map(|post| {
+            Receipt::structify(&post.post.payload.value)
+                .ok()
+                .filter(|receipt| receipt.check().is_ok())
+                .map(|receipt| (post, receipt))
+        })
+}
+
+/// Posts others sent `me` that `me` has not yet sent a `kind` receipt for, grouped by owner in batches.
+/// Receipts, profiles, attachment chunks and conversations turned off in `settings` are skipped; `only` limits the posts considered.
+pub fn pending(
+    me: &XOnlyPublicKey,
+    posts: &[LocalPostModel],
+    kind: ReceiptKind,
+    settings: &ReceiptSettings,
+    only: Option<&[String]>,
+) -> BTreeMap<XOnlyPublicKey, Vec<Vec<String>>> {
+    let acknowledged: BTreeSet<String> = receipts(posts, |owner| owner == me)
+        .filter(|(_, receipt)| kind == ReceiptKind::Delivered || receipt.kind == ReceiptKind::Read)
+        .flat_map(|(_, receipt)| receipt.post_ids)
+        .collect();
+    let mut pending = BTreeMap::<XOnlyPublicKey, Vec<String>>::new();
+    for post in posts.iter().filter(|post| post.owner != *me && post.verify().is_ok()) {
+        if matches!(post.post.payload.kind, PayloadKind::Receipt | PayloadKind::Profile | PayloadKind::Chunk)
+            || acknowledged.contains(&post.id)
+            || only.map(|only| !only.contains(&post.id)).unwrap_or(false)
+        {
+            continue;
+        }
+        let counter_party = match post.post.to.kind {
+            RecipientKind::Direct => post.owner.to_string(),
+            RecipientKind::Group => post.post.to.value.clone(),
+        };
+        if settings.allows(&counter_party, kind) {
+            pending.entry(post.owner).or_default().push(post.id.clone());
+        }
+    }
+    pending
+        .into_iter()
+        .map(|(owner, ids)| (owner, ids.chunks(MAX_RECEIPT_BATCH).map(|batch| batch.to_vec()).collect()))
+        .collect()
+}
+
+/// Receipts others sent `me`, merged per post of `me`'s in the order of `posts`. Only members who could see a post count for it.
+pub fn statuses(me: &XOnlyPublicKey, posts: &[LocalPostModel]) -> Vec<PostStatus> {
+    let mine: BTreeMap<&String, &LocalPostModel> =
+        posts.iter().filter(|post| post.owner == *me).map(|post| (&post.id, post)).collect();
+    let mut statuses = BTreeMap::<String, PostStatus>::new();
+    for (receipt_post, receipt) in receipts(posts, |owner| owner != me) {
+        if receipt_post.post.to.value != me.to_string() {
+            continue;
+        }
+        let by = receipt_post.owner;
+        for id in receipt.post_ids.iter() {
+            let post = match mine.get(id) {
+                Some(post) => post,
+                None => continue,
+            };
+            if matches!(post.post.to.kind, RecipientKind::Direct) && post.post.to.value != by.to_string() {
+                continue;
+            }
+            let status = statuses.entry(id.clone()).or_insert_with(|| PostStatus {
+                post_id: id.clone(),
+                delivered: vec![],
+                read: vec![],
+            });
+            if !status.delivered.contains(&by) {
+                status.delivered.push(by);
+            }
+            if receipt.kind == ReceiptKind::Read && !status.read.contains(&by) {
+                status.read.push(by);
+            }
+        }
+    }
+    // in the order of the posts
+    posts.iter().filter_map(|post| statuses.remove(&post.id)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::ec::XOnlyPair;
+    use crate::util::testing::keys;
+    use crate::network::post::model::{Payload, Post, Recipient};
+
+    fn post(from: &XOnlyPair, id: &str, to: Recipient, payload: Payload) -> LocalPostModel {
+        LocalPostModel {
+            id: id.to_string(),
+            genesis: 0,
+            expiry: 0,
+            owner: from.pubkey,
+            post: Post::new(to, payload, from.clone()),
+        }
+    }
+
+    fn receipt(from: &XOnlyPair, id: &str, to: &XOnlyPair, kind: ReceiptKind, post_ids: &[&str]) -> LocalPostModel {
+        let receipt = Receipt {
+            kind,
+            post_ids: post_ids.iter().map(|id| id.to_string()).collect(),
+        };
+        post(
+            from,
+            id,
+            Recipient::new(RecipientKind::Direct, to.pubkey.to_string()),
+            Payload::new(PayloadKind::Receipt, receipt.stringify().unwrap()),
+        )
+    }
+
+    #[test]
+    fn test_receipts() {
+        let (alice, bob, carol) = (keys(), keys(), keys());
+        let message = |text: &str| Payload::new(PayloadKind::Message, text.to_string());
+        let to_bob = Recipient::new(RecipientKind::Direct, bob.pubkey.to_string());
+        let to_group = Recipient::new(RecipientKind::Group, "market".to_string());
+        let mut posts = vec![
+            post(&alice, "a1", to_bob.clone(), message("hi bob")),
+            post(&alice, "a2", to_bob, message("still there?")),
+            post(&alice, "a3", to_group.clone(), message("selling")),
+            post(&carol, "c1", to_group, message("buying")),
+        ];
+
+        let pending_for_bob = pending(&bob.pubkey, &posts, ReceiptKind::Delivered, &ReceiptSettings::default(), None);
+        assert_eq!(pending_for_bob[&alice.pubkey], vec![vec!["a1", "a2", "a3"]]);
+        assert_eq!(pending_for_bob[&carol.pubkey], vec![vec!["c1"]]);
+        let settings = ReceiptSettings {
+            off: vec!["market".to_string()],
+            read_off: vec![alice.pubkey.to_string()],
+        };
+        assert!(pending(&bob.pubkey, &posts, ReceiptKind::Read, &settings, None).is_empty());
+        let only = ["a2".to_string()];
+        let pending_for_bob = pending(&bob.pubkey, &posts, ReceiptKind::Delivered, &settings, Some(&only));
+        assert_eq!(pending_for_bob[&alice.pubkey], vec![vec!["a2"]]);
+
+        posts.push(receipt(&bob, "r1", &alice, ReceiptKind::Read, &["a1", "a3"]));
+        posts.push(receipt(&bob, "r2", &alice, ReceiptKind::Delivered, &["a2"]));
+        // carol was not sent a1, and her receipt for it is ignored
+        posts.push(receipt(&carol, "r3", &alice, ReceiptKind::Read, &["a1", "a3", "c1"]));
+        let pending_for_bob = pending(&bob.pubkey, &posts, ReceiptKind::Read, &ReceiptSettings::default(), None);
+        assert_eq!(pending_for_bob[&alice.pubkey], vec![vec!["a2"]]);
+        assert!(!pending(&bob.pubkey, &posts, ReceiptKind::Delivered, &ReceiptSettings::default(), None)
+            .contains_key(&alice.pubkey));
+
+        let statuses = statuses(&alice.pubkey, &posts);
+        assert_eq!(statuses.len(), 3);
+        assert_eq!(statuses[0].post_id, "a1");
+        assert_eq!(statuses[0].read, vec![bob.pubkey]);
+        assert_eq!(statuses[1].delivered, vec![bob.pubkey]);
+        assert!(statuses[1].read.is_empty());
+        assert_eq!(statuses[2].read, vec![bob.pubkey, carol.pubkey]);
+
+        let too_many = Receipt {
+            kind: ReceiptKind::Read,
+            post_ids: vec!["x".to_string(); MAX_RECEIPT_BATCH + 1],
+        };
+        assert!(too_many.check().is_err());
+    }
+}
@@ -0,0 +1,403 @@
+//! Social recovery. A secret, e.g. a master mnemonic, is split into k-of-n Shamir shares and each share
+//! is sent to a trusted member as a direct `PayloadKind::Secret` post holding a `RecoveryShare` json.
+//! To recover, guardians send their share back to the owner's new identity, which rebuilds the secret
+//! and checks it against the fingerprint carried by every share.
+use crate::key::encryption::nonce;
+use crate::network::post::model::{LocalPostModel, PayloadKind, RecipientKind};
+use crate::util::e::{ErrorKind, S5Error};
+use bitcoin::hashes::{sha256, Hash, HashEngine, Hmac, HmacEngine};
+use bitcoin::secp256k1::XOnlyPublicKey;
+use serde::{Deserialize, Serialize};
+use sharks::{Share, Sharks};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::ffi::CString;
+use std::os::raw::c_char;
+
+pub const MAX_RECOVERY_HOLDERS: usize = 255;
+/// Most subsets `combine_any` tries, so a set padded with bad shares cannot stall `get_recoveries`.
+const MAX_COMBINE_ATTEMPTS: usize = 1000;
+
+/// The value of a recovery Secret post.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecoveryShare {
+    pub set_id: String,
+    pub threshold: u8,
+    pub total: u8,
+    /// Checks the rebuilt secret, see `fingerprint`.
+    pub fingerprint: String,
+    /// Hex of the share, its first byte is the share index.
+    pub share: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+impl RecoveryShare {
+    pub fn structify(stringified: &str) -> Result<RecoveryShare, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Input, "Error structifying RecoveryShare")),
+        }
+    }
+    pub fn stringify(&self) -> Result<String, S5Error> {
+        match serde_json::to_string(self) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Internal, "Error stringifying RecoveryShare")),
+        }
+    }
+    fn to_share(&self) -> Result<Share, S5Error> {
+        let bytes = hex::decode(&self.share).map_err(|_| S5Error::new(ErrorKind::Input, "Bad recovery share."))?;
+        Share::try_from(bytes.as_slice()).map_err(|_| S5Error::new(ErrorKind::Input, "Bad recovery share."))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ShareHolder {
+    pub pubkey: XOnlyPublicKey,
+    /// The share index, to tell holders apart when shares come back.
+    pub index: u8,
+    /// The Secret post carrying the share, once sent.
+    pub post_id: Option<String>,
+    /// Derivation index of the post when its keys were not sent, so the holder cannot read it yet.
+    /// Finish with `send_keys` using this index.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keys_pending: Option<u32>,
+}
+
+/// Who holds which share of a secret. Kept by the owner, e.g. next to their contacts.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecoverySet {
+    pub id: String,
+    pub label: Option<String>,
+    pub threshold: u8,
+    pub fingerprint: String,
+    pub holders: Vec<ShareHolder>,
+    /// Why distribution stopped early. Holders without a post_id never got their share, those with `keys_pending` cannot read it yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<S5Error>,
+}
+
+impl RecoverySet {
+    pub fn structify(stringified: &str) -> Result<RecoverySet, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Input, "Error structifying RecoverySet")),
+        }
+    }
+    pub fn c_stringify(&self) -> *mut c_char {
+        CString::new(serde_json::to_string(self).unwrap()).unwrap().into_raw()
+    }
+}
+
+/// A share posted to `me` by `owner`: held for them as a guardian, or returned by a guardian during recovery.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HeldShare {
+    pub owner: XOnlyPublicKey,
+    pub post_id: String,
+    pub genesis: u64,
+    pub share: RecoveryShare,
+}
+
+/// The shares of one set posted to `me`, and the secret once there are enough of them.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecoveryProgress {
+    pub set_id: String,
+    pub label: Option<String>,
+    pub threshold: u8,
+    pub fingerprint: String,
+    pub shares: Vec<HeldShare>,
+    pub secret: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Recoveries {
+    pub recoveries: Vec<RecoveryProgress>,
+}
+
+impl Recoveries {
+    pub fn c_stringify(&self) -> *mut c_char {
+        CString::new(serde_json::to_string(self).unwrap()).unwrap().into_raw()
+    }
+}
+
+/// HMAC-SHA256(set_id, secret), 16 hex chars. Catches a wrong or tampered share.
+/// Keyed by the random set id, so the same secret split twice gets unrelated fingerprints and a holder
+/// cannot check guesses of a weak secret against a precomputed table.
+pub fn fingerprint(set_id: &str, secret: &str) -> String {
+    let mut engine = HmacEngine::<sha256::Hash>::new(set_id.as_bytes());
+    engine.input(secret.as_bytes());
+    let hmac = Hmac::<sha256::Hash>::from_engine(engine);
+    hex::encode(&hmac[0..8])
+}
+
+/// Split `secret` so that any `threshold` of `holders` can rebuild it. Returns the set and one share per holder, in order.
+pub fn split(
+    secret: &str,
+    threshold: u8,
+    holders: &[XOnlyPublicKey],
+    label: Option<String>,
+) -> Result<(RecoverySet, Vec<RecoveryShare>), S5Error> {
+    if secret.is_empty() {
+        return Err(S5Error::new(ErrorKind::Input, "Nothing to split."));
+    }
+    if holders.is_empty() || holders.len() > MAX_RECOVERY_HOLDERS {
+        return Err(S5Error::new(
+            ErrorKind::Input,
+            &format!("Recovery needs between 1 and {} holders.", MAX_RECOVERY_HOLDERS),
+        ));
+    }
+    let mut unique = holders.to_vec();
+    unique.sort();
+    unique.dedup();
+    if unique.len() != holders.len() {
+        return Err(S5Error::new(ErrorKind::Input, "Each holder can only hold one share."));
+    }
+    if threshold == 0 || threshold as usize > holders.len() {
+        return Err(S5Error::new(ErrorKind::Input, "Threshold must be between 1 and the number of holders."));
+    }
+    let id = nonce();
+    let set = RecoverySet {
+        fingerprint: fingerprint(&id, secret),
+        id,
+        label,
+        threshold,
+        holders: vec![],
+        error: None,
+    };
+    let shares: Vec<Share> = Sharks(threshold).dealer(secret.as_bytes()).take(holders.len()).collect();
+    let shares: Vec<RecoveryShare> = shares
+        .iter()
+        .map(|share| RecoveryShare {
+            set_id: set.id.clone(),
+            threshold,
+            total: holders.len() as u8,
+            fingerprint: set.fingerprint.clone(),
+            share: hex::encode(Vec::from(share)),
+            label: set.label.clone(),
+        })
+        .collect();
+    let holders = holders
+        .iter()
+        .zip(shares.iter())
+        .map(|(pubkey, share)| ShareHolder {
+            pubkey: *pubkey,
+            index: share.to_share().map(|share| Vec::from(&share)[0]).unwrap_or(0),
+            post_id: None,
+            keys_pending: None,
+        })
+        .collect();
+    Ok((RecoverySet { holders, ..set }, shares))
+}
+
+/// Rebuild a secret from shares of one set. Errors without enough distinct shares or if the fingerprint does not match.
+pub fn combine(shares: &[RecoveryShare]) -> Result<String, S5Error> {
+    let first = match shares.first() {
+        Some(first) => first,
+        None => return Err(S5Error::new(ErrorKind::Input, "No recovery shares.")),
+    };
+    if shares
+        .iter()
+        .any(|share| share.set_id != first.set_id || share.threshold != first.threshold || share.fingerprint != first.fingerprint)
+    {
+        return Err(S5Error::new(ErrorKind::Input, "Recovery shares are from different sets."));
+    }
+    let mut distinct = BTreeMap::<u8, Share>::new();
+    for share in shares {
+        let share = share.to_share()?;
+        distinct.insert(Vec::from(&share)[0], share);
+    }
+    if distinct.len() < first.threshold as usize {
+        return Err(S5Error::new(
+            ErrorKind::Input,
+            &format!("Need {} shares, have {}.", first.threshold, distinct.len()),
+        ));
+    }
+    let secret = Sharks(first.threshold)
+        .recover(distinct.values())
+        .map_err(|e| S5Error::new(ErrorKind::Input, &format!("Could not recover secret: {}", e)))?;
+    match String::from_utf8(secret) {
+        Ok(secret) if fingerprint(&first.set_id, &secret) == first.fingerprint => Ok(secret),
+        _ => Err(S5Error::new(ErrorKind::Key, "Recovered secret does not match its fingerprint.")),
+    }
+}
+
+/// Recovery shares in direct Secret posts to `me` from other members.
+pub fn held_shares(me: &XOnlyPublicKey, posts: &[LocalPostModel]) -> Vec<HeldShare> {
+    posts
+        .iter()
+        .filter(|post| post.owner != *me && post.verify().is_ok())
+        .filter(|post| matches!(post.post.to.kind, RecipientKind::Direct) && post.post.to.value == me.to_string())
+        .filter(|post| matches!(post.post.payload.kind, PayloadKind::Secret))
+        .filter_map(|post| {
+            RecoveryShare::structify(&post.post.payload.value).ok().map(|share| HeldShare {
+                owner: post.owner,
+                post_id: post.id.clone(),
+                genesis: post.genesis,
+                share,
+            })
+        })
+        .collect()
+}
+
+/// Like `combine`, but if a bad share spoils the whole lot, retry with `threshold` sized subsets until one matches the fingerprint.
+pub fn combine_any(shares: &[RecoveryShare]) -> Result<String, S5Error> {
+    let error = match combine(shares) {
+        Ok(secret) => return Ok(secret),
+        Err(e) => e,
+    };
+    let threshold = shares.first().map(|share| share.threshold as usize).unwrap_or(0);
+    if threshold == 0 || shares.len() <= threshold {
+        return Err(error);
+    }
+    // indexes of the current subset, stepped through in lexicographic order
+    let mut picked: Vec<usize> = (0..threshold).collect();
+    for _ in 0..MAX_COMBINE_ATTEMPTS {
+        let subset: Vec<RecoveryShare> = picked.iter().map(|i| shares[*i].clone()).collect();
+        if let Ok(secret) = combine(&subset) {
+            return Ok(secret);
+        }
+        let mut i = threshold;
+        loop {
+            if i == 0 {
+                return Err(error);
+            }
+            i -= 1;
+            if picked[i] < shares.len() - threshold + i {
+                break;
+            }
+        }
+        picked[i] += 1;
+        for j in i + 1..threshold {
+            picked[j] = picked[j - 1] + 1;
+        }
+    }
+    Err(error)
+}
+
+/// Group held shares by set, keeping the latest share from each owner, and rebuild each secret that has enough of them.
+/// Shares of a set are tried in groups that agree on threshold and fingerprint, largest first, so a lone forged share
+/// cannot stand in for the set.
+pub fn recoveries(held: Vec<HeldShare>) -> Recoveries {
+    let mut sets = BTreeMap::<String, BTreeMap<XOnlyPublicKey, HeldShare>>::new();
+    for share in held {
+        let owners = sets.entry(share.share.set_id.clone()).or_default();
+        match owners.get(&share.owner) {
+            Some(kept) if kept.genesis >= share.genesis => {}
+            _ => {
+                owners.insert(share.owner, share);
+            }
+        }
+    }
+    Recoveries {
+        recoveries: sets
+            .into_values()
+            .map(|owners| {
+                let shares: Vec<HeldShare> = owners.into_values().collect();
+                let mut groups = BTreeMap::<(u8, String), Vec<RecoveryShare>>::new();
+                for held in &shares {
+                    groups
+                        .entry((held.share.threshold, held.share.fingerprint.clone()))
+                        .or_default()
+                        .push(held.share.clone());
+                }
+                let mut groups: Vec<((u8, String), Vec<RecoveryShare>)> = groups.into_iter().collect();
+                groups.sort_by_key(|(_, group)| std::cmp::Reverse(group.len()));
+                let recovered = groups
+                    .iter()
+                    .find_map(|(key, group)| combine_any(group).ok().map(|secret| (key.clone(), secret)));
+                let ((threshold, fingerprint), secret) = match recovered {
+                    Some((key, secret)) => (key, Some(secret)),
+                    None => (groups[0].0.clone(), None),
+                };
+                RecoveryProgress {
+                    set_id: shares[0].share.set_id.clone(),
+                    label: shares[0].share.label.clone(),
+                    threshold,
+                    fingerprint,
+                    shares,
+                    secret,
+                }
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::seed::MasterKeySeed;
+    use crate::util::testing::pubkey;
+    use bitcoin::network::constants::Network;
+
+
+    #[test]
+    fn test_split_and_combine() {
+        let mnemonic = MasterKeySeed::generate(24, "", Network::Bitcoin).unwrap().mnemonic.to_string();
+        let holders = [pubkey(), pubkey(), pubkey(), pubkey(), pubkey()];
+        let (set, shares) = split(&mnemonic, 3, &holders, Some("main wallet".to_string())).unwrap();
+        assert_eq!(set.holders.len(), 5);
+        assert_eq!(set.holders[2].pubkey, holders[2]);
+        assert_eq!(shares[0].fingerprint, fingerprint(&set.id, &mnemonic));
+        // keyed by the set, so splitting the same secret again says nothing about it
+        let (again, _) = split(&mnemonic, 3, &holders, None).unwrap();
+        assert_ne!(again.fingerprint, set.fingerprint);
+
+        assert_eq!(combine(&[shares[4].clone(), shares[0].clone(), shares[2].clone()]).unwrap(), mnemonic);
+        assert_eq!(combine(&shares).unwrap(), mnemonic);
+        // the same share twice does not count twice
+        assert!(combine(&[shares[0].clone(), shares[0].clone(), shares[1].clone()]).is_err());
+        let mut tampered = shares[1].clone();
+        tampered.share.replace_range(4..6, if &tampered.share[4..6] == "00" { "01" } else { "00" });
+        assert!(combine(&[shares[0].clone(), tampered, shares[2].clone()]).is_err());
+        let (_, other) = split(&mnemonic, 3, &holders, None).unwrap();
+        assert!(combine(&[shares[0].clone(), shares[1].clone(), other[2].clone()]).is_err());
+
+        assert!(split(&mnemonic, 4, &holders[..3], None).is_err());
+        assert!(split(&mnemonic, 0, &holders, None).is_err());
+        assert!(split(&mnemonic, 2, &[holders[0], holders[0]], None).is_err());
+        assert!(split("", 1, &holders, None).is_err());
+    }
+
+    #[test]
+    fn test_recoveries() {
+        let holders = [pubkey(), pubkey(), pubkey(), pubkey()];
+        let (set, shares) = split("the secret", 2, &holders, None).unwrap();
+        let held = |owner: XOnlyPublicKey, genesis: u64, share: &RecoveryShare| HeldShare {
+            owner,
+            post_id: nonce(),
+            genesis,
+            share: share.clone(),
+        };
+        let mut tampered = shares[1].clone();
+        tampered.share.replace_range(4..6, if &tampered.share[4..6] == "00" { "01" } else { "00" });
+
+        // one owner posting every share still counts once
+        let spam: Vec<HeldShare> = shares.iter().enumerate().map(|(i, share)| held(holders[0], i as u64, share)).collect();
+        let progress = &recoveries(spam).recoveries[0];
+        assert_eq!(progress.shares.len(), 1);
+        assert_eq!(progress.secret, None);
+
+        // a bad share among good ones is left out
+        let progress = &recoveries(vec![
+            held(holders[0], 1, &shares[0]),
+            held(holders[1], 1, &tampered),
+            held(holders[2], 1, &shares[2]),
+        ])
+        .recoveries[0];
+        assert_eq!(progress.secret, Some("the secret".to_string()));
+        assert_eq!(progress.fingerprint, set.fingerprint);
+
+        // a forged 1 of n share with its own fingerprint loses to the larger group
+        let mut forged = split("not the secret", 1, &holders[..1], None).unwrap().1.remove(0);
+        forged.set_id = set.id.clone();
+        forged.fingerprint = fingerprint(&set.id, "not the secret");
+        let progress = &recoveries(vec![
+            held(holders[3], 1, &forged),
+            held(holders[0], 1, &shares[0]),
+            held(holders[2], 1, &shares[2]),
+        ])
+        .recoveries[0];
+        assert_eq!(progress.secret, Some("the secret".to_string()));
+        assert_eq!(progress.threshold, 2);
+    }
+}
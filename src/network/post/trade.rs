@@ -0,0 +1,538 @@
+//! Trade negotiation over `PayloadKind::Trade` posts. Each post holds one signed `TradeMessage`;
+//! every message after the offer names the hash of the one before it, so a thread can only be read one way.
+//! `fold` replays the messages of a conversation into the current `TradeState` of each trade.
+use crate::key::ec::{schnorr_verify, XOnlyPair};
+use crate::key::encryption::{key_hash256, nonce};
+use crate::network::badge::model::BadgeDetails;
+use crate::network::handler::AnnouncementType;
+use crate::network::post::model::{LocalPostModel, PayloadKind};
+use crate::util::e::{ErrorKind, S5Error};
+use bitcoin::secp256k1::schnorr::Signature;
+use bitcoin::secp256k1::XOnlyPublicKey;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::fmt;
+use std::os::raw::c_char;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const MAX_PAYMENT_METHOD_LENGTH: usize = 64;
+pub const MAX_PRICE_LENGTH: usize = 32;
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TradeSide {
+    /// The proposer buys bitcoin.
+    Buy,
+    /// The proposer sells bitcoin.
+    Sell,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TradeTerms {
+    pub side: TradeSide,
+    /// In sats.
+    pub amount: u64,
+    /// Fiat price for the whole amount, e.g. "620 EUR".
+    pub price: String,
+    pub payment_method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub escrow: Option<XOnlyPublicKey>,
+    /// Millisecond timestamp by which the trade must be accepted.
+    pub deadline: u64,
+}
+
+impl TradeTerms {
+    pub fn structify(stringified: &str) -> Result<TradeTerms, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Input, "Error structifying TradeTerms")),
+        }
+    }
+    pub fn check(&self) -> Result<(), S5Error> {
+        if self.amount == 0 {
+            return Err(S5Error::new(ErrorKind::Input, "Trade amount must be more than 0 sats."));
+        }
+        if self.price.trim().is_empty() || self.price.chars().count() > MAX_PRICE_LENGTH {
+            return Err(S5Error::new(
+                ErrorKind::Input,
+                &format!("Trade price must be 1 to {} characters.", MAX_PRICE_LENGTH),
+            ));
+        }
+        if self.payment_method.trim().is_empty() || self.payment_method.chars().count() > MAX_PAYMENT_METHOD_LENGTH {
+            return Err(S5Error::new(
+                ErrorKind::Input,
+                &format!("Trade payment method must be 1 to {} characters.", MAX_PAYMENT_METHOD_LENGTH),
+            ));
+        }
+        Ok(())
+    }
+    /// The same terms as seen by the other party.
+    fn flipped(&self) -> TradeTerms {
+        TradeTerms {
+            side: match self.side {
+                TradeSide::Buy => TradeSide::Sell,
+                TradeSide::Sell => TradeSide::Buy,
+            },
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TradeAction {
+    Offer,
+    Counter,
+    Accept,
+    Cancel,
+    Complete,
+}
+
+impl fmt::Display for TradeAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let action = match self {
+            TradeAction::Offer => "offer",
+            TradeAction::Counter => "counter",
+            TradeAction::Accept => "accept",
+            TradeAction::Cancel => "cancel",
+            TradeAction::Complete => "complete",
+        };
+        write!(f, "{}", action)
+    }
+}
+
+impl FromStr for TradeAction {
+    type Err = S5Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "offer" => Ok(TradeAction::Offer),
+            "counter" => Ok(TradeAction::Counter),
+            "accept" => Ok(TradeAction::Accept),
+            "cancel" => Ok(TradeAction::Cancel),
+            "complete" => Ok(TradeAction::Complete),
+            _ => Err(S5Error::new(ErrorKind::Input, "Trade action must be offer, counter, accept, cancel or complete.")),
+        }
+    }
+}
+
+/// The value of a trade post.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TradeMessage {
+    pub trade_id: String,
+    pub action: TradeAction,
+    /// Set on offers and counter offers, from the point of view of `by`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terms: Option<TradeTerms>,
+    /// Hash of the message this one answers. None on offers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<String>,
+    pub by: XOnlyPublicKey,
+    pub timestamp: u64,
+    pub signature: Signature,
+}
+
+impl TradeMessage {
+    /// A new trade.
+    pub fn offer(keys: &XOnlyPair, terms: TradeTerms) -> Result<TradeMessage, S5Error> {
+        TradeMessage::new(keys, nonce(), TradeAction::Offer, Some(terms), None)
+    }
+    fn new(
+        keys: &XOnlyPair,
+        trade_id: String,
+        action: TradeAction,
+        terms: Option<TradeTerms>,
+        previous: Option<String>,
+    ) -> Result<TradeMessage, S5Error> {
+        if let Some(terms) = &terms {
+            terms.check()?;
+        }
+        let timestamp = now();
+        let message = TradeMessage::message(&trade_id, action, &terms, &previous, &keys.pubkey, timestamp);
+        Ok(TradeMessage {
+            signature: keys.schnorr_sign(&message)?,
+            trade_id,
+            action,
+            terms,
+            previous,
+            by: keys.pubkey,
+            timestamp,
+        })
+    }
+    pub fn structify(stringified: &str) -> Result<TradeMessage, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Input, "Error structifying TradeMessage")),
+        }
+    }
+    pub fn stringify(&self) -> Result<String, S5Error> {
+        match serde_json::to_string(self) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Internal, "Error stringifying TradeMessage")),
+        }
+    }
+    pub fn c_stringify(&self) -> *mut c_char {
+        CString::new(serde_json::to_string(self).unwrap()).unwrap().into_raw()
+    }
+    /// "trade_id:action:terms:previous:by:timestamp", terms as json.
+    fn message(
+        trade_id: &str,
+        action: TradeAction,
+        terms: &Option<TradeTerms>,
+        previous: &Option<String>,
+        by: &XOnlyPublicKey,
+        timestamp: u64,
+    ) -> String {
+        let terms = match terms {
+            Some(terms) => serde_json::to_string(terms).unwrap(),
+            None => "".to_string(),
+        };
+        format!(
+            "{}:{}:{}:{}:{}:{}",
+            trade_id,
+            action,
+            terms,
+            previous.clone().unwrap_or_default(),
+            by,
+            timestamp
+        )
+    }
+    pub fn verify(&self) -> Result<(), S5Error> {
+        let message = TradeMessage::message(&self.trade_id, self.action, &self.terms, &self.previous, &self.by, self.timestamp);
+        schnorr_verify(self.signature, &message, self.by)
+    }
+    /// What the next message in the thread names as `previous`.
+    pub fn hash(&self) -> String {
+        let message = TradeMessage::message(&self.trade_id, self.action, &self.terms, &self.previous, &self.by, self.timestamp);
+        key_hash256(&format!("{}:{}", message, self.signature))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TradeStatus {
+    /// Offered or countered, waiting for the other party.
+    Open,
+    Accepted,
+    Cancelled,
+    Completed,
+}
+
+/// A badge the viewer should consider giving once a trade completes.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BadgePrompt {
+    pub to: XOnlyPublicKey,
+    pub kind: AnnouncementType,
+    pub details: BadgeDetails,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TradeState {
+    pub trade_id: String,
+    pub status: TradeStatus,
+    /// Who made the offer.
+    pub maker: XOnlyPublicKey,
+    /// Who answered it first. Only the maker and the taker can negotiate.
+    pub taker: Option<XOnlyPublicKey>,
+    /// The terms on the table, from the point of view of the maker.
+    pub terms: TradeTerms,
+    /// Who put the current terms on the table; the other party accepts or counters them.
+    pub proposed_by: XOnlyPublicKey,
+    /// Parties who marked the trade complete. It completes once both have, or the escrow agent has.
+    pub completed_by: Vec<XOnlyPublicKey>,
+    /// Open past the deadline.
+    pub expired: bool,
+    pub messages: Vec<TradeMessage>,
+    /// Hashes of messages for this trade that were badly signed, out of order or not allowed.
+    pub rejected: Vec<String>,
+    /// Filled for the maker and taker once the trade completes.
+    pub prompts: Vec<BadgePrompt>,
+}
+
+impl TradeState {
+    pub fn structify(stringified: &str) -> Result<TradeState, S5Error> {
+        match serde_json::from_str(stringified) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(ErrorKind::Input, "Error structifying TradeState")),
+        }
+    }
+    fn start(offer: &TradeMessage) -> Result<TradeState, S5Error> {
+        match (&offer.action, &offer.terms, &offer.previous) {
+            (TradeAction::Offer, Some(terms), None) => Ok(TradeState {
+                trade_id: offer.trade_id.clone(),
+                status: TradeStatus::Open,
+                maker: offer.by,
+                taker: None,
+                terms: terms.clone(),
+                proposed_by: offer.by,
+                completed_by: vec![],
+                expired: false,
+                messages: vec![offer.clone()],
+                rejected: vec![],
+                prompts: vec![],
+            }),
+            _ => Err(S5Error::new(ErrorKind::Input, "A trade must start with an offer.")),
+        }
+    }
+    fn counter_party(&self, of: &XOnlyPublicKey) -> Option<XOnlyPublicKey> {
+        if *of == self.maker {
+            self.taker
+        } else if Some(*of) == self.taker {
+            Some(self.maker)
+        } else {
+            None
+        }
+    }
+    /// Apply `message`, posted at `at`. Leaves the state untouched on error.
+    fn apply(&mut self, message: &TradeMessage, at: u64) -> Result<(), S5Error> {
+        message.verify()?;
+        let last = self.messages.last().map(|last| last.hash());
+        if message.trade_id != self.trade_id || message.previous != last {
+            return Err(S5Error::new(ErrorKind::Input, "Trade message does not follow the last one."));
+        }
+        let by = message.by;
+        let is_party = by == self.maker || Some(by) == self.taker;
+        let is_escrow = Some(by) == self.terms.escrow;
+        let may_answer = by != self.proposed_by && (self.taker.is_none() || is_party) && !is_escrow;
+        match (message.action, self.status) {
+            (TradeAction::Counter, TradeStatus::Open) | (TradeAction::Accept, TradeStatus::Open) if may_answer => {
+                if at > self.terms.deadline {
+                    return Err(S5Error::new(ErrorKind::Input, "Trade deadline has passed."));
+                }
+                if self.taker.is_none() {
+                    self.taker = Some(by);
+                }
+                if let TradeAction::Counter = message.action {
+                    let terms = match &message.terms {
+                        Some(terms) => terms,
+                        None => return Err(S5Error::new(ErrorKind::Input, "A counter offer needs terms.")),
+                    };
+                    // terms are kept from the maker's side
+                    self.terms = if by == self.maker { terms.clone() } else { terms.flipped() };
+                    self.proposed_by = by;
+                } else {
+                    self.status = TradeStatus::Accepted;
+                }
+            }
+            // before anyone answers only the maker can withdraw the offer; once countered, either party can walk away
+            (TradeAction::Cancel, TradeStatus::Open) if is_party => {
+                self.status = TradeStatus::Cancelled;
+            }
+            (TradeAction::Cancel, TradeStatus::Accepted) if is_party || is_escrow => {
+                self.status = TradeStatus::Cancelled;
+            }
+            (TradeAction::Complete, TradeStatus::Accepted) if is_party || is_escrow => {
+                if !self.completed_by.contains(&by) {
+                    self.completed_by.push(by);
+                }
+                let both = self.completed_by.contains(&self.maker) && self.taker.is_some_and(|taker| self.completed_by.contains(&taker));
+                if both || is_escrow {
+                    self.status = TradeStatus::Completed;
+                }
+            }
+            _ => {
+                return Err(S5Error::new(
+                    ErrorKind::Input,
+                    &format!("Cannot {} this trade now.", message.action),
+                ))
+            }
+        }
+        self.messages.push(message.clone());
+        Ok(())
+    }
+    /// Sign the next message of this trade as `keys`. Checks the message would be accepted by `fold`.
+    pub fn respond(&self, keys: &XOnlyPair, action: TradeAction, terms: Option<TradeTerms>) -> Result<TradeMessage, S5Error> {
+        let last = self.messages.last().map(|last| last.hash());
+        let message = TradeMessage::new(keys, self.trade_id.clone(), action, terms, last)?;
+        self.clone().apply(&message, message.timestamp)?;
+        Ok(message)
+    }
+    /// Badges for `me` to give now that the trade is complete: trust to the other party, escrow to the agent.
+    fn badge_prompts(&self, me: &XOnlyPublicKey) -> Vec<BadgePrompt> {
+        if self.status != TradeStatus::Completed {
+            return vec![];
+        }
+        let details = BadgeDetails {
+            reason: Some("completed trade".to_string()),
+            expiry: None,
+            reference: Some(self.trade_id.clone()),
+        };
+        let mut prompts = vec![];
+        if let Some(counter_party) = self.counter_party(me) {
+            prompts.push(BadgePrompt { to: counter_party, kind: AnnouncementType::Trust, details: details.clone() });
+        }
+        match self.terms.escrow {
+            Some(escrow) if self.counter_party(me).is_some() => {
+                prompts.push(BadgePrompt { to: escrow, kind: AnnouncementType::Escrow, details })
+            }
+            _ => (),
+        }
+        prompts
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Trades {
+    pub trades: Vec<TradeState>,
+}
+
+impl Trades {
+    pub fn c_stringify(&self) -> *mut c_char {
+        CString::new(serde_json::to_string(self).unwrap()).unwrap().into_raw()
+    }
+    pub fn get(&self, trade_id: &str) -> Result<&TradeState, S5Error> {
+        match self.trades.iter().find(|trade| trade.trade_id == trade_id) {
+            Some(trade) => Ok(trade),
+            None => Err(S5Error::new(ErrorKind::NoResource, "No trade with this id.")),
+        }
+    }
+}
+
+/// Replay the trade posts among `posts`, earliest first, into the state of each trade as seen by `me`.
+/// A message only counts if it was posted by the member who signed it.
+pub fn fold(me: &XOnlyPublicKey, posts: &[LocalPostModel]) -> Trades {
+    let mut posts: Vec<&LocalPostModel> = posts
+        .iter()
+        .filter(|post| matches!(post.post.payload.kind, PayloadKind::Trade) && post.verify().is_ok())
+        .collect();
+    posts.sort_by_key(|post| post.genesis);
+    let mut trades = BTreeMap::<String, TradeState>::new();
+    let mut seen = vec![];
+    for post in posts {
+        let message = match TradeMessage::structify(&post.post.payload.value) {
+            Ok(message) if message.by == post.owner => message,
+            _ => continue,
+        };
+        // the same message posted to several chats counts once
+        let hash = message.hash();
+        if seen.contains(&hash) {
+            continue;
+        }
+        seen.push(hash.clone());
+        match trades.get_mut(&message.trade_id) {
+            Some(trade) => {
+                if trade.apply(&message, post.genesis).is_err() {
+                    trade.rejected.push(hash);
+                }
+            }
+            None => {
+                if message.verify().is_ok() {
+                    if let Ok(trade) = TradeState::start(&message) {
+                        trades.insert(message.trade_id.clone(), trade);
+                    }
+                }
+            }
+        }
+    }
+    let now = now();
+    Trades {
+        trades: trades
+            .into_values()
+            .map(|mut trade| {
+                trade.expired = trade.status == TradeStatus::Open && now > trade.terms.deadline;
+                trade.prompts = trade.badge_prompts(me);
+                trade
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing::keys;
+    use crate::network::post::model::{Payload, Post, Recipient, RecipientKind};
+
+    fn post(from: &XOnlyPair, message: &TradeMessage, genesis: u64) -> LocalPostModel {
+        LocalPostModel {
+            id: genesis.to_string(),
+            genesis,
+            expiry: 0,
+            owner: from.pubkey,
+            post: Post::new(
+                Recipient::new(RecipientKind::Group, "market".to_string()),
+                Payload::new(PayloadKind::Trade, message.stringify().unwrap()),
+                from.clone(),
+            ),
+        }
+    }
+
+    #[test]
+    fn test_trade_flow() {
+        let (alice, bob, carol, escrow) = (keys(), keys(), keys(), keys());
+        let terms = TradeTerms {
+            side: TradeSide::Sell,
+            amount: 1_000_000,
+            price: "620 EUR".to_string(),
+            payment_method: "SEPA".to_string(),
+            escrow: Some(escrow.pubkey),
+            deadline: now() + 3_600_000,
+        };
+        let offer = TradeMessage::offer(&alice, terms.clone()).unwrap();
+        let mut posts = vec![post(&alice, &offer, 1)];
+        let state = fold(&alice.pubkey, &posts).trades[0].clone();
+        assert_eq!(state.status, TradeStatus::Open);
+        assert!(state.respond(&alice, TradeAction::Accept, None).is_err());
+        // only alice can withdraw an offer nobody has answered
+        assert!(state.respond(&bob, TradeAction::Cancel, None).is_err());
+        assert!(state.respond(&alice, TradeAction::Cancel, None).is_ok());
+
+        // bob counters from his side: he buys for less
+        let counter_terms = TradeTerms { side: TradeSide::Buy, price: "600 EUR".to_string(), ..terms.clone() };
+        let counter = state.respond(&bob, TradeAction::Counter, Some(counter_terms)).unwrap();
+        posts.push(post(&bob, &counter, 2));
+        let state = fold(&alice.pubkey, &posts).trades[0].clone();
+        assert_eq!(state.taker, Some(bob.pubkey));
+        assert_eq!(state.terms.side, TradeSide::Sell);
+        assert_eq!(state.terms.price, "600 EUR");
+        // bob can walk away from his own counter, carol cannot end it for them
+        let walk_away = state.respond(&bob, TradeAction::Cancel, None).unwrap();
+        let mut walked = posts.clone();
+        walked.push(post(&bob, &walk_away, 3));
+        assert_eq!(fold(&alice.pubkey, &walked).trades[0].status, TradeStatus::Cancelled);
+        assert!(state.respond(&carol, TradeAction::Cancel, None).is_err());
+        // carol is not part of this trade, and her message is dropped
+        let butting_in = TradeMessage::new(&carol, state.trade_id.clone(), TradeAction::Accept, None, Some(counter.hash())).unwrap();
+        assert!(state.respond(&carol, TradeAction::Accept, None).is_err());
+        posts.push(post(&carol, &butting_in, 3));
+
+        let accept = state.respond(&alice, TradeAction::Accept, None).unwrap();
+        posts.push(post(&alice, &accept, 4));
+        // replayed by someone else
+        posts.push(post(&carol, &accept, 5));
+        let state = fold(&alice.pubkey, &posts).trades[0].clone();
+        assert_eq!(state.status, TradeStatus::Accepted);
+        assert_eq!(state.rejected, vec![butting_in.hash()]);
+        assert!(state.prompts.is_empty());
+
+        let complete = state.respond(&alice, TradeAction::Complete, None).unwrap();
+        posts.push(post(&alice, &complete, 6));
+        let state = fold(&alice.pubkey, &posts).trades[0].clone();
+        assert_eq!(state.status, TradeStatus::Accepted);
+        let complete = state.respond(&bob, TradeAction::Complete, None).unwrap();
+        posts.push(post(&bob, &complete, 7));
+        let state = fold(&bob.pubkey, &posts).trades[0].clone();
+        assert_eq!(state.status, TradeStatus::Completed);
+        assert_eq!(state.prompts.len(), 2);
+        assert_eq!(state.prompts[0].to, alice.pubkey);
+        assert_eq!(state.prompts[1].to, escrow.pubkey);
+        assert_eq!(state.prompts[0].details.reference.as_deref(), Some(state.trade_id.as_str()));
+        assert!(fold(&carol.pubkey, &posts).trades[0].prompts.is_empty());
+        assert!(state.respond(&bob, TradeAction::Cancel, None).is_err());
+
+        // an edited message no longer verifies
+        let mut tampered = TradeMessage::offer(&alice, terms.clone()).unwrap();
+        tampered.terms.as_mut().unwrap().amount = 2;
+        assert!(fold(&alice.pubkey, &[post(&alice, &tampered, 1)]).trades.is_empty());
+        let late = TradeMessage::offer(&alice, TradeTerms { deadline: 1, ..terms }).unwrap();
+        let state = fold(&alice.pubkey, &[post(&alice, &late, 1)]).trades[0].clone();
+        assert!(state.expired);
+        assert!(state.respond(&bob, TradeAction::Accept, None).is_err());
+    }
+}
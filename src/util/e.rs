@@ -37,7 +37,7 @@ impl PartialEq for ErrorKind {
 }
 
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct S5Error {
   pub kind: String,
   pub error: String,
@@ -61,7 +61,10 @@ impl S5Error {
             409 => ErrorKind::Input,
             _=> ErrorKind::Internal
         };
-        S5Error::new(kind, &response.into_string().unwrap())
+        match response.into_string() {
+          Ok(body) => S5Error::new(kind, &body),
+          Err(_) => S5Error::new(kind, &format!("Server returned status {}.", code)),
+        }
       }
       _ => { 
         S5Error::new(ErrorKind::Network, "Transport Error. Check your internet connection AND/OR your request object.")
@@ -81,4 +84,12 @@ impl S5Error {
     CString::new(stringified).unwrap().into_raw()
   }
 
-}
\ No newline at end of file
+}
+
+impl Display for S5Error {
+  fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+    write!(f, "{}: {}", self.kind, self.error)
+  }
+}
+
+impl std::error::Error for S5Error {}
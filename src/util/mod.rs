@@ -1 +1,4 @@
 pub mod e;
+pub mod task;
+#[cfg(test)]
+pub mod testing;
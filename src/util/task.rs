@@ -0,0 +1,256 @@
+// Background requests for the non-blocking ffi.
+use crate::util::e::{ErrorKind, S5Error};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Called from a worker thread with the request id and its JSON result.
+/// The receiver owns `result` and must pass it into cstring_free.
+pub type ResultCallback = extern "C" fn(request_id: u64, result: *mut c_char);
+
+enum TaskState {
+  /// `callback` is set when the result goes to a callback, so nobody will poll for it.
+  Pending { callback: bool },
+  Done(String),
+  Cancelled,
+}
+
+static TASKS: Mutex<BTreeMap<u64, TaskState>> = Mutex::new(BTreeMap::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+fn tasks() -> std::sync::MutexGuard<'static, BTreeMap<u64, TaskState>> {
+  TASKS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Run `job` on its own thread and return a request id immediately.
+/// With a callback the result is delivered to it and never stored; otherwise it waits for poll.
+/// A job that panics completes with an Internal S5Error.
+pub fn spawn<F>(job: F, callback: Option<ResultCallback>) -> u64
+where
+  F: FnOnce() -> String + Send + 'static,
+{
+  let request_id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+  tasks().insert(request_id, TaskState::Pending { callback: callback.is_some() });
+  thread::spawn(move || {
+    let result = match panic::catch_unwind(AssertUnwindSafe(job)) {
+      Ok(result) => result,
+      Err(_) => serde_json::to_string(&S5Error::new(ErrorKind::Internal, "Request failed unexpectedly. BAD NEWS! Contact Support."))
+        .unwrap_or_default(),
+    };
+    let deliver = {
+      let mut tasks = tasks();
+      match tasks.get(&request_id) {
+        Some(TaskState::Pending { .. }) => {
+          if callback.is_some() {
+            tasks.remove(&request_id);
+            true
+          } else {
+            tasks.insert(request_id, TaskState::Done(result.clone()));
+            false
+          }
+        }
+        // cancelled while in flight: drop the result, the entry stays until polled unless nobody will poll it
+        Some(TaskState::Cancelled) if callback.is_some() => {
+          tasks.remove(&request_id);
+          false
+        }
+        _ => false,
+      }
+    };
+    if let (true, Some(callback)) = (deliver, callback) {
+      let result = CString::new(result).unwrap_or_else(|_| {
+        CString::new("Error:Result contained a nul byte. BAD NEWS! Contact Support.").unwrap()
+      });
+      callback(request_id, result.into_raw());
+    }
+  });
+  request_id
+}
+
+/// Status of a request. A finished or cancelled request is forgotten once polled.
+pub fn poll(request_id: u64) -> Result<RequestStatus, S5Error> {
+  let mut tasks = tasks();
+  match tasks.get(&request_id) {
+    None => Err(S5Error::new(ErrorKind::NoResource, "No request with this id. It may have already been polled.")),
+    Some(TaskState::Pending { .. }) => Ok(RequestStatus::new(request_id, "pending", None)),
+    Some(_) => match tasks.remove(&request_id) {
+      Some(TaskState::Done(result)) => Ok(RequestStatus::new(request_id, "done", Some(result))),
+      _ => Ok(RequestStatus::new(request_id, "cancelled", None)),
+    },
+  }
+}
+
+/// The http call itself cannot be interrupted, its result is discarded and no callback fires.
+/// The request reports "cancelled" until it is polled once. A request with a callback is forgotten right away.
+pub fn cancel(request_id: u64) -> Result<(), S5Error> {
+  let mut tasks = tasks();
+  match tasks.get(&request_id) {
+    Some(TaskState::Pending { callback: true }) => {
+      tasks.remove(&request_id);
+      Ok(())
+    }
+    Some(TaskState::Pending { callback: false }) => {
+      tasks.insert(request_id, TaskState::Cancelled);
+      Ok(())
+    }
+    Some(_) => Err(S5Error::new(ErrorKind::Input, "Request has already completed or been cancelled.")),
+    None => Err(S5Error::new(ErrorKind::NoResource, "No request with this id.")),
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RequestId {
+  pub request_id: u64,
+}
+impl RequestId {
+  pub fn new(request_id: u64) -> Self {
+    RequestId { request_id }
+  }
+  pub fn c_stringify(&self) -> *mut c_char {
+    let stringified = match serde_json::to_string(self) {
+      Ok(result) => result,
+      Err(_) => {
+        return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+          .unwrap()
+          .into_raw()
+      }
+    };
+    CString::new(stringified).unwrap().into_raw()
+  }
+  pub fn structify(stringified: &str) -> Result<RequestId, S5Error> {
+    match serde_json::from_str(stringified) {
+      Ok(result) => Ok(result),
+      Err(_) => Err(S5Error::new(ErrorKind::Internal, "Error structifying RequestId")),
+    }
+  }
+}
+
+/// `result` is the JSON the blocking call would have returned (including S5Error)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RequestStatus {
+  pub request_id: u64,
+  pub status: String,
+  pub result: Option<serde_json::Value>,
+}
+impl RequestStatus {
+  pub fn new(request_id: u64, status: &str, result: Option<String>) -> Self {
+    RequestStatus {
+      request_id,
+      status: status.to_string(),
+      result: result.map(|result| match serde_json::from_str(&result) {
+        Ok(value) => value,
+        Err(_) => serde_json::Value::String(result),
+      }),
+    }
+  }
+  pub fn c_stringify(&self) -> *mut c_char {
+    let stringified = match serde_json::to_string(self) {
+      Ok(result) => result,
+      Err(_) => {
+        return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+          .unwrap()
+          .into_raw()
+      }
+    };
+    CString::new(stringified).unwrap().into_raw()
+  }
+  pub fn structify(stringified: &str) -> Result<RequestStatus, S5Error> {
+    match serde_json::from_str(stringified) {
+      Ok(result) => Ok(result),
+      Err(_) => Err(S5Error::new(ErrorKind::Internal, "Error structifying RequestStatus")),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::mpsc::{channel, Sender};
+  use std::time::Duration;
+
+  fn wait_for(request_id: u64) -> RequestStatus {
+    for _ in 0..200 {
+      let status = poll(request_id).unwrap();
+      if status.status != "pending" {
+        return status;
+      }
+      thread::sleep(Duration::from_millis(5));
+    }
+    panic!("request never completed");
+  }
+
+  #[test]
+  fn test_poll_and_cancel() {
+    let request_id = spawn(|| "{\"status\":true}".to_string(), None);
+    let status = wait_for(request_id);
+    assert_eq!(status.status, "done");
+    assert_eq!(status.result.unwrap()["status"], true);
+    // forgotten once polled
+    assert!(poll(request_id).is_err());
+
+    let (release, blocked) = channel::<()>();
+    let request_id = spawn(
+      move || {
+        blocked.recv().unwrap();
+        "late".to_string()
+      },
+      None,
+    );
+    assert_eq!(poll(request_id).unwrap().status, "pending");
+    cancel(request_id).unwrap();
+    assert!(cancel(request_id).is_err());
+    release.send(()).unwrap();
+    let status = poll(request_id).unwrap();
+    assert_eq!(status.status, "cancelled");
+    assert!(status.result.is_none());
+  }
+
+  static CALLBACK_RESULTS: Mutex<Option<Sender<(u64, String)>>> = Mutex::new(None);
+
+  extern "C" fn test_callback(request_id: u64, result: *mut c_char) {
+    let result = unsafe { CString::from_raw(result) }.into_string().unwrap();
+    if let Some(sender) = CALLBACK_RESULTS.lock().unwrap().as_ref() {
+      sender.send((request_id, result)).unwrap();
+    }
+  }
+
+  #[test]
+  fn test_callback_delivery() {
+    let (sender, receiver) = channel();
+    *CALLBACK_RESULTS.lock().unwrap() = Some(sender);
+    let request_id = spawn(|| "{\"id\":\"abc\"}".to_string(), Some(test_callback));
+    let (delivered_id, result) = receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(delivered_id, request_id);
+    assert_eq!(result, "{\"id\":\"abc\"}");
+    // delivered results are not kept for polling
+    assert!(poll(request_id).is_err());
+
+    // a cancelled request with a callback leaves nothing behind and never calls back
+    let (release, blocked) = channel::<()>();
+    let request_id = spawn(
+      move || {
+        blocked.recv().unwrap();
+        "late".to_string()
+      },
+      Some(test_callback),
+    );
+    cancel(request_id).unwrap();
+    assert!(poll(request_id).is_err());
+    release.send(()).unwrap();
+    assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+    assert!(!tasks().contains_key(&request_id));
+  }
+
+  #[test]
+  fn test_panicking_job() {
+    let request_id = spawn(|| panic!("job failed"), None);
+    let status = wait_for(request_id);
+    assert_eq!(status.status, "done");
+    assert_eq!(status.result.unwrap()["kind"], ErrorKind::Internal.to_string());
+  }
+}
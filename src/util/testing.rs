@@ -0,0 +1,14 @@
+//! Helpers shared by the unit tests.
+use crate::key::ec::XOnlyPair;
+use crate::key::seed::MasterKeySeed;
+use bitcoin::network::constants::Network;
+use bitcoin::secp256k1::XOnlyPublicKey;
+
+/// Fresh random keys for a test member.
+pub fn keys() -> XOnlyPair {
+    XOnlyPair::from_xprv(MasterKeySeed::generate(12, "", Network::Bitcoin).unwrap().xprv)
+}
+
+pub fn pubkey() -> XOnlyPublicKey {
+    keys().pubkey
+}